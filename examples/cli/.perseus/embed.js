@@ -0,0 +1,18 @@
+import init, { embed } from "./dist/pkg/perseus_cli_builder.js";
+// Everything is loaded from the Perseus server this script was loaded from, not the host page's server
+const origin = new URL(import.meta.url).origin;
+const ready = init(`${origin}/.perseus/bundle.wasm`);
+// Host pages can embed pages with `perseusEmbed("#widget", "/about")`, or declaratively with `<div data-perseus-path="/about"></div>`
+window.perseusEmbed = async (selector, path) => {
+    await ready;
+    embed(selector, path, origin);
+};
+async function main() {
+    await ready;
+    document.querySelectorAll("[data-perseus-path]").forEach((elem, idx) => {
+        const selector = `[data-perseus-embed-id="${idx}"]`;
+        elem.setAttribute("data-perseus-embed-id", idx.toString());
+        embed(selector, elem.getAttribute("data-perseus-path"), origin);
+    });
+}
+main();
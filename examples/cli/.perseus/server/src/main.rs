@@ -1,39 +1,112 @@
 use actix_web::{App, HttpServer};
-use app::{get_config_manager, get_locales, get_templates_map, get_translations_manager};
+use app::{
+    get_config_manager, get_locales, get_noscript, get_redirects, get_surrogate_key_header,
+    get_templates_map, get_translations_manager, init_resources, init_translator_backend,
+};
 use futures::executor::block_on;
-use perseus_actix_web::{configurer, Options};
+use perseus_actix_web::{configurer, wait_for_jobs, AccessControl, Options, ROUTE_TABLE_TOKEN_VAR};
 use std::env;
+use std::path::Path;
+use std::time::Duration;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // So we don't have to define a different `FsConfigManager` just for the server, we shift the execution context to the same level as everything else
     // The server has to be a separate crate because otherwise the dependencies don't work with WASM bundling
     env::set_current_dir("../").unwrap();
+    // This has to be done before the translations manager creates any translators
+    init_translator_backend();
 
     let host = env::var("HOST").unwrap_or_else(|_| "localhost".to_string());
     let port = env::var("PORT")
         .unwrap_or_else(|_| "8080".to_string())
         .parse::<u16>();
+    // Staging deployments can be protected with HTTP basic auth or an IP allowlist in the environment, and we refuse to start if that's
+    // misconfigured rather than serving the deployment unprotected (or exiting as if nothing went wrong)
+    let access_control = AccessControl::from_env().map_err(|err| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("couldn't set up access control: {}", err),
+        )
+    })?;
     if let Ok(port) = port {
-        HttpServer::new(|| {
+        // The app's shared resources (like database pools) are created once and shared between all the server's threads
+        init_resources().await;
+        let server = HttpServer::new(move || {
             App::new().configure(block_on(configurer(
-                Options {
-                    index: "../index.html".to_string(), // The user must define their own `index.html` file
-                    js_bundle: "dist/pkg/bundle.js".to_string(),
+                Options::new(
+                    "../index.html", // The user must define their own `index.html` file
+                    "dist/pkg/bundle.js",
                     // Our crate has the same name, so this will be predictable
-                    wasm_bundle: "dist/pkg/perseus_cli_builder_bg.wasm".to_string(),
-                    templates_map: get_templates_map(),
-                    locales: get_locales(),
-                },
+                    "dist/pkg/perseus_cli_builder_bg.wasm",
+                    get_templates_map(),
+                    get_locales(),
+                )
+                .surrogate_key_header(get_surrogate_key_header())
+                .noscript(get_noscript())
+                .redirects(get_redirects())
+                // This is only bundled if the app was built with `--embed`
+                .embed_bundle(
+                    Path::new("dist/pkg/embed.js")
+                        .exists()
+                        .then(|| "dist/pkg/embed.js".to_string()),
+                )
+                .access_control(access_control.clone())
+                // The user can put static files (like images and videos) in a `static/` directory next to `index.html`
+                .static_dir(
+                    Path::new("../static")
+                        .exists()
+                        .then(|| "../static".to_string()),
+                )
+                // The live route table is only served if a token for it has been set (an empty one would let anyone see it)
+                .route_table_token(
+                    env::var(ROUTE_TABLE_TOKEN_VAR)
+                        .ok()
+                        .filter(|token| !token.trim().is_empty()),
+                ),
                 get_config_manager(),
                 block_on(get_translations_manager()),
             )))
         })
         .bind((host, port))?
-        .run()
-        .await
+        // We handle shutdown ourselves so that background jobs can finish first
+        .disable_signals()
+        .run();
+        let handle = server.clone();
+        actix_web::rt::spawn(async move {
+            shutdown_signal().await;
+            // We stop taking new connections, give background jobs some time to finish, and then let in-flight requests finish
+            handle.pause().await;
+            let _ = actix_web::rt::time::timeout(Duration::from_secs(30), wait_for_jobs()).await;
+            handle.stop(true).await;
+        });
+        server.await
     } else {
         eprintln!("Port must be a number.");
         Ok(())
     }
 }
+
+/// Waits for a signal that the server should shut down (Ctrl+C, or `SIGTERM` on Unix, which is what most hosting platforms send).
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                futures::future::select(
+                    Box::pin(actix_web::rt::signal::ctrl_c()),
+                    Box::pin(sigterm.recv()),
+                )
+                .await;
+            }
+            Err(_) => {
+                let _ = actix_web::rt::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = actix_web::rt::signal::ctrl_c().await;
+    }
+}
@@ -1,30 +1,148 @@
-use app::{get_config_manager, get_locales, get_templates_vec, get_translations_manager};
+use app::{
+    builds_locales_in_parallel, get_build_concurrency, get_build_error_mode, get_config_manager,
+    get_link_check_mode, get_locales, get_size_budget, get_templates_map, get_templates_vec,
+    get_translations_manager, init_resources, init_translator_backend, is_i18n_strict,
+    should_audit_html,
+};
 use futures::executor::block_on;
-use perseus::{build_app, SsrNode};
+use perseus::{
+    audit_html, build_app, build_app_in_parallel, check_artifact_sizes, check_links,
+    errors::ErrorKind, plan_app, BuildFilter, SsrNode,
+};
 
 fn main() {
     let exit_code = real_main();
     std::process::exit(exit_code)
 }
 
+/// Gets what to restrict the build to from the arguments the CLI passed through (`--only template=<name>` or `--only path=<path>`, any
+/// number of times). The CLI has already validated these.
+fn get_build_filter() -> BuildFilter {
+    let args: Vec<String> = std::env::args().collect();
+    let mut filter = BuildFilter::new();
+    for (idx, arg) in args.iter().enumerate() {
+        if arg != "--only" {
+            continue;
+        }
+        match args.get(idx + 1).and_then(|spec| spec.split_once('=')) {
+            Some(("template", name)) => filter = filter.template(name),
+            Some(("path", path)) => filter = filter.path(path),
+            _ => (),
+        }
+    }
+
+    filter
+}
+
 fn real_main() -> i32 {
+    // This has to be done before the translations manager creates any translators
+    init_translator_backend();
     let config_manager = get_config_manager();
     let translations_manager = block_on(get_translations_manager());
     let locales = get_locales();
 
-    // Build the site for all the common locales (done in parallel)
-    let fut = build_app(
-        get_templates_vec::<SsrNode>(),
+    // If the CLI passed `--dry-run` through, we'll just list what would be built
+    if std::env::args().any(|arg| arg == "--dry-run") {
+        let templates = get_templates_vec::<SsrNode>();
+        return match block_on(plan_app(&templates, &locales, &get_build_filter())) {
+            Ok(plan) => {
+                println!("{}", plan);
+                0
+            }
+            Err(err) => {
+                eprintln!("Planning static generation failed: '{}'", err);
+                1
+            }
+        };
+    }
+
+    // Create the app's shared resources (like database pools) once for the whole build
+    block_on(init_resources());
+
+    // Build the site for all the common locales (done concurrently, and each on its own thread if the app wants that)
+    let res = match builds_locales_in_parallel() {
+        true => build_app_in_parallel(
+            get_templates_vec::<SsrNode>,
+            &locales,
+            &config_manager,
+            &translations_manager,
+            get_build_error_mode(),
+            &get_build_filter(),
+            get_build_concurrency(),
+            is_i18n_strict(),
+        ),
+        false => block_on(build_app(
+            get_templates_vec::<SsrNode>(),
+            &locales,
+            &config_manager,
+            &translations_manager,
+            get_build_error_mode(),
+            &get_build_filter(),
+            get_build_concurrency(),
+            is_i18n_strict(),
+        )),
+    };
+    if let Err(err) = res {
+        match err.kind() {
+            // This is a multi-line report, so we don't quote it
+            ErrorKind::PagesFailed(_) => eprintln!("Static generation failed: {}", err),
+            _ => eprintln!("Static generation failed: '{}'", err),
+        }
+        return 1;
+    }
+
+    // Check the internal links in everything we just built (if the user wants to)
+    let res = block_on(check_links(
         &locales,
+        get_link_check_mode(),
         &config_manager,
-        &translations_manager,
-    );
-    let res = block_on(fut);
-    if let Err(err) = res {
-        eprintln!("Static generation failed: '{}'", err);
-        1
-    } else {
-        println!("Static generation successfully completed!");
-        0
+    ));
+    match res {
+        Ok(broken_links) => {
+            for broken_link in broken_links {
+                eprintln!("Warning: {}", broken_link);
+            }
+        }
+        Err(err) => {
+            eprintln!("Static generation failed: '{}'", err);
+            return 1;
+        }
     }
+
+    // Audit the generated HTML (if the user wants to), reporting everything by template
+    if should_audit_html() {
+        match block_on(audit_html(&locales, &config_manager)) {
+            Ok(issues) => {
+                for issue in issues {
+                    eprintln!("Warning: {}", issue);
+                }
+            }
+            Err(err) => {
+                eprintln!("Static generation failed: '{}'", err);
+                return 1;
+            }
+        }
+    }
+
+    // Report any pages with artifacts over their size budgets, since those will be slow without anything else noticing
+    let res = block_on(check_artifact_sizes(
+        &locales,
+        &get_templates_map::<SsrNode>(),
+        &get_size_budget(),
+        &config_manager,
+    ));
+    match res {
+        Ok(warnings) => {
+            for warning in warnings {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+        Err(err) => {
+            eprintln!("Static generation failed: '{}'", err);
+            return 1;
+        }
+    }
+
+    println!("Static generation successfully completed!");
+    0
 }
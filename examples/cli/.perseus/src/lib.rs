@@ -1,6 +1,14 @@
-use app::{get_error_pages, get_locales, get_routes, APP_ROUTE};
+use app::{
+    get_aux_roots, get_error_pages, get_locale_redirect, get_locales, get_routes,
+    init_translator_backend, APP_ROUTE,
+};
 use perseus::router::{RouteInfo, RouteVerdict};
-use perseus::{app_shell, detect_locale, ClientTranslationsManager, DomNode};
+use perseus::{
+    app_shell, detect_locale_with,
+    embed::{set_embed_origin, EMBED_CLASS, EMBED_PATH_ATTR},
+    follow_redirect, get_app_root, mount_aux_roots, watch_translations, ClientTranslationsManager,
+    DomNode, MountedTemplate, ScopedStates,
+};
 use std::cell::RefCell;
 use std::rc::Rc;
 use sycamore::prelude::template;
@@ -13,22 +21,28 @@ use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 pub fn run() -> Result<(), JsValue> {
     // Panics should always go to the console
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    // This has to be done before the app shell creates any translators
+    init_translator_backend();
     // Get the root (for the router) we'll be injecting page content into
-    let root = web_sys::window()
-        .unwrap()
-        .document()
-        .unwrap()
-        .query_selector(APP_ROUTE)
-        .unwrap()
-        .unwrap();
+    let root = get_app_root(APP_ROUTE);
+    // Make sure any other roots the app renders into exist
+    mount_aux_roots(&get_aux_roots());
 
     // Create a mutable translations manager to control caching
     let translations_manager =
         Rc::new(RefCell::new(ClientTranslationsManager::new(&get_locales())));
+    // In development, edited translations are reloaded without rebuilding anything
+    watch_translations(Rc::clone(&translations_manager));
     // Get the error pages in an `Rc` so we aren't creating hundreds of them
     let error_pages = Rc::new(get_error_pages());
     // Get the routes in an `Rc` as well
     let routes = Rc::new(get_routes::<DomNode>());
+    // Keep track of any template that's kept mounted between navigations
+    let mounted_template = Rc::new(RefCell::new(MountedTemplate::new()));
+    // Keep the states of any named scopes across navigations
+    let scoped_states = ScopedStates::new();
+    // Get how users should be redirected to their locale in an `Rc` as well
+    let locale_redirect = Rc::new(get_locale_redirect::<DomNode>());
 
     sycamore::render_to(
         || {
@@ -51,11 +65,15 @@ pub fn run() -> Result<(), JsValue> {
                                     locale,
                                     // We give the app shell a translations manager and let it get the `Rc<Translator>` itself (because it can do async safely)
                                     Rc::clone(&translations_manager),
-                                    Rc::clone(&error_pages)
+                                    Rc::clone(&error_pages),
+                                    Rc::clone(&mounted_template),
+                                    scoped_states.clone()
                                 ),
                                 // If the user is using i18n, then they'll want to detect the locale on any paths missing a locale
                                 // Those all go to the same system that redirects to the appropriate locale
-                                RouteVerdict::LocaleDetection(path) => detect_locale(path, get_locales()),
+                                RouteVerdict::LocaleDetection(path) => detect_locale_with(path, get_locales(), &locale_redirect),
+                                // Paths in the app's static redirects go wherever they've moved to
+                                RouteVerdict::Redirect(to) => follow_redirect(to),
                                 // We handle the 404 for the user for convenience
                                 // To get a translator here, we'd have to go async and dangerously check the URL
                                 RouteVerdict::NotFound => get_error_pages().get_template_for_page("", &404, "not found", None),
@@ -70,3 +88,71 @@ pub fn run() -> Result<(), JsValue> {
 
     Ok(())
 }
+
+/// The entrypoint for embedding a single page of the app into a host page that isn't part of it (e.g. a page of a legacy site). This
+/// renders the page at the given path (which can leave out the locale, in which case the default locale will be used) into the element
+/// matching the given selector, loading everything from the Perseus server at the given origin. Embedded pages don't take over the host
+/// page's routing, URL, or metadata, so this can be called several times to embed several pages.
+#[wasm_bindgen]
+pub fn embed(selector: String, path: String, origin: String) -> Result<(), JsValue> {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    init_translator_backend();
+    set_embed_origin(origin);
+    let document = web_sys::window().unwrap().document().unwrap();
+    let host = document
+        .query_selector(&selector)?
+        .ok_or_else(|| JsValue::from_str(&format!("no element matches '{}'", selector)))?;
+
+    let locales = get_locales();
+    let routes = Rc::new(get_routes::<DomNode>());
+    let path = path.trim_matches('/').to_string();
+    let route = match routes.match_route(&path.split('/').collect::<Vec<&str>>()) {
+        // We can't redirect the host page to the user's locale, so we'll just use the default one
+        RouteVerdict::LocaleDetection(path) => {
+            let path = format!("{}/{}", locales.default, path);
+            routes.match_route(&path.split('/').collect::<Vec<&str>>())
+        }
+        route => route,
+    };
+    let RouteInfo {
+        path: page_path,
+        template_fn,
+        locale,
+    } = match route {
+        RouteVerdict::Found(route_info) => route_info,
+        _ => {
+            return Err(JsValue::from_str(&format!(
+                "no page to embed at '{}'",
+                path
+            )))
+        }
+    };
+
+    let translations_manager = Rc::new(RefCell::new(ClientTranslationsManager::new(&locales)));
+    let error_pages = Rc::new(get_error_pages());
+    // Each embedded page is managed separately
+    let mounted_template = Rc::new(RefCell::new(MountedTemplate::new()));
+    let scoped_states = ScopedStates::new();
+
+    // Styles can be scoped to embedded pages with the class of the element we render into
+    let root = document.create_element("div")?;
+    root.set_class_name(EMBED_CLASS);
+    root.set_attribute(EMBED_PATH_ATTR, &path)?;
+    host.append_child(&root)?;
+    sycamore::render_to(
+        || {
+            app_shell(
+                page_path,
+                template_fn,
+                locale,
+                translations_manager,
+                error_pages,
+                mounted_template,
+                scoped_states,
+            )
+        },
+        &root,
+    );
+
+    Ok(())
+}
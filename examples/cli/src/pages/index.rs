@@ -22,7 +22,7 @@ pub fn get_page<G: GenericNode>() -> Template<G> {
         .template(template_fn())
 }
 
-pub async fn get_static_props(_path: String) -> StringResultWithCause<String> {
+pub async fn get_static_props(_path: String, _locale: String) -> StringResultWithCause<String> {
     Ok(serde_json::to_string(&IndexPageProps {
         greeting: "Hello World!".to_string(),
     })
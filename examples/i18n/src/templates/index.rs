@@ -1,4 +1,4 @@
-use perseus::{link, t, Template, Translator};
+use perseus::{t, url, Template};
 use std::rc::Rc;
 use sycamore::prelude::{component, template, GenericNode, Template as SycamoreTemplate};
 
@@ -9,7 +9,7 @@ pub fn index_page() -> SycamoreTemplate<G> {
         p { (t!("hello", {
             "user": username
         })) }
-        a(href = link!("/about")) { "About" }
+        a(href = url!("/about")) { "About" }
     }
 }
 
@@ -10,7 +10,9 @@ pub fn about_page() -> SycamoreTemplate<G> {
 }
 
 pub fn get_template<G: GenericNode>() -> Template<G> {
-    Template::new("about").template(template_fn())
+    Template::new("about")
+        .template(template_fn())
+        .head(head_fn())
 }
 
 pub fn template_fn<G: GenericNode>() -> perseus::template::TemplateFn<G> {
@@ -20,3 +22,11 @@ pub fn template_fn<G: GenericNode>() -> perseus::template::TemplateFn<G> {
         }
     })
 }
+
+pub fn head_fn() -> perseus::template::HeadFn {
+    Rc::new(|_| {
+        template! {
+            title { "About Page | Perseus Example – Showcase" }
+        }
+    })
+}
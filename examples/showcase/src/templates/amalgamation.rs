@@ -19,36 +19,37 @@ pub fn get_template<G: GenericNode>() -> Template<G> {
     Template::new("amalgamation")
         .build_state_fn(Rc::new(get_build_state))
         .request_state_fn(Rc::new(get_request_state))
-        .amalgamate_states_fn(Rc::new(amalgamate_states))
+        .typed_amalgamate_states_fn(amalgamate_states)
         .template(template_fn())
 }
 
-pub fn amalgamate_states(states: States) -> StringResultWithCause<Option<String>> {
+pub async fn amalgamate_states(
+    states: States<AmalagamationPageProps>,
+) -> StringResultWithCause<Option<AmalagamationPageProps>> {
     // We know they'll both be defined
-    let build_state =
-        serde_json::from_str::<AmalagamationPageProps>(&states.build_state.unwrap()).unwrap();
-    let req_state =
-        serde_json::from_str::<AmalagamationPageProps>(&states.request_state.unwrap()).unwrap();
+    let build_state = states.build_state.unwrap();
+    let req_state = states.request_state.unwrap();
 
-    Ok(Some(
-        serde_json::to_string(&AmalagamationPageProps {
-            message: format!(
-                "Hello from the amalgamation! (Build says: '{}', server says: '{}'.)",
-                build_state.message, req_state.message
-            ),
-        })
-        .unwrap(),
-    ))
+    Ok(Some(AmalagamationPageProps {
+        message: format!(
+            "Hello from the amalgamation! (Build says: '{}', server says: '{}'.)",
+            build_state.message, req_state.message
+        ),
+    }))
 }
 
-pub async fn get_build_state(_path: String) -> StringResultWithCause<String> {
+pub async fn get_build_state(_path: String, _locale: String) -> StringResultWithCause<String> {
     Ok(serde_json::to_string(&AmalagamationPageProps {
         message: "Hello from the build process!".to_string(),
     })
     .unwrap())
 }
 
-pub async fn get_request_state(_path: String, _req: Request) -> StringResultWithCause<String> {
+pub async fn get_request_state(
+    _path: String,
+    _locale: String,
+    _req: Request,
+) -> StringResultWithCause<String> {
     // Err(("this is a test error!".to_string(), perseus::ErrorCause::Client(None)))
     Ok(serde_json::to_string(&AmalagamationPageProps {
         message: "Hello from the server!".to_string(),
@@ -1,6 +1,5 @@
 use perseus::{StringResultWithCause, Template};
 use serde::{Deserialize, Serialize};
-use std::rc::Rc;
 use sycamore::prelude::{component, template, GenericNode, Template as SycamoreTemplate};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -15,25 +14,22 @@ pub fn index_page(props: IndexPageProps) -> SycamoreTemplate<G> {
     }
 }
 
+// This template uses typed state, so we don't have to worry about serializing and deserializing it ourselves
 pub fn get_template<G: GenericNode>() -> Template<G> {
     Template::new("index")
-        .build_state_fn(Rc::new(get_static_props))
-        .template(template_fn())
+        .typed_build_state_fn(get_static_props)
+        .typed_template(|props: Option<IndexPageProps>| {
+            template! {
+                IndexPage(props.unwrap())
+            }
+        })
 }
 
-pub async fn get_static_props(_path: String) -> StringResultWithCause<String> {
-    Ok(serde_json::to_string(&IndexPageProps {
+pub async fn get_static_props(
+    _path: String,
+    _locale: String,
+) -> StringResultWithCause<IndexPageProps> {
+    Ok(IndexPageProps {
         greeting: "Hello World!".to_string(),
     })
-    .unwrap())
-}
-
-pub fn template_fn<G: GenericNode>() -> perseus::template::TemplateFn<G> {
-    Rc::new(|props| {
-        template! {
-            IndexPage(
-                serde_json::from_str::<IndexPageProps>(&props.unwrap()).unwrap()
-            )
-        }
-    })
 }
@@ -27,7 +27,11 @@ pub fn get_template<G: GenericNode>() -> Template<G> {
         .template(template_fn())
 }
 
-pub async fn get_request_state(_path: String, req: Request) -> StringResultWithCause<String> {
+pub async fn get_request_state(
+    _path: String,
+    _locale: String,
+    req: Request,
+) -> StringResultWithCause<String> {
     // Err(("this is a test error!".to_string(), perseus::ErrorCause::Client(None)))
     Ok(serde_json::to_string(&IpPageProps {
         // Gets the client's IP address
@@ -1,4 +1,4 @@
-use perseus::{ErrorCause, StringResultWithCause, Template};
+use perseus::{BuildState, StringResultWithCause, Template};
 use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 use sycamore::prelude::{component, template, GenericNode, Template as SycamoreTemplate};
@@ -31,10 +31,10 @@ pub fn get_template<G: GenericNode>() -> Template<G> {
         .template(template_fn())
 }
 
-pub async fn get_static_props(path: String) -> StringResultWithCause<String> {
-    // This path is illegal, and can't be rendered
+pub async fn get_static_props(path: String, _locale: String) -> StringResultWithCause<BuildState> {
+    // This path doesn't correspond to a real post, so it should be a 404
     if path == "post/tests" {
-        return Err(("illegal page".to_string(), ErrorCause::Client(Some(404))));
+        return Ok(BuildState::not_found());
     }
     // This is just an example
     let title = urlencoding::decode(&path).unwrap();
@@ -47,7 +47,8 @@ pub async fn get_static_props(path: String) -> StringResultWithCause<String> {
         title: title.to_string(),
         content,
     })
-    .unwrap())
+    .unwrap()
+    .into())
 }
 
 pub async fn get_static_paths() -> Result<Vec<String>, String> {
@@ -25,7 +25,7 @@ pub fn get_template<G: GenericNode>() -> Template<G> {
         .build_paths_fn(Rc::new(get_build_paths))
 }
 
-pub async fn get_build_state(_path: String) -> StringResultWithCause<String> {
+pub async fn get_build_state(_path: String, _locale: String) -> StringResultWithCause<String> {
     Ok(serde_json::to_string(&TimePageProps {
         time: format!("{:?}", std::time::SystemTime::now()),
     })
@@ -1,4 +1,4 @@
-use perseus::{StringResultWithCause, Template};
+use perseus::{Request, RevalidationContext, StringResultWithCause, Template};
 use serde::{Deserialize, Serialize};
 use std::rc::Rc;
 use sycamore::prelude::{component, template, GenericNode, Template as SycamoreTemplate};
@@ -21,11 +21,13 @@ pub fn get_template<G: GenericNode>() -> Template<G> {
         // This page will revalidate every five seconds (to illustrate revalidation)
         // Try changing this to a week, even though the below custom logic says to always revalidate, we'll only do it weekly
         .revalidate_after("5s".to_string())
-        .should_revalidate_fn(Rc::new(|| async { Ok(true) }))
+        .should_revalidate_fn(Rc::new(
+            |_: String, _: Request, _: RevalidationContext| async { Ok(true) },
+        ))
         .build_state_fn(Rc::new(get_build_state))
 }
 
-pub async fn get_build_state(_path: String) -> StringResultWithCause<String> {
+pub async fn get_build_state(_path: String, _locale: String) -> StringResultWithCause<String> {
     Ok(serde_json::to_string(&TimePageProps {
         time: format!("{:?}", std::time::SystemTime::now()),
     })
@@ -0,0 +1,29 @@
+use crate::conv_req::convert_req;
+use actix_web::dev::ServiceRequest;
+use actix_web::HttpResponse;
+use perseus::{AccessControl, AccessVerdict};
+
+/// Checks if the given request is allowed through the app's access control, returning the response that should be sent instead if it
+/// isn't.
+pub fn check_access(req: &ServiceRequest, access_control: &AccessControl) -> Option<HttpResponse> {
+    if access_control.is_open() {
+        return None;
+    }
+    // We need to turn the Actix Web request into one acceptable for Perseus (uses `http` internally)
+    let http_req = match convert_req(req.request()) {
+        Ok(http_req) => http_req,
+        // If this fails, the client request is malformed, so it's a 400
+        Err(err) => return Some(HttpResponse::BadRequest().body(err.to_string())),
+    };
+    let peer_ip = req.peer_addr().map(|addr| addr.ip());
+
+    match access_control.check(&http_req, peer_ip) {
+        AccessVerdict::Allowed => None,
+        AccessVerdict::Unauthorized(challenge) => Some(
+            HttpResponse::Unauthorized()
+                .header("WWW-Authenticate", challenge)
+                .body("unauthorized"),
+        ),
+        AccessVerdict::Forbidden => Some(HttpResponse::Forbidden().body("forbidden")),
+    }
+}
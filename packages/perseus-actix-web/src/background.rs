@@ -0,0 +1,9 @@
+use perseus::background::take_queued_jobs;
+
+/// Spawns the background jobs the current request queued, which will start running once the handler has returned its response (since
+/// each Actix Web worker runs everything on one thread).
+pub fn spawn_background_jobs() {
+    for job in take_queued_jobs() {
+        actix_web::rt::spawn(job);
+    }
+}
@@ -1,10 +1,20 @@
+use crate::access::check_access;
+use crate::initial_load::initial_load;
 use crate::page_data::page_data;
-use crate::translations::translations;
+use crate::route_table::route_table;
+use crate::translations::{translations, translations_revision};
 use actix_files::NamedFile;
-use actix_web::web;
-use perseus::{get_render_cfg, ConfigManager, Locales, SsrNode, TemplateMap, TranslationsManager};
+use actix_web::dev::Service;
+use actix_web::middleware::{Condition, DefaultHeaders};
+use actix_web::{web, HttpRequest};
+use futures::future::{ok, Either};
+use perseus::{
+    get_render_cfg, AccessControl, Artifact, ConfigManager, ContentTypes, Locales, Redirects,
+    SsrNode, SurrogateKeyHeader, TemplateMap, TranslationsManager,
+};
 
-/// The options for setting up the Actix Web integration. This should be literally constructed, as nothing is optional.
+/// The options for setting up the Actix Web integration. These should be created with `Options::new()`, which takes everything that's
+/// required, and then anything optional can be set with the builder methods of the same names as the fields (e.g. `.static_dir()`).
 #[derive(Clone)]
 pub struct Options {
     /// The location on the filesystem of your JavaScript bundle.
@@ -13,20 +23,140 @@ pub struct Options {
     pub wasm_bundle: String,
     /// The location on the filesystem of your `index.html` file that includes the JS bundle.
     pub index: String,
-    /// A `HashMap` of your app's templates by their paths.
+    /// A map of your app's templates by their paths.
     pub templates_map: TemplateMap<SsrNode>,
     /// The locales information for the app.
     pub locales: Locales,
+    /// The header to send surrogate keys in for CDN cache purging (e.g. `Surrogate-Key` for Fastly or `Cache-Tag` for Cloudflare), if
+    /// they should be sent at all.
+    pub surrogate_key_header: Option<SurrogateKeyHeader>,
+    /// HTML to show at the top of every page to users without JavaScript (in a `<noscript>` element), if any.
+    pub noscript: Option<String>,
+    /// The app's static redirects from old paths to new paths or URLs.
+    pub redirects: Redirects,
+    /// The location on the filesystem of the script for embedding your app's pages into other sites, if they should be embeddable. If
+    /// this is set, other sites will be allowed to load your app's bundles, page data, and translations.
+    pub embed_bundle: Option<String>,
+    /// The protection for the whole app (like HTTP basic auth for a staging deployment), which can be `AccessControl::new()` to allow
+    /// everything.
+    pub access_control: AccessControl,
+    /// The content types to send the app's artifacts with, which can be `ContentTypes::new()` to use the defaults.
+    pub content_types: ContentTypes,
+    /// The location on the filesystem of a directory of static files (like images and videos) to serve at `/.perseus/static`, if there
+    /// is one.
+    pub static_dir: Option<String>,
+    /// The token that has to be given as a bearer token to get the app's live route table from `/.perseus/routes` (for debugging stale
+    /// pages in production), if it should be available at all.
+    pub route_table_token: Option<String>,
+}
+impl Options {
+    /// Creates new options from the locations on the filesystem of your `index.html` file, your JavaScript bundle, and your WASM bundle,
+    /// along with your app's templates and locales. Everything else is left out (or allows everything, in the case of access control).
+    pub fn new(
+        index: impl Into<String>,
+        js_bundle: impl Into<String>,
+        wasm_bundle: impl Into<String>,
+        templates_map: TemplateMap<SsrNode>,
+        locales: Locales,
+    ) -> Self {
+        Self {
+            js_bundle: js_bundle.into(),
+            wasm_bundle: wasm_bundle.into(),
+            index: index.into(),
+            templates_map,
+            locales,
+            surrogate_key_header: None,
+            noscript: None,
+            redirects: Redirects::default(),
+            embed_bundle: None,
+            access_control: AccessControl::new(),
+            content_types: ContentTypes::new(),
+            static_dir: None,
+            route_table_token: None,
+        }
+    }
+    /// Sets the header to send surrogate keys in for CDN cache purging.
+    pub fn surrogate_key_header(mut self, val: Option<SurrogateKeyHeader>) -> Self {
+        self.surrogate_key_header = val;
+        self
+    }
+    /// Sets the HTML to show at the top of every page to users without JavaScript.
+    pub fn noscript(mut self, val: Option<String>) -> Self {
+        self.noscript = val;
+        self
+    }
+    /// Sets the app's static redirects.
+    pub fn redirects(mut self, val: Redirects) -> Self {
+        self.redirects = val;
+        self
+    }
+    /// Sets the location on the filesystem of the script for embedding your app's pages into other sites, which will make them
+    /// embeddable.
+    pub fn embed_bundle(mut self, val: Option<String>) -> Self {
+        self.embed_bundle = val;
+        self
+    }
+    /// Sets the protection for the whole app.
+    pub fn access_control(mut self, val: AccessControl) -> Self {
+        self.access_control = val;
+        self
+    }
+    /// Sets the content types to send the app's artifacts with.
+    pub fn content_types(mut self, val: ContentTypes) -> Self {
+        self.content_types = val;
+        self
+    }
+    /// Sets the location on the filesystem of the directory of static files to serve at `/.perseus/static`.
+    pub fn static_dir(mut self, val: Option<String>) -> Self {
+        self.static_dir = val;
+        self
+    }
+    /// Sets the token that has to be given to get the app's live route table from `/.perseus/routes`.
+    pub fn route_table_token(mut self, val: Option<String>) -> Self {
+        self.route_table_token = val;
+        self
+    }
 }
 
+/// Opens the given file to be sent as the given artifact, with the content type the app wants for it rather than one guessed by Actix Web.
+fn open_artifact(
+    path: impl AsRef<std::path::Path>,
+    artifact: Artifact,
+    opts: &Options,
+) -> std::io::Result<NamedFile> {
+    let file = NamedFile::open(path)?;
+    match opts.content_types.get(&artifact).parse::<mime::Mime>() {
+        Ok(content_type) => Ok(file.set_content_type(content_type)),
+        Err(_) => Ok(file),
+    }
+}
 async fn js_bundle(opts: web::Data<Options>) -> std::io::Result<NamedFile> {
-    NamedFile::open(&opts.js_bundle)
+    open_artifact(&opts.js_bundle, Artifact::Js, &opts)
 }
 async fn wasm_bundle(opts: web::Data<Options>) -> std::io::Result<NamedFile> {
-    NamedFile::open(&opts.wasm_bundle)
+    open_artifact(&opts.wasm_bundle, Artifact::Wasm, &opts)
 }
-async fn index(opts: web::Data<Options>) -> std::io::Result<NamedFile> {
-    NamedFile::open(&opts.index)
+async fn embed_bundle(opts: web::Data<Options>) -> std::io::Result<NamedFile> {
+    match &opts.embed_bundle {
+        Some(embed_bundle) => open_artifact(embed_bundle, Artifact::Js, &opts),
+        None => Err(std::io::ErrorKind::NotFound.into()),
+    }
+}
+
+/// Serves a file from the static directory, supporting requests for ranges of it (so that videos can be seeked through).
+async fn static_file(req: HttpRequest, opts: web::Data<Options>) -> std::io::Result<NamedFile> {
+    let not_found = || std::io::Error::from(std::io::ErrorKind::NotFound);
+    let static_dir = opts.static_dir.as_ref().ok_or_else(not_found)?;
+    let filename = req.match_info().query("filename");
+    // Nothing outside the static directory should be served, so we resolve any `..`s and symlinks (which also checks that the file
+    // exists) and make sure we're still in it
+    let static_dir = std::fs::canonicalize(static_dir)?;
+    let path = std::fs::canonicalize(static_dir.join(filename)).map_err(|_| not_found())?;
+    if !path.starts_with(&static_dir) || !path.is_file() {
+        return Err(not_found());
+    }
+    // `NamedFile` handles `Range` headers for us
+    open_artifact(path, Artifact::Static(filename), &opts)
 }
 
 /// Configures an existing Actix Web app for Perseus. This returns a function that does the configuring so it can take arguments.
@@ -39,29 +169,55 @@ pub async fn configurer<C: ConfigManager + 'static, T: TranslationsManager + 'st
         .await
         .expect("Couldn't get render configuration!");
     move |cfg: &mut web::ServiceConfig| {
+        // Embedded pages load everything from here, but they're rendered on other sites
+        let allow_embedding = Condition::new(
+            opts.embed_bundle.is_some(),
+            DefaultHeaders::new().header("Access-Control-Allow-Origin", "*"),
+        );
+        let access_control = opts.access_control.clone();
         cfg
             // We implant the render config in the app data for better performance, it's needed on every request
             .data(render_cfg.clone())
             .data(config_manager.clone())
             .data(translations_manager.clone())
             .data(opts.clone())
-            // TODO chunk JS and WASM bundles
-            // These allow getting the basic app code (not including the static data)
-            // This contains everything in the spirit of a pseudo-SPA
-            .route("/.perseus/bundle.js", web::get().to(js_bundle))
-            .route("/.perseus/bundle.wasm", web::get().to(wasm_bundle))
-            // This allows getting the static HTML/JSON of a page
-            // We stream both together in a single JSON object so SSR works (otherwise we'd have request IDs and weird caching...)
-            .route(
-                "/.perseus/page/{locale}/{filename:.*}",
-                web::get().to(page_data::<C, T>),
-            )
-            // This allows the app shell to fetch translations for a given page
-            .route(
-                "/.perseus/translations/{locale}",
-                web::get().to(translations::<T>),
-            )
-            // For everything else, we'll serve the app shell directly
-            .route("*", web::get().to(index));
+            // This has its own authentication, so it's outside the app's access control (which might use the same header)
+            .route("/.perseus/routes", web::get().to(route_table::<C>))
+            .service(
+                web::scope("")
+                    // Everything is behind the app's access control (if it has any)
+                    .wrap_fn(move |req, srv| match check_access(&req, &access_control) {
+                        Some(res) => Either::Right(ok(req.into_response(res))),
+                        None => Either::Left(srv.call(req)),
+                    })
+                    .service(
+                        web::scope("/.perseus")
+                            .wrap(allow_embedding)
+                            // TODO chunk JS and WASM bundles
+                            // These allow getting the basic app code (not including the static data)
+                            // This contains everything in the spirit of a pseudo-SPA
+                            .route("/bundle.js", web::get().to(js_bundle))
+                            .route("/bundle.wasm", web::get().to(wasm_bundle))
+                            // This allows other sites to embed the app's pages
+                            .route("/embed.js", web::get().to(embed_bundle))
+                            // This allows getting the static HTML/JSON of a page
+                            // We stream both together in a single JSON object so SSR works (otherwise we'd have request IDs and weird caching...)
+                            .route(
+                                "/page/{locale}/{filename:.*}",
+                                web::get().to(page_data::<C, T>),
+                            )
+                            // This allows the app shell to fetch translations for a given page
+                            .route("/translations/{locale}", web::get().to(translations::<T>))
+                            // In development, this tells the app shell when translations have been edited so it can reload them
+                            .route(
+                                "/dev/translations",
+                                web::get().to(translations_revision::<T>),
+                            )
+                            // This serves the user's static files (like images and videos)
+                            .route("/static/{filename:.*}", web::get().to(static_file)),
+                    )
+                    // For everything else, we'll serve the app shell with the page prerendered into it
+                    .route("*", web::get().to(initial_load::<C, T>)),
+            );
     }
 }
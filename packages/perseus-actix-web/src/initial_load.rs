@@ -0,0 +1,86 @@
+use crate::background::spawn_background_jobs;
+use crate::conv_req::convert_req;
+use crate::stream::stream_chunks;
+use crate::Options;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use perseus::flash::set_redirect_flash;
+use perseus::http::header::HeaderMap;
+use perseus::surrogate_keys::set_surrogate_keys;
+use perseus::{get_initial_load, Artifact, ConfigManager, InitialLoad, TranslationsManager};
+use std::collections::HashMap;
+use std::fs;
+
+/// The handler for requests for pages themselves, which serves the app shell with the page prerendered into it, so that it works even
+/// without JavaScript.
+pub async fn initial_load<C: ConfigManager, T: TranslationsManager>(
+    req: HttpRequest,
+    opts: web::Data<Options>,
+    render_cfg: web::Data<HashMap<String, String>>,
+    config_manager: web::Data<C>,
+    translations_manager: web::Data<T>,
+) -> HttpResponse {
+    let index = match fs::read_to_string(&opts.index) {
+        Ok(index) => index,
+        Err(err) => return HttpResponse::InternalServerError().body(err.to_string()),
+    };
+    // Perseus expects paths decoded, like Actix Web gives them to the other handlers
+    let path = match urlencoding::decode(req.path()) {
+        Ok(path) => path.to_string(),
+        Err(_) => return HttpResponse::BadRequest().body("malformed path".to_string()),
+    };
+    // We need to turn the Actix Web request into one acceptable for Perseus (uses `http` internally)
+    let http_req = match convert_req(&req) {
+        Ok(http_req) => http_req,
+        // If this fails, the client request is malformed, so it's a 400
+        Err(err) => return HttpResponse::BadRequest().body(err.to_string()),
+    };
+    let initial_load = get_initial_load(
+        &path,
+        &index,
+        http_req,
+        &render_cfg,
+        &opts.templates_map,
+        &opts.locales,
+        &opts.redirects,
+        opts.noscript.as_deref(),
+        config_manager.get_ref(),
+        translations_manager.get_ref(),
+    )
+    .await;
+    // Anything the page queued to run after the response will start once we've returned it
+    spawn_background_jobs();
+
+    match initial_load {
+        InitialLoad::Page(page) => {
+            let mut res = HttpResponse::Ok();
+            res.content_type(opts.content_types.get(&Artifact::Html));
+            // Headers like `Cache-Control` should apply to the page itself, not just its data
+            let mut headers = page.headers().clone();
+            // CDNs cache the page as well as its data, so they need to know what to purge it for too
+            if let Some(surrogate_key_header) = &opts.surrogate_key_header {
+                set_surrogate_keys(&mut headers, page.surrogate_keys(), surrogate_key_header);
+            }
+            for (key, val) in headers.iter() {
+                res.header(key.clone(), val.clone());
+            }
+            res.streaming(stream_chunks(page.into_chunks()))
+        }
+        InitialLoad::Redirect(redirect) => {
+            let mut res = HttpResponse::build(
+                StatusCode::from_u16(redirect.status).unwrap_or(StatusCode::FOUND),
+            );
+            // Any flash message the redirect has should be delivered to wherever the user ends up
+            let mut headers = HeaderMap::new();
+            set_redirect_flash(&mut headers, &redirect);
+            for (key, val) in headers.iter() {
+                res.header(key.clone(), val.clone());
+            }
+            res.header("Location", redirect.to).finish()
+        }
+        InitialLoad::Shell(html, status) => {
+            HttpResponse::build(StatusCode::from_u16(status).unwrap())
+                .content_type(opts.content_types.get(&Artifact::Html))
+                .body(html)
+        }
+    }
+}
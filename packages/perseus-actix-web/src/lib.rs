@@ -28,10 +28,24 @@
 
 #![deny(missing_docs)]
 
+mod access;
+mod background;
 mod configurer;
 mod conv_req;
 pub mod errors;
+mod initial_load;
 mod page_data;
+mod route_table;
+mod stream;
 mod translations;
+#[cfg(feature = "uploads")]
+mod uploads;
 
 pub use crate::configurer::{configurer, Options};
+// These are part of the options, and the server the CLI generates doesn't depend on Perseus directly
+#[cfg(feature = "uploads")]
+pub use crate::uploads::parse_upload;
+pub use perseus::route_table::ROUTE_TABLE_TOKEN_VAR;
+pub use perseus::{AccessControl, ContentTypes};
+// The server the CLI generates waits for these when it shuts down
+pub use perseus::background::wait_for_jobs;
@@ -1,7 +1,16 @@
+use crate::background::spawn_background_jobs;
 use crate::conv_req::convert_req;
+use crate::stream::stream_chunks;
 use crate::Options;
 use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
-use perseus::{err_to_status_code, get_page, ConfigManager, TranslationsManager};
+use perseus::flash::{clear_flash, set_redirect_flash};
+use perseus::http::header::HeaderMap;
+use perseus::serve::PageData;
+use perseus::surrogate_keys::set_surrogate_keys;
+use perseus::{
+    err_to_retry_after, err_to_status_code, get_streamed_page_for_template, get_template_for_path,
+    Artifact, ConfigManager, TranslationsManager,
+};
 use std::collections::HashMap;
 
 /// The handler for calls to `.perseus/page/*`. This will manage returning errors and the like.
@@ -17,6 +26,12 @@ pub async fn page_data<C: ConfigManager, T: TranslationsManager>(
     // Check if the locale is supported
     if opts.locales.is_supported(locale) {
         let path = req.match_info().query("filename");
+        // Pages that have moved are redirected before we bother rendering anything
+        if let Some(redirect) = opts.redirects.get_for_page(path, locale, &opts.locales) {
+            return HttpResponse::Ok()
+                .content_type(opts.content_types.get(&Artifact::PageData))
+                .body(serde_json::to_string(&PageData::for_redirect(redirect)).unwrap());
+        }
         // We need to turn the Actix Web request into one acceptable for Perseus (uses `http` internally)
         let http_req = convert_req(&req);
         let http_req = match http_req {
@@ -27,23 +42,67 @@ pub async fn page_data<C: ConfigManager, T: TranslationsManager>(
                     .body(err.to_string())
             }
         };
-        let page_data = get_page(
+        // Get the template to use
+        let template = match get_template_for_path(path, &render_cfg, templates) {
+            Ok(template) => template,
+            Err(err) => {
+                return HttpResponse::build(StatusCode::from_u16(err_to_status_code(&err)).unwrap())
+                    .body(err.to_string())
+            }
+        };
+        // Streaming templates have their pages' content rendered while the rest of their data is being sent
+        let page_data = get_streamed_page_for_template(
             path,
             locale,
+            template,
             http_req,
-            &render_cfg,
-            templates,
             config_manager.get_ref(),
             translations_manager.get_ref(),
         )
         .await;
+        // Anything the page queued to run after the response will start once we've returned it
+        spawn_background_jobs();
 
         match page_data {
-            Ok(page_data) => HttpResponse::Ok().body(serde_json::to_string(&page_data).unwrap()),
+            Ok(streamed) => {
+                let page_data = &streamed.page_data;
+                let mut res = HttpResponse::Ok();
+                res.content_type(opts.content_types.get(&Artifact::PageData));
+                // Add any headers the template wants for this page (placeholders don't have any state to base them on)
+                let mut headers = match page_data.fallback {
+                    true => HeaderMap::new(),
+                    false => template.get_headers(page_data.state.clone()),
+                };
+                // If we've delivered a flash message, it shouldn't be delivered again
+                if page_data.flash.is_some() {
+                    clear_flash(&mut headers);
+                }
+                // A flash message sent with a redirect is delivered to wherever the user ends up, which the app will navigate to
+                if let Some(redirect) = &page_data.redirect {
+                    set_redirect_flash(&mut headers, redirect);
+                }
+                // Let CDNs know what this page depends on so it can be purged when that changes
+                if let Some(surrogate_key_header) = &opts.surrogate_key_header {
+                    let keys = template.get_surrogate_keys(path, locale, page_data.state.clone());
+                    set_surrogate_keys(&mut headers, &keys, surrogate_key_header);
+                }
+                for (key, val) in headers.iter() {
+                    res.header(key.clone(), val.clone());
+                }
+                match template.is_streaming() {
+                    true => res.streaming(stream_chunks(streamed.into_chunks())),
+                    false => res.body(serde_json::to_string(&streamed.page_data).unwrap()),
+                }
+            }
             // We parse the error to return an appropriate status code
             Err(err) => {
-                HttpResponse::build(StatusCode::from_u16(err_to_status_code(&err)).unwrap())
-                    .body(err.to_string())
+                let mut res =
+                    HttpResponse::build(StatusCode::from_u16(err_to_status_code(&err)).unwrap());
+                // If the server was too busy, the client should know when to try again
+                if let Some(retry_after) = err_to_retry_after(&err) {
+                    res.header("Retry-After", retry_after.to_string());
+                }
+                res.body(err.to_string())
             }
         }
     } else {
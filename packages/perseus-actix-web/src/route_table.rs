@@ -0,0 +1,43 @@
+use crate::Options;
+use actix_web::{web, HttpRequest, HttpResponse};
+use perseus::route_table::is_authorized;
+use perseus::{get_route_table, ConfigManager};
+use std::collections::HashMap;
+
+/// The handler for calls to `.perseus/routes`, which returns the live route table of the app as JSON if the request gives the route table
+/// token as a bearer token in its `Authorization` header. If there's no token, this will always return a 404.
+pub async fn route_table<C: ConfigManager>(
+    req: HttpRequest,
+    opts: web::Data<Options>,
+    render_cfg: web::Data<HashMap<String, String>>,
+    config_manager: web::Data<C>,
+) -> HttpResponse {
+    let token = match &opts.route_table_token {
+        Some(token) => token,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    let auth_header = req
+        .headers()
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok());
+    if !is_authorized(auth_header, token) {
+        return HttpResponse::Unauthorized()
+            .header("WWW-Authenticate", "Bearer")
+            .body("unauthorized");
+    }
+    let route_table = get_route_table(
+        &render_cfg,
+        &opts.templates_map,
+        &opts.locales,
+        config_manager.get_ref(),
+    )
+    .await;
+
+    match serde_json::to_string(&route_table) {
+        Ok(route_table) => HttpResponse::Ok()
+            .content_type("application/json")
+            .header("Cache-Control", "no-store")
+            .body(route_table),
+        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+    }
+}
@@ -0,0 +1,44 @@
+use actix_web::web::Bytes;
+use futures::stream::{self, Stream};
+use futures::Future;
+use std::fmt::Display;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A future that's pending the first time it's polled, which gives Actix Web the chance to send everything it's been given so far.
+struct YieldNow(bool);
+impl Future for YieldNow {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.0 {
+            true => Poll::Ready(()),
+            false => {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Streams the given chunks of a response, sending each one before the next is rendered (so the browser gets the start of a streamed
+/// page while its content is still being rendered). If rendering a chunk fails, the response is aborted, since the status code has
+/// already been sent, and the browser shouldn't be left with a response that looks complete.
+pub fn stream_chunks<E: Display>(
+    chunks: impl Iterator<Item = Result<String, E>> + 'static,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> + Unpin {
+    Box::pin(stream::unfold(
+        (chunks, true),
+        |(mut chunks, first)| async move {
+            if !first {
+                YieldNow(false).await;
+            }
+            chunks.next().map(|chunk| {
+                let chunk = chunk
+                    .map(Bytes::from)
+                    .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()));
+                (chunk, (chunks, false))
+            })
+        },
+    ))
+}
@@ -1,28 +1,80 @@
 use crate::Options;
+use actix_web::web::Bytes;
 use actix_web::{web, HttpRequest, HttpResponse};
-use perseus::TranslationsManager;
+use futures::stream;
+use perseus::translations_manager::get_translation_prefixes;
+use perseus::{Artifact, TranslationsManager};
+use std::time::Duration;
 
 /// The handler for calls to `.perseus/translations/{locale}`. This will manage returning errors and the like. THe JSON body returned
-/// from this does NOT include the `locale` key, just a `HashMap<String, String>` of the translations themselves.
+/// from this does NOT include the `locale` key, just a `HashMap<String, String>` of the translations themselves. If the request has a
+/// `prefixes` query parameter, only the translations with IDs starting with those (comma-separated) prefixes will be returned.
 pub async fn translations<T: TranslationsManager>(
     req: HttpRequest,
     opts: web::Data<Options>,
     translations_manager: web::Data<T>,
 ) -> HttpResponse {
     let locale = req.match_info().query("locale");
+    let prefixes = get_translation_prefixes(req.query_string());
     // Check if the locale is supported
     if opts.locales.is_supported(locale) {
         // We know that the locale is supported, so any failure to get translations is a 500
         let translations = translations_manager
-            .get_translations_str_for_locale(locale.to_string())
+            .get_translations_str_for_prefixes(locale.to_string(), prefixes)
             .await;
         let translations = match translations {
             Ok(translations) => translations,
             Err(err) => return HttpResponse::InternalServerError().body(err.to_string()),
         };
 
-        HttpResponse::Ok().body(translations)
+        HttpResponse::Ok()
+            .content_type(opts.content_types.get(&Artifact::Translations))
+            .body(translations)
     } else {
         HttpResponse::NotFound().body("locale not supported".to_string())
     }
 }
+
+/// The handler for calls to `.perseus/dev/translations`, which only exists in development. This streams the revision of the app's
+/// translations as server-sent events, sending a new one whenever they're edited, so that the app shell can reload them. If the
+/// translations manager can't tell when translations change, this will return a 404.
+pub async fn translations_revision<T: TranslationsManager + 'static>(
+    translations_manager: web::Data<T>,
+) -> HttpResponse {
+    let revision = match cfg!(debug_assertions) {
+        true => translations_manager.get_translations_revision().await,
+        false => None,
+    };
+    let revision = match revision {
+        Some(revision) => revision,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    let revisions = stream::unfold(
+        (translations_manager, revision, true),
+        |(translations_manager, mut revision, is_first)| async move {
+            // We check for changes every second, which is frequent enough for someone editing translations
+            if !is_first {
+                loop {
+                    actix_web::rt::time::delay_for(Duration::from_secs(1)).await;
+                    match translations_manager.get_translations_revision().await {
+                        Some(new_revision) if new_revision != revision => {
+                            revision = new_revision;
+                            break;
+                        }
+                        _ => continue,
+                    }
+                }
+            }
+            let event = Bytes::from(format!("data: {}\n\n", revision));
+            Some((
+                Ok::<_, actix_web::Error>(event),
+                (translations_manager, revision, false),
+            ))
+        },
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(Box::pin(revisions))
+}
@@ -0,0 +1,20 @@
+use actix_web::{web, HttpRequest};
+use perseus::errors::Result;
+use perseus::uploads::parse_multipart;
+use perseus::{UploadForm, UploadLimits};
+
+/// Parses a file upload (a `multipart/form-data` body) sent to one of your app's own routes, writing any files in it to disk within the
+/// given limits. If this fails, `perseus::err_to_status_code` will give the status code to respond with (like `413` for an upload
+/// that's too large).
+pub async fn parse_upload(
+    req: &HttpRequest,
+    payload: web::Payload,
+    limits: &UploadLimits,
+) -> Result<UploadForm> {
+    let content_type = req
+        .headers()
+        .get("Content-Type")
+        .and_then(|val| val.to_str().ok())
+        .unwrap_or_default();
+    parse_multipart(content_type, payload, limits).await
+}
@@ -0,0 +1,11 @@
+# Messages for the Perseus CLI's output in English (United States)
+# The IDs here are stable, so they can be used to customize stage names in `[package.metadata.perseus.cli.stages]`
+
+generating = Generating your app
+building = Building your app to WASM
+finalizing = Finalizing bundle
+planning = Planning your app
+building-server = Building server
+serving = Your app is now live on http://{ $host }:{ $port }! To change this, re-run this command with different settings of the HOST/PORT environment variables.
+success = success!
+failure = failed!
@@ -0,0 +1,11 @@
+# Messages for the Perseus CLI's output in French (France)
+# The IDs here are stable, so they can be used to customize stage names in `[package.metadata.perseus.cli.stages]`
+
+generating = Génération de votre application
+building = Compilation de votre application en WASM
+finalizing = Finalisation du bundle
+planning = Planification de votre application
+building-server = Compilation du serveur
+serving = Votre application est en ligne sur http://{ $host }:{ $port } ! Pour changer cela, relancez cette commande avec d'autres valeurs pour les variables d'environnement HOST/PORT.
+success = succès !
+failure = échec !
@@ -1,6 +1,7 @@
 use perseus_cli::errors::*;
 use perseus_cli::{
-    build, check_env, delete_artifacts, delete_bad_dir, help, prepare, serve, PERSEUS_VERSION,
+    build, check_env, delete_artifacts, delete_bad_dir, doctor, help, prepare, routes, serve,
+    PERSEUS_VERSION,
 };
 use std::env;
 use std::io::Write;
@@ -59,6 +60,14 @@ fn core(dir: PathBuf) -> Result<i32> {
     let mut prog_args: Vec<String> = env::args().collect();
     // This will panic if the first argument is not found (which is probably someone trying to fuzz us)
     let _executable_name = prog_args.remove(0);
+    // The doctor diagnoses problems with the user's environment itself, so it has to run before we check that
+    if prog_args.get(0).map(|arg| arg.as_str()) == Some("doctor") {
+        return doctor(&dir);
+    }
+    // This only talks to a running server, so it doesn't need anything to build apps
+    if prog_args.get(0).map(|arg| arg.as_str()) == Some("routes") {
+        return routes(&prog_args, stdout);
+    }
     // Check the user's environment to make sure they have prerequisites
     check_env()?;
     // Check for special arguments
@@ -74,8 +83,12 @@ fn core(dir: PathBuf) -> Result<i32> {
             if prog_args[0] == "build" {
                 // Set up the '.perseus/' directory if needed
                 prepare(dir.clone())?;
-                // Delete old build artifacts
-                delete_artifacts(dir.clone())?;
+                // Delete old build artifacts, unless we're only rebuilding some of them (or not building anything at all)
+                if !prog_args.contains(&"--only".to_string())
+                    && !prog_args.contains(&"--dry-run".to_string())
+                {
+                    delete_artifacts(dir.clone())?;
+                }
                 let exit_code = build(dir, &prog_args)?;
                 Ok(exit_code)
             } else if prog_args[0] == "serve" {
@@ -1,14 +1,17 @@
 use crate::cmd::run_stage;
 use crate::errors::*;
+use crate::output::Output;
+use crate::translations_check::check_translations;
 use console::{style, Emoji};
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // Emojis for stages
 static GENERATING: Emoji<'_, '_> = Emoji("🔨", "");
 static BUILDING: Emoji<'_, '_> = Emoji("🏗️ ", ""); // Yes, there's a space here, for some reason it's needed...
 static FINALIZING: Emoji<'_, '_> = Emoji("📦", "");
+static PLANNING: Emoji<'_, '_> = Emoji("📋", "");
 
 /// Returns the exit code if it's non-zero.
 macro_rules! handle_exit_code {
@@ -20,24 +23,61 @@ macro_rules! handle_exit_code {
     };
 }
 
+/// Gets the filters given with `--only` in the given program arguments (e.g. `template=blog` or `path=blog/foo`), which restrict static
+/// generation to certain templates or pages.
+pub fn get_build_filters(prog_args: &[String]) -> Result<Vec<String>> {
+    let mut filters = Vec::new();
+    for (idx, arg) in prog_args.iter().enumerate() {
+        if arg != "--only" {
+            continue;
+        }
+        let filter = match prog_args.get(idx + 1) {
+            Some(filter) => filter,
+            None => bail!(ErrorKind::InvalidBuildFilter(String::new())),
+        };
+        // The filter is passed through a shell in single quotes
+        let is_valid = (filter.starts_with("template=") || filter.starts_with("path="))
+            && !filter.contains('\'');
+        if !is_valid {
+            bail!(ErrorKind::InvalidBuildFilter(filter.to_string()))
+        }
+        filters.push(filter.to_string());
+    }
+
+    Ok(filters)
+}
+
 /// Actually builds the user's code, program arguments having been interpreted. This needs to know how many steps there are in total
-/// because the serving logic also uses it.
-pub fn build_internal(dir: PathBuf, num_steps: u8) -> Result<i32> {
-    let mut target = dir;
+/// because the serving logic also uses it. If any filters are given, only the templates/pages they include will be statically generated
+/// (see `get_build_filters()`). If `embed` is set, a script for embedding the app's pages into other sites will be bundled too.
+pub fn build_internal(
+    dir: PathBuf,
+    num_steps: u8,
+    output: &Output,
+    filters: &[String],
+    embed: bool,
+) -> Result<i32> {
+    let mut target = dir.clone();
     target.extend([".perseus"]);
+    // Anything after `--` is passed through to the builder
+    let builder_args = match filters.is_empty() {
+        true => String::new(),
+        false => filters
+            .iter()
+            .map(|filter| format!(" --only '{}'", filter))
+            .fold(" --".to_string(), |args, arg| args + &arg),
+    };
 
     // Static generation
     handle_exit_code!(run_stage(
         vec![&format!(
-            "{} run",
-            env::var("PERSEUS_CARGO_PATH").unwrap_or_else(|_| "cargo".to_string())
+            "{} run{}",
+            env::var("PERSEUS_CARGO_PATH").unwrap_or_else(|_| "cargo".to_string()),
+            builder_args
         )],
         &target,
-        format!(
-            "{} {} Generating your app",
-            style(format!("[1/{}]", num_steps)).bold().dim(),
-            GENERATING
-        )
+        output.stage("generating", None, 1, num_steps, &GENERATING),
+        output
     )?);
     // WASM building
     handle_exit_code!(run_stage(
@@ -46,11 +86,8 @@ pub fn build_internal(dir: PathBuf, num_steps: u8) -> Result<i32> {
             env::var("PERSEUS_WASM_PACK_PATH").unwrap_or_else(|_| "wasm-pack".to_string())
         )],
         &target,
-        format!(
-            "{} {} Building your app to WASM",
-            style(format!("[2/{}]", num_steps)).bold().dim(),
-            BUILDING
-        )
+        output.stage("building", None, 2, num_steps, &BUILDING),
+        output
     )?);
     // Move the `pkg/` directory into `dist/pkg/`
     let pkg_dir = target.join("dist/pkg");
@@ -64,18 +101,111 @@ pub fn build_internal(dir: PathBuf, num_steps: u8) -> Result<i32> {
         bail!(ErrorKind::MovePkgDirFailed(err.to_string()));
     }
     // JS bundle generation
+    let rollup = env::var("PERSEUS_ROLLUP_PATH").unwrap_or_else(|_| "rollup".to_string());
+    let mut bundle_cmds = vec![format!(
+        "{} main.js --format iife --file dist/pkg/bundle.js",
+        rollup
+    )];
+    if embed {
+        bundle_cmds.push(format!(
+            "{} embed.js --format iife --file dist/pkg/embed.js",
+            rollup
+        ));
+    }
     handle_exit_code!(run_stage(
+        bundle_cmds.iter().map(|cmd| cmd.as_str()).collect(),
+        &target,
+        output.stage("finalizing", None, 3, num_steps, &FINALIZING),
+        output
+    )?);
+    // Let the user know how big everything is, and what's ended up in the WASM
+    report_sizes(&target, &dir.join("translations"));
+
+    Ok(0)
+}
+
+/// Formats the given number of bytes in a human-readable way.
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// Prints the sizes of the built bundles and the user's translations, along with which translator was compiled into the WASM bundle.
+/// Translations are fetched separately at runtime, so they're never part of the bundle. This is purely informational, so anything
+/// that can't be read is skipped.
+fn report_sizes(target: &Path, translations_dir: &Path) {
+    let pkg_dir = target.join("dist/pkg");
+    let wasm_size = fs::read_dir(&pkg_dir).ok().and_then(|entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.path().extension().map_or(false, |ext| ext == "wasm"))
+            .and_then(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+    });
+    let js_size = fs::metadata(pkg_dir.join("bundle.js"))
+        .ok()
+        .map(|metadata| metadata.len());
+    // Optional dependencies that weren't enabled don't end up in the lockfile
+    let translator = match fs::read_to_string(target.join("Cargo.lock")) {
+        Ok(lockfile) if lockfile.contains("name = \"fluent-bundle\"") => "with Fluent",
+        Ok(_) => "without Fluent",
+        Err(_) => "unknown translator",
+    };
+
+    println!("{}", style("Bundle sizes:").bold());
+    if let Some(wasm_size) = wasm_size {
+        println!("    WASM: {} ({})", format_size(wasm_size), translator);
+    }
+    if let Some(js_size) = js_size {
+        println!("    JS: {}", format_size(js_size));
+    }
+    if let Ok(entries) = fs::read_dir(translations_dir) {
+        let mut translations: Vec<(String, u64)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let size = entry.metadata().ok()?.len();
+                Some((entry.file_name().to_string_lossy().to_string(), size))
+            })
+            .collect();
+        translations.sort();
+        if !translations.is_empty() {
+            println!("    Translations (fetched at runtime, not in the WASM):");
+            for (name, size) in translations {
+                println!("        {}: {}", name, format_size(size));
+            }
+        }
+    }
+}
+
+/// Lists the pages that static generation would create without building anything, by running the builder in dry-run mode. Filters work
+/// just like they do for `build_internal()`.
+fn plan_internal(dir: PathBuf, output: &Output, filters: &[String]) -> Result<i32> {
+    let mut target = dir;
+    target.extend([".perseus"]);
+    let builder_args = filters
+        .iter()
+        .map(|filter| format!(" --only '{}'", filter))
+        .fold(" -- --dry-run".to_string(), |args, arg| args + &arg);
+
+    let (stdout, _, code) = run_stage(
         vec![&format!(
-            "{} main.js --format iife --file dist/pkg/bundle.js",
-            env::var("PERSEUS_ROLLUP_PATH").unwrap_or_else(|_| "rollup".to_string())
+            "{} run{}",
+            env::var("PERSEUS_CARGO_PATH").unwrap_or_else(|_| "cargo".to_string()),
+            builder_args
         )],
         &target,
-        format!(
-            "{} {} Finalizing bundle",
-            style(format!("[3/{}]", num_steps)).bold().dim(),
-            FINALIZING
-        )
-    )?);
+        output.stage("planning", None, 1, 1, &PLANNING),
+        output,
+    )?;
+    if code != 0 {
+        return Ok(code);
+    }
+    print!("{}", stdout);
 
     Ok(0)
 }
@@ -90,7 +220,25 @@ pub fn build(dir: PathBuf, prog_args: &[String]) -> Result<i32> {
     if should_watch == Some(&"-w".to_string()) || should_watch == Some(&"--watch".to_string()) {
         todo!("watching not yet supported, try a tool like 'entr'");
     }
-    let exit_code = build_internal(dir.clone(), 3)?;
+    let output = Output::load(&dir)?;
+    let filters = get_build_filters(prog_args)?;
+    // Missing translations would otherwise only be found when a page using them is rendered
+    if prog_args.contains(&"--check-translations".to_string()) {
+        let report = check_translations(&dir)?;
+        if !report.is_ok() {
+            bail!(ErrorKind::MissingTranslations(report.to_string()));
+        }
+    }
+    let exit_code = match prog_args.contains(&"--dry-run".to_string()) {
+        true => plan_internal(dir, &output, &filters)?,
+        false => build_internal(
+            dir,
+            3,
+            &output,
+            &filters,
+            prog_args.contains(&"--embed".to_string()),
+        )?,
+    };
 
     Ok(exit_code)
 }
@@ -1,4 +1,5 @@
 use crate::errors::*;
+use crate::output::Output;
 use console::Emoji;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::io::Write;
@@ -53,7 +54,12 @@ pub fn run_cmd(cmd: String, dir: &Path, pre_dump: impl Fn()) -> Result<(String,
 
 /// Runs a series of commands and provides a nice spinner with a custom message. Returns the last command's output and an appropriate exit
 /// code (0 if everything worked, otherwise the exit code of the one that failed).
-pub fn run_stage(cmds: Vec<&str>, target: &Path, message: String) -> Result<(String, String, i32)> {
+pub fn run_stage(
+    cmds: Vec<&str>,
+    target: &Path,
+    message: String,
+    output: &Output,
+) -> Result<(String, String, i32)> {
     // Tell the user about the stage with a nice progress bar
     let spinner = ProgressBar::new_spinner();
     spinner.set_style(ProgressStyle::default_spinner().tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "));
@@ -67,7 +73,7 @@ pub fn run_stage(cmds: Vec<&str>, target: &Path, message: String) -> Result<(Str
         // We make sure all commands run in the target directory ('.perseus/' itself)
         let (stdout, stderr, exit_code) = run_cmd(cmd.to_string(), target, || {
             // We're done, we'll write a more permanent version of the message
-            spinner.finish_with_message(format!("{}...{}", message, FAILURE))
+            spinner.finish_with_message(format!("{}...{}", message, output.failure()))
         })?;
         last_output = (stdout, stderr);
         // If we have a non-zero exit code, we should NOT continue (stderr has been written to the console already)
@@ -77,7 +83,7 @@ pub fn run_stage(cmds: Vec<&str>, target: &Path, message: String) -> Result<(Str
     }
 
     // We're done, we'll write a more permanent version of the message
-    spinner.finish_with_message(format!("{}...{}", message, SUCCESS));
+    spinner.finish_with_message(format!("{}...{}", message, output.success()));
 
     Ok((last_output.0, last_output.1, 0))
 }
@@ -0,0 +1,378 @@
+// This diagnoses common problems with the user's environment and project, suggesting fixes for them
+
+use crate::errors::*;
+use crate::output::Output;
+use crate::PERSEUS_VERSION;
+use cargo_toml::Manifest;
+use console::{style, Emoji};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// Emojis for outcomes
+static PASSED: Emoji<'_, '_> = Emoji("✅", "[ok]");
+static WARNING: Emoji<'_, '_> = Emoji("⚠️ ", "[warn]");
+static FAILED: Emoji<'_, '_> = Emoji("❌", "[fail]");
+
+/// The oldest version of Rust that Perseus supports.
+const MIN_RUSTC_VERSION: (u32, u32, u32) = (1, 53, 0);
+/// The target that Perseus apps are compiled to for the browser.
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// The outcome of a single check.
+enum Outcome {
+    /// Everything's fine, with some information about what was found.
+    Passed(String),
+    /// Something might cause problems, with a description and a suggested fix.
+    Warning(String, String),
+    /// Something will stop Perseus from working, with a description and a suggested fix.
+    Failed(String, String),
+}
+
+/// Runs the given command, returning its `stdout` if it succeeded.
+fn get_cmd_output(cmd: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(cmd).args(args).output().ok()?;
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => None,
+    }
+}
+
+/// Extracts the first thing that looks like a semantic version (e.g. `1.54.0`) from the given string.
+fn parse_version(text: &str) -> Option<(u32, u32, u32)> {
+    text.split(|c: char| c.is_whitespace() || c == '=')
+        .find_map(|word| {
+            // This handles things like `v1.2.3`, `^1.2`, and `"1.2.3",`
+            let word = word.trim_matches(|c: char| !c.is_ascii_digit());
+            let mut parts = word.split('.').map(|part| part.parse::<u32>());
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(Ok(major)), Some(Ok(minor)), patch) => Some((
+                    major,
+                    minor,
+                    patch.and_then(|patch| patch.ok()).unwrap_or(0),
+                )),
+                _ => None,
+            }
+        })
+}
+
+/// Checks if two versions are compatible under Cargo's rules (the same major version, or the same minor version before `1.0.0`).
+fn versions_compatible(a: (u32, u32, u32), b: (u32, u32, u32)) -> bool {
+    match a.0 {
+        0 => a.0 == b.0 && a.1 == b.1,
+        _ => a.0 == b.0,
+    }
+}
+
+/// Checks the installed version of the given tool, which is required if `required` is `true` (otherwise Perseus can get by without it).
+fn check_tool(cmd: &str, env_var: Option<&str>, required: bool, fix: &str) -> Outcome {
+    let path = env_var
+        .and_then(|env_var| env::var(env_var).ok())
+        .unwrap_or_else(|| cmd.to_string());
+    match get_cmd_output(&path, &["--version"]) {
+        Some(version) => Outcome::Passed(version),
+        None => {
+            let fix = match env_var {
+                Some(env_var) => format!(
+                    "{} If you've installed it somewhere else, set the '{}' environment variable to its path.",
+                    fix, env_var
+                ),
+                None => fix.to_string(),
+            };
+            match required {
+                true => Outcome::Failed(format!("'{}' couldn't be run", path), fix),
+                false => Outcome::Warning(format!("'{}' couldn't be run", path), fix),
+            }
+        }
+    }
+}
+
+/// Checks that the installed version of Rust is new enough.
+fn check_rustc() -> Outcome {
+    let version_str = match get_cmd_output("rustc", &["--version"]) {
+        Some(version_str) => version_str,
+        None => {
+            return Outcome::Failed(
+                "'rustc' couldn't be run".to_string(),
+                "Install Rust from https://rustup.rs.".to_string(),
+            )
+        }
+    };
+    match parse_version(&version_str) {
+        Some(version) if version < MIN_RUSTC_VERSION => Outcome::Failed(
+            format!("{} is too old", version_str),
+            format!(
+                "Perseus needs at least Rust v{}.{}.{}, run 'rustup update' to upgrade.",
+                MIN_RUSTC_VERSION.0, MIN_RUSTC_VERSION.1, MIN_RUSTC_VERSION.2
+            ),
+        ),
+        _ => Outcome::Passed(version_str),
+    }
+}
+
+/// Checks that the WebAssembly target is installed.
+fn check_wasm_target() -> Outcome {
+    match get_cmd_output("rustup", &["target", "list", "--installed"]) {
+        Some(targets) if targets.lines().any(|target| target.trim() == WASM_TARGET) => {
+            Outcome::Passed(format!("'{}' is installed", WASM_TARGET))
+        }
+        Some(_) => Outcome::Failed(
+            format!("the '{}' target isn't installed", WASM_TARGET),
+            format!("Run 'rustup target add {}'.", WASM_TARGET),
+        ),
+        None => Outcome::Warning(
+            "couldn't list installed targets (you might not be using 'rustup')".to_string(),
+            format!(
+                "Make sure the '{}' target is installed for your Rust toolchain.",
+                WASM_TARGET
+            ),
+        ),
+    }
+}
+
+/// Checks that the installed `wasm-bindgen` CLI (if any) matches the version of the library that the app is built with, which
+/// `wasm-pack` needs.
+fn check_wasm_bindgen(dir: &Path) -> Outcome {
+    let cli_version = match get_cmd_output("wasm-bindgen", &["--version"]) {
+        Some(cli_version) => cli_version,
+        None => {
+            return Outcome::Passed(
+                "not installed globally ('wasm-pack' will download the right version)".to_string(),
+            )
+        }
+    };
+    // The subcrates' lockfile has the exact version of the library that was last built with
+    let lockfile = match fs::read_to_string(dir.join(".perseus/Cargo.lock")) {
+        Ok(lockfile) => lockfile,
+        // We can't tell what version the app uses until it's been built
+        Err(_) => return Outcome::Passed(cli_version),
+    };
+    let lib_version = lockfile
+        .split("[[package]]")
+        .find(|package| package.contains("name = \"wasm-bindgen\"\n"))
+        .and_then(|package| {
+            package
+                .lines()
+                .find_map(|line| line.strip_prefix("version = "))
+                .map(|version| version.trim_matches('"').to_string())
+        });
+    match (lib_version, parse_version(&cli_version)) {
+        (Some(lib_version), Some(version)) if parse_version(&lib_version) != Some(version) => {
+            Outcome::Warning(
+                format!(
+                    "{} is installed, but your app uses v{} of the library",
+                    cli_version, lib_version
+                ),
+                format!(
+                    "Run 'cargo install wasm-bindgen-cli --version {}', or uninstall the global version to let 'wasm-pack' manage it.",
+                    lib_version
+                ),
+            )
+        }
+        _ => Outcome::Passed(cli_version),
+    }
+}
+
+/// Checks that the user's manifest is valid for a Perseus app, and that it depends on a version of Perseus that's compatible with this
+/// CLI.
+fn check_manifest(dir: &Path) -> Outcome {
+    let manifest = match Manifest::from_path(dir.join("Cargo.toml")) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            return Outcome::Failed(
+                format!("couldn't read your Cargo.toml ({})", err),
+                "Make sure you're running Perseus in your app's directory, and that its Cargo.toml is valid.".to_string(),
+            )
+        }
+    };
+    if manifest.package.is_none() {
+        return Outcome::Failed(
+            "your Cargo.toml has no '[package]' section".to_string(),
+            "Perseus apps must be a single crate, not a workspace root.".to_string(),
+        );
+    }
+    // The subcrates depend on the user's crate as a library
+    if manifest.lib.is_none() && !dir.join("src/lib.rs").exists() {
+        return Outcome::Failed(
+            "your crate isn't a library".to_string(),
+            "Perseus apps are defined with 'define_app!' in 'src/lib.rs'.".to_string(),
+        );
+    }
+    for required_dep in ["perseus", "sycamore"] {
+        if !manifest.dependencies.contains_key(required_dep) {
+            return Outcome::Failed(
+                format!("your crate doesn't depend on '{}'", required_dep),
+                format!(
+                    "Add '{}' to the '[dependencies]' in your Cargo.toml.",
+                    required_dep
+                ),
+            );
+        }
+    }
+    let perseus_req = manifest.dependencies["perseus"].req().to_string();
+    match (parse_version(&perseus_req), parse_version(PERSEUS_VERSION)) {
+        (Some(version), Some(cli_version)) if !versions_compatible(version, cli_version) => {
+            Outcome::Failed(
+                format!(
+                    "your crate depends on Perseus '{}', but this is the CLI for v{}",
+                    perseus_req, PERSEUS_VERSION
+                ),
+                format!(
+                    "Use 'perseus = \"{}\"' in your Cargo.toml, or install the matching CLI with 'cargo install perseus-cli --version {}'.",
+                    PERSEUS_VERSION, perseus_req
+                ),
+            )
+        }
+        _ => Outcome::Passed(format!("depends on Perseus '{}'", perseus_req)),
+    }
+}
+
+/// Checks that the `.perseus/` subcrates (if they've been prepared) were created by a compatible version of the CLI.
+fn check_scaffold(dir: &Path) -> Outcome {
+    let scaffold_dir = dir.join(".perseus");
+    if !scaffold_dir.exists() {
+        return Outcome::Passed("not prepared yet (it will be on the next build)".to_string());
+    }
+    let manifest = match fs::read_to_string(scaffold_dir.join("Cargo.toml")) {
+        Ok(manifest) => manifest,
+        Err(_) => {
+            return Outcome::Failed(
+                "'.perseus/' exists, but it has no Cargo.toml".to_string(),
+                "Run 'perseus clean' to have it recreated.".to_string(),
+            )
+        }
+    };
+    // We can't use `cargo_toml` here, because it would try to resolve the user's crate relative to this one
+    let scaffold_version = manifest
+        .lines()
+        .find(|line| line.starts_with("perseus = "))
+        .and_then(parse_version);
+    match (scaffold_version, parse_version(PERSEUS_VERSION)) {
+        (Some(version), Some(cli_version)) if !versions_compatible(version, cli_version) => {
+            Outcome::Failed(
+                format!(
+                    "'.perseus/' was prepared for Perseus v{}.{}.{}, but this is the CLI for v{}",
+                    version.0, version.1, version.2, PERSEUS_VERSION
+                ),
+                "Run 'perseus clean' to have it recreated with this version of the CLI."
+                    .to_string(),
+            )
+        }
+        _ => match fs::read_to_string(dir.join(".gitignore")) {
+            Ok(gitignore) if gitignore.contains(".perseus/") => {
+                Outcome::Passed("prepared and ignored by Git".to_string())
+            }
+            _ => Outcome::Warning(
+                "'.perseus/' isn't in your .gitignore".to_string(),
+                "Add '.perseus/' to your .gitignore so the generated subcrates aren't committed."
+                    .to_string(),
+            ),
+        },
+    }
+}
+
+/// Checks that the user's configuration for the CLI's output is valid.
+fn check_config(dir: &Path) -> Outcome {
+    match Output::load(dir) {
+        Ok(_) => Outcome::Passed("valid".to_string()),
+        Err(err) => Outcome::Failed(
+            err.to_string(),
+            "Fix the '[package.metadata.perseus.cli]' section of your Cargo.toml (see https://arctic-hen7.github.io/perseus/cli.html)."
+                .to_string(),
+        ),
+    }
+}
+
+/// Diagnoses common problems with the user's environment (toolchain versions and prerequisites) and project (manifest and `.perseus/`
+/// compatibility, and configuration), printing suggested fixes for anything that's wrong. This doesn't need the prerequisites to be
+/// installed, since finding out which ones aren't is part of its job. Returns an exit code, which will be non-zero if any checks failed.
+pub fn doctor(dir: &Path) -> Result<i32> {
+    // The user's configuration might be what's broken, so we fall back to the defaults
+    let output = Output::load(dir).unwrap_or_default();
+    let checks = vec![
+        ("rustc", check_rustc()),
+        ("wasm32 target", check_wasm_target()),
+        (
+            "cargo",
+            check_tool(
+                "cargo",
+                Some("PERSEUS_CARGO_PATH"),
+                true,
+                "Install Rust from https://rustup.rs.",
+            ),
+        ),
+        (
+            "wasm-pack",
+            check_tool(
+                "wasm-pack",
+                Some("PERSEUS_WASM_PACK_PATH"),
+                true,
+                "Run 'cargo install wasm-pack'.",
+            ),
+        ),
+        ("wasm-bindgen", check_wasm_bindgen(dir)),
+        (
+            "wasm-opt",
+            check_tool(
+                "wasm-opt",
+                None,
+                false,
+                "'wasm-pack' will download it when it needs to, but you can install it from https://github.com/WebAssembly/binaryen to avoid that.",
+            ),
+        ),
+        (
+            "rollup",
+            check_tool(
+                "rollup",
+                Some("PERSEUS_ROLLUP_PATH"),
+                true,
+                "Run 'npm install --global rollup'.",
+            ),
+        ),
+        ("Cargo.toml", check_manifest(dir)),
+        (".perseus/", check_scaffold(dir)),
+        ("CLI configuration", check_config(dir)),
+    ];
+
+    let mut num_failed = 0;
+    let mut num_warnings = 0;
+    for (name, outcome) in checks {
+        match outcome {
+            Outcome::Passed(info) => {
+                println!(
+                    "{} {}: {}",
+                    output.marker(&PASSED),
+                    style(name).bold(),
+                    info
+                )
+            }
+            Outcome::Warning(problem, fix) => {
+                num_warnings += 1;
+                println!(
+                    "{} {}: {}",
+                    output.marker(&WARNING),
+                    style(name).bold(),
+                    problem
+                );
+                println!("    {}", style(fix).dim());
+            }
+            Outcome::Failed(problem, fix) => {
+                num_failed += 1;
+                println!(
+                    "{} {}: {}",
+                    output.marker(&FAILED),
+                    style(name).bold(),
+                    problem
+                );
+                println!("    {}", style(fix).dim());
+            }
+        }
+    }
+    println!("\n{} failed, {} warnings", num_failed, num_warnings);
+
+    match num_failed {
+        0 => Ok(0),
+        _ => Ok(1),
+    }
+}
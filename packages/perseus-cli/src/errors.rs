@@ -78,6 +78,61 @@ error_chain! {
             description("couldn't move `pkg/` to `dist/pkg/`")
             display("Couldn't move `.perseus/pkg/` to `.perseus/dist/pkg`. Error was: '{}'.", err)
         }
+        /// For when a filter given to `perseus build --only` is invalid.
+        InvalidBuildFilter(filter: String) {
+            description("invalid build filter")
+            display("Invalid build filter '{}'. Filters must be given after '--only' as 'template=<name>' or 'path=<path>' (without any single quotes).", filter)
+        }
+        /// For when the user's configuration for the CLI's output (in `[package.metadata.perseus.cli]`) is invalid.
+        InvalidOutputConfig(err: String) {
+            description("invalid cli output configuration")
+            display("Your configuration for the CLI's output (in the `[package.metadata.perseus.cli]` section of your Cargo.toml) is invalid. Error was: '{}'.", err)
+        }
+        /// For when the user's code or translations couldn't be read to check their translation IDs.
+        ReadTranslationsFailed(path: String, err: String) {
+            description("reading translations or code failed")
+            display("Couldn't read '{}' to check your app's translation IDs. Error was: '{}'.", path, err)
+        }
+        /// For when one of the user's translations files couldn't be parsed.
+        InvalidTranslations(path: String, err: String) {
+            description("invalid translations file")
+            display("Couldn't parse the translations file at '{}'. Error was: '{}'.", path, err)
+        }
+        /// For when translation IDs used in the user's code are missing from some of their locales.
+        MissingTranslations(report: String) {
+            description("translation ids missing")
+            display("Some translation IDs used in your app are missing from your translations (by locale):\n{}", report)
+        }
+        /// For when building an app for end-to-end tests failed.
+        TestAppBuildFailed(exit_code: i32) {
+            description("building app for tests failed")
+            display("Couldn't build your app for testing, the build exited with code {} (its errors should have been printed above).", exit_code)
+        }
+        /// For when an app couldn't be copied into its own directory to be built for end-to-end tests.
+        TestAppCopyFailed(path: String, err: String) {
+            description("copying app for tests failed")
+            display("Couldn't copy your app into '{}' to build it for testing. Error was: '{}'.", path, err)
+        }
+        /// For when the server of an app being tested couldn't be started.
+        TestServerStartFailed(err: String) {
+            description("starting test server failed")
+            display("Couldn't start your app's server for testing. Error was: '{}'.", err)
+        }
+        /// For when a headless browser couldn't be started to test an app in.
+        TestBrowserStartFailed(webdriver_url: String, err: String) {
+            description("starting test browser failed")
+            display("Couldn't start a headless browser through the WebDriver server at '{}' (is one running?). Error was: '{}'.", webdriver_url, err)
+        }
+        /// For when a request to the server of an app being tested failed.
+        TestRequestFailed(url: String, err: String) {
+            description("test request failed")
+            display("Couldn't make a test request to '{}'. Error was: '{}'.", url, err)
+        }
+        /// For when the route table of a running server couldn't be fetched.
+        RouteTableFailed(url: String, err: String) {
+            description("fetching route table failed")
+            display("Couldn't get the route table from '{}'. Error was: '{}'.", url, err)
+        }
     }
 }
 
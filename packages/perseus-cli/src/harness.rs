@@ -0,0 +1,442 @@
+// This lets apps write end-to-end tests against a real build of themselves, using the same machinery as `perseus serve`
+
+use crate::build::build_internal;
+use crate::errors::*;
+use crate::output::Output;
+use crate::serve::build_server;
+use crate::{delete_artifacts, prepare};
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::process::{self, Child, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The host test servers are bound to.
+const TEST_HOST: &str = "127.0.0.1";
+/// How long to wait for a test server to start accepting connections after it's been built.
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+/// The environment variable that can be set to the URL of the WebDriver server browsers for tests should be started with.
+#[cfg(feature = "browser")]
+pub const WEBDRIVER_URL_VAR: &str = "PERSEUS_WEBDRIVER_URL";
+/// The WebDriver server used if `PERSEUS_WEBDRIVER_URL` isn't set (the default for `geckodriver` and `chromedriver --port=4444`).
+#[cfg(feature = "browser")]
+const DEFAULT_WEBDRIVER_URL: &str = "http://localhost:4444";
+/// The files and directories in an app that are never copied into its build directory for tests, since they're build artifacts.
+const UNCOPIED_PATHS: [&str; 6] = [
+    ".git",
+    "target",
+    ".perseus/target",
+    ".perseus/dist",
+    ".perseus/server/target",
+    ".perseus/Cargo.lock",
+];
+
+/// Counts the test apps started by this process, so that each one gets its own build directory.
+static TEST_APPS: AtomicUsize = AtomicUsize::new(0);
+
+/// A full build of a Perseus app being served on a random port, for end-to-end tests. This copies the app into its own temporary directory
+/// and builds it there exactly like `perseus serve` would, so tests can run in parallel without touching the app's own `.perseus/`
+/// directory, then starts its server and waits until it's accepting connections. The server is stopped and the temporary directory is
+/// deleted when this is dropped.
+///
+/// Pages can be requested with [`TestApp::get`], and [`TestApp::url`] can be given to any other HTTP client. With the `browser` feature,
+/// [`TestApp::browser`] will start a headless browser to test the app in.
+pub struct TestApp {
+    server: Child,
+    port: u16,
+    // This is declared after the server so that it's only deleted once the server has been stopped
+    #[allow(dead_code)]
+    build_dir: BuildDir,
+}
+impl TestApp {
+    /// Builds the app in the given directory (the one with its `Cargo.toml`) in a temporary directory and starts its server. The build's
+    /// progress will be printed just like it would be by the CLI. Any customizations made to the app's `.perseus/` directory will be used,
+    /// but none of its build artifacts will be, so every test app is built from scratch (setting `CARGO_TARGET_DIR` will let them share
+    /// compiled dependencies).
+    pub fn start(app_dir: impl Into<PathBuf>) -> Result<Self> {
+        let app_dir = app_dir.into();
+        let build_dir = BuildDir::new()?;
+        let dir = build_dir.0.clone();
+        copy_app(&app_dir, &dir)?;
+        // The copy's manifests can't refer to anything outside the app relatively anymore (including those of the subcrates, which we
+        // do after they've been prepared in case the app didn't have them yet)
+        absolutize_manifest(&app_dir, &dir, Path::new("Cargo.toml"))?;
+        prepare(dir.clone())?;
+        absolutize_manifest(&app_dir, &dir, Path::new(".perseus/Cargo.toml"))?;
+        absolutize_manifest(&app_dir, &dir, Path::new(".perseus/server/Cargo.toml"))?;
+        // We didn't copy any of the app's build artifacts, so there's nowhere for them to go yet
+        let dist_dir = dir.join(".perseus").join("dist");
+        fs::create_dir_all(&dist_dir).map_err(|err| {
+            ErrorKind::TestAppCopyFailed(dist_dir.to_string_lossy().to_string(), err.to_string())
+        })?;
+        delete_artifacts(dir.clone())?;
+        let output = Output::load(&dir)?;
+        let exit_code = build_internal(dir.clone(), 5, &output, &[], false)?;
+        if exit_code != 0 {
+            bail!(ErrorKind::TestAppBuildFailed(exit_code))
+        }
+        let target = dir.join(".perseus").join("server");
+        let server_exec_path = match build_server(&target, 5, &output)? {
+            Ok(server_exec_path) => server_exec_path,
+            Err(exit_code) => bail!(ErrorKind::TestAppBuildFailed(exit_code)),
+        };
+
+        let port = get_free_port()?;
+        let server = Command::new(&server_exec_path)
+            .current_dir(&target)
+            .env("HOST", TEST_HOST)
+            .env("PORT", port.to_string())
+            .spawn()
+            .map_err(|err| ErrorKind::CmdExecFailed(server_exec_path.clone(), err.to_string()))?;
+        let mut app = Self {
+            server,
+            port,
+            build_dir,
+        };
+        app.wait_for_server()?;
+
+        Ok(app)
+    }
+
+    /// Gets the full URL of the given path (like `/about`) on the test server.
+    pub fn url(&self, path: &str) -> String {
+        format!(
+            "http://{}:{}/{}",
+            TEST_HOST,
+            self.port,
+            path.trim_start_matches('/')
+        )
+    }
+    /// Makes a `GET` request to the given path (like `/about`) on the test server. This doesn't follow redirects, so they can be tested
+    /// too.
+    pub fn get(&self, path: &str) -> Result<TestResponse> {
+        let url = self.url(path);
+        let request_failed =
+            |err: std::io::Error| ErrorKind::TestRequestFailed(url.clone(), err.to_string());
+        let mut stream = TcpStream::connect((TEST_HOST, self.port)).map_err(request_failed)?;
+        // HTTP/1.0 means the server will close the connection when it's done, so we don't have to deal with chunked bodies
+        write!(
+            stream,
+            "GET /{} HTTP/1.0\r\nHost: {}:{}\r\n\r\n",
+            path.trim_start_matches('/'),
+            TEST_HOST,
+            self.port
+        )
+        .map_err(request_failed)?;
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).map_err(request_failed)?;
+
+        parse_response(&url, &String::from_utf8_lossy(&res))
+    }
+    /// Starts a headless browser to test the app in, through the WebDriver server at `PERSEUS_WEBDRIVER_URL` (or `http://localhost:4444`
+    /// if that isn't set), which should be running something like `geckodriver` or `chromedriver`. Pages can be opened with
+    /// `.goto(&app.url("/about"))`, and the browser should be closed with `.close()` at the end of the test. This needs a Tokio runtime
+    /// (e.g. `#[tokio::test]`).
+    #[cfg(feature = "browser")]
+    pub async fn browser(&self) -> Result<fantoccini::Client> {
+        let webdriver_url =
+            env::var(WEBDRIVER_URL_VAR).unwrap_or_else(|_| DEFAULT_WEBDRIVER_URL.to_string());
+        // We don't know which browser the WebDriver server is for, so we ask both Firefox and Chrome to be headless
+        let mut capabilities = serde_json::Map::new();
+        capabilities.insert(
+            "moz:firefoxOptions".to_string(),
+            serde_json::json!({ "args": ["-headless"] }),
+        );
+        capabilities.insert(
+            "goog:chromeOptions".to_string(),
+            serde_json::json!({ "args": ["--headless", "--no-sandbox"] }),
+        );
+        let client = fantoccini::ClientBuilder::native()
+            .capabilities(capabilities)
+            .connect(&webdriver_url)
+            .await
+            .map_err(|err| ErrorKind::TestBrowserStartFailed(webdriver_url, err.to_string()))?;
+
+        Ok(client)
+    }
+
+    /// Waits until the server is accepting connections, failing if it exits or takes too long to start.
+    fn wait_for_server(&mut self) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            if TcpStream::connect((TEST_HOST, self.port)).is_ok() {
+                return Ok(());
+            }
+            if let Ok(Some(status)) = self.server.try_wait() {
+                bail!(ErrorKind::TestServerStartFailed(format!(
+                    "server exited with {}",
+                    status
+                )))
+            }
+            if started.elapsed() > START_TIMEOUT {
+                bail!(ErrorKind::TestServerStartFailed(format!(
+                    "server wasn't accepting connections after {} seconds",
+                    START_TIMEOUT.as_secs()
+                )))
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        // The server might have already exited, in which case there's nothing to do
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+    }
+}
+
+/// A response from a [`TestApp`].
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    /// The status code of the response.
+    pub status: u16,
+    /// The headers of the response, in the order they were sent.
+    pub headers: Vec<(String, String)>,
+    /// The body of the response.
+    pub body: String,
+}
+impl TestResponse {
+    /// Gets the value of the first header with the given name (case-insensitively), if there is one.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, val)| val.as_str())
+    }
+}
+
+/// A temporary directory an app is built in for tests, which is deleted when this is dropped.
+struct BuildDir(PathBuf);
+impl BuildDir {
+    /// Creates a new build directory, which will be unique to this test app even if other processes are running tests too.
+    fn new() -> Result<Self> {
+        let path = env::temp_dir().join(format!(
+            "perseus-test-{}-{}",
+            process::id(),
+            TEST_APPS.fetch_add(1, Ordering::SeqCst)
+        ));
+        // Anything left here is from an old process with the same ID that didn't clean up after itself
+        if path.exists() {
+            fs::remove_dir_all(&path).map_err(|err| {
+                ErrorKind::TestAppCopyFailed(path.to_string_lossy().to_string(), err.to_string())
+            })?;
+        }
+        fs::create_dir_all(&path).map_err(|err| {
+            ErrorKind::TestAppCopyFailed(path.to_string_lossy().to_string(), err.to_string())
+        })?;
+
+        Ok(Self(path))
+    }
+}
+impl Drop for BuildDir {
+    fn drop(&mut self) {
+        // If this fails, the OS will clean it up eventually
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Copies the app in the given directory into the given build directory, leaving out any build artifacts.
+fn copy_app(app_dir: &Path, build_dir: &Path) -> Result<()> {
+    copy_dir(app_dir, build_dir, Path::new(""))
+}
+/// Recursively copies the given directory (relative to the app's root) from the app into the build directory.
+fn copy_dir(app_dir: &Path, build_dir: &Path, rel_dir: &Path) -> Result<()> {
+    let from = app_dir.join(rel_dir);
+    let copy_failed = |path: &Path, err: std::io::Error| {
+        ErrorKind::TestAppCopyFailed(path.to_string_lossy().to_string(), err.to_string())
+    };
+    let entries = fs::read_dir(&from).map_err(|err| copy_failed(&from, err))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| copy_failed(&from, err))?;
+        let rel_path = rel_dir.join(entry.file_name());
+        if UNCOPIED_PATHS
+            .iter()
+            .any(|uncopied| rel_path == Path::new(uncopied))
+        {
+            continue;
+        }
+        let to = build_dir.join(&rel_path);
+        // We follow symlinks, so the copy doesn't depend on where they were relative to
+        if entry.path().is_dir() {
+            fs::create_dir_all(&to).map_err(|err| copy_failed(&to, err))?;
+            copy_dir(app_dir, build_dir, &rel_path)?;
+        } else {
+            fs::copy(entry.path(), &to).map_err(|err| copy_failed(&to, err))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrites the paths in the given manifest (relative to the app's root) in the build directory that point outside the app (like
+/// `path = "../../packages/perseus"`) to point to the same places from the build directory, if the manifest exists.
+fn absolutize_manifest(app_dir: &Path, build_dir: &Path, manifest: &Path) -> Result<()> {
+    let manifest_path = build_dir.join(manifest);
+    let contents = match fs::read_to_string(&manifest_path) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(()),
+    };
+    // Canonicalizing the app's directory means the rewritten paths don't depend on where the tests are run from
+    let app_dir = fs::canonicalize(app_dir).unwrap_or_else(|_| app_dir.to_path_buf());
+    let manifest_dir = manifest.parent().unwrap_or_else(|| Path::new(""));
+    let updated = absolutize_paths(&contents, &app_dir, manifest_dir);
+    fs::write(&manifest_path, updated).map_err(|err| {
+        ErrorKind::TestAppCopyFailed(manifest_path.to_string_lossy().to_string(), err.to_string())
+    })?;
+
+    Ok(())
+}
+/// Rewrites every `path = "..."` in the given manifest contents that points outside the app in the given directory to an absolute path.
+/// The manifest is in the given directory relative to the app's root. Paths inside the app are copied with it, so they're left alone.
+fn absolutize_paths(contents: &str, app_dir: &Path, manifest_dir: &Path) -> String {
+    let mut updated = String::new();
+    let mut rest = contents;
+    while let Some(idx) = rest.find("path = \"") {
+        let (before, after) = rest.split_at(idx + "path = \"".len());
+        updated.push_str(before);
+        let end = match after.find('"') {
+            Some(end) => end,
+            None => break,
+        };
+        let path = &after[..end];
+        match get_path_outside_app(manifest_dir, path) {
+            Some(rel_path) => {
+                // TOML strings treat backslashes as escapes, and forward slashes work everywhere
+                let abs_path = app_dir.join(rel_path);
+                updated.push_str(&abs_path.to_string_lossy().replace('\\', "/"));
+            }
+            None => updated.push_str(path),
+        }
+        rest = &after[end..];
+    }
+    updated.push_str(rest);
+
+    updated
+}
+/// Gets the given path (from a manifest in the given directory relative to the app's root) relative to the app's root, if it points
+/// outside the app (in which case it'll start with `..`).
+fn get_path_outside_app(manifest_dir: &Path, path: &str) -> Option<PathBuf> {
+    if Path::new(path).is_absolute() {
+        return None;
+    }
+    // We resolve the path lexically, since it might not exist inside the build directory
+    let mut resolved: Vec<Component> = Vec::new();
+    for component in manifest_dir.join(path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match resolved.last() {
+                Some(Component::Normal(_)) => {
+                    resolved.pop();
+                }
+                _ => resolved.push(component),
+            },
+            _ => resolved.push(component),
+        }
+    }
+    match resolved.first() {
+        Some(Component::ParentDir) => Some(resolved.iter().collect()),
+        _ => None,
+    }
+}
+
+/// Gets a port that's free right now by binding to port 0 and letting the OS choose one. Something else could take the port before the
+/// server binds to it, but that's very unlikely.
+fn get_free_port() -> Result<u16> {
+    let listener = TcpListener::bind((TEST_HOST, 0))
+        .map_err(|err| ErrorKind::TestServerStartFailed(err.to_string()))?;
+    let port = listener
+        .local_addr()
+        .map_err(|err| ErrorKind::TestServerStartFailed(err.to_string()))?
+        .port();
+
+    Ok(port)
+}
+
+/// Parses the given raw HTTP response to a request to the given URL.
+fn parse_response(url: &str, res: &str) -> Result<TestResponse> {
+    let malformed =
+        || ErrorKind::TestRequestFailed(url.to_string(), "malformed response".to_string());
+    let (head, body) = res.split_once("\r\n\r\n").ok_or_else(malformed)?;
+    let mut lines = head.split("\r\n");
+    // The status line looks like `HTTP/1.0 200 OK`
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(malformed)?;
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, val)| (name.trim().to_string(), val.trim().to_string()))
+        .collect();
+
+    Ok(TestResponse {
+        status,
+        headers,
+        body: body.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolutizes_only_paths_outside_app() {
+        let manifest = r#"app = { package = "my-app", path = "../" }
+perseus = { path = "../../../packages/perseus", default-features = false }
+"#;
+        let updated = absolutize_paths(
+            manifest,
+            Path::new("/repo/examples/basic"),
+            Path::new(".perseus"),
+        );
+        assert_eq!(
+            updated,
+            r#"app = { package = "my-app", path = "../" }
+perseus = { path = "/repo/examples/basic/../../packages/perseus", default-features = false }
+"#
+        );
+    }
+
+    #[test]
+    fn leaves_paths_inside_app() {
+        let manifest = r#"helpers = { path = "helpers" }
+[lib]
+path = "./src/lib.rs"
+"#;
+        assert_eq!(
+            absolutize_paths(manifest, Path::new("/app"), Path::new("")),
+            manifest
+        );
+    }
+
+    #[test]
+    fn finds_paths_outside_app() {
+        assert_eq!(
+            get_path_outside_app(Path::new(".perseus/server"), "../../../other"),
+            Some(PathBuf::from("../other"))
+        );
+        assert_eq!(
+            get_path_outside_app(Path::new(".perseus/server"), "../../"),
+            None
+        );
+        assert_eq!(get_path_outside_app(Path::new(""), "/abs/path"), None);
+    }
+
+    #[test]
+    fn parses_responses() {
+        let res = parse_response(
+            "http://127.0.0.1/",
+            "HTTP/1.0 302 Found\r\nLocation: /en-US/\r\nSet-Cookie: a=b\r\n\r\nbody",
+        )
+        .unwrap();
+        assert_eq!(res.status, 302);
+        assert_eq!(res.header("location"), Some("/en-US/"));
+        assert_eq!(res.body, "body");
+        assert!(parse_response("http://127.0.0.1/", "garbage").is_err());
+    }
+}
@@ -12,8 +12,10 @@ This is the CLI for Perseus, a super-fast WebAssembly frontend development frame
 -h, --help			prints this help page
 -v, --version			prints the current version of the CLI
 
-build				builds your app
-serve				serves your app (accepts $PORT and $HOST env vars, --no-build to serve pre-built files)
+build				builds your app (--only template=<name> or --only path=<path> to only rebuild some pages, --dry-run to list the pages that would be built, --check-translations to make sure every translation ID used exists in every locale first, --embed to also bundle a script for embedding pages into other sites)
+serve				serves your app (accepts $PORT and $HOST env vars, --no-build to serve pre-built files, --embed to also serve a script for embedding pages into other sites)
+doctor				checks your environment and project for common problems, suggesting fixes
+routes <url>			prints the live route table of the app served at the given URL (needs $PERSEUS_ROUTE_TABLE_TOKEN to be set to the server's token, --json to print it as JSON)
 
 Please note that watching for file changes is not yet inbuilt, but can be achieved with a tool like 'entr' in the meantime.
 Further information can be found at https://arctic-hen7.github.io/perseus.
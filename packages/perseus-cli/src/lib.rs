@@ -29,10 +29,16 @@
 
 mod build;
 mod cmd;
+mod doctor;
 pub mod errors;
+/// Utilities for writing end-to-end tests against a full build of an app.
+pub mod harness;
 mod help;
+mod output;
 mod prepare;
+mod routes;
 mod serve;
+mod translations_check;
 
 mod extraction;
 
@@ -43,8 +49,10 @@ use std::path::PathBuf;
 /// The current version of the CLI, extracted from the crate version.
 pub const PERSEUS_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub use build::build;
+pub use doctor::doctor;
 pub use help::help;
 pub use prepare::{check_env, prepare};
+pub use routes::routes;
 pub use serve::serve;
 
 /// Deletes a corrupted '.perseus/' directory. This will be called on certain error types that would leave the user with a half-finished
@@ -0,0 +1,175 @@
+// This manages how the CLI presents its output, which organizations can customize through their project's `Cargo.toml`
+
+use crate::cmd::{FAILURE, SUCCESS};
+use crate::errors::*;
+use cargo_toml::Manifest;
+use console::{style, Emoji};
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use unic_langid::LanguageIdentifier;
+
+/// The locale that the CLI's output is in by default.
+const DFLT_LOCALE: &str = "en-US";
+/// The locales that the CLI's output can be in, with their messages (in Fluent syntax). These are embedded in the CLI so that it doesn't
+/// need to read anything at runtime.
+const LOCALES: [(&str, &str); 2] = [
+    ("en-US", include_str!("../locales/en-US.ftl")),
+    ("fr-FR", include_str!("../locales/fr-FR.ftl")),
+];
+
+/// The structure of the `[package.metadata]` section of the user's `Cargo.toml`, of which we only care about the `perseus.cli` table.
+#[derive(Deserialize, Default)]
+struct Metadata {
+    #[serde(default)]
+    perseus: PerseusMetadata,
+}
+#[derive(Deserialize, Default)]
+struct PerseusMetadata {
+    #[serde(default)]
+    cli: OutputConfig,
+}
+
+/// The user's configuration for the CLI's output, from `[package.metadata.perseus.cli]`.
+#[derive(Deserialize, Default)]
+struct OutputConfig {
+    /// Whether or not to print emoji (they'll be printed by default if the terminal supports them).
+    emoji: Option<bool>,
+    /// The locale to print messages in.
+    locale: Option<String>,
+    /// Custom names for stages, by their message IDs. These override the localized messages.
+    #[serde(default)]
+    stages: HashMap<String, String>,
+}
+
+/// The CLI's output settings, which control how stages and their outcomes are presented. Stage messages are always formatted as
+/// `[n/N] <emoji> <name>`, so wrapper tooling can rely on their structure even when they're customized.
+pub struct Output {
+    /// Whether or not emoji should be printed.
+    emoji: bool,
+    /// Custom names for stages, by their message IDs.
+    stages: HashMap<String, String>,
+    /// The messages for the configured locale.
+    bundle: FluentBundle<FluentResource>,
+}
+impl Output {
+    /// Loads the output settings from the `Cargo.toml` file in the given directory. If there isn't any configuration, the defaults will
+    /// be used.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let manifest = Manifest::<Metadata>::from_path_with_metadata(dir.join("Cargo.toml"))
+            .map_err(|err| ErrorKind::GetUserManifestFailed(err.to_string()))?;
+        let cfg = manifest
+            .package
+            .and_then(|package| package.metadata)
+            .map(|metadata| metadata.perseus.cli)
+            .unwrap_or_default();
+
+        Self::from_config(cfg)
+    }
+    /// Creates the output settings from the given configuration.
+    fn from_config(cfg: OutputConfig) -> Result<Self> {
+        let locale = cfg.locale.unwrap_or_else(|| DFLT_LOCALE.to_string());
+        let ftl_string = match LOCALES.iter().find(|(name, _)| *name == locale) {
+            Some((_, ftl_string)) => ftl_string,
+            None => {
+                let supported: Vec<&str> = LOCALES.iter().map(|(name, _)| *name).collect();
+                bail!(ErrorKind::InvalidOutputConfig(format!(
+                    "the locale '{}' isn't supported (supported locales are: {})",
+                    locale,
+                    supported.join(", ")
+                )))
+            }
+        };
+        // The embedded messages are known to be valid
+        let resource = FluentResource::try_new(ftl_string.to_string())
+            .expect("embedded cli messages should be valid fluent");
+        let lang_id: LanguageIdentifier = locale
+            .parse()
+            .expect("embedded cli locales should be valid identifiers");
+        let mut bundle = FluentBundle::new(vec![lang_id]);
+        // Unicode isolation marks would mess with the terminal (and with anything parsing our output)
+        bundle.set_use_isolating(false);
+        bundle
+            .add_resource(resource)
+            .expect("embedded cli messages shouldn't conflict");
+
+        Ok(Self {
+            emoji: cfg.emoji.unwrap_or(true),
+            stages: cfg.stages,
+            bundle,
+        })
+    }
+    /// Gets the message with the given ID in the configured locale, interpolating the given arguments. If the message doesn't exist,
+    /// the ID will be returned.
+    pub fn msg(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        let pattern = match self.bundle.get_message(id).and_then(|msg| msg.value()) {
+            Some(pattern) => pattern,
+            None => return id.to_string(),
+        };
+        let mut errors = Vec::new();
+        self.bundle
+            .format_pattern(pattern, args, &mut errors)
+            .to_string()
+    }
+    /// Gets the given emoji (or its fallback for terminals that don't support emoji), or nothing if emoji have been disabled.
+    pub fn emoji(&self, emoji: &Emoji) -> String {
+        match self.emoji {
+            true => emoji.to_string(),
+            false => String::new(),
+        }
+    }
+    /// Gets the given emoji (or its fallback for terminals that don't support emoji), or always its fallback if emoji have been disabled.
+    /// This is for emoji that carry meaning, which shouldn't just disappear.
+    pub fn marker(&self, emoji: &Emoji) -> String {
+        match self.emoji {
+            true => emoji.to_string(),
+            false => emoji.1.to_string(),
+        }
+    }
+    /// Formats the message for a stage with the given ID, which is the step `step` of `num_steps`. This will use a custom name for the
+    /// stage if one has been set, otherwise the localized message will be used, interpolating the given arguments.
+    pub fn stage(
+        &self,
+        id: &str,
+        args: Option<&FluentArgs>,
+        step: u8,
+        num_steps: u8,
+        emoji: &Emoji,
+    ) -> String {
+        let name = match self.stages.get(id) {
+            Some(name) => name.to_string(),
+            None => self.msg(id, args),
+        };
+        let prefix = style(format!("[{}/{}]", step, num_steps)).bold().dim();
+        match self.emoji(emoji).as_str() {
+            "" => format!("{} {}", prefix, name),
+            emoji => format!("{} {} {}", prefix, emoji, name),
+        }
+    }
+    /// Gets the marker for a stage that succeeded.
+    pub fn success(&self) -> String {
+        let text = self.msg("success", None);
+        match self.emoji {
+            // Terminals that don't support emoji will get the localized text instead
+            true => Emoji(SUCCESS.0, &text).to_string(),
+            false => text,
+        }
+    }
+    /// Gets the marker for a stage that failed.
+    pub fn failure(&self) -> String {
+        let text = self.msg("failure", None);
+        match self.emoji {
+            // Terminals that don't support emoji will get the localized text instead
+            true => Emoji(FAILURE.0, &text).to_string(),
+            false => text,
+        }
+    }
+}
+impl Default for Output {
+    fn default() -> Self {
+        // The default configuration only uses embedded messages
+        Self::from_config(OutputConfig::default())
+            .expect("default cli output settings should be valid")
+    }
+}
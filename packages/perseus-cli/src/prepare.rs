@@ -18,7 +18,7 @@ const SUBCRATES: Dir = include_dir!("./.perseus");
 /// have to execute the right commands in the CLI. We can essentially treat the subcrates themselves as a blackbox of just a folder.
 pub fn prepare(dir: PathBuf) -> Result<()> {
     // The location in the target directory at which we'll put the subcrates
-    let mut target = dir;
+    let mut target = dir.clone();
     target.extend([".perseus"]);
 
     if target.exists() {
@@ -65,8 +65,8 @@ pub fn prepare(dir: PathBuf) -> Result<()> {
             )
         })?;
         // Get the name of the user's crate (which the subcrates depend on)
-        // We assume they're running this in a folder with a Cargo.toml...
-        let user_manifest = Manifest::from_path("./Cargo.toml")
+        // We assume they've given us a folder with a Cargo.toml...
+        let user_manifest = Manifest::from_path(dir.join("Cargo.toml"))
             .map_err(|err| ErrorKind::GetUserManifestFailed(err.to_string()))?;
         let user_crate_name = user_manifest.package;
         let user_crate_name = match user_crate_name {
@@ -87,8 +87,11 @@ pub fn prepare(dir: PathBuf) -> Result<()> {
         // If we're not in development, also update relative path references
         #[cfg(not(debug_assertions))]
         let updated_root_manifest = updated_root_manifest.replace(
-            "{ path = \"../../../packages/perseus\" }",
-            &format!("\"{}\"", PERSEUS_VERSION),
+            "{ path = \"../../../packages/perseus\", default-features = false }",
+            &format!(
+                "{{ version = \"{}\", default-features = false }}",
+                PERSEUS_VERSION
+            ),
         );
         #[cfg(not(debug_assertions))]
         let updated_server_manifest = updated_server_manifest.replace(
@@ -111,7 +114,8 @@ pub fn prepare(dir: PathBuf) -> Result<()> {
         }
 
         // If we aren't already gitignoring the subcrates, update .gitignore to do so
-        if let Ok(contents) = fs::read_to_string(".gitignore") {
+        let gitignore = dir.join(".gitignore");
+        if let Ok(contents) = fs::read_to_string(&gitignore) {
             if contents.contains(".perseus/") {
                 return Ok(());
             }
@@ -119,7 +123,7 @@ pub fn prepare(dir: PathBuf) -> Result<()> {
         let file = OpenOptions::new()
             .append(true)
             .create(true) // If it doesn't exist, create it
-            .open(".gitignore");
+            .open(&gitignore);
         let mut file = match file {
             Ok(file) => file,
             Err(err) => bail!(ErrorKind::GitignoreUpdateFailed(err.to_string())),
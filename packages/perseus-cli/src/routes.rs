@@ -0,0 +1,124 @@
+// This gets the live route table of a running server, which is useful for working out why a page is stale in production
+
+use crate::errors::*;
+// The token is read from the same environment variable as the server's
+use perseus::route_table::ROUTE_TABLE_TOKEN_VAR;
+use serde::Deserialize;
+use std::env;
+use std::io::Write;
+use std::process::Command;
+
+/// The route table of an app, as its server sends it.
+#[derive(Deserialize)]
+struct RouteTable {
+    templates: Vec<TemplateRoute>,
+    pages: Vec<PageRoute>,
+}
+/// A template in a route table.
+#[derive(Deserialize)]
+struct TemplateRoute {
+    path: String,
+    strategies: Vec<String>,
+    revalidate_after: Option<u64>,
+    revalidates_with_logic: bool,
+}
+/// A page in a route table.
+#[derive(Deserialize)]
+struct PageRoute {
+    path: String,
+    locale: String,
+    template: String,
+    incremental: bool,
+    rendered_at: Option<String>,
+    revalidate_at: Option<String>,
+    stale: bool,
+}
+
+/// Gets the route table of the app served at the given URL (like `https://example.com`) with `curl`, returning it as JSON.
+fn fetch_route_table(url: &str) -> Result<String> {
+    let route_table_url = format!("{}/.perseus/routes", url.trim_end_matches('/'));
+    let fetch_failed = |err: String| ErrorKind::RouteTableFailed(route_table_url.clone(), err);
+    let token = env::var(ROUTE_TABLE_TOKEN_VAR)
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+        .ok_or_else(|| {
+            fetch_failed(format!(
+                "the '{}' environment variable must be set to the server's token",
+                ROUTE_TABLE_TOKEN_VAR
+            ))
+        })?;
+    let curl = env::var("PERSEUS_CURL_PATH").unwrap_or_else(|_| "curl".to_string());
+    let output = Command::new(&curl)
+        .args(&[
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--header",
+            &format!("Authorization: Bearer {}", token),
+            &route_table_url,
+        ])
+        .output()
+        .map_err(|err| fetch_failed(err.to_string()))?;
+    if !output.status.success() {
+        bail!(fetch_failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        ))
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Prints the live route table of the app served at the URL given in the arguments, either as a summary or as JSON (with `--json`).
+/// Returns an exit code.
+pub fn routes(prog_args: &[String], output: &mut impl Write) -> Result<i32> {
+    let url = match prog_args.iter().skip(1).find(|arg| !arg.starts_with("--")) {
+        Some(url) => url,
+        None => {
+            writeln!(
+                output,
+                "Please provide the URL your app is served at (e.g. `perseus routes https://example.com`)."
+            )
+            .expect("Failed to write to stdout.");
+            return Ok(1);
+        }
+    };
+    let route_table_json = fetch_route_table(url)?;
+    if prog_args.contains(&"--json".to_string()) {
+        writeln!(output, "{}", route_table_json).expect("Failed to write to stdout.");
+        return Ok(0);
+    }
+    let route_table: RouteTable = serde_json::from_str(&route_table_json)
+        .map_err(|err| ErrorKind::RouteTableFailed(url.to_string(), err.to_string()))?;
+
+    writeln!(output, "Templates:").expect("Failed to write to stdout.");
+    for template in route_table.templates {
+        let mut line = format!("  {} ({})", template.path, template.strategies.join(", "));
+        if let Some(revalidate_after) = template.revalidate_after {
+            line.push_str(&format!(", revalidates every {}s", revalidate_after));
+        }
+        if template.revalidates_with_logic {
+            line.push_str(", revalidates with custom logic");
+        }
+        writeln!(output, "{}", line).expect("Failed to write to stdout.");
+    }
+    writeln!(output, "Pages:").expect("Failed to write to stdout.");
+    for page in route_table.pages {
+        let mut line = format!("  {}/{} ({}", page.locale, page.path, page.template);
+        if page.incremental {
+            line.push_str(", incremental");
+        }
+        line.push(')');
+        if let Some(rendered_at) = page.rendered_at {
+            line.push_str(&format!(", rendered at {}", rendered_at));
+        }
+        if let Some(revalidate_at) = page.revalidate_at {
+            line.push_str(&format!(", revalidates at {}", revalidate_at));
+        }
+        if page.stale {
+            line.push_str(", stale");
+        }
+        writeln!(output, "{}", line).expect("Failed to write to stdout.");
+    }
+
+    Ok(0)
+}
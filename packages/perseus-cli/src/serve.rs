@@ -1,54 +1,44 @@
 use crate::build::build_internal;
 use crate::cmd::run_stage;
 use crate::errors::*;
-use console::{style, Emoji};
+use crate::output::Output;
+use console::Emoji;
+use fluent_bundle::FluentArgs;
 use std::env;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 // Emojis for stages
 static BUILDING_SERVER: Emoji<'_, '_> = Emoji("📡", "");
 static SERVING: Emoji<'_, '_> = Emoji("🛰️ ", "");
 
-/// Returns the exit code if it's non-zero.
-macro_rules! handle_exit_code {
-    ($code:expr) => {{
-        let (stdout, stderr, code) = $code;
-        if code != 0 {
-            return Ok(code);
-        }
-        (stdout, stderr)
-    }};
-}
-
-/// Actually serves the user's app, program arguments having been interpreted. This needs to know if we've built as part of this process
-/// so it can show an accurate progress count.
-fn serve_internal(dir: PathBuf, did_build: bool) -> Result<i32> {
-    let num_steps = match did_build {
-        true => 5,
-        false => 2,
-    };
-    let mut target = dir;
-    // All the serving work can be done in the `server` subcrate after building is finished
-    target.extend([".perseus", "server"]);
-
-    // Build the server runner
+/// Builds the server runner in the given `.perseus/server/` directory, returning the path to its executable, or the exit code of the
+/// build if it failed.
+pub(crate) fn build_server(
+    target: &Path,
+    num_steps: u8,
+    output: &Output,
+) -> Result<std::result::Result<String, i32>> {
     // We use the JSON message format so we can get extra info about the generated executable
-    let (stdout, _stderr) = handle_exit_code!(run_stage(
+    let (stdout, _stderr, code) = run_stage(
         vec![&format!(
             "{} build --message-format json",
             env::var("PERSEUS_CARGO_PATH").unwrap_or_else(|_| "cargo".to_string())
         )],
-        &target,
-        format!(
-            "{} {} Building server",
-            style(format!("[{}/{}]", num_steps - 1, num_steps))
-                .bold()
-                .dim(),
-            BUILDING_SERVER
-        )
-    )?);
+        target,
+        output.stage(
+            "building-server",
+            None,
+            num_steps - 1,
+            num_steps,
+            &BUILDING_SERVER,
+        ),
+        output,
+    )?;
+    if code != 0 {
+        return Ok(Err(code));
+    }
     let msgs: Vec<&str> = stdout.trim().split('\n').collect();
     // If we got to here, the exit code was 0 and everything should've worked
     // The last message will just tell us that the build finished, the second-last one will tell us the executable path
@@ -76,14 +66,34 @@ fn serve_internal(dir: PathBuf, did_build: bool) -> Result<i32> {
         )),
     };
 
+    Ok(Ok(server_exec_path.to_string()))
+}
+
+/// Actually serves the user's app, program arguments having been interpreted. This needs to know if we've built as part of this process
+/// so it can show an accurate progress count.
+fn serve_internal(dir: PathBuf, did_build: bool, output: &Output) -> Result<i32> {
+    let num_steps = match did_build {
+        true => 5,
+        false => 2,
+    };
+    let mut target = dir;
+    // All the serving work can be done in the `server` subcrate after building is finished
+    target.extend([".perseus", "server"]);
+
+    // Build the server runner
+    let server_exec_path = match build_server(&target, num_steps, output)? {
+        Ok(server_exec_path) => server_exec_path,
+        Err(code) => return Ok(code),
+    };
+
     // Manually run the generated binary (invoking in the right directory context for good measure if it ever needs it in future)
-    let child = Command::new(server_exec_path)
+    let child = Command::new(&server_exec_path)
         .current_dir(target)
         // We should be able to access outputs in case there's an error
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
-        .map_err(|err| ErrorKind::CmdExecFailed(server_exec_path.to_string(), err.to_string()))?;
+        .map_err(|err| ErrorKind::CmdExecFailed(server_exec_path.clone(), err.to_string()))?;
     // Figure out what host/port the app will be live on
     let host = env::var("HOST").unwrap_or_else(|_| "localhost".to_string());
     let port = env::var("PORT")
@@ -91,12 +101,12 @@ fn serve_internal(dir: PathBuf, did_build: bool) -> Result<i32> {
         .parse::<u16>()
         .map_err(|err| ErrorKind::PortNotNumber(err.to_string()))?;
     // Give the user a nice informational message
+    let mut args = FluentArgs::new();
+    args.set("host", host);
+    args.set("port", port.to_string());
     println!(
-        "  {} {} Your app is now live on http://{host}:{port}! To change this, re-run this command with different settings of the HOST/PORT environment variables.",
-        style(format!("[{}/{}]", num_steps, num_steps)).bold().dim(),
-        SERVING,
-        host=host,
-        port=port
+        "  {}",
+        output.stage("serving", Some(&args), num_steps, num_steps, &SERVING)
     );
 
     // Wait on the child process to finish (which it shouldn't unless there's an error), then perform error handling
@@ -119,17 +129,24 @@ fn serve_internal(dir: PathBuf, did_build: bool) -> Result<i32> {
 /// Builds the subcrates to get a directory that we can serve. Returns an exit code.
 pub fn serve(dir: PathBuf, prog_args: &[String]) -> Result<i32> {
     // TODO support watching files
+    let output = Output::load(&dir)?;
     let mut did_build = false;
     // Only build if the user hasn't set `--no-build`, handling non-zero exit codes
     if !prog_args.contains(&"--no-build".to_string()) {
         did_build = true;
-        let build_exit_code = build_internal(dir.clone(), 5)?;
+        let build_exit_code = build_internal(
+            dir.clone(),
+            5,
+            &output,
+            &[],
+            prog_args.contains(&"--embed".to_string()),
+        )?;
         if build_exit_code != 0 {
             return Ok(build_exit_code);
         }
     }
     // Now actually serve the user's data
-    let exit_code = serve_internal(dir.clone(), did_build)?;
+    let exit_code = serve_internal(dir.clone(), did_build, &output)?;
 
     Ok(exit_code)
 }
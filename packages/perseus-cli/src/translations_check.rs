@@ -0,0 +1,274 @@
+// This verifies that every translation ID used in the user's code exists in every locale's translations before the app is built
+
+use crate::errors::*;
+use fluent_bundle::{FluentBundle, FluentResource};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unic_langid::LanguageIdentifier;
+
+/// The macros and methods whose first argument is a translation ID.
+const TRANSLATION_CALLS: [&str; 4] = ["t!(", "translate(", "translate_checked(", "t! ("];
+
+/// A translation ID used somewhere in the user's code.
+#[derive(Debug, Clone)]
+struct UsedId {
+    id: String,
+    /// Where the ID was first used, as `file:line`.
+    location: String,
+}
+
+/// The translation IDs that are missing from each locale, by locale.
+#[derive(Debug, Default)]
+pub struct TranslationsReport {
+    /// Translation IDs and where they're used, by the locale they're missing from.
+    missing: BTreeMap<String, Vec<(String, String)>>,
+}
+impl TranslationsReport {
+    /// Checks if every translation ID is present in every locale.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+impl fmt::Display for TranslationsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (locale, missing) in &self.missing {
+            writeln!(f, "    {} ({} missing):", locale, missing.len())?;
+            for (id, location) in missing {
+                writeln!(f, "        {} (used at {})", id, location)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The translations for a single locale, in whichever format the app uses.
+enum LocaleTranslations {
+    Fluent(FluentBundle<FluentResource>),
+    Json(HashMap<String, String>),
+}
+impl LocaleTranslations {
+    /// Checks if the given translation ID exists. With Fluent, IDs like `[id].[variant]` refer to an attribute of a message, and structured
+    /// IDs with more dots (like `nav.menu.settings`) refer to hyphenated messages (see `get_fluent_id_candidates`).
+    fn has(&self, id: &str) -> bool {
+        match self {
+            Self::Fluent(bundle) => get_fluent_id_candidates(id)
+                .iter()
+                .any(
+                    |(msg_id, variant)| match (bundle.get_message(msg_id), variant) {
+                        (Some(msg), Some(variant)) => {
+                            msg.attributes().any(|attr| attr.id() == variant)
+                        }
+                        (Some(msg), None) => msg.value().is_some(),
+                        (None, _) => false,
+                    },
+                ),
+            Self::Json(translations) => translations.contains_key(id),
+        }
+    }
+}
+
+/// Gets the messages (and the variants of them) that the given translation ID could refer to in Fluent, in the order the app's translator
+/// tries them. This must match `perseus::translator::get_fluent_id_candidates()`.
+fn get_fluent_id_candidates(id: &str) -> Vec<(String, Option<String>)> {
+    let segments: Vec<&str> = id.split('.').collect();
+    match segments.split_last() {
+        Some((variant, msg_segments)) if !msg_segments.is_empty() => vec![
+            (msg_segments.join("-"), Some(variant.to_string())),
+            (segments.join("-"), None),
+        ],
+        _ => vec![(id.to_string(), None)],
+    }
+}
+
+/// Checks that every translation ID used in the app in the given directory (with `t!`, `.translate()`, or `.translate_checked()` and a
+/// string literal) exists in every locale's translations file in its `translations/` directory, returning a report of any that are
+/// missing, by locale. IDs that aren't string literals can't be checked. If the app doesn't have a `translations/` directory, there's
+/// nothing to check.
+pub fn check_translations(dir: &Path) -> Result<TranslationsReport> {
+    let mut report = TranslationsReport::default();
+    let translations_dir = dir.join("translations");
+    if !translations_dir.exists() {
+        return Ok(report);
+    }
+    let mut used_ids = Vec::new();
+    for file in get_rs_files(&dir.join("src"))? {
+        let contents = read(&file)?;
+        let name = file.strip_prefix(dir).unwrap_or(&file).to_string_lossy();
+        for used_id in get_used_ids(&contents, &name) {
+            if !used_ids.iter().any(|other: &UsedId| other.id == used_id.id) {
+                used_ids.push(used_id);
+            }
+        }
+    }
+
+    for (locale, translations) in get_translations(&translations_dir)? {
+        let missing: Vec<(String, String)> = used_ids
+            .iter()
+            .filter(|used_id| !translations.has(&used_id.id))
+            .map(|used_id| (used_id.id.clone(), used_id.location.clone()))
+            .collect();
+        if !missing.is_empty() {
+            report.missing.insert(locale, missing);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Reads the given file into a string.
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|err| {
+        ErrorKind::ReadTranslationsFailed(path.to_string_lossy().to_string(), err.to_string())
+            .into()
+    })
+}
+
+/// Gets every Rust file in the given directory, recursively.
+fn get_rs_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => bail!(ErrorKind::ReadTranslationsFailed(
+            dir.to_string_lossy().to_string(),
+            err.to_string()
+        )),
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(get_rs_files(&path)?);
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Gets the translation IDs given as string literals to translation macros and methods in the given Rust code, along with where they
+/// were used.
+fn get_used_ids(contents: &str, file_name: &str) -> Vec<UsedId> {
+    let mut used_ids = Vec::new();
+    for call in TRANSLATION_CALLS.iter() {
+        let mut search_from = 0;
+        while let Some(idx) = contents[search_from..].find(call) {
+            let start = search_from + idx;
+            search_from = start + call.len();
+            // Things like `format!(` and `assert!(` end in `t!(` too
+            let is_boundary = contents[..start]
+                .chars()
+                .last()
+                .map_or(true, |c| !(c.is_alphanumeric() || c == '_'));
+            if !is_boundary {
+                continue;
+            }
+            let rest = contents[search_from..].trim_start();
+            let id = match rest.strip_prefix('"').and_then(|rest| rest.split_once('"')) {
+                Some((id, _)) if !id.is_empty() && !id.contains('\\') => id,
+                // This isn't a literal we can check
+                _ => continue,
+            };
+            let line = contents[..start].matches('\n').count() + 1;
+            used_ids.push(UsedId {
+                id: id.to_string(),
+                location: format!("{}:{}", file_name, line),
+            });
+        }
+    }
+
+    used_ids
+}
+
+/// Gets the translations for every locale in the given directory, which should contain files named as the locale they describe (e.g.
+/// `en-US.ftl` or `en-US.json`), or directories named as the locale holding several files that are checked together (e.g.
+/// `en-US/common.ftl` and `en-US/blog.ftl`).
+fn get_translations(dir: &Path) -> Result<BTreeMap<String, LocaleTranslations>> {
+    let mut translations = BTreeMap::new();
+    for path in read_dir_sorted(dir)? {
+        let (locale, files) = if path.is_dir() {
+            let locale = match path.file_name() {
+                Some(locale) => locale.to_string_lossy().to_string(),
+                None => continue,
+            };
+            (locale, read_dir_sorted(&path)?)
+        } else {
+            let locale = match path.file_stem() {
+                Some(locale) => locale.to_string_lossy().to_string(),
+                None => continue,
+            };
+            (locale, vec![path])
+        };
+        if let Some(locale_translations) = get_locale_translations(&locale, &files)? {
+            translations.insert(locale, locale_translations);
+        }
+    }
+
+    Ok(translations)
+}
+
+/// Gets the paths of everything in the given directory, sorted so that files are always read in the same order.
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => bail!(ErrorKind::ReadTranslationsFailed(
+            dir.to_string_lossy().to_string(),
+            err.to_string()
+        )),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parses the given translations files for a locale together, using the format of the first one we know how to check (any files in
+/// other formats are ignored). This returns `None` if there aren't any files we know how to check.
+fn get_locale_translations(locale: &str, files: &[PathBuf]) -> Result<Option<LocaleTranslations>> {
+    let ext = files
+        .iter()
+        .filter_map(|path| path.extension().and_then(|ext| ext.to_str()))
+        .find(|ext| *ext == "ftl" || *ext == "json");
+    let files = files
+        .iter()
+        .filter(|path| path.is_file() && path.extension().and_then(|ext| ext.to_str()) == ext);
+    let locale_translations = match ext {
+        Some("ftl") => {
+            let lang_id: LanguageIdentifier = locale.parse().unwrap_or_default();
+            let mut bundle = FluentBundle::new(vec![lang_id]);
+            for path in files {
+                let file_name = path.to_string_lossy().to_string();
+                let resource = FluentResource::try_new(read(path)?).map_err(|(_, errs)| {
+                    ErrorKind::InvalidTranslations(file_name.clone(), format!("{:?}", errs))
+                })?;
+                bundle.add_resource(resource).map_err(|errs| {
+                    ErrorKind::InvalidTranslations(file_name.clone(), format!("{:?}", errs))
+                })?;
+            }
+            LocaleTranslations::Fluent(bundle)
+        }
+        Some("json") => {
+            let mut map = HashMap::new();
+            for path in files {
+                let file_name = path.to_string_lossy().to_string();
+                let contents = read(path)?;
+                // Empty files are allowed in a locale's directory, just like the app's translator allows them
+                if contents.trim().is_empty() {
+                    continue;
+                }
+                let file_map: HashMap<String, String> = serde_json::from_str(&contents)
+                    .map_err(|err| ErrorKind::InvalidTranslations(file_name, err.to_string()))?;
+                map.extend(file_map);
+            }
+            LocaleTranslations::Json(map)
+        }
+        // Anything else isn't something we know how to check
+        _ => return Ok(None),
+    };
+
+    Ok(Some(locale_translations))
+}
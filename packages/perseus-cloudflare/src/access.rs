@@ -0,0 +1,37 @@
+use crate::conv_req::convert_req;
+use perseus::{AccessControl, AccessVerdict};
+use worker::{Headers, Request, Response};
+
+/// Checks if the given request is allowed through the app's access control, returning the response that should be sent instead if it
+/// isn't.
+pub fn check_access(
+    req: &Request,
+    access_control: &AccessControl,
+) -> worker::Result<Option<Response>> {
+    if access_control.is_open() {
+        return Ok(None);
+    }
+    // We need to turn the Workers request into one acceptable for Perseus (uses `http` internally)
+    let http_req = match convert_req(req) {
+        Ok(http_req) => http_req,
+        // If this fails, the client request is malformed, so it's a 400
+        Err(err) => return Response::error(err.to_string(), 400).map(Some),
+    };
+    // Cloudflare always sets this to the address of the user, and it can't be spoofed
+    let peer_ip = req
+        .headers()
+        .get("CF-Connecting-IP")?
+        .and_then(|ip| ip.parse().ok());
+
+    match access_control.check(&http_req, peer_ip) {
+        AccessVerdict::Allowed => Ok(None),
+        AccessVerdict::Unauthorized(challenge) => {
+            let mut headers = Headers::new();
+            headers.set("WWW-Authenticate", &challenge)?;
+            Ok(Some(
+                Response::error("unauthorized", 401)?.with_headers(headers),
+            ))
+        }
+        AccessVerdict::Forbidden => Response::error("forbidden", 403).map(Some),
+    }
+}
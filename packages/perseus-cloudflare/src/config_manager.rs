@@ -0,0 +1,63 @@
+use perseus::config_manager::{ErrorKind, Result};
+use perseus::ConfigManager;
+use worker::kv::KvStore;
+
+/// A config manager backed by Workers KV. Workers don't have a filesystem, so everything built ahead of time is uploaded to an assets
+/// namespace, which is never written to, and anything Perseus generates at request time (revalidated pages, incrementally generated
+/// pages, etc.) is written to a separate store namespace. Both use the same keys, which are the names of the files under `dist/`
+/// (prefixed with the given prefix, if it isn't empty).
+///
+/// Note that KV is eventually consistent, so a page revalidated in one location may take up to a minute to be seen in others, and it may
+/// be revalidated more than once in the meantime.
+#[derive(Clone)]
+pub struct KvConfigManager {
+    assets: KvStore,
+    store: KvStore,
+    prefix: String,
+}
+impl KvConfigManager {
+    /// Creates a new KV config manager, reading built files from `assets` and reading/writing generated files in `store`. You should
+    /// provide a prefix like `dist` here if your built files weren't uploaded at the root of the assets namespace.
+    pub fn new(assets: KvStore, store: KvStore, prefix: String) -> Self {
+        Self {
+            assets,
+            store,
+            prefix,
+        }
+    }
+    /// Gets the KV key for the given asset name.
+    fn get_key(&self, name: &str) -> String {
+        match self.prefix.is_empty() {
+            true => name.to_string(),
+            false => format!("{}/{}", self.prefix, name),
+        }
+    }
+}
+#[async_trait::async_trait(?Send)]
+impl ConfigManager for KvConfigManager {
+    async fn read(&self, name: &str) -> Result<String> {
+        let key = self.get_key(name);
+        // Anything generated at request time is newer than what was built, so the store takes precedence
+        for namespace in &[&self.store, &self.assets] {
+            let val = namespace
+                .get(&key)
+                .text()
+                .await
+                .map_err(|err| ErrorKind::ReadFailed(key.clone(), err.to_string()))?;
+            if let Some(val) = val {
+                return Ok(val);
+            }
+        }
+
+        Err(ErrorKind::NotFound(key).into())
+    }
+    async fn write(&self, name: &str, content: &str) -> Result<()> {
+        let key = self.get_key(name);
+        self.store
+            .put(&key, content)
+            .map_err(|err| ErrorKind::WriteFailed(key.clone(), err.to_string()))?
+            .execute()
+            .await
+            .map_err(|err| ErrorKind::WriteFailed(key, err.to_string()).into())
+    }
+}
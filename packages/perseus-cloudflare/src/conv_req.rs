@@ -0,0 +1,26 @@
+use crate::errors::*;
+use perseus::{HttpRequest, Request};
+
+/// Converts a Workers request into an `http::request`.
+pub fn convert_req(raw: &worker::Request) -> Result<Request> {
+    let mut builder = HttpRequest::builder();
+    // Add headers one by one
+    for (name, val) in raw.headers().entries() {
+        // Each method call consumes and returns `self`, so we re-self-assign
+        builder = builder.header(name, val);
+    }
+    // The URL to which the request was sent
+    let url = raw
+        .url()
+        .map_err(|err| ErrorKind::RequestConversionFailed(err.to_string()))?;
+    builder = builder.uri(url.as_str());
+    // The method (e.g. GET, POST, etc.)
+    builder = builder.method(raw.method().to_string().as_str());
+    // Workers don't tell us the HTTP version used, so we leave the default
+
+    builder
+        // We always use an empty body because, in a Perseus request, only the URI matters
+        // Any custom data should therefore be sent in headers (if you're doing that, consider a dedicated API)
+        .body(())
+        .map_err(|err| ErrorKind::RequestConversionFailed(err.to_string()).into())
+}
@@ -0,0 +1,16 @@
+#![allow(missing_docs)]
+
+pub use error_chain::bail;
+use error_chain::error_chain;
+
+// The `error_chain` setup for the whole crate
+error_chain! {
+    // The custom errors for this crate (very broad)
+    errors {
+        /// For if converting an HTTP request from Workers format to Perseus format failed.
+        RequestConversionFailed(err: String) {
+            description("converting the request from workers format to perseus format failed")
+            display("converting the request from workers format to perseus format failed: {:?}", err)
+        }
+    }
+}
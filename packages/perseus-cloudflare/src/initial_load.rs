@@ -0,0 +1,91 @@
+use crate::conv_req::convert_req;
+use crate::{content_type_headers, Options};
+use perseus::flash::set_redirect_flash;
+use perseus::http::header::HeaderMap;
+use perseus::surrogate_keys::set_surrogate_keys;
+use perseus::{
+    err_to_status_code, get_initial_load, get_render_cfg, Artifact, ConfigManager, InitialLoad,
+    TranslationsManager,
+};
+use worker::{Headers, Request, Response};
+
+/// The handler for requests for pages themselves, which serves the app shell with the page prerendered into it, so that it works even
+/// without JavaScript. The given path should already be decoded.
+pub async fn initial_load<C: ConfigManager, T: TranslationsManager>(
+    req: &Request,
+    path: &str,
+    opts: &Options,
+    config_manager: &C,
+    translations_manager: &T,
+) -> worker::Result<Response> {
+    let index = match opts.assets.get(&opts.index).text().await? {
+        Some(index) => index,
+        None => return Response::error(format!("asset '{}' not found", opts.index), 500),
+    };
+    // We need to turn the Workers request into one acceptable for Perseus (uses `http` internally)
+    let http_req = match convert_req(req) {
+        Ok(http_req) => http_req,
+        // If this fails, the client request is malformed, so it's a 400
+        Err(err) => return Response::error(err.to_string(), 400),
+    };
+    // Workers don't keep state between requests reliably, so we get the render configuration every time
+    let render_cfg = match get_render_cfg(config_manager).await {
+        Ok(render_cfg) => render_cfg,
+        Err(err) => return Response::error(err.to_string(), err_to_status_code(&err)),
+    };
+    let initial_load = get_initial_load(
+        path,
+        &index,
+        http_req,
+        &render_cfg,
+        &opts.templates_map,
+        &opts.locales,
+        &opts.redirects,
+        opts.noscript.as_deref(),
+        config_manager,
+        translations_manager,
+    )
+    .await;
+
+    match initial_load {
+        InitialLoad::Page(page) => {
+            // Headers like `Cache-Control` should apply to the page itself, not just its data
+            let mut page_headers = page.headers().clone();
+            // CDNs cache the page as well as its data, so they need to know what to purge it for too
+            if let Some(surrogate_key_header) = &opts.surrogate_key_header {
+                set_surrogate_keys(&mut page_headers, page.surrogate_keys(), surrogate_key_header);
+            }
+            let mut headers = content_type_headers(opts, Artifact::Html)?;
+            for (key, val) in page_headers.iter() {
+                // Workers can only send headers that are valid strings
+                if let Ok(val) = val.to_str() {
+                    headers.append(key.as_str(), val)?;
+                }
+            }
+            // The version of the Workers runtime bindings we use can't stream response bodies, so the page is sent all at once (which means
+            // we can still send an error if rendering it fails)
+            match page.into_string() {
+                Ok(html) => Ok(Response::ok(html)?.with_headers(headers)),
+                Err(err) => Response::error(err.to_string(), err_to_status_code(&err)),
+            }
+        }
+        InitialLoad::Redirect(redirect) => {
+            let mut headers = Headers::new();
+            headers.set("Location", &redirect.to)?;
+            // Any flash message the redirect has should be delivered to wherever the user ends up
+            let mut flash_headers = HeaderMap::new();
+            set_redirect_flash(&mut flash_headers, &redirect);
+            for (key, val) in flash_headers.iter() {
+                if let Ok(val) = val.to_str() {
+                    headers.append(key.as_str(), val)?;
+                }
+            }
+            Ok(Response::empty()?
+                .with_status(redirect.status)
+                .with_headers(headers))
+        }
+        InitialLoad::Shell(html, status) => Ok(Response::ok(html)?
+            .with_status(status)
+            .with_headers(content_type_headers(opts, Artifact::Html)?)),
+    }
+}
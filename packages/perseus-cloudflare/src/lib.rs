@@ -0,0 +1,236 @@
+/*!
+ * Perseus is a blazingly fast frontend web development framework built in Rust with support for major rendering strategies,
+ * reactivity without a virtual DOM, and extreme customizability. It wraps the lower-level capabilities of [Sycamore](https://github.com/sycamore-rs/sycamore)
+ * and provides a NextJS-like API!
+ *
+ * - ✨ Supports static generation (serving only static resources)
+ * - ✨ Supports server-side rendering (serving dynamic resources)
+ * - ✨ Supports revalidation after time and/or with custom logic (updating rendered pages)
+ * - ✨ Supports incremental regeneration (build on demand)
+ * - ✨ Open build matrix (use any rendering strategy with anything else, mostly)
+ * - ✨ CLI harness that lets you build apps with ease and confidence
+ *
+ * This is the documentation for the Perseus Cloudflare Workers integration, but there's also [a CLI](https://arctic-hen7.github.io/perseus/cli.html),
+ * [the core package](https://crates.io/crates/perseus), and other [integrations](https://arctic-hen7.github.io/perseus/serving.html)
+ * to make serving apps on other platforms easier!
+ *
+ * # Resources
+ *
+ * These docs will help you as a reference, but [the book](https://arctic-hen7.github.io/perseus/integrations/cloudflare-workers.html) should
+ * be your first port of call for learning about how to use Perseus and how it works.
+ *
+ * - [The Book](https://arctic-hen7.github.io/perseus)
+ * - [GitHub repository](https://github.com/arctic-hen7/perseus)
+ * - [Crate page](https://crates.io/crates/perseus)
+ * - [Gitter chat](https://gitter.im/perseus-framework/community)
+ * - [Discord server channel](https://discord.com/channels/820400041332179004/883168134331256892) (for Sycamore-related stuff)
+ */
+
+#![deny(missing_docs)]
+
+mod access;
+mod config_manager;
+mod conv_req;
+pub mod errors;
+mod initial_load;
+mod page_data;
+mod static_file;
+mod translations;
+mod translations_manager;
+
+use crate::access::check_access;
+pub use crate::config_manager::KvConfigManager;
+use crate::initial_load::initial_load;
+use crate::page_data::page_data;
+use crate::static_file::static_file;
+use crate::translations::translations;
+pub use crate::translations_manager::KvTranslationsManager;
+use perseus::background::take_queued_jobs;
+use perseus::{
+    AccessControl, Artifact, ConfigManager, ContentTypes, Locales, Redirects, SsrNode,
+    SurrogateKeyHeader, TemplateMap, TranslationsManager,
+};
+use worker::kv::KvStore;
+use worker::{Headers, Method, Request, Response};
+
+/// The options for setting up the Cloudflare Workers integration. These should be created with `Options::new()`, which takes everything
+/// that's required, and then anything optional can be set with the builder methods of the same names as the fields (e.g.
+/// `.static_prefix()`).
+#[derive(Clone)]
+pub struct Options {
+    /// The KV namespace that your app's built files (including the bundles and `index.html`) were uploaded to.
+    pub assets: KvStore,
+    /// The key of your JavaScript bundle in the assets namespace.
+    pub js_bundle: String,
+    /// The key of your WASM bundle in the assets namespace.
+    pub wasm_bundle: String,
+    /// The key of your `index.html` file that includes the JS bundle in the assets namespace.
+    pub index: String,
+    /// A map of your app's templates by their paths.
+    pub templates_map: TemplateMap<SsrNode>,
+    /// The locales information for the app.
+    pub locales: Locales,
+    /// The header to send surrogate keys in for CDN cache purging (e.g. `Cache-Tag` for Cloudflare), if they should be sent at all.
+    pub surrogate_key_header: Option<SurrogateKeyHeader>,
+    /// HTML to show at the top of every page to users without JavaScript (in a `<noscript>` element), if any.
+    pub noscript: Option<String>,
+    /// The app's static redirects from old paths to new paths or URLs.
+    pub redirects: Redirects,
+    /// The protection for the whole app (like HTTP basic auth for a staging deployment), which can be `AccessControl::new()` to allow
+    /// everything.
+    pub access_control: AccessControl,
+    /// The content types to send the app's artifacts with, which can be `ContentTypes::new()` to use the defaults.
+    pub content_types: ContentTypes,
+    /// The prefix of the keys of static files (like images and videos) in the assets namespace (e.g. `static`), which will be served at
+    /// `/.perseus/static`, if there are any.
+    pub static_prefix: Option<String>,
+}
+impl Options {
+    /// Creates new options from the assets namespace and the keys of your `index.html` file, your JavaScript bundle, and your WASM bundle
+    /// in it, along with your app's templates and locales. Everything else is left out (or allows everything, in the case of access
+    /// control).
+    pub fn new(
+        assets: KvStore,
+        index: impl Into<String>,
+        js_bundle: impl Into<String>,
+        wasm_bundle: impl Into<String>,
+        templates_map: TemplateMap<SsrNode>,
+        locales: Locales,
+    ) -> Self {
+        Self {
+            assets,
+            js_bundle: js_bundle.into(),
+            wasm_bundle: wasm_bundle.into(),
+            index: index.into(),
+            templates_map,
+            locales,
+            surrogate_key_header: None,
+            noscript: None,
+            redirects: Redirects::default(),
+            access_control: AccessControl::new(),
+            content_types: ContentTypes::new(),
+            static_prefix: None,
+        }
+    }
+    /// Sets the header to send surrogate keys in for CDN cache purging.
+    pub fn surrogate_key_header(mut self, val: Option<SurrogateKeyHeader>) -> Self {
+        self.surrogate_key_header = val;
+        self
+    }
+    /// Sets the HTML to show at the top of every page to users without JavaScript.
+    pub fn noscript(mut self, val: Option<String>) -> Self {
+        self.noscript = val;
+        self
+    }
+    /// Sets the app's static redirects.
+    pub fn redirects(mut self, val: Redirects) -> Self {
+        self.redirects = val;
+        self
+    }
+    /// Sets the protection for the whole app.
+    pub fn access_control(mut self, val: AccessControl) -> Self {
+        self.access_control = val;
+        self
+    }
+    /// Sets the content types to send the app's artifacts with.
+    pub fn content_types(mut self, val: ContentTypes) -> Self {
+        self.content_types = val;
+        self
+    }
+    /// Sets the prefix of the keys of static files in the assets namespace, which will be served at `/.perseus/static`.
+    pub fn static_prefix(mut self, val: Option<String>) -> Self {
+        self.static_prefix = val;
+        self
+    }
+}
+
+/// Creates headers with the content type the app wants the given artifact sent with.
+pub(crate) fn content_type_headers(opts: &Options, artifact: Artifact) -> worker::Result<Headers> {
+    let mut headers = Headers::new();
+    headers.set("Content-Type", &opts.content_types.get(&artifact))?;
+
+    Ok(headers)
+}
+
+/// Serves the asset with the given key from the assets namespace as the given artifact.
+async fn asset(opts: &Options, key: &str, artifact: Artifact<'_>) -> worker::Result<Response> {
+    match opts.assets.get(key).bytes().await? {
+        Some(bytes) => {
+            Ok(Response::from_bytes(bytes)?.with_headers(content_type_headers(opts, artifact)?))
+        }
+        None => Response::error(format!("asset '{}' not found", key), 500),
+    }
+}
+
+/// Handles a request to a Worker for Perseus, returning the response. This should be called from your Worker's `fetch` event handler,
+/// after any other logic, because it serves the app shell for every path it doesn't otherwise recognize.
+///
+/// Workers can stop as soon as they've responded, and this version of `worker` can't ask them to wait, so any jobs queued with
+/// `spawn_after_response` are run before the response is returned.
+pub async fn handle<C: ConfigManager, T: TranslationsManager>(
+    req: Request,
+    opts: &Options,
+    config_manager: &C,
+    translations_manager: &T,
+) -> worker::Result<Response> {
+    let res = route(req, opts, config_manager, translations_manager).await;
+    for job in take_queued_jobs() {
+        job.await;
+    }
+
+    res
+}
+
+/// Routes a request to the right handler.
+async fn route<C: ConfigManager, T: TranslationsManager>(
+    req: Request,
+    opts: &Options,
+    config_manager: &C,
+    translations_manager: &T,
+) -> worker::Result<Response> {
+    if req.method() != Method::Get {
+        return Response::error("method not allowed", 405);
+    }
+    // Everything is behind the app's access control (if it has any)
+    if let Some(res) = check_access(&req, &opts.access_control)? {
+        return Ok(res);
+    }
+    // Paths can contain encoded characters, which Perseus expects decoded (like Actix Web does)
+    let path = match urlencoding::decode(&req.path()) {
+        Ok(path) => path.to_string(),
+        Err(_) => return Response::error("malformed path", 400),
+    };
+    // TODO chunk JS and WASM bundles
+    if path == "/.perseus/bundle.js" {
+        // These allow getting the basic app code (not including the static data)
+        // This contains everything in the spirit of a pseudo-SPA
+        asset(opts, &opts.js_bundle, Artifact::Js).await
+    } else if path == "/.perseus/bundle.wasm" {
+        asset(opts, &opts.wasm_bundle, Artifact::Wasm).await
+    } else if let Some(rest) = path.strip_prefix("/.perseus/page/") {
+        // This allows getting the static HTML/JSON of a page
+        // The first segment is the locale, and everything after it is the page's path (which may be empty for the index page)
+        let (locale, filename) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx + 1..]),
+            None => (rest, ""),
+        };
+        page_data(
+            &req,
+            locale,
+            filename,
+            opts,
+            config_manager,
+            translations_manager,
+        )
+        .await
+    } else if let Some(locale) = path.strip_prefix("/.perseus/translations/") {
+        // This allows the app shell to fetch translations for a given page
+        translations(&req, locale, opts, translations_manager).await
+    } else if let Some(filename) = path.strip_prefix("/.perseus/static/") {
+        // This serves the user's static files (like images and videos)
+        static_file(&req, filename, opts).await
+    } else {
+        // For everything else, we'll serve the app shell with the page prerendered into it
+        initial_load(&req, &path, opts, config_manager, translations_manager).await
+    }
+}
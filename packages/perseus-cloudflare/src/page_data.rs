@@ -0,0 +1,103 @@
+use crate::conv_req::convert_req;
+use crate::{content_type_headers, Options};
+use perseus::flash::{clear_flash, set_redirect_flash};
+use perseus::http::header::HeaderMap;
+use perseus::serve::PageData;
+use perseus::surrogate_keys::set_surrogate_keys;
+use perseus::{
+    err_to_retry_after, err_to_status_code, get_page_for_template, get_render_cfg, get_template_for_path, Artifact,
+    ConfigManager, TranslationsManager,
+};
+use worker::{Headers, Request, Response};
+
+/// The handler for calls to `.perseus/page/*`. This will manage returning errors and the like.
+pub async fn page_data<C: ConfigManager, T: TranslationsManager>(
+    req: &Request,
+    locale: &str,
+    path: &str,
+    opts: &Options,
+    config_manager: &C,
+    translations_manager: &T,
+) -> worker::Result<Response> {
+    let templates = &opts.templates_map;
+    // Check if the locale is supported
+    if !opts.locales.is_supported(locale) {
+        return Response::error("locale not supported", 404);
+    }
+    // Pages that have moved are redirected before we bother rendering anything
+    if let Some(redirect) = opts.redirects.get_for_page(path, locale, &opts.locales) {
+        return Ok(Response::ok(
+            serde_json::to_string(&PageData::for_redirect(redirect)).unwrap(),
+        )?
+        .with_headers(content_type_headers(opts, Artifact::PageData)?));
+    }
+    // We need to turn the Workers request into one acceptable for Perseus (uses `http` internally)
+    let http_req = match convert_req(req) {
+        Ok(http_req) => http_req,
+        // If this fails, the client request is malformed, so it's a 400
+        Err(err) => return Response::error(err.to_string(), 400),
+    };
+    // Workers don't keep state between requests reliably, so we get the render configuration every time
+    let render_cfg = match get_render_cfg(config_manager).await {
+        Ok(render_cfg) => render_cfg,
+        Err(err) => return Response::error(err.to_string(), err_to_status_code(&err)),
+    };
+    // Get the template to use
+    let template = match get_template_for_path(path, &render_cfg, templates) {
+        Ok(template) => template,
+        Err(err) => return Response::error(err.to_string(), err_to_status_code(&err)),
+    };
+    let page_data = get_page_for_template(
+        path,
+        locale,
+        template,
+        http_req,
+        config_manager,
+        translations_manager,
+    )
+    .await;
+
+    match page_data {
+        Ok(page_data) => {
+            // Add any headers the template wants for this page (placeholders don't have any state to base them on)
+            let mut headers = match page_data.fallback {
+                true => HeaderMap::new(),
+                false => template.get_headers(page_data.state.clone()),
+            };
+            // If we've delivered a flash message, it shouldn't be delivered again
+            if page_data.flash.is_some() {
+                clear_flash(&mut headers);
+            }
+            // A flash message sent with a redirect is delivered to wherever the user ends up, which the app will navigate to
+            if let Some(redirect) = &page_data.redirect {
+                set_redirect_flash(&mut headers, redirect);
+            }
+            // Let CDNs know what this page depends on so it can be purged when that changes
+            if let Some(surrogate_key_header) = &opts.surrogate_key_header {
+                let keys = template.get_surrogate_keys(path, locale, page_data.state.clone());
+                set_surrogate_keys(&mut headers, &keys, surrogate_key_header);
+            }
+            let mut res_headers = content_type_headers(opts, Artifact::PageData)?;
+            for (key, val) in headers.iter() {
+                // Workers can only send headers that are valid strings
+                if let Ok(val) = val.to_str() {
+                    res_headers.append(key.as_str(), val)?;
+                }
+            }
+            Ok(Response::ok(serde_json::to_string(&page_data).unwrap())?.with_headers(res_headers))
+        }
+        // We parse the error to return an appropriate status code
+        Err(err) => {
+            let res = Response::error(err.to_string(), err_to_status_code(&err))?;
+            // If the server was too busy, the client should know when to try again
+            match err_to_retry_after(&err) {
+                Some(retry_after) => {
+                    let mut headers = Headers::new();
+                    headers.set("Retry-After", &retry_after.to_string())?;
+                    Ok(res.with_headers(headers))
+                }
+                None => Ok(res),
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{content_type_headers, Options};
+use perseus::{Artifact, ByteRange};
+use worker::{Request, Response};
+
+/// The handler for calls to `.perseus/static/*`. This supports requests for ranges of files (so that videos can be seeked through).
+pub async fn static_file(
+    req: &Request,
+    filename: &str,
+    opts: &Options,
+) -> worker::Result<Response> {
+    let static_prefix = match &opts.static_prefix {
+        Some(static_prefix) => static_prefix,
+        None => return Response::error("not found", 404),
+    };
+    let key = format!("{}/{}", static_prefix.trim_end_matches('/'), filename);
+    let bytes = match opts.assets.get(&key).bytes().await? {
+        Some(bytes) => bytes,
+        None => return Response::error("not found", 404),
+    };
+    let len = bytes.len() as u64;
+    let mut headers = content_type_headers(opts, Artifact::Static(filename))?;
+    headers.set("Accept-Ranges", "bytes")?;
+    let range = ByteRange::from_header(req.headers().get("Range")?.as_deref(), len);
+    if let Some(content_range) = range.content_range(len) {
+        headers.set("Content-Range", &content_range)?;
+    }
+
+    match range {
+        ByteRange::Full => Ok(Response::from_bytes(bytes)?.with_headers(headers)),
+        ByteRange::Partial { start, end } => {
+            let part = bytes[start as usize..=end as usize].to_vec();
+            Ok(Response::from_bytes(part)?
+                .with_headers(headers)
+                .with_status(206))
+        }
+        ByteRange::Unsatisfiable => Ok(Response::empty()?.with_headers(headers).with_status(416)),
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{content_type_headers, Options};
+use perseus::translations_manager::get_translation_prefixes;
+use perseus::{Artifact, TranslationsManager};
+use worker::{Request, Response};
+
+/// The handler for calls to `.perseus/translations/{locale}`. This will manage returning errors and the like. THe JSON body returned
+/// from this does NOT include the `locale` key, just a `HashMap<String, String>` of the translations themselves. If the request has a
+/// `prefixes` query parameter, only the translations with IDs starting with those (comma-separated) prefixes will be returned.
+pub async fn translations<T: TranslationsManager>(
+    req: &Request,
+    locale: &str,
+    opts: &Options,
+    translations_manager: &T,
+) -> worker::Result<Response> {
+    let prefixes = match req.url() {
+        Ok(url) => get_translation_prefixes(url.query().unwrap_or_default()),
+        Err(_) => None,
+    };
+    // Check if the locale is supported
+    if opts.locales.is_supported(locale) {
+        // We know that the locale is supported, so any failure to get translations is a 500
+        let translations = translations_manager
+            .get_translations_str_for_prefixes(locale.to_string(), prefixes)
+            .await;
+        match translations {
+            Ok(translations) => Ok(Response::ok(translations)?
+                .with_headers(content_type_headers(opts, Artifact::Translations)?)),
+            Err(err) => Response::error(err.to_string(), 500),
+        }
+    } else {
+        Response::error("locale not supported", 404)
+    }
+}
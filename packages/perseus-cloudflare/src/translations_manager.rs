@@ -0,0 +1,41 @@
+use perseus::translations_manager::{ErrorKind, Result};
+use perseus::TranslationsManager;
+use worker::kv::KvStore;
+
+/// A translations manager backed by Workers KV, which reads translations from the assets namespace that the app's built files are uploaded
+/// to. Like `FsTranslationsManager`, this mandates that translations be stored under keys named as the locale they describe (e.g.
+/// `translations/en-US.ftl`). Workers don't live long enough for caching to be worthwhile, so translations are read on every request.
+#[derive(Clone)]
+pub struct KvTranslationsManager {
+    assets: KvStore,
+    prefix: String,
+    /// The file extension expected (e.g. JSON, FTL, etc).
+    file_ext: String,
+}
+impl KvTranslationsManager {
+    /// Creates a new KV translations manager. You should provide a prefix like `translations` here, and the file extension of your
+    /// translations files (which you can get from `perseus::TRANSLATOR_FILE_EXT`).
+    pub fn new(assets: KvStore, prefix: String, file_ext: String) -> Self {
+        Self {
+            assets,
+            prefix,
+            file_ext,
+        }
+    }
+}
+#[async_trait::async_trait(?Send)]
+impl TranslationsManager for KvTranslationsManager {
+    async fn get_translations_str_for_locale(&self, locale: String) -> Result<String> {
+        let key = format!("{}/{}.{}", self.prefix, locale, self.file_ext);
+        let translations_str = self
+            .assets
+            .get(&key)
+            .text()
+            .await
+            .map_err(|err| ErrorKind::ReadFailed(key.clone(), err.to_string()))?;
+        match translations_str {
+            Some(translations_str) => Ok(translations_str),
+            None => Err(ErrorKind::NotFound(key).into()),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use fluent_syntax::ast::Entry;
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::path::PathBuf;
+use syn::parse::{Parse, ParseStream};
+use syn::{LitStr, Token};
+
+/// The environment variable that can be set to the path of the default locale's FTL file (relative to the crate root), so that it
+/// doesn't have to be given to every `checked_t!`.
+const FTL_PATH_VAR: &str = "PERSEUS_DEFAULT_FTL";
+
+/// The input to `checked_t!`, which is an optional path to an FTL file, a translation ID, and then anything `t!` accepts after the ID.
+pub struct CheckedTInput {
+    ftl_path: Option<LitStr>,
+    id: LitStr,
+    rest: TokenStream,
+}
+impl Parse for CheckedTInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let first: LitStr = input.parse()?;
+        // Two string literals in a row are a path and then an ID, since arguments can't be strings
+        let (ftl_path, id) = if input.peek(Token![,]) && input.peek2(LitStr) {
+            input.parse::<Token![,]>()?;
+            (Some(first), input.parse()?)
+        } else {
+            (None, first)
+        };
+
+        Ok(Self {
+            ftl_path,
+            id,
+            rest: input.parse()?,
+        })
+    }
+}
+
+/// Checks that the translation ID in the given input exists in the FTL file it refers to, expanding to a call to `t!` if it does, or a
+/// compile-time error pointing at the ID if it doesn't.
+pub fn checked_t_impl(input: CheckedTInput) -> syn::Result<TokenStream> {
+    let id = input.id.value();
+    let (ftl_path, path_span) = match &input.ftl_path {
+        Some(ftl_path) => (ftl_path.value(), ftl_path.span()),
+        None => match std::env::var(FTL_PATH_VAR) {
+            Ok(ftl_path) => (ftl_path, input.id.span()),
+            Err(_) => {
+                return Err(syn::Error::new(
+                    input.id.span(),
+                    format!(
+                        "no ftl file to check against (give one before the id like `checked_t!(\"translations/en-US.ftl\", \"{}\")`, or set the `{}` environment variable)",
+                        id, FTL_PATH_VAR
+                    ),
+                ))
+            }
+        },
+    };
+    // Paths are relative to the crate being compiled, like with `include_str!`
+    let full_path = match std::env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest_dir) => PathBuf::from(manifest_dir).join(&ftl_path),
+        Err(_) => PathBuf::from(&ftl_path),
+    };
+    let ftl_string = std::fs::read_to_string(&full_path).map_err(|err| {
+        syn::Error::new(
+            path_span,
+            format!(
+                "couldn't read ftl file at '{}': {}",
+                full_path.display(),
+                err
+            ),
+        )
+    })?;
+    // We check against everything that could be parsed, since Fluent skips entries it can't parse at runtime too
+    let resource = match fluent_syntax::parser::parse(ftl_string.as_str()) {
+        Ok(resource) => resource,
+        Err((resource, _)) => resource,
+    };
+
+    let get_msg = |msg_id: &str| {
+        resource.body.iter().find_map(|entry| match entry {
+            Entry::Message(msg) if msg.id.name == msg_id => Some(msg),
+            _ => None,
+        })
+    };
+    // Structured IDs could refer to a few different messages (or variants of them), and any of them will do
+    let candidates = get_id_candidates(&id);
+    let resolves = candidates
+        .iter()
+        .any(|(msg_id, variant)| match (get_msg(msg_id), variant) {
+            (Some(msg), Some(variant)) => {
+                msg.attributes.iter().any(|attr| attr.id.name == *variant)
+            }
+            (Some(msg), None) => msg.value.is_some(),
+            (None, _) => false,
+        });
+    if !resolves {
+        // We report the problem with the first message the ID could refer to, which is the most likely
+        let (msg_id, variant) = &candidates[0];
+        let err = match (get_msg(msg_id), variant) {
+            (Some(_), Some(variant)) => format!(
+                "translation id '{}' has no variant '{}' in '{}'",
+                msg_id, variant, ftl_path
+            ),
+            (Some(_), None) => format!(
+                "translation id '{}' is a compound message in '{}', so a variant must be given (like '{}.[variant]')",
+                msg_id, ftl_path, msg_id
+            ),
+            (None, _) => format!("translation id '{}' not found in '{}'", id, ftl_path),
+        };
+        return Err(syn::Error::new(input.id.span(), err));
+    }
+
+    let id = &input.id;
+    let rest = &input.rest;
+    // Including the file makes sure the check is run again whenever the translations change
+    let full_path = full_path.to_string_lossy().to_string();
+    Ok(quote! {
+        {
+            const _: &str = include_str!(#full_path);
+            ::perseus::t!(#id #rest)
+        }
+    })
+}
+
+/// Gets the messages (and the variants of them) that the given translation ID could refer to, in the order they're tried. This must match
+/// `perseus::translator::get_fluent_id_candidates()`, which we can't depend on here.
+fn get_id_candidates(id: &str) -> Vec<(String, Option<String>)> {
+    let segments: Vec<&str> = id.split('.').collect();
+    match segments.split_last() {
+        Some((variant, msg_segments)) if !msg_segments.is_empty() => vec![
+            (msg_segments.join("-"), Some(variant.to_string())),
+            (segments.join("-"), None),
+        ],
+        _ => vec![(id.to_string(), None)],
+    }
+}
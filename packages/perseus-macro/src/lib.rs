@@ -0,0 +1,85 @@
+/*!
+ * This crate contains the procedural macros used by [Perseus](https://github.com/arctic-hen7/perseus). These are all re-exported by the
+ * [core package](https://crates.io/crates/perseus), which is where you should use them from.
+ */
+
+#![deny(missing_docs)]
+
+mod checked_t;
+
+use crate::checked_t::{checked_t_impl, CheckedTInput};
+use proc_macro::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+/// Derives `IntoFluentArgs` for a struct with named fields, so that it can be given to a Fluent translation as its arguments, with each
+/// field becoming the argument of the same name. Every field's type must implement `IntoFluentValue` (which Perseus implements for
+/// numbers, strings, `bool`, and `Option`s of those), and using any other type will be a compile-time error pointing at that field.
+#[proc_macro_derive(IntoFluentArgs)]
+pub fn derive_into_fluent_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return syn::Error::new(
+                    input.span(),
+                    "`IntoFluentArgs` can only be derived for structs with named fields (the names are used as the argument names)",
+                )
+                .to_compile_error()
+                .into()
+            }
+        },
+        _ => {
+            return syn::Error::new(
+                input.span(),
+                "`IntoFluentArgs` can only be derived for structs",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+    // We span each conversion to its field so that errors about unsupported types point at the field rather than the derive
+    let set_args = fields.iter().map(|field| {
+        // These are all named fields
+        let ident = field.ident.as_ref().unwrap();
+        let arg_name = ident.to_string();
+        let ty = &field.ty;
+        quote_spanned! {ty.span()=>
+            args.set(#arg_name, <#ty as ::perseus::translator::IntoFluentValue>::into_fluent_value(self.#ident));
+        }
+    });
+
+    let output = quote! {
+        impl #impl_generics ::perseus::translator::IntoFluentArgs for #name #ty_generics #where_clause {
+            fn into_fluent_args(self) -> ::perseus::translator::FluentArgs<'static> {
+                let mut args = ::perseus::translator::FluentArgs::new();
+                #(#set_args)*
+                args
+            }
+        }
+    };
+
+    output.into()
+}
+
+/// Translates the given ID just like `t!`, but checks at compile-time that it exists in your default locale's FTL file, so that a
+/// missing message (or variant, with IDs like `[id].[variant]`) is an error in `cargo check` rather than a panic in the browser. The
+/// path to the FTL file (relative to your crate's root) can be given before the ID, like
+/// `checked_t!("translations/en-US.ftl", "greeting", { "name": name })`, or set once in the `PERSEUS_DEFAULT_FTL` environment variable
+/// (e.g. in the `[env]` section of `.cargo/config.toml`) so that it can be left out. Anything after the ID is given to `t!` as it is.
+///
+/// This only checks the default locale, since that's the one your IDs are written against, so you should still check your other
+/// locales with `perseus build --check-translations`.
+#[proc_macro]
+pub fn checked_t(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as CheckedTInput);
+    match checked_t_impl(input) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,346 @@
+use crate::errors::*;
+use crate::route_table::constant_time_eq;
+use crate::Request;
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+/// The environment variable HTTP basic auth credentials are read from by `AccessControl::from_env()`, as `username:password`.
+pub const BASIC_AUTH_VAR: &str = "PERSEUS_BASIC_AUTH";
+/// The environment variable an allowlist of IP addresses and CIDR ranges is read from by `AccessControl::from_env()`, separated by
+/// commas (e.g. `10.0.0.0/8,203.0.113.7`).
+pub const IP_ALLOWLIST_VAR: &str = "PERSEUS_IP_ALLOWLIST";
+/// The environment variable that makes `AccessControl::from_env()` trust the `X-Forwarded-For` header, which can be set to `true` if
+/// there's one proxy in front of the server, or to the number of proxies there are.
+pub const TRUST_FORWARDED_FOR_VAR: &str = "PERSEUS_TRUST_FORWARDED_FOR";
+
+/// A layer of protection for a whole app, which is useful for keeping staging deployments private (and out of search engines). Apps can
+/// be protected with HTTP basic auth credentials, an allowlist of IP addresses and CIDR ranges, or both, in which case users from an
+/// allowed address won't have to give credentials, but everyone else will. By default, nothing is protected.
+///
+/// This is enforced by the server integrations for every request (including for the app's bundles, page data, and translations), and
+/// can be configured per environment with `AccessControl::from_env()`.
+#[derive(Debug, Clone)]
+pub struct AccessControl {
+    /// The username and password users have to give with HTTP basic auth, if any.
+    basic_auth: Option<(String, String)>,
+    /// The IP addresses and ranges users can access the app from without credentials.
+    allowlist: Vec<IpNet>,
+    /// The number of proxies in front of the server that append to the `X-Forwarded-For` header, which is how many addresses from the
+    /// right of it are skipped to get the user's address. If this is `0`, that header isn't trusted at all (otherwise anyone could claim
+    /// to be anywhere).
+    trusted_proxies: usize,
+    /// The realm given to browsers when they're asked for credentials.
+    realm: String,
+}
+
+impl Default for AccessControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether or not a request is allowed through an app's `AccessControl`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessVerdict {
+    /// The request is allowed.
+    Allowed,
+    /// The request needs valid credentials, and should be answered with a `401 Unauthorized` and the given value in the
+    /// `WWW-Authenticate` header.
+    Unauthorized(String),
+    /// The request is from an address that isn't allowed, and should be answered with a `403 Forbidden`.
+    Forbidden,
+}
+
+impl AccessControl {
+    /// Creates a new access control layer that allows everything, which can then be configured with the other methods.
+    pub fn new() -> Self {
+        Self {
+            basic_auth: None,
+            allowlist: Vec::new(),
+            trusted_proxies: 0,
+            realm: "Perseus".to_string(),
+        }
+    }
+    /// Creates an access control layer from the environment, which allows protecting staging deployments without changing any code.
+    /// Credentials are read from `PERSEUS_BASIC_AUTH` (as `username:password`), an allowlist from `PERSEUS_IP_ALLOWLIST` (separated by
+    /// commas), and `X-Forwarded-For` will be trusted if `PERSEUS_TRUST_FORWARDED_FOR` is `true` (for one proxy) or a number of proxies. If
+    /// none of these are set, everything will be allowed.
+    pub fn from_env() -> Result<Self> {
+        let mut access_control = Self::new();
+        if let Ok(basic_auth) = std::env::var(BASIC_AUTH_VAR) {
+            let (username, password) = basic_auth
+                .split_once(':')
+                .ok_or_else(|| ErrorKind::InvalidBasicAuth(BASIC_AUTH_VAR.to_string()))?;
+            access_control = access_control.basic_auth(username, password);
+        }
+        if let Ok(allowlist) = std::env::var(IP_ALLOWLIST_VAR) {
+            access_control = access_control.allowlist(&allowlist)?;
+        }
+        if let Ok(trusted_proxies) = std::env::var(TRUST_FORWARDED_FOR_VAR) {
+            let trusted_proxies = match trusted_proxies.trim() {
+                "true" => 1,
+                "false" | "" => 0,
+                count => count.parse().map_err(|_| {
+                    ErrorKind::InvalidTrustedProxies(
+                        TRUST_FORWARDED_FOR_VAR.to_string(),
+                        trusted_proxies.clone(),
+                    )
+                })?,
+            };
+            access_control = access_control.trusted_proxies(trusted_proxies);
+        }
+
+        Ok(access_control)
+    }
+    /// Requires users to give the given username and password with HTTP basic auth (unless they're on the allowlist).
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.basic_auth = Some((username.into(), password.into()));
+        self
+    }
+    /// Only allows users from the given IP addresses and CIDR ranges, separated by commas (e.g. `10.0.0.0/8,203.0.113.7`). If credentials
+    /// are also required, users from anywhere else will be able to access the app with them.
+    pub fn allowlist(mut self, val: &str) -> Result<Self> {
+        for entry in val
+            .split(',')
+            .map(|entry| entry.trim())
+            .filter(|entry| !entry.is_empty())
+        {
+            // Single addresses are ranges of one
+            let net = match entry.parse::<IpNet>() {
+                Ok(net) => net,
+                Err(_) => entry.parse::<IpAddr>().map(IpNet::from).map_err(|err| {
+                    ErrorKind::InvalidAllowlistEntry(entry.to_string(), err.to_string())
+                })?,
+            };
+            self.allowlist.push(net);
+        }
+
+        Ok(self)
+    }
+    /// Sets whether or not the `X-Forwarded-For` header should be trusted for the user's address, which should only be enabled if the
+    /// server is behind exactly one proxy that appends to that header. The last address in it (the one that proxy added) will be used,
+    /// since any before it could have been sent by the user. For more than one proxy, use `.trusted_proxies()`.
+    pub fn trust_forwarded_for(self, val: bool) -> Self {
+        self.trusted_proxies(if val { 1 } else { 0 })
+    }
+    /// Sets the number of proxies in front of the server that append to the `X-Forwarded-For` header, which will be used to find the
+    /// user's address in it by skipping the addresses the other proxies added from the right (anything further left could have been sent
+    /// by the user). If the header has fewer addresses than this, or the user's one is malformed, the user's address will be treated as
+    /// unknown, so they won't be on the allowlist. By default, this is `0`, and the header isn't trusted.
+    pub fn trusted_proxies(mut self, val: usize) -> Self {
+        self.trusted_proxies = val;
+        self
+    }
+    /// Sets the realm browsers show users when asking them for credentials.
+    pub fn realm(mut self, val: impl Into<String>) -> Self {
+        self.realm = val.into();
+        self
+    }
+    /// Checks if this allows every request, in which case there's no need to check any.
+    pub fn is_open(&self) -> bool {
+        self.basic_auth.is_none() && self.allowlist.is_empty()
+    }
+    /// Checks if the given request is allowed through. The address the request was received from should be given if the server knows it
+    /// (it may be replaced with the `X-Forwarded-For` header if that's trusted).
+    pub fn check(&self, req: &Request, peer_ip: Option<IpAddr>) -> AccessVerdict {
+        if self.is_open() {
+            return AccessVerdict::Allowed;
+        }
+        let ip = match self.trusted_proxies {
+            0 => peer_ip,
+            trusted_proxies => get_forwarded_for(req, trusted_proxies, peer_ip),
+        };
+        let is_allowlisted = ip.map_or(false, |ip| {
+            self.allowlist.iter().any(|net| net.contains(&ip))
+        });
+        if is_allowlisted {
+            return AccessVerdict::Allowed;
+        }
+
+        match &self.basic_auth {
+            Some((username, password)) => {
+                let given = req
+                    .headers()
+                    .get(http::header::AUTHORIZATION)
+                    .and_then(|header| header.to_str().ok())
+                    .and_then(|header| header.strip_prefix("Basic "))
+                    .and_then(|encoded| base64::decode(encoded.trim()).ok())
+                    .and_then(|decoded| String::from_utf8(decoded).ok());
+                let expected = format!("{}:{}", username, password);
+                match given {
+                    Some(given) if constant_time_eq(given.as_bytes(), expected.as_bytes()) => {
+                        AccessVerdict::Allowed
+                    }
+                    _ => AccessVerdict::Unauthorized(format!(
+                        "Basic realm=\"{}\", charset=\"UTF-8\"",
+                        self.realm
+                    )),
+                }
+            }
+            // Without credentials, the allowlist is the only way in
+            None => AccessVerdict::Forbidden,
+        }
+    }
+}
+
+/// Gets the user's address from the `X-Forwarded-For` headers of the given request, given the number of proxies that append to them.
+/// If there aren't any of those headers, the request didn't come through a proxy, so the address it was received from is used.
+fn get_forwarded_for(
+    req: &Request,
+    trusted_proxies: usize,
+    peer_ip: Option<IpAddr>,
+) -> Option<IpAddr> {
+    let headers = req.headers().get_all("X-Forwarded-For");
+    if headers.iter().next().is_none() {
+        return peer_ip;
+    }
+    // Proxies can either append to the header or add another one, so we read them all in order
+    let mut addrs = Vec::new();
+    for header in headers.iter() {
+        addrs.extend(header.to_str().ok()?.split(',').map(|addr| addr.trim()));
+    }
+    // Each proxy appends the address it got the request from, so the outermost one added the user's this many from the right
+    let addr = *addrs.iter().rev().nth(trusted_proxies - 1)?;
+    parse_forwarded_addr(addr)
+}
+
+/// Parses an address in an `X-Forwarded-For` header, which some proxies give with a port (bracketed if it's IPv6).
+fn parse_forwarded_addr(addr: &str) -> Option<IpAddr> {
+    addr.parse::<IpAddr>()
+        .or_else(|_| addr.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(forwarded_for: &[&str]) -> Request {
+        let mut builder = http::Request::builder();
+        for header in forwarded_for {
+            builder = builder.header("X-Forwarded-For", *header);
+        }
+        builder.body(()).unwrap()
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ignores_forwarded_for_unless_trusted() {
+        let access_control = AccessControl::new().allowlist("203.0.113.7").unwrap();
+        assert_eq!(
+            access_control.check(&req(&["203.0.113.7"]), ip("198.51.100.1")),
+            AccessVerdict::Forbidden
+        );
+        assert_eq!(
+            access_control.check(&req(&[]), ip("203.0.113.7")),
+            AccessVerdict::Allowed
+        );
+    }
+
+    #[test]
+    fn uses_rightmost_forwarded_for_with_one_proxy() {
+        let access_control = AccessControl::new()
+            .allowlist("203.0.113.7")
+            .unwrap()
+            .trust_forwarded_for(true);
+        assert_eq!(
+            access_control.check(&req(&["198.51.100.1, 203.0.113.7"]), ip("10.0.0.1")),
+            AccessVerdict::Allowed
+        );
+        // A user can put whatever they like before the address the proxy appends
+        assert_eq!(
+            access_control.check(&req(&["203.0.113.7, 198.51.100.1"]), ip("10.0.0.1")),
+            AccessVerdict::Forbidden
+        );
+    }
+
+    #[test]
+    fn skips_trusted_proxy_hops() {
+        let access_control = AccessControl::new()
+            .allowlist("203.0.113.7")
+            .unwrap()
+            .trusted_proxies(2);
+        assert_eq!(
+            access_control.check(&req(&["203.0.113.7, 10.0.0.2"]), ip("10.0.0.1")),
+            AccessVerdict::Allowed
+        );
+        // Proxies that add their own header instead of appending are read in order
+        assert_eq!(
+            access_control.check(&req(&["203.0.113.7", "10.0.0.2"]), ip("10.0.0.1")),
+            AccessVerdict::Allowed
+        );
+        assert_eq!(
+            access_control.check(
+                &req(&["203.0.113.7, 198.51.100.1, 10.0.0.2"]),
+                ip("10.0.0.1")
+            ),
+            AccessVerdict::Forbidden
+        );
+    }
+
+    #[test]
+    fn rejects_too_few_forwarded_for_hops() {
+        // The peer is on the allowlist, but it's a proxy, so it mustn't be mistaken for the user
+        let access_control = AccessControl::new()
+            .allowlist("10.0.0.0/8")
+            .unwrap()
+            .trusted_proxies(2);
+        assert_eq!(
+            access_control.check(&req(&["10.0.0.2"]), ip("10.0.0.1")),
+            AccessVerdict::Forbidden
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_forwarded_for() {
+        let access_control = AccessControl::new()
+            .allowlist("0.0.0.0/0")
+            .unwrap()
+            .trust_forwarded_for(true);
+        for header in [
+            "not an address",
+            "203.0.113.7, ",
+            "203.0.113.300",
+            "unknown",
+        ] {
+            assert_eq!(
+                access_control.check(&req(&[header]), ip("10.0.0.1")),
+                AccessVerdict::Forbidden,
+                "{}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn parses_forwarded_for_with_ports() {
+        assert_eq!(parse_forwarded_addr("203.0.113.7:8080"), ip("203.0.113.7"));
+        assert_eq!(parse_forwarded_addr("[2001:db8::1]:443"), ip("2001:db8::1"));
+        assert_eq!(parse_forwarded_addr("2001:db8::1"), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn matches_ipv6_ranges() {
+        let access_control = AccessControl::new()
+            .allowlist("2001:db8::/32, 203.0.113.7")
+            .unwrap();
+        assert_eq!(
+            access_control.check(&req(&[]), ip("2001:db8:1234::1")),
+            AccessVerdict::Allowed
+        );
+        assert_eq!(
+            access_control.check(&req(&[]), ip("2001:db9::1")),
+            AccessVerdict::Forbidden
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_allowlist_entries() {
+        assert!(AccessControl::new().allowlist("10.0.0.0/33").is_err());
+        assert!(AccessControl::new().allowlist("2001:db8::/129").is_err());
+        assert!(AccessControl::new().allowlist("localhost").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+// This provides a small HTTP client for the APIs that build and request state functions get their content from (like a headless CMS)
+// Requests are made on their own threads so this works from any executor (the build process doesn't use the same one as the server)
+
+use crate::errors::*;
+use futures::channel::oneshot;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// An authorization header whose value is read from an environment variable whenever a request is made.
+#[derive(Debug, Clone)]
+struct EnvAuth {
+    header: String,
+    var: String,
+    /// Anything to put before the variable's value in the header (like `Bearer `).
+    prefix: String,
+}
+
+/// A cache of the responses to requests on disk, so that rebuilding an app in development doesn't hit the API for every page every time.
+#[derive(Debug, Clone)]
+pub(crate) struct DevCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+impl DevCache {
+    /// Gets the file the response to a request with the given key (like its URL) would be cached in.
+    fn get_file(&self, key: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.dir.join(format!("{:x}.txt", hasher.finish()))
+    }
+    /// Gets the cached response to a request with the given key, if there is one that hasn't expired. Any problems reading the cache
+    /// are treated as if nothing were cached.
+    pub(crate) fn get(&self, key: &str) -> Option<String> {
+        let file = self.get_file(key);
+        let modified = fs::metadata(&file).ok()?.modified().ok()?;
+        match SystemTime::now().duration_since(modified) {
+            Ok(age) if age <= self.ttl => fs::read_to_string(&file).ok(),
+            _ => None,
+        }
+    }
+    /// Caches the given response to a request with the given key. Failing to cache a response isn't worth failing the request over, so
+    /// any errors are ignored.
+    pub(crate) fn set(&self, key: &str, body: &str) {
+        let _ = fs::create_dir_all(&self.dir);
+        let _ = fs::write(self.get_file(key), body);
+    }
+}
+
+/// A simple HTTP client for getting content from an API in build and request state functions. This retries failed `GET` requests,
+/// injects authorization headers from environment variables (so secrets don't end up in your code), and can cache responses on disk in
+/// development. This is only available with the `api-client` feature.
+///
+/// Errors from this can be converted into the errors that state functions return with
+/// `.map_err(|err| (err.to_string(), ErrorCause::Server(None)))`.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    base_url: String,
+    auth: Option<EnvAuth>,
+    headers: Vec<(String, String)>,
+    /// The number of times to retry a failed `GET` request.
+    retries: u32,
+    /// The delay before the first retry, which will double for each retry after that.
+    retry_delay: Duration,
+    timeout: Duration,
+    dev_cache: Option<DevCache>,
+}
+impl ApiClient {
+    /// Creates a new client for the API at the given base URL (like `https://cms.example.com/api`). Paths given to this client's methods
+    /// will be appended to that. By default, failed `GET` requests will be retried twice, starting after 200 milliseconds.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            auth: None,
+            headers: Vec::new(),
+            retries: 2,
+            retry_delay: Duration::from_millis(200),
+            timeout: Duration::from_secs(30),
+            dev_cache: None,
+        }
+    }
+
+    // Builder setters
+    /// Sends the value of the given environment variable in the given header with every request (e.g. an API key in `X-Api-Key`). The
+    /// variable is read when each request is made, and requests will fail if it isn't set.
+    pub fn auth_header_from_env(
+        mut self,
+        header: impl Into<String>,
+        var: impl Into<String>,
+    ) -> Self {
+        self.auth = Some(EnvAuth {
+            header: header.into(),
+            var: var.into(),
+            prefix: String::new(),
+        });
+        self
+    }
+    /// Sends the value of the given environment variable as a bearer token in the `Authorization` header with every request. The
+    /// variable is read when each request is made, and requests will fail if it isn't set.
+    pub fn bearer_auth_from_env(mut self, var: impl Into<String>) -> Self {
+        self.auth = Some(EnvAuth {
+            header: "Authorization".to_string(),
+            var: var.into(),
+            prefix: "Bearer ".to_string(),
+        });
+        self
+    }
+    /// Sends the given header with every request.
+    pub fn header(mut self, name: impl Into<String>, val: impl Into<String>) -> Self {
+        self.headers.push((name.into(), val.into()));
+        self
+    }
+    /// Sets the number of times to retry a `GET` request that failed to connect or returned a server error (or a `429 Too Many Requests`).
+    pub fn retries(mut self, val: u32) -> Self {
+        self.retries = val;
+        self
+    }
+    /// Sets the delay before the first retry of a failed `GET` request, which will double for each retry after that.
+    pub fn retry_delay(mut self, val: Duration) -> Self {
+        self.retry_delay = val;
+        self
+    }
+    /// Sets how long to wait for each request before giving up on it.
+    pub fn timeout(mut self, val: Duration) -> Self {
+        self.timeout = val;
+        self
+    }
+    /// Caches the responses to `GET` requests in the given directory for the given time. This only works in development (debug builds),
+    /// so production builds will always get fresh content. Something like `.perseus/cache` is a good place to put this.
+    pub fn dev_cache(mut self, dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        self.dev_cache = Some(DevCache {
+            dir: dir.into(),
+            ttl,
+        });
+        self
+    }
+
+    /// Makes a `GET` request to the given path, returning the body of the response.
+    pub async fn get(&self, path: &str) -> Result<String> {
+        let url = self.get_url(path);
+        let dev_cache = self.get_dev_cache();
+        if let Some(body) = dev_cache.as_ref().and_then(|cache| cache.get(&url)) {
+            return Ok(body);
+        }
+        let client = self.clone();
+        let body = run_on_thread(&url, {
+            let url = url.clone();
+            move || client.get_blocking(&url)
+        })
+        .await?;
+        if let Some(dev_cache) = dev_cache {
+            dev_cache.set(&url, &body);
+        }
+
+        Ok(body)
+    }
+    /// Makes a `GET` request to the given path, deserializing the body of the response from JSON.
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let body = self.get(path).await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+    /// Makes a `POST` request to the given path with the given body serialized as JSON, deserializing the body of the response from JSON.
+    /// These requests aren't retried or cached, since they might not be safe to repeat.
+    pub async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T> {
+        let url = self.get_url(path);
+        let body = serde_json::to_string(body)?;
+        let client = self.clone();
+        let res_body = run_on_thread(&url, {
+            let url = url.clone();
+            move || {
+                let req = client
+                    .build_req(reqwest::Method::POST, &url)?
+                    .header("Content-Type", "application/json")
+                    .body(body);
+                let (status, res_body) = send(req, &url)?;
+                check_status(&url, status, res_body)
+            }
+        })
+        .await?;
+
+        Ok(serde_json::from_str(&res_body)?)
+    }
+
+    /// Gets the full URL for the given path.
+    pub(crate) fn get_url(&self, path: &str) -> String {
+        match path.trim_start_matches('/') {
+            "" => self.base_url.clone(),
+            path => format!("{}/{}", self.base_url, path),
+        }
+    }
+    /// Gets the cache to use for responses, if there is one and this is a development build.
+    pub(crate) fn get_dev_cache(&self) -> Option<DevCache> {
+        self.dev_cache.clone().filter(|_| cfg!(debug_assertions))
+    }
+    /// Builds a request with the given method to the given URL, including all the headers this client should send.
+    fn build_req(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+    ) -> Result<reqwest::blocking::RequestBuilder> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .map_err(|err| ErrorKind::ApiRequestFailed(url.to_string(), err.to_string()))?;
+        let mut req = client.request(method, url);
+        for (name, val) in &self.headers {
+            req = req.header(name.as_str(), val.as_str());
+        }
+        if let Some(auth) = &self.auth {
+            let val = match std::env::var(&auth.var) {
+                Ok(val) => val,
+                Err(_) => bail!(ErrorKind::ApiAuthNotSet(auth.var.clone())),
+            };
+            req = req.header(auth.header.as_str(), format!("{}{}", auth.prefix, val));
+        }
+
+        Ok(req)
+    }
+    /// Makes a `GET` request to the given URL, blocking the current thread and retrying it if it fails in a way that might be temporary.
+    fn get_blocking(&self, url: &str) -> Result<String> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let res = send(self.build_req(reqwest::Method::GET, url)?, url);
+            let retryable = match &res {
+                Ok((status, _)) => status.is_server_error() || status.as_u16() == 429,
+                // We couldn't get a response at all
+                Err(_) => true,
+            };
+            if !retryable || attempt >= self.retries {
+                let (status, body) = res?;
+                return check_status(url, status, body);
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// Sends the given request, returning the status and body of the response.
+fn send(
+    req: reqwest::blocking::RequestBuilder,
+    url: &str,
+) -> Result<(reqwest::StatusCode, String)> {
+    let res = req
+        .send()
+        .map_err(|err| ErrorKind::ApiRequestFailed(url.to_string(), err.to_string()))?;
+    let status = res.status();
+    let body = res
+        .text()
+        .map_err(|err| ErrorKind::ApiRequestFailed(url.to_string(), err.to_string()))?;
+
+    Ok((status, body))
+}
+
+/// Returns the body of a response if its status was a success, and an error otherwise.
+fn check_status(url: &str, status: reqwest::StatusCode, body: String) -> Result<String> {
+    match status.is_success() {
+        true => Ok(body),
+        false => bail!(ErrorKind::ApiResponseNotOk(
+            url.to_string(),
+            status.as_u16(),
+            body
+        )),
+    }
+}
+
+/// Runs the given blocking function for a request to the given URL on its own thread, resolving to its result when it's done.
+async fn run_on_thread<T: Send + 'static>(
+    url: &str,
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let (tx, rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+    match rx.await {
+        Ok(res) => res,
+        // The thread panicked before sending anything
+        Err(_) => bail!(ErrorKind::ApiRequestFailed(
+            url.to_string(),
+            "the thread making the request panicked".to_string()
+        )),
+    }
+}
@@ -0,0 +1,349 @@
+// This runs some basic checks over the pages generated at build-time to catch malformed HTML and common accessibility problems
+
+use crate::build::get_prerendered_pages;
+use crate::config_manager::ConfigManager;
+use crate::errors::*;
+use crate::Locales;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Elements that show something to the user without any text in them.
+const MEDIA_ELEMENTS: [&str; 6] = ["img", "svg", "video", "picture", "iframe", "object"];
+/// Elements that never have any content, and so never have closing tags.
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// A problem found by the audit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditIssueKind {
+    /// An element was opened but never closed.
+    UnclosedTag(String),
+    /// An element was closed without having been opened.
+    UnexpectedClosingTag(String),
+    /// An image has no `alt` attribute (use `alt=""` for images that are purely decorative).
+    MissingAlt,
+    /// More than one element has the given `id`.
+    DuplicateId(String),
+    /// There's more than one `<main>` landmark.
+    MultipleMains,
+    /// A heading skips one or more levels from the previous heading (e.g. an `<h1>` followed by an `<h3>`).
+    SkippedHeadingLevel(u8, u8),
+    /// The page has no text or media in it before it's hydrated, so it'll be blank for users without JavaScript (this is expected for
+    /// client-side only templates).
+    EmptyWithoutJs,
+}
+impl fmt::Display for AuditIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnclosedTag(name) => write!(f, "'<{}>' is never closed", name),
+            Self::UnexpectedClosingTag(name) => {
+                write!(f, "'</{}>' doesn't close any open element", name)
+            }
+            Self::MissingAlt => write!(f, "an image is missing an 'alt' attribute"),
+            Self::DuplicateId(id) => write!(f, "the id '{}' is used more than once", id),
+            Self::MultipleMains => write!(f, "there's more than one '<main>' landmark"),
+            Self::SkippedHeadingLevel(from, to) => {
+                write!(f, "'<h{}>' follows '<h{}>', skipping a level", to, from)
+            }
+            Self::EmptyWithoutJs => write!(
+                f,
+                "the page is blank until it's hydrated, so users without JavaScript won't see anything"
+            ),
+        }
+    }
+}
+
+/// A problem found by the audit in a page prerendered at build-time.
+#[derive(Debug, Clone)]
+pub struct AuditIssue {
+    /// The template that rendered the page.
+    pub template: String,
+    /// The locale of the page.
+    pub locale: String,
+    /// The path of the page (without the locale).
+    pub page: String,
+    /// The problem itself.
+    pub kind: AuditIssueKind,
+}
+impl fmt::Display for AuditIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] page '{}' (locale '{}'): {}",
+            self.template, self.page, self.locale, self.kind
+        )
+    }
+}
+
+/// Audits every page prerendered at build-time for malformed HTML (unclosed or stray tags) and basic accessibility problems (images
+/// without alternative text, duplicate IDs, multiple `<main>` landmarks, skipped heading levels, and pages that are blank without
+/// JavaScript). This must be run after the build
+/// process. The issues found will be ordered by the template that rendered them. This is deliberately simple, and is no substitute for
+/// a proper accessibility audit!
+pub async fn audit_html(
+    locales: &Locales,
+    config_manager: &impl ConfigManager,
+) -> Result<Vec<AuditIssue>> {
+    let mut issues = Vec::new();
+    for page in get_prerendered_pages(locales, config_manager).await? {
+        for kind in audit_page(&page.html) {
+            issues.push(AuditIssue {
+                template: page.template.clone(),
+                locale: page.locale.clone(),
+                page: page.path.clone(),
+                kind,
+            });
+        }
+    }
+    // Group everything by template so it can be reported that way
+    issues
+        .sort_by(|a, b| (&a.template, &a.page, &a.locale).cmp(&(&b.template, &b.page, &b.locale)));
+
+    Ok(issues)
+}
+
+/// A tag in an HTML document.
+enum Tag {
+    /// An opening tag, with its attributes.
+    Open {
+        name: String,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    /// A closing tag.
+    Close(String),
+}
+
+/// Audits the given HTML, returning any problems found.
+fn audit_page(html: &str) -> Vec<AuditIssueKind> {
+    let mut issues = Vec::new();
+    let mut open_elems: Vec<String> = Vec::new();
+    let mut ids = HashSet::new();
+    let mut mains = 0;
+    let mut last_heading: Option<u8> = None;
+    let mut has_media = false;
+
+    for tag in get_tags(html) {
+        match tag {
+            Tag::Open {
+                name,
+                attrs,
+                self_closing,
+            } => {
+                has_media |= MEDIA_ELEMENTS.contains(&name.as_str());
+                if name == "img" && !attrs.iter().any(|(attr, _)| attr == "alt") {
+                    issues.push(AuditIssueKind::MissingAlt);
+                }
+                if let Some((_, id)) = attrs.iter().find(|(attr, _)| attr == "id") {
+                    if !ids.insert(id.to_string()) {
+                        issues.push(AuditIssueKind::DuplicateId(id.to_string()));
+                    }
+                }
+                if name == "main" {
+                    mains += 1;
+                    // We only want to report this once
+                    if mains == 2 {
+                        issues.push(AuditIssueKind::MultipleMains);
+                    }
+                }
+                if let Some(level) = get_heading_level(&name) {
+                    if let Some(last_level) = last_heading {
+                        if level > last_level + 1 {
+                            issues.push(AuditIssueKind::SkippedHeadingLevel(last_level, level));
+                        }
+                    }
+                    last_heading = Some(level);
+                }
+
+                if !self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
+                    open_elems.push(name);
+                }
+            }
+            Tag::Close(name) => {
+                if VOID_ELEMENTS.contains(&name.as_str()) {
+                    continue;
+                }
+                match open_elems.iter().rposition(|open| open == &name) {
+                    // Anything opened after this element should've been closed before it
+                    Some(idx) => {
+                        for unclosed in open_elems.drain(idx..).skip(1) {
+                            issues.push(AuditIssueKind::UnclosedTag(unclosed));
+                        }
+                    }
+                    None => issues.push(AuditIssueKind::UnexpectedClosingTag(name)),
+                }
+            }
+        }
+    }
+    for unclosed in open_elems {
+        issues.push(AuditIssueKind::UnclosedTag(unclosed));
+    }
+    if !has_media && !has_text(html) {
+        issues.push(AuditIssueKind::EmptyWithoutJs);
+    }
+
+    issues
+}
+
+/// Gets the level of the given element if it's a heading (e.g. `2` for `h2`).
+fn get_heading_level(name: &str) -> Option<u8> {
+    match name.strip_prefix('h')?.parse::<u8>() {
+        Ok(level) if (1..=6).contains(&level) => Some(level),
+        _ => None,
+    }
+}
+
+/// Extracts all the tags in the given HTML. We don't need a full parser for this, because we only need to handle what Sycamore renders
+/// (plus anything the user has interpolated directly).
+fn get_tags(html: &str) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        // Comments (which Sycamore uses for hydration markers) can contain anything
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = match comment.find("-->") {
+                Some(end) => &comment[(end + 3)..],
+                None => "",
+            };
+            continue;
+        }
+        // A stray `<` in text isn't a tag
+        if !rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || "/!?".contains(c)) {
+            rest = &rest[1..];
+            continue;
+        }
+        let end = match get_tag_end(rest) {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[1..end];
+        rest = &rest[(end + 1)..];
+
+        // Doctypes and the like aren't elements
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            tags.push(Tag::Close(name.trim().to_lowercase()));
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag.find(char::is_whitespace).unwrap_or_else(|| tag.len());
+        let name = tag[..name_end].to_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+        let attrs = get_attrs(&tag[name_end..]);
+        // The contents of scripts and styles aren't HTML
+        if !self_closing && (name == "script" || name == "style") {
+            let closing_tag = format!("</{}", name);
+            if let Some(close) = rest.to_ascii_lowercase().find(&closing_tag) {
+                rest = &rest[close..];
+            }
+        }
+        tags.push(Tag::Open {
+            name,
+            attrs,
+            self_closing,
+        });
+    }
+
+    tags
+}
+
+/// Checks if the given HTML has any text in it outside of tags, comments, scripts, and styles.
+fn has_text(html: &str) -> bool {
+    let mut rest = html;
+    loop {
+        let start = rest.find('<').unwrap_or_else(|| rest.len());
+        if !rest[..start].trim().is_empty() {
+            return true;
+        }
+        rest = &rest[start..];
+        if rest.is_empty() {
+            return false;
+        }
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = match comment.find("-->") {
+                Some(end) => &comment[(end + 3)..],
+                None => "",
+            };
+            continue;
+        }
+        let end = match get_tag_end(rest) {
+            Some(end) => end,
+            None => return false,
+        };
+        let name = rest[1..end]
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        rest = &rest[(end + 1)..];
+        // The contents of scripts and styles aren't shown
+        if name == "script" || name == "style" {
+            let closing_tag = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&closing_tag) {
+                Some(close) => &rest[close..],
+                None => "",
+            };
+        }
+    }
+}
+
+/// Gets the index of the `>` that ends the tag at the start of the given HTML, ignoring any in quoted attribute values.
+fn get_tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (idx, c) in html.char_indices() {
+        match (c, quote) {
+            ('"', None) | ('\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('>', None) => return Some(idx),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Parses the attributes in the given part of a tag into key-value pairs (attributes without values will have empty values).
+fn get_attrs(attrs_str: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut chars = attrs_str.trim().chars().peekable();
+    while chars.peek().is_some() {
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            name.push(c);
+        }
+        // We might've stopped at some whitespace before an `=`
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            while chars.peek().map_or(false, |c| c.is_whitespace()) {
+                chars.next();
+            }
+            match chars.peek().copied() {
+                Some(quote) if quote == '"' || quote == '\'' => {
+                    chars.next();
+                    value = chars.by_ref().take_while(|c| *c != quote).collect();
+                }
+                _ => value = chars.by_ref().take_while(|c| !c.is_whitespace()).collect(),
+            }
+        }
+        if !name.is_empty() {
+            attrs.push((name.to_lowercase(), value));
+        }
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    attrs
+}
@@ -0,0 +1,104 @@
+// This lets request state functions queue work that shouldn't hold up their response (like sending an email), which the server
+// integrations then run once the response is ready, while keeping count of unfinished jobs so servers can wait for them when they shut down
+
+use futures::Future;
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+
+thread_local! {
+    /// The jobs queued on this thread that haven't been taken by a server integration yet. Each request is handled on one thread from
+    /// start to finish, so the integration will find the jobs its request queued here.
+    static QUEUED_JOBS: RefCell<Vec<BackgroundJob>> = RefCell::new(Vec::new());
+}
+/// The number of jobs that have been queued but haven't finished yet (on any thread).
+static UNFINISHED_JOBS: AtomicUsize = AtomicUsize::new(0);
+/// The tasks waiting for every job to finish.
+static DRAIN_WAKERS: Lazy<Mutex<Vec<Waker>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Marks a job as unfinished for as long as it exists, so that a job that's dropped without finishing (like if the runtime it was
+/// spawned on stops) doesn't leave anything waiting for it forever.
+struct UnfinishedGuard;
+impl UnfinishedGuard {
+    fn new() -> Self {
+        UNFINISHED_JOBS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+impl Drop for UnfinishedGuard {
+    fn drop(&mut self) {
+        if UNFINISHED_JOBS.fetch_sub(1, Ordering::SeqCst) == 1 {
+            if let Ok(mut wakers) = DRAIN_WAKERS.lock() {
+                for waker in wakers.drain(..) {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+/// A job queued with `spawn_after_response`, which server integrations should spawn (or otherwise run) once the response for the
+/// request that queued it is ready.
+pub struct BackgroundJob {
+    job: Pin<Box<dyn Future<Output = ()>>>,
+    _guard: UnfinishedGuard,
+}
+impl Future for BackgroundJob {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.job.as_mut().poll(cx)
+    }
+}
+
+/// Queues the given work to be run once the response to the current request is ready, so that it doesn't hold up the response (e.g.
+/// sending a welcome email from a request state function). This should only be called on the server, while handling a request.
+///
+/// Unlike a task spawned with `tokio::spawn` or the like, the server knows about these jobs, and will wait for them to finish before it
+/// shuts down (up to a timeout). Jobs can't return anything, so they should handle their own errors (e.g. by logging them).
+pub fn spawn_after_response(job: impl Future<Output = ()> + 'static) {
+    let job = BackgroundJob {
+        job: Box::pin(job),
+        _guard: UnfinishedGuard::new(),
+    };
+    QUEUED_JOBS.with(|jobs| jobs.borrow_mut().push(job));
+}
+
+/// Takes the jobs that have been queued on this thread. Server integrations should call this once they've got the response for a
+/// request and spawn the jobs it gives.
+pub fn take_queued_jobs() -> Vec<BackgroundJob> {
+    QUEUED_JOBS.with(|jobs| jobs.borrow_mut().drain(..).collect())
+}
+
+/// Gets the number of jobs that have been queued but haven't finished yet.
+pub fn get_unfinished_jobs() -> usize {
+    UNFINISHED_JOBS.load(Ordering::SeqCst)
+}
+
+/// A future that resolves once every background job has finished (or been dropped).
+pub struct JobsFinished;
+impl Future for JobsFinished {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if get_unfinished_jobs() == 0 {
+            return Poll::Ready(());
+        }
+        if let Ok(mut wakers) = DRAIN_WAKERS.lock() {
+            wakers.push(cx.waker().clone());
+        }
+        // The last job might have finished while we were registering
+        match get_unfinished_jobs() {
+            0 => Poll::Ready(()),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+/// Waits for every background job to finish, which servers should do (with a timeout) before they shut down.
+pub fn wait_for_jobs() -> JobsFinished {
+    JobsFinished
+}
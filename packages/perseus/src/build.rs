@@ -1,191 +1,816 @@
 // This binary builds all the templates with SSG
 
 use crate::errors::*;
+use crate::path_equivalents::{write_path_equivalents, PathEquivalents};
+use crate::path_pattern::is_path_pattern;
+use crate::render_panic::catch_render_panic;
+use crate::serve::{
+    get_page_artifact_name, get_page_intervals, get_render_cfg, read_page_artifact,
+    write_render_time, write_state_schema,
+};
+use crate::translator::record_failed_translations;
 use crate::Locales;
 use crate::TranslationsManager;
 use crate::Translator;
-use crate::{config_manager::ConfigManager, decode_time_str::decode_time_str, template::Template};
-use futures::future::try_join_all;
-use std::collections::HashMap;
+use crate::{
+    config_manager::ConfigManager,
+    decode_time_str::get_datetime_after,
+    template::{BuildState, Template},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use futures::executor::block_on;
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::rc::Rc;
 use sycamore::prelude::SsrNode;
 
-/// Builds a template, writing static data as appropriate. This should be used as part of a larger build process. This returns both a list
-/// of the extracted render options for this template (needed at request time), a list of pages that it explicitly generated, and a boolean
-/// as to whether or not it only generated a single page to occupy the template's root path (`true` unless using using build-time path
-/// generation).
-pub async fn build_template(
+/// The number of pages of each template that the build process will build at once by default.
+pub const DFLT_BUILD_CONCURRENCY: usize = 16;
+
+/// How the build process should handle pages that fail to build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildErrorMode {
+    /// Stop at the first error (the default). Panics will still be collected, since they're caught around each page.
+    FailFast,
+    /// Keep building everything else, and then fail with a report of every page that failed, grouped by template.
+    Aggregate,
+}
+impl Default for BuildErrorMode {
+    fn default() -> Self {
+        Self::FailFast
+    }
+}
+
+/// A page that failed to build.
+#[derive(Debug, Clone)]
+pub struct PageFailure {
+    /// The template that was building the page.
+    pub template: String,
+    /// The locale the page was being built for.
+    pub locale: String,
+    /// The path of the page, or `None` if the template failed before it got to any pages (e.g. while getting its build paths).
+    pub path: Option<String>,
+    /// The error that occurred.
+    pub err: String,
+}
+impl fmt::Display for PageFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(
+                f,
+                "page '{}' (locale '{}'): {}",
+                path, self.locale, self.err
+            ),
+            None => write!(f, "all pages (locale '{}'): {}", self.locale, self.err),
+        }
+    }
+}
+
+/// Restricts a build to certain templates and/or pages, leaving everything else that was built before untouched. This is useful for
+/// iterating on a few pages of a large app. An empty filter (the default) includes everything, which means a full build.
+#[derive(Debug, Clone, Default)]
+pub struct BuildFilter {
+    /// The names (full paths) of the templates to build all the pages of.
+    templates: Vec<String>,
+    /// The paths of the pages to build (without locales), each of which also includes any pages underneath it.
+    paths: Vec<String>,
+}
+impl BuildFilter {
+    /// Creates a new filter that includes everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Includes every page of the template with the given name (the path it was defined with).
+    pub fn template(mut self, name: impl Into<String>) -> Self {
+        self.templates.push(name.into());
+        self
+    }
+    /// Includes the page at the given path (without the locale), and any pages underneath it.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.paths.push(path.into().trim_matches('/').to_string());
+        self
+    }
+    /// Checks if this filter includes everything, meaning the build will be a full one.
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty() && self.paths.is_empty()
+    }
+    /// Checks if the given path is one of the filter's paths or underneath one.
+    fn includes_path(&self, path: &str) -> bool {
+        self.paths.iter().any(|filter_path| {
+            path == filter_path || path.starts_with(&format!("{}/", filter_path))
+        })
+    }
+    /// Checks if the filter includes all of the pages of the given template.
+    fn includes_whole_template(&self, template: &Template<SsrNode>) -> bool {
+        self.is_empty()
+            || self.templates.contains(&template.get_path())
+            || self.includes_path(&template.get_root_path())
+    }
+    /// Checks if the filter includes any of the pages of the given template.
+    fn includes_template(&self, template: &Template<SsrNode>) -> bool {
+        self.includes_whole_template(template)
+            || self
+                .paths
+                .iter()
+                .any(|filter_path| template.matches_path(filter_path))
+    }
+    /// Checks if the filter includes the page at the given path of the given template.
+    fn includes_page(&self, template: &Template<SsrNode>, path: &str) -> bool {
+        self.includes_whole_template(template) || self.includes_path(path)
+    }
+}
+
+/// Formats the given failures into a report grouped by template, in the order the templates first failed.
+pub(crate) fn format_page_failures(failures: &[PageFailure]) -> String {
+    let mut templates: Vec<&str> = Vec::new();
+    for failure in failures {
+        if !templates.contains(&failure.template.as_str()) {
+            templates.push(&failure.template);
+        }
+    }
+    let mut report = String::new();
+    for template in templates {
+        report.push_str(&format!("\n[{}]", template));
+        for failure in failures
+            .iter()
+            .filter(|failure| failure.template == template)
+        {
+            report.push_str(&format!("\n    {}", failure));
+        }
+    }
+
+    report
+}
+
+/// Gets the paths that the given template generates at build-time for the given locale (relative to its root path), along with their
+/// build states if they were generated together, and whether or not the template only generates a single page to occupy its root path.
+async fn get_paths_with_state(
     template: &Template<SsrNode>,
-    translator: Rc<Translator>,
-    config_manager: &impl ConfigManager,
-) -> Result<(Vec<String>, bool)> {
-    let mut single_page = false;
-    let template_path = template.get_path();
+    locale: &str,
+) -> Result<(Vec<(String, Option<BuildState>)>, bool)> {
+    // If the template's path is dynamic and it doesn't generate any paths, we don't know what to prerender, so its pages will all be
+    // rendered at request-time
+    if template.has_dynamic_path() && !template.uses_build_paths() {
+        return Ok((Vec::new(), true));
+    }
 
     // Handle static path generation
     // Because we iterate over the paths, we need a base path if we're not generating custom ones (that'll be overriden if needed)
-    let paths = match template.uses_build_paths() {
-        true => template.get_build_paths().await?,
-        false => {
-            single_page = true;
-            vec![String::new()]
-        }
-    };
+    // If the paths were generated along with their states, we'll keep those so we don't have to generate them again
+    match template.uses_build_paths_with_state() {
+        true => Ok((
+            template
+                .get_build_paths_with_state(locale.to_string())
+                .await?
+                .into_iter()
+                .map(|(path, state)| (path, Some(state)))
+                .collect(),
+            false,
+        )),
+        false => match template.uses_build_paths() {
+            true => Ok((
+                template
+                    .get_build_paths()
+                    .await?
+                    .into_iter()
+                    // Some paths might only exist in some locales
+                    .filter(|path| path.is_for_locale(locale))
+                    .map(|path| (path.path, None))
+                    .collect(),
+                false,
+            )),
+            false => Ok((vec![(String::new(), None)], true)),
+        },
+    }
+}
+
+/// Gets the full path (without the locale) of the page at the given path generated by the given template.
+pub(crate) fn get_page_path(template: &Template<SsrNode>, path: &str) -> String {
+    // Generated paths go under the root of the template's path (before any dynamic segments)
+    let template_path = template.get_root_path();
+    // We don't want to concatenate the name twice if we don't have to
+    match template.uses_build_paths() {
+        true if template_path.is_empty() => path.to_string(),
+        true => format!("{}/{}", &template_path, path),
+        false => template_path,
+    }
+}
 
-    // Iterate through the paths to generate initial states if needed
-    for path in paths.iter() {
-        // If needed, we'll contruct a full path that's URL encoded so we can easily save it as a file
-        // BUG: insanely nested paths won't work whatsoever if the filename is too long, maybe hash instead?
-        let full_path = match template.uses_build_paths() {
-            true => urlencoding::encode(&format!("{}/{}", &template_path, path)).to_string(),
-            // We don't want to concatenate the name twice if we don't have to
-            false => urlencoding::encode(&template_path).to_string(),
+/// Prerenders the page at the given path with the given template and state. In strict i18n mode, this will fail if any translations
+/// failed while it was being rendered, even if they were handled gracefully.
+fn prerender_page(
+    template: &Template<SsrNode>,
+    state: Option<String>,
+    translator: Rc<Translator>,
+    page_path: &str,
+    i18n_strict: bool,
+) -> Result<String> {
+    if !i18n_strict {
+        return Ok(template.render_html_str(state, translator, page_path));
+    }
+    let (prerendered, failed_ids) =
+        record_failed_translations(|| template.render_html_str(state, translator, page_path));
+    match failed_ids.is_empty() {
+        true => Ok(prerendered),
+        false => bail!(ErrorKind::MissingTranslations(failed_ids)),
+    }
+}
+
+/// Builds a single page of the given template, writing its static data, its state (generating that if it wasn't generated along with the
+/// page's path), and its prerendered HTML as appropriate. This returns `None` if the page turned out not to exist, and otherwise whether or
+/// not it set its own revalidation interval from its build state.
+async fn build_page(
+    template: &Template<SsrNode>,
+    translator: Rc<Translator>,
+    config_manager: &impl ConfigManager,
+    page_path: &str,
+    build_state: Option<BuildState>,
+    i18n_strict: bool,
+) -> Result<Option<bool>> {
+    // If needed, we'll contruct a full path that's URL encoded (in the locale's directory) so we can easily save it as a file
+    // BUG: insanely nested paths won't work whatsoever if the filename is too long, maybe hash instead?
+    let full_path = get_page_artifact_name(&translator.get_locale(), page_path);
+
+    // Pages can override the template's revalidation interval from their build state
+    let mut revalidate_interval = template.get_revalidate_interval();
+    let mut own_interval = false;
+
+    // Handle static initial state generation
+    // We'll only write a static state if one is explicitly generated
+    if template.uses_build_state() {
+        // We pass in the path to get a state (including the template path for consistency with the incremental logic)
+        // This runs once for every locale, so the user can generate locale-specific state
+        let build_state = match build_state {
+            Some(build_state) => build_state,
+            None => {
+                template
+                    .get_build_state(page_path.to_string(), translator.get_locale())
+                    .await?
+            }
         };
-        // Add the current locale to the front of that
-        let full_path = format!("{}-{}", translator.get_locale(), full_path);
-
-        // Handle static initial state generation
-        // We'll only write a static state if one is explicitly generated
-        if template.uses_build_state() {
-            // We pass in the path to get a state (including the template path for consistency with the incremental logic)
-            let initial_state = template.get_build_state(full_path.clone()).await?;
-            // Write that intial state to a static JSON file
-            config_manager
-                .write(&format!("static/{}.json", full_path), &initial_state)
-                .await?;
-            // Prerender the template using that state
-            let prerendered = sycamore::render_to_string(|| {
-                template.render_for_template(Some(initial_state), Rc::clone(&translator))
-            });
-            // Write that prerendered HTML to a static file
-            config_manager
-                .write(&format!("static/{}.html", full_path), &prerendered)
-                .await?;
+        // There's nothing to redirect at build-time
+        if build_state.redirect.is_some() {
+            bail!(ErrorKind::RedirectNotAllowed(page_path.to_string()))
         }
-
-        // Handle revalidation, we need to parse any given time strings into datetimes
-        // We don't need to worry about revalidation that operates by logic, that's request-time only
-        if template.revalidates_with_time() {
-            let datetime_to_revalidate =
-                decode_time_str(&template.get_revalidate_interval().unwrap())?;
-            // Write that to a static file, we'll update it every time we revalidate
-            // Note that this runs for every path generated, so it's fully usable with ISR
-            // Yes, there's a different revalidation schedule for each locale, but that means we don't have to rebuild every locale simultaneously
-            config_manager
-                .write(
-                    &format!("static/{}.revld.txt", full_path),
-                    &datetime_to_revalidate.to_string(),
-                )
-                .await?;
+        // If the page doesn't exist after all, we just won't build it
+        if build_state.not_found {
+            return Ok(None);
         }
+        revalidate_interval = template.get_revalidate_interval_for_page(&build_state)?;
+        own_interval = build_state.revalidate_after.is_some();
+        let initial_state = build_state.state;
+        // Write that intial state to a static JSON file
+        config_manager
+            .write(&format!("static/{}.json", full_path), &initial_state)
+            .await?;
+        // Prerender the template and its head using that state
+        let prerendered = prerender_page(
+            template,
+            Some(initial_state.clone()),
+            Rc::clone(&translator),
+            page_path,
+            i18n_strict,
+        )?;
+        let head = template.render_head_str(Some(initial_state));
+        // Write those prerendered HTML strings to static files
+        config_manager
+            .write(&format!("static/{}.html", full_path), &prerendered)
+            .await?;
+        config_manager
+            .write(&format!("static/{}.head.html", full_path), &head)
+            .await?;
+    }
 
-        // Note that SSR has already been handled by checking for `.uses_request_state()` above, we don't need to do any rendering here
-        // If a template only uses SSR, it won't get prerendered at build time whatsoever
+    // Handle revalidation, we need to parse any given time strings into datetimes
+    // We don't need to worry about revalidation that operates by logic, that's request-time only
+    if let Some(revalidate_interval) = revalidate_interval {
+        let datetime_to_revalidate = get_datetime_after(revalidate_interval)?;
+        // Write that to a static file, we'll update it every time we revalidate
+        // Note that this runs for every path generated, so it's fully usable with ISR
+        // Yes, there's a different revalidation schedule for each locale, but that means we don't have to rebuild every locale simultaneously
+        config_manager
+            .write(
+                &format!("static/{}.revld.txt", full_path),
+                &datetime_to_revalidate.to_string(),
+            )
+            .await?;
+    }
 
-        // If the template is very basic, prerender without any state
-        // It's safe to add a property to the render options here because `.is_basic()` will only return true if path generation is not being used (or anything else)
-        if template.is_basic() {
-            let prerendered = sycamore::render_to_string(|| {
-                template.render_for_template(None, Rc::clone(&translator))
-            });
-            // Write that prerendered HTML to a static file
-            config_manager
-                .write(&format!("static/{}.html", full_path), &prerendered)
-                .await?;
+    // Note that SSR has already been handled by checking for `.uses_request_state()` above, we don't need to do any rendering here
+    // If a template only uses SSR, it won't get prerendered at build time whatsoever
+
+    // If the template is very basic, prerender without any state
+    // It's safe to add a property to the render options here because `.is_basic()` will only return true if path generation is not being used (or anything else)
+    if template.is_basic() {
+        let prerendered = prerender_page(
+            template,
+            None,
+            Rc::clone(&translator),
+            page_path,
+            i18n_strict,
+        )?;
+        let head = template.render_head_str(None);
+        // Write those prerendered HTML strings to static files
+        config_manager
+            .write(&format!("static/{}.html", full_path), &prerendered)
+            .await?;
+        config_manager
+            .write(&format!("static/{}.head.html", full_path), &head)
+            .await?;
+    }
+
+    // The server needs to know what shape of state prerendered pages have, and custom revalidation logic can check how old they are
+    if template.uses_build_state() || template.is_basic() {
+        write_state_schema(template, &full_path, config_manager).await?;
+        write_render_time(&full_path, config_manager).await?;
+    }
+
+    Ok(Some(own_interval))
+}
+
+/// Builds a template, writing static data as appropriate. This should be used as part of a larger build process. This returns a list of
+/// pages that it explicitly generated, a boolean as to whether or not it only generated a single page to occupy the template's root path
+/// (`true` unless using using build-time path generation), and the artifact names (see [`get_page_artifact_name`]) of the pages that set
+/// their own revalidation intervals from their build states. Pages that panic or have translations that fail in strict i18n mode (or that fail at all with `BuildErrorMode::Aggregate`)
+/// won't stop the others from being built, but
+/// will be returned together in a `PagesFailed` error. Only the pages included by the given filter will be built, and up to `concurrency`
+/// of them will be built at once (their states are generated concurrently, which is much faster if that involves I/O).
+pub async fn build_template(
+    template: &Template<SsrNode>,
+    translator: Rc<Translator>,
+    config_manager: &impl ConfigManager,
+    error_mode: BuildErrorMode,
+    filter: &BuildFilter,
+    concurrency: usize,
+    i18n_strict: bool,
+) -> Result<(Vec<String>, bool, Vec<String>)> {
+    let (paths_with_state, single_page) =
+        get_paths_with_state(template, &translator.get_locale()).await?;
+    // Pages can turn out not to exist when their states are generated, so we only keep the paths we actually render
+    let mut paths: Vec<String> = Vec::new();
+    // The server only checks when pages without the template's interval should be revalidated if they're recorded as having their own
+    let mut own_intervals: Vec<String> = Vec::new();
+    // Pages that failed will be reported after everything else has been built
+    let mut failures: Vec<PageFailure> = Vec::new();
+
+    // Generate initial states and prerender each page, with up to the given number of pages being built at once
+    let futs = paths_with_state
+        .into_iter()
+        .filter_map(|(path, build_state)| {
+            let page_path = get_page_path(template, &path);
+            if !filter.includes_page(template, &page_path) {
+                return None;
+            }
+            let translator = Rc::clone(&translator);
+            Some(async move {
+                // Any panics in the user's code are caught so the rest of the pages can still be built
+                let res = catch_render_panic(
+                    &template.get_path(),
+                    &page_path,
+                    build_page(
+                        template,
+                        translator,
+                        config_manager,
+                        &page_path,
+                        build_state,
+                        i18n_strict,
+                    ),
+                )
+                .await;
+                (path, page_path, res)
+            })
+        });
+    // We keep the pages in order so that builds are deterministic
+    let mut pages = stream::iter(futs).buffered(concurrency.max(1));
+    while let Some((path, page_path, res)) = pages.next().await {
+        match res {
+            Ok(Some(own_interval)) => {
+                if own_interval {
+                    own_intervals
+                        .push(get_page_artifact_name(&translator.get_locale(), &page_path));
+                }
+                paths.push(path)
+            }
+            Ok(None) => (),
+            // Panics are always collected, since the alternative would be taking down the whole build, and so are missing translations,
+            // so that every page with them is reported at once
+            Err(err)
+                if error_mode == BuildErrorMode::Aggregate
+                    || matches!(
+                        err.kind(),
+                        ErrorKind::RenderPanicked(_, _, _) | ErrorKind::MissingTranslations(_)
+                    ) =>
+            {
+                failures.push(PageFailure {
+                    template: template.get_path(),
+                    locale: translator.get_locale(),
+                    path: Some(page_path),
+                    err: err.to_string(),
+                })
+            }
+            // Any pages still being built will be dropped
+            Err(err) => return Err(err),
         }
     }
+    // Everything else has been built, but the build as a whole has still failed
+    if !failures.is_empty() {
+        bail!(ErrorKind::PagesFailed(failures))
+    }
 
-    Ok((paths, single_page))
+    Ok((paths, single_page, own_intervals))
+}
+
+/// What the build records about the pages it's built for the server.
+#[derive(Default)]
+struct BuiltCfg {
+    /// The render configuration, which maps the paths of pages (and patterns for pages rendered at request-time) to their templates.
+    render_cfg: HashMap<String, String>,
+    /// The artifact names of the pages that set their own revalidation intervals, mapped to their templates.
+    page_intervals: HashMap<String, String>,
+}
+impl BuiltCfg {
+    fn extend(&mut self, other: BuiltCfg) {
+        self.render_cfg.extend(other.render_cfg.into_iter());
+        self.page_intervals.extend(other.page_intervals.into_iter());
+    }
 }
 
 async fn build_template_and_get_cfg(
     template: &Template<SsrNode>,
     translator: Rc<Translator>,
     config_manager: &impl ConfigManager,
-) -> Result<HashMap<String, String>> {
+    error_mode: BuildErrorMode,
+    filter: &BuildFilter,
+    concurrency: usize,
+    i18n_strict: bool,
+) -> Result<BuiltCfg> {
     let mut render_cfg = HashMap::new();
-    let template_root_path = template.get_path();
+    // Templates are identified by their full paths (which might contain dynamic segments), but their pages go under their root paths
+    let template_name = template.get_path();
+    let template_root_path = template.get_root_path();
     let is_incremental = template.uses_incremental();
 
-    let (pages, single_page) = build_template(template, translator, config_manager).await?;
+    let (pages, single_page, own_intervals) = build_template(
+        template,
+        translator,
+        config_manager,
+        error_mode,
+        filter,
+        concurrency,
+        i18n_strict,
+    )
+    .await?;
     // If the template represents a single page itself, we don't need any concatenation
+    // If its path is dynamic, this will add the pattern, which will be matched at request-time
     if single_page {
-        render_cfg.insert(template_root_path.clone(), template_root_path.clone());
+        render_cfg.insert(template_name.clone(), template_name.clone());
     } else {
         // Add each page that the template explicitly generated (ignoring ISR for now)
         for page in pages {
-            render_cfg.insert(
-                format!("{}/{}", &template_root_path, &page),
-                template_root_path.clone(),
-            );
+            let page_path = match template_root_path.is_empty() {
+                true => page,
+                false => format!("{}/{}", &template_root_path, &page),
+            };
+            render_cfg.insert(page_path, template_name.clone());
         }
         // Now if the page uses ISR, add an explicit `/*` in there after the template root path
         // Incremental rendering requires build-time path generation
+        // Templates with dynamic paths add their own pattern instead, so only matching paths will be rendered
         if is_incremental {
-            render_cfg.insert(
-                format!("{}/*", &template_root_path),
-                template_root_path.clone(),
-            );
+            let pattern = match template.has_dynamic_path() {
+                true => template_name.clone(),
+                false => format!("{}/*", &template_root_path),
+            };
+            render_cfg.insert(pattern, template_name.clone());
         }
     }
+    let page_intervals = own_intervals
+        .into_iter()
+        .map(|page| (page, template_name.clone()))
+        .collect();
 
-    Ok(render_cfg)
+    Ok(BuiltCfg {
+        render_cfg,
+        page_intervals,
+    })
 }
 
-/// Runs the build process of building many different templates for a single locale. If you're not using i18n, provide a `Translator::empty()`
-/// for this. You should only build the most commonly used locales here (the rest should be built on demand).
-pub async fn build_templates_for_locale(
-    templates: &[Template<SsrNode>],
-    translator_raw: Translator,
+/// Builds the given templates (which must all be included by the given filter) for a single locale, returning what was recorded about
+/// everything that was built (like the render configuration), along with any pages that failed to build (if they didn't stop the build).
+async fn build_templates_and_get_cfg_for_locale(
+    templates: &[&Template<SsrNode>],
+    translator: Rc<Translator>,
     config_manager: &impl ConfigManager,
-) -> Result<()> {
-    let translator = Rc::new(translator_raw);
+    error_mode: BuildErrorMode,
+    filter: &BuildFilter,
+    concurrency: usize,
+    i18n_strict: bool,
+) -> Result<(BuiltCfg, Vec<PageFailure>)> {
     // The render configuration stores a list of pages to the root paths of their templates
-    let mut render_cfg: HashMap<String, String> = HashMap::new();
+    let mut built_cfg = BuiltCfg::default();
     // Create each of the templates
     let mut futs = Vec::new();
-    for template in templates {
+    for template in templates.iter() {
         futs.push(build_template_and_get_cfg(
             template,
             Rc::clone(&translator),
             config_manager,
+            error_mode,
+            filter,
+            concurrency,
+            i18n_strict,
         ));
     }
-    let template_cfgs = try_join_all(futs).await?;
-    for template_cfg in template_cfgs {
-        render_cfg.extend(template_cfg.into_iter())
+    // We don't stop at templates with pages that failed, so everything else still gets built
+    let mut failures = Vec::new();
+    for (template, template_cfg) in templates.iter().zip(join_all(futs).await) {
+        match template_cfg {
+            Ok(template_cfg) => built_cfg.extend(template_cfg),
+            Err(Error(ErrorKind::PagesFailed(template_failures), _)) => {
+                failures.extend(template_failures)
+            }
+            Err(err) if error_mode == BuildErrorMode::Aggregate => failures.push(PageFailure {
+                template: template.get_path(),
+                locale: translator.get_locale(),
+                path: None,
+                err: err.to_string(),
+            }),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok((built_cfg, failures))
+}
+
+/// Writes the given render configuration (and the record of which pages set their own revalidation intervals) for the given templates
+/// (which must all be included by the given filter). If the filter isn't empty, this will update what was written by the last build rather
+/// than replacing it.
+async fn write_render_cfg(
+    templates: &[&Template<SsrNode>],
+    built_cfg: BuiltCfg,
+    config_manager: &impl ConfigManager,
+    filter: &BuildFilter,
+) -> Result<()> {
+    // Partial builds keep everything from the last build that they don't rebuild
+    // This is ordered so that `render_conf.json` is the same for the same build
+    let mut render_cfg: BTreeMap<String, String> = match filter.is_empty() {
+        true => BTreeMap::new(),
+        false => get_render_cfg(config_manager)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+    };
+    // Templates that are being rebuilt entirely might not generate the same pages as last time
+    for template in templates.iter() {
+        if filter.includes_whole_template(template) {
+            render_cfg.retain(|_, template_name| *template_name != template.get_path());
+        }
     }
+    render_cfg.extend(built_cfg.render_cfg.into_iter());
 
     config_manager
         .write("render_conf.json", &serde_json::to_string(&render_cfg)?)
         .await?;
 
+    // The server uses this to avoid checking if pages without the template's interval have one of their own on every request
+    let mut page_intervals: BTreeMap<String, String> = match filter.is_empty() {
+        true => BTreeMap::new(),
+        false => get_page_intervals(config_manager)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+    };
+    for template in templates.iter() {
+        if filter.includes_whole_template(template) {
+            page_intervals.retain(|_, template_name| *template_name != template.get_path());
+        }
+    }
+    page_intervals.extend(built_cfg.page_intervals.into_iter());
+
+    config_manager
+        .write(
+            "page_intervals.json",
+            &serde_json::to_string(&page_intervals)?,
+        )
+        .await?;
+
     Ok(())
 }
 
-/// Gets a translator and builds templates for a single locale.
-async fn build_templates_and_translator_for_locale(
+/// Runs the build process of building many different templates for a single locale. If you're not using i18n, provide a `Translator::empty()`
+/// for this. You should only build the most commonly used locales here (the rest should be built on demand). With
+/// `BuildErrorMode::Aggregate`, templates that fail entirely won't stop the others from being built either. If the given filter isn't
+/// empty, only what it includes will be built, and the render configuration from the last build will be updated rather than replaced.
+/// Note that this will replace the render configuration with only the pages built for this locale, so, if your app has pages that only
+/// exist in some locales, use [`build_app`] instead. In strict i18n mode, pages that have translations that fail will fail to build.
+pub async fn build_templates_for_locale(
     templates: &[Template<SsrNode>],
+    translator_raw: Translator,
+    config_manager: &impl ConfigManager,
+    error_mode: BuildErrorMode,
+    filter: &BuildFilter,
+    concurrency: usize,
+    i18n_strict: bool,
+) -> Result<()> {
+    let translator = Rc::new(translator_raw);
+    let templates: Vec<&Template<SsrNode>> = templates
+        .iter()
+        .filter(|template| filter.includes_template(template))
+        .collect();
+    let (built_cfg, failures) = build_templates_and_get_cfg_for_locale(
+        &templates,
+        translator,
+        config_manager,
+        error_mode,
+        filter,
+        concurrency,
+        i18n_strict,
+    )
+    .await?;
+
+    write_render_cfg(&templates, built_cfg, config_manager, filter).await?;
+    if !failures.is_empty() {
+        bail!(ErrorKind::PagesFailed(failures))
+    }
+
+    Ok(())
+}
+
+/// Gets a translator and builds templates for a single locale, returning what was recorded about everything that was built and any pages
+/// that failed.
+#[allow(clippy::too_many_arguments)]
+async fn build_templates_and_translator_for_locale(
+    templates: &[&Template<SsrNode>],
     locale: String,
     config_manager: &impl ConfigManager,
     translations_manager: &impl TranslationsManager,
-) -> Result<()> {
+    error_mode: BuildErrorMode,
+    filter: &BuildFilter,
+    concurrency: usize,
+    i18n_strict: bool,
+) -> Result<(BuiltCfg, Vec<PageFailure>)> {
     let translator = translations_manager
         .get_translator_for_locale(locale)
         .await?;
-    build_templates_for_locale(templates, translator, config_manager).await?;
+    build_templates_and_get_cfg_for_locale(
+        templates,
+        Rc::new(translator),
+        config_manager,
+        error_mode,
+        filter,
+        concurrency,
+        i18n_strict,
+    )
+    .await
+}
 
-    Ok(())
+/// A page that the build process would generate, as found by [`plan_app`].
+#[derive(Debug, Clone)]
+pub struct PlannedPage {
+    /// The template that would render the page.
+    pub template: String,
+    /// The locale the page would be rendered for.
+    pub locale: String,
+    /// The path of the page (without the locale).
+    pub path: String,
+}
+
+/// The pages that the build process would generate, as found by [`plan_app`]. This can be displayed as a report with counts per template.
+#[derive(Debug, Clone, Default)]
+pub struct BuildPlan {
+    /// The names of the templates that would be built, in order (including any that wouldn't generate any pages at build-time).
+    pub templates: Vec<String>,
+    /// The pages that would be generated, in the order of their templates.
+    pub pages: Vec<PlannedPage>,
+}
+impl fmt::Display for BuildPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} page(s) would be built:", self.pages.len())?;
+        for template in self.templates.iter() {
+            let pages: Vec<&PlannedPage> = self
+                .pages
+                .iter()
+                .filter(|page| &page.template == template)
+                .collect();
+            write!(f, "\n[{}] {} page(s)", template, pages.len())?;
+            for page in pages {
+                write!(f, "\n    page '{}' (locale '{}')", page.path, page.locale)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Works out which pages the build process would generate for the given locales without rendering anything or writing any files, which
+/// is useful for sanity-checking path generation logic before a long build. This runs the build paths functions of all the templates
+/// included by the given filter (and their build state functions if they generate paths and states together, since pages can turn out not
+/// to exist from those). Pages that would only be rendered at request-time (e.g. with incremental generation) aren't included.
+pub async fn plan_app(
+    templates: &[Template<SsrNode>],
+    locales: &Locales,
+    filter: &BuildFilter,
+) -> Result<BuildPlan> {
+    let mut plan = BuildPlan::default();
+    for template in templates
+        .iter()
+        .filter(|template| filter.includes_template(template))
+    {
+        plan.templates.push(template.get_path());
+        for locale in locales.get_all() {
+            let (paths_with_state, _) = get_paths_with_state(template, locale).await?;
+            for (path, build_state) in paths_with_state {
+                let page_path = get_page_path(template, &path);
+                // Pages that turn out not to exist wouldn't be built
+                let not_found = build_state.map_or(false, |build_state| build_state.not_found);
+                if not_found || !filter.includes_page(template, &page_path) {
+                    continue;
+                }
+                plan.pages.push(PlannedPage {
+                    template: template.get_path(),
+                    locale: locale.to_string(),
+                    path: page_path,
+                });
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// A page prerendered at build-time, as found by [`get_prerendered_pages`].
+pub(crate) struct PrerenderedPage {
+    /// The locale the page was rendered for.
+    pub locale: String,
+    /// The path of the page (without the locale).
+    pub path: String,
+    /// The name of the template that rendered the page.
+    pub template: String,
+    /// The prerendered HTML of the page.
+    pub html: String,
+}
+
+/// Gets every page that was prerendered by the build process for the given locales, which is useful for checking the output of the build.
+/// Pages that are only rendered at request-time won't be included.
+pub(crate) async fn get_prerendered_pages(
+    locales: &Locales,
+    config_manager: &impl ConfigManager,
+) -> Result<Vec<PrerenderedPage>> {
+    let render_cfg = get_render_cfg(config_manager).await?;
+
+    let mut pages = Vec::new();
+    for locale in locales.get_all() {
+        for (path, template) in render_cfg.iter() {
+            // Patterns don't correspond to any actual prerendered pages
+            if is_path_pattern(path) {
+                continue;
+            }
+            // Pages that are only rendered at request-time won't have any HTML
+            let path_encoded = get_page_artifact_name(locale, path);
+            if let Ok(html) = read_page_artifact(config_manager, &path_encoded, "html").await {
+                pages.push(PrerenderedPage {
+                    locale: locale.to_string(),
+                    path: path.to_string(),
+                    template: template.to_string(),
+                    html,
+                });
+            }
+        }
+    }
+
+    Ok(pages)
 }
 
 /// Runs the build process of building many templates for the given locales data, building directly for all supported locales. This is
-/// fine because of how ridiculously fast builds are.
+/// fine because of how ridiculously fast builds are. With `BuildErrorMode::Aggregate`, every page that fails will be reported together in
+/// a `PagesFailed` error at the end, rather than stopping at the first. To only rebuild some templates or pages (leaving everything else
+/// from the last build untouched), provide a non-empty [`BuildFilter`]. The pages of each template are built concurrently, up to the given
+/// limit for each template in each locale (e.g. [`DFLT_BUILD_CONCURRENCY`]). In strict i18n mode, any page that has translations that fail
+/// while it's being prerendered (even if they're handled gracefully, like with `.translate_checked()` or a fallback) will fail to build,
+/// and every such page will be reported together with the IDs that failed. This also works out which paths are the same page in different
+/// locales (see [`BuildPath::key`](crate::BuildPath::key)) before anything is built, so that pages can link to themselves in other locales.
+#[allow(clippy::too_many_arguments)]
 pub async fn build_app(
     templates: Vec<Template<SsrNode>>,
     locales: &Locales,
     config_manager: &impl ConfigManager,
     translations_manager: &impl TranslationsManager,
+    error_mode: BuildErrorMode,
+    filter: &BuildFilter,
+    concurrency: usize,
+    i18n_strict: bool,
 ) -> Result<()> {
+    let templates: Vec<&Template<SsrNode>> = templates
+        .iter()
+        .filter(|template| filter.includes_template(template))
+        .collect();
+    // Pages are prerendered with links to themselves in other locales, so we need to know which paths are equivalent first
+    let path_equivalents = PathEquivalents::from_templates(&templates).await?;
+    write_path_equivalents(&templates, path_equivalents, config_manager).await?;
     let locales = locales.get_all();
     let mut futs = Vec::new();
 
@@ -195,10 +820,130 @@ pub async fn build_app(
             locale.to_string(),
             config_manager,
             translations_manager,
+            error_mode,
+            filter,
+            concurrency,
+            i18n_strict,
         ));
     }
-    // Build all locales in parallel
-    try_join_all(futs).await?;
+    // Build all locales in parallel, letting the others finish if any pages fail
+    // Some pages might only exist in some locales, so the render configuration covers all of them
+    let mut built_cfg = BuiltCfg::default();
+    let mut failures = Vec::new();
+    for res in join_all(futs).await {
+        let (locale_cfg, locale_failures) = res?;
+        built_cfg.extend(locale_cfg);
+        failures.extend(locale_failures);
+    }
+
+    write_render_cfg(&templates, built_cfg, config_manager, filter).await?;
+    if !failures.is_empty() {
+        bail!(ErrorKind::PagesFailed(failures))
+    }
 
     Ok(())
 }
+
+/// Builds the app like [`build_app`], but with each locale built on a thread of its own, which is much faster for apps with many locales
+/// if rendering their pages is expensive (rather than waiting on I/O). Templates can't be shared between threads, so each thread gets
+/// its own from the given function (e.g. `get_templates_vec::<SsrNode>` from `define_app!`), and the config and translations managers
+/// are cloned into each thread. The futures on each thread are run with a simple executor, so state functions that need a particular
+/// async runtime should start one themselves. Every locale's artifacts are written to a directory of its own, so the threads never write
+/// to the same files, and the render configuration is written once they've all finished.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::too_many_arguments)]
+pub fn build_app_in_parallel<C, T>(
+    get_templates: fn() -> Vec<Template<SsrNode>>,
+    locales: &Locales,
+    config_manager: &C,
+    translations_manager: &T,
+    error_mode: BuildErrorMode,
+    filter: &BuildFilter,
+    concurrency: usize,
+    i18n_strict: bool,
+) -> Result<()>
+where
+    C: ConfigManager + Send + 'static,
+    T: TranslationsManager + Send + 'static,
+{
+    let all_templates = get_templates();
+    let templates: Vec<&Template<SsrNode>> = all_templates
+        .iter()
+        .filter(|template| filter.includes_template(template))
+        .collect();
+    // Pages are prerendered with links to themselves in other locales, so we need to know which paths are equivalent first (these are
+    // kept for the whole process, so every thread will have them)
+    let path_equivalents = block_on(PathEquivalents::from_templates(&templates))?;
+    block_on(write_path_equivalents(
+        &templates,
+        path_equivalents,
+        config_manager,
+    ))?;
+
+    let handles: Vec<(String, std::thread::JoinHandle<LocaleBuildResult>)> = locales
+        .get_all()
+        .into_iter()
+        .map(|locale| {
+            let locale = locale.to_string();
+            let config_manager = config_manager.clone();
+            let translations_manager = translations_manager.clone();
+            let filter = filter.clone();
+            let thread_locale = locale.clone();
+            let handle = std::thread::spawn(move || {
+                let templates = get_templates();
+                let templates: Vec<&Template<SsrNode>> = templates
+                    .iter()
+                    .filter(|template| filter.includes_template(template))
+                    .collect();
+                block_on(build_templates_and_translator_for_locale(
+                    &templates,
+                    thread_locale,
+                    &config_manager,
+                    &translations_manager,
+                    error_mode,
+                    &filter,
+                    concurrency,
+                    i18n_strict,
+                ))
+            });
+            (locale, handle)
+        })
+        .collect();
+    // We let every locale finish before reporting anything, like when they're built concurrently
+    let mut built_cfg = BuiltCfg::default();
+    let mut failures = Vec::new();
+    let mut first_err = None;
+    for (locale, handle) in handles {
+        match handle.join() {
+            Ok(Ok((locale_cfg, locale_failures))) => {
+                built_cfg.extend(locale_cfg);
+                failures.extend(locale_failures);
+            }
+            Ok(Err(err)) => {
+                first_err.get_or_insert(err);
+            }
+            Err(_) => {
+                first_err.get_or_insert(ErrorKind::LocaleBuildPanicked(locale).into());
+            }
+        }
+    }
+    if let Some(err) = first_err {
+        return Err(err);
+    }
+
+    block_on(write_render_cfg(
+        &templates,
+        built_cfg,
+        config_manager,
+        filter,
+    ))?;
+    if !failures.is_empty() {
+        bail!(ErrorKind::PagesFailed(failures))
+    }
+
+    Ok(())
+}
+
+/// What was recorded about everything built for a single locale and the pages that failed, or the error that stopped it.
+#[cfg(not(target_arch = "wasm32"))]
+type LocaleBuildResult = Result<(BuiltCfg, Vec<PageFailure>)>;
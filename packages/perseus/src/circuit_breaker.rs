@@ -0,0 +1,217 @@
+// This lets templates stop calling their state functions for a while when they keep failing (like during an outage of an upstream API),
+// serving the last page that rendered successfully (or a degraded one) instead of hammering whatever's down on every request
+
+use crate::errors::*;
+use crate::serve::PageData;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The default number of failures in a row after which a template's circuit will open.
+pub const DFLT_FAILURE_THRESHOLD: u32 = 5;
+/// The default time a template's circuit will stay open for before its state functions are tried again.
+pub const DFLT_OPEN_FOR: Duration = Duration::from_secs(30);
+/// The default number of last good pages that will be kept for each template, if they're being served.
+pub const DFLT_MAX_LAST_GOOD: usize = 1000;
+
+/// A circuit breaker for the state functions of a template, which can be set with `.circuit_breaker()` on a template. When rendering a
+/// page of the template fails on the server (because a state function returned an error caused by the server, or panicked) a certain
+/// number of times in a row, the circuit will *open*, and the template's state functions won't be called at all for a while. Instead,
+/// the template will be rendered with a degraded state if that's been set, or a `503 Service Unavailable` will be sent with a
+/// `Retry-After` header. Once the circuit has been open for long enough, the state functions will be tried again, and the circuit will
+/// close as soon as a page renders successfully.
+///
+/// The last page of the same path and locale that rendered successfully can also be served while the circuit is open, with
+/// `.serve_last_good(true)`. Those pages are kept in memory (up to a limit for each template, dropping the least recently used ones
+/// first), and they're served to *anyone*, so this can't be used with templates that generate state at request-time, which could be
+/// personalized (e.g. from a cookie). Templates that try will be rejected with an `IncompatibleStrategies` error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: Duration,
+    serve_last_good: bool,
+    max_last_good: usize,
+    degraded_state: Option<String>,
+}
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CircuitBreaker {
+    /// Creates a new circuit breaker that opens after 5 failures in a row and stays open for 30 seconds, responding with a `503` while
+    /// it's open.
+    pub fn new() -> Self {
+        Self {
+            failure_threshold: DFLT_FAILURE_THRESHOLD,
+            open_for: DFLT_OPEN_FOR,
+            serve_last_good: false,
+            max_last_good: DFLT_MAX_LAST_GOOD,
+            degraded_state: None,
+        }
+    }
+    /// Sets the number of failures in a row after which the circuit will open.
+    pub fn failure_threshold(mut self, val: u32) -> Self {
+        self.failure_threshold = val;
+        self
+    }
+    /// Sets how long the circuit will stay open for before the template's state functions are tried again.
+    pub fn open_for(mut self, val: Duration) -> Self {
+        self.open_for = val;
+        self
+    }
+    /// Sets whether or not the last page of the same path and locale that rendered successfully should be served while the circuit is
+    /// open. This can only be turned on for templates that don't generate state at request-time.
+    pub fn serve_last_good(mut self, val: bool) -> Self {
+        self.serve_last_good = val;
+        self
+    }
+    /// Sets the most last good pages that will be kept for the template, after which the least recently used ones will be dropped.
+    pub fn max_last_good(mut self, val: usize) -> Self {
+        self.max_last_good = val;
+        self
+    }
+    /// Sets the state to render the template with while the circuit is open if there's no last good page to serve (e.g. a state that
+    /// makes the template say that what it shows is temporarily unavailable).
+    pub fn degraded_state(mut self, val: impl Into<String>) -> Self {
+        self.degraded_state = Some(val.into());
+        self
+    }
+
+    /// Checks if this serves the last good pages of its template while the circuit is open.
+    pub(crate) fn serves_last_good(&self) -> bool {
+        self.serve_last_good
+    }
+
+    /// Checks if the circuit for the given template is open, and if it is, works out what should be served for the page at the given path
+    /// in the given locale instead of rendering it.
+    pub(crate) fn check(&self, template: &str, locale: &str, path: &str) -> Option<Tripped> {
+        let mut circuits = CIRCUITS.lock().ok()?;
+        let circuit = circuits.get_mut(template)?;
+        let remaining = (circuit.open_until? - Utc::now()).to_std().ok()?;
+        if remaining == Duration::from_secs(0) {
+            return None;
+        }
+        let last_good = match self.serve_last_good {
+            true => circuit.get_last_good(locale, path),
+            false => None,
+        };
+        match (last_good, &self.degraded_state) {
+            (Some(page_data), _) => Some(Tripped::LastGood(page_data)),
+            (None, Some(degraded_state)) => Some(Tripped::Degraded(degraded_state.to_string())),
+            // We round up so that clients won't try again before the circuit could have closed
+            (None, None) => Some(Tripped::Unavailable(match remaining.subsec_nanos() {
+                0 => remaining.as_secs(),
+                _ => remaining.as_secs() + 1,
+            })),
+        }
+    }
+    /// Records that the page at the given path in the given locale rendered successfully with the given template, which closes its
+    /// circuit.
+    pub(crate) fn record_success(
+        &self,
+        template: &str,
+        locale: &str,
+        path: &str,
+        page_data: &PageData,
+    ) {
+        // If the lock has been poisoned, the circuit will never open
+        if let Ok(mut circuits) = CIRCUITS.lock() {
+            let circuit = circuits.entry(template.to_string()).or_default();
+            circuit.failures = 0;
+            circuit.open_until = None;
+            // Placeholders aren't the real page, and flash messages are only for the user they were sent to
+            if self.serve_last_good && !page_data.fallback {
+                let mut page_data = page_data.clone();
+                page_data.flash = None;
+                circuit.set_last_good(locale, path, page_data, self.max_last_good);
+            }
+        }
+    }
+    /// Records that rendering a page with the given template failed, which will open its circuit if that's happened too many times in a
+    /// row.
+    pub(crate) fn record_failure(&self, template: &str) {
+        if let Ok(mut circuits) = CIRCUITS.lock() {
+            let circuit = circuits.entry(template.to_string()).or_default();
+            circuit.failures = circuit.failures.saturating_add(1);
+            // If this was the trial after the circuit was open, this will open it again straight away
+            if circuit.failures >= self.failure_threshold {
+                circuit.open_until = chrono::Duration::from_std(self.open_for)
+                    .ok()
+                    .and_then(|open_for| Utc::now().checked_add_signed(open_for));
+            }
+        }
+    }
+}
+
+/// What should be served instead of a page because its template's circuit is open.
+pub(crate) enum Tripped {
+    /// The last page of the same path and locale that rendered successfully.
+    LastGood(PageData),
+    /// The template rendered with the given degraded state.
+    Degraded(String),
+    /// Nothing, the client should try again after the given number of seconds.
+    Unavailable(u64),
+}
+
+/// The circuit of a single template.
+#[derive(Default)]
+struct Circuit {
+    /// The number of times in a row rendering a page of the template has failed.
+    failures: u32,
+    /// When the circuit will close again, if it's open.
+    open_until: Option<DateTime<Utc>>,
+    /// The last page that rendered successfully, by locale and path, along with when it was last used.
+    last_good: HashMap<(String, String), (PageData, u64)>,
+    /// Counts the uses of the last good pages, so that we know which was used least recently.
+    uses: u64,
+}
+impl Circuit {
+    /// Gets the last good page at the given path in the given locale, if there is one, marking it as used.
+    fn get_last_good(&mut self, locale: &str, path: &str) -> Option<PageData> {
+        self.uses += 1;
+        let uses = self.uses;
+        self.last_good
+            .get_mut(&(locale.to_string(), path.to_string()))
+            .map(|(page_data, last_used)| {
+                *last_used = uses;
+                page_data.clone()
+            })
+    }
+    /// Sets the last good page at the given path in the given locale, dropping the least recently used pages if there are more than the
+    /// given number.
+    fn set_last_good(&mut self, locale: &str, path: &str, page_data: PageData, max: usize) {
+        self.uses += 1;
+        self.last_good.insert(
+            (locale.to_string(), path.to_string()),
+            (page_data, self.uses),
+        );
+        while self.last_good.len() > max {
+            let least_recent = self
+                .last_good
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            match least_recent {
+                Some(key) => self.last_good.remove(&key),
+                None => break,
+            };
+        }
+    }
+}
+
+/// The circuits for every template with a circuit breaker, by template path. A failure on any of the server's workers should open the
+/// circuit for all of them (they each have their own copy of the template), so these are kept here rather than in the `CircuitBreaker`.
+static CIRCUITS: Lazy<Mutex<HashMap<String, Circuit>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Checks if the given error from rendering a page means that one of its template's state functions failed because of something on the
+/// server's side (like an upstream outage), rather than because of something the client did.
+pub(crate) fn is_state_failure(err: &Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::RenderFnFailed(_, _, ErrorCause::Server(_), _)
+            | ErrorKind::RenderPanicked(_, _, _)
+    )
+}
@@ -10,6 +10,8 @@ use std::rc::Rc;
 pub struct ClientTranslationsManager {
     /// The cached translator. If the same locale is requested again, this will simply be returned.
     cached_translator: Option<Rc<Translator>>,
+    /// The translation ID prefixes the cached translator has translations for, or `None` if it has all of them.
+    cached_prefixes: Option<Vec<String>>,
     locales: Locales,
 }
 impl ClientTranslationsManager {
@@ -18,22 +20,74 @@ impl ClientTranslationsManager {
     pub fn new(locales: &Locales) -> Self {
         Self {
             cached_translator: None,
+            cached_prefixes: None,
             locales: locales.clone(),
         }
     }
+    /// Clears the cached translator, so that translations will be fetched from the server again the next time they're needed (e.g. after
+    /// they've been edited in development).
+    pub fn clear(&mut self) {
+        self.cached_translator = None;
+        self.cached_prefixes = None;
+    }
     /// Gets an `Rc<Translator>` for the given locale. This will use the internally cached `Translator` if possible, and will otherwise
     /// fetch the translations from the server. This needs mutability because it will modify its internal cache if necessary.
     pub async fn get_translator_for_locale(&mut self, locale: &str) -> Result<Rc<Translator>> {
+        self.get_translator_for_prefixes(locale, None).await
+    }
+    /// Gets an `Rc<Translator>` for the given locale that has at least the translations with IDs starting with the given prefixes (or all
+    /// translations if none are given), which should be those declared by the template being rendered. Translations are fetched lazily,
+    /// so the internally cached `Translator` will be used if it already has them, and otherwise a new one will be created with the
+    /// translations for both the prefixes it had and the new ones.
+    pub async fn get_translator_for_prefixes(
+        &mut self,
+        locale: &str,
+        prefixes: Option<&[String]>,
+    ) -> Result<Rc<Translator>> {
+        let is_cached_locale = self.cached_translator.is_some()
+            && self.cached_translator.as_ref().unwrap().get_locale() == locale;
+        // Work out if the cached translator will do, and which prefixes we'd need to fetch if not (`None` meaning everything)
+        let (is_cached, prefixes_to_fetch) = match (prefixes, &self.cached_prefixes) {
+            // If we already have everything for this locale, we don't need anything else
+            (_, None) if is_cached_locale => (true, None),
+            (None, _) => (false, None),
+            (Some(prefixes), Some(cached_prefixes)) if is_cached_locale => {
+                let missing: Vec<String> = prefixes
+                    .iter()
+                    .filter(|prefix| {
+                        !cached_prefixes
+                            .iter()
+                            .any(|cached| prefix.starts_with(cached.as_str()))
+                    })
+                    .cloned()
+                    .collect();
+                // We refetch the prefixes we had too, because a new prefix might overlap with them and duplicate IDs would be an error
+                (
+                    missing.is_empty(),
+                    Some(cached_prefixes.iter().cloned().chain(missing).collect()),
+                )
+            }
+            (Some(prefixes), _) => (false, Some(prefixes.to_vec())),
+        };
         // Check if we've already cached
-        if self.cached_translator.is_some()
-            && self.cached_translator.as_ref().unwrap().get_locale() == locale
-        {
+        if is_cached {
             Ok(Rc::clone(self.cached_translator.as_ref().unwrap()))
         } else {
             // Check if the locale is supported
             if self.locales.is_supported(locale) {
                 // Get the translations data
-                let asset_url = format!("/.perseus/translations/{}", locale);
+                let asset_url = match &prefixes_to_fetch {
+                    Some(prefixes) => format!(
+                        "/.perseus/translations/{}?prefixes={}",
+                        locale,
+                        prefixes
+                            .iter()
+                            .map(|prefix| urlencoding::encode(prefix).to_string())
+                            .collect::<Vec<String>>()
+                            .join(",")
+                    ),
+                    None => format!("/.perseus/translations/{}", locale),
+                };
                 // If this doesn't exist, then it's a 404 (we went here by explicit navigation after checking the locale, so that's a bug)
                 let translations_str = fetch(&asset_url).await;
                 let translator = match translations_str {
@@ -66,6 +120,7 @@ impl ClientTranslationsManager {
                 };
                 // Cache that translator
                 self.cached_translator = Some(Rc::new(translator));
+                self.cached_prefixes = prefixes_to_fetch;
                 // Now return that
                 Ok(Rc::clone(self.cached_translator.as_ref().unwrap()))
             } else {
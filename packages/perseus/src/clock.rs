@@ -0,0 +1,70 @@
+// This abstracts over the current time for revalidation, so that apps can test their revalidation logic without actually waiting
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// The clock used for revalidation in this process, which is the system clock unless it's been replaced with [`set_clock`].
+static CLOCK: Lazy<RwLock<Arc<dyn Clock>>> = Lazy::new(|| RwLock::new(Arc::new(SystemClock)));
+
+/// A source of the current time. Everything to do with revalidation (working out when pages should next be revalidated, checking if
+/// they should be revalidated now, and recording when they were last rendered) gets the time from the clock set with [`set_clock`].
+pub trait Clock: Send + Sync {
+    /// Gets the current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real system clock, which is used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when it's told to, for testing revalidation deterministically. Clones of this clock share the same time, so
+/// you can keep one to advance after setting another with [`set_clock`].
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    time: Arc<RwLock<DateTime<Utc>>>,
+}
+impl MockClock {
+    /// Creates a new clock stopped at the given time.
+    pub fn new(time: DateTime<Utc>) -> Self {
+        Self {
+            time: Arc::new(RwLock::new(time)),
+        }
+    }
+    /// Moves the clock forward by the given duration. Durations too large to represent will leave the clock where it is.
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.time.write().unwrap();
+        if let Some(advanced) = chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| time.checked_add_signed(duration))
+        {
+            *time = advanced;
+        }
+    }
+    /// Sets the clock to the given time.
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.time.write().unwrap() = time;
+    }
+}
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.time.read().unwrap()
+    }
+}
+
+/// Sets the clock used for revalidation in this process, replacing the system clock. This is intended for tests, and shouldn't be used
+/// in production.
+pub fn set_clock(clock: impl Clock + 'static) {
+    *CLOCK.write().unwrap() = Arc::new(clock);
+}
+
+/// Gets the current time from the clock used for revalidation.
+pub fn now() -> DateTime<Utc> {
+    CLOCK.read().unwrap().now()
+}
@@ -0,0 +1,206 @@
+// This lets templates that are expensive to render at request-time (like those that call slow APIs or databases) limit how many of their
+// pages can be rendered at once across the whole server, so that a burst of requests for one page can't hammer what it depends on
+
+use crate::errors::*;
+use chrono::{DateTime, Utc};
+use futures::Future;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// The default longest time a request will wait for a render of a template to finish before it's turned away.
+pub const DFLT_MAX_WAIT: Duration = Duration::from_secs(5);
+/// The default time clients that are turned away are told to wait before they try again.
+pub const DFLT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// How many pages of a template can be rendered at once on the server, which can be set with `.concurrency_limit()` on a template. When
+/// this many are already being rendered, requests for more will wait for one of them to finish, and if that takes too long, they'll
+/// get a `503 Service Unavailable` with a `Retry-After` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrencyLimit {
+    max_renders: usize,
+    max_wait: Duration,
+    retry_after: Duration,
+}
+impl ConcurrencyLimit {
+    /// Creates a new limit of the given number of renders at once. Requests will wait up to 5 seconds for a render to finish, and
+    /// clients that are turned away will be told to try again after 5 seconds.
+    pub fn new(max_renders: usize) -> Self {
+        Self {
+            max_renders,
+            max_wait: DFLT_MAX_WAIT,
+            retry_after: DFLT_RETRY_AFTER,
+        }
+    }
+    /// Sets the longest time a request will wait for a render to finish before it's turned away. If this is zero, requests will be turned
+    /// away as soon as the limit is reached.
+    pub fn max_wait(mut self, val: Duration) -> Self {
+        self.max_wait = val;
+        self
+    }
+    /// Sets how long clients that are turned away are told to wait before they try again (rounded up to a whole number of seconds).
+    pub fn retry_after(mut self, val: Duration) -> Self {
+        self.retry_after = val;
+        self
+    }
+    /// Gets the number of seconds clients that are turned away should wait before they try again.
+    fn get_retry_after_secs(&self) -> u64 {
+        match self.retry_after.subsec_nanos() {
+            0 => self.retry_after.as_secs(),
+            _ => self.retry_after.as_secs() + 1,
+        }
+    }
+}
+
+/// The renders happening for a single template.
+#[derive(Default)]
+struct TemplateRenders {
+    /// The number of pages of the template being rendered right now.
+    active: usize,
+    /// The requests waiting for a render of the template to finish.
+    waiting: Vec<Waker>,
+}
+
+/// The renders happening for every template with a limit, by template path. The limit is for the whole server, not each of its workers
+/// (which have their own copies of the templates and their `ConcurrencyLimit`s), so the renders are counted here.
+static RENDERS: Lazy<Mutex<HashMap<String, TemplateRenders>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A render of a page of a template with a limit, which lets another one start when it's dropped.
+pub(crate) struct RenderPermit {
+    template: String,
+}
+impl Drop for RenderPermit {
+    fn drop(&mut self) {
+        if let Ok(mut renders) = RENDERS.lock() {
+            if let Some(template_renders) = renders.get_mut(&self.template) {
+                template_renders.active = template_renders.active.saturating_sub(1);
+                // Everything waiting will race for the render that just became available
+                for waker in template_renders.waiting.drain(..) {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+/// A future that resolves once a page of the given template can be rendered under its limit, or with an error once it's waited too long.
+struct AcquirePermit<'a> {
+    template: &'a str,
+    limit: &'a ConcurrencyLimit,
+    /// When to stop waiting, which is `None` if the longest time to wait is too long to represent.
+    deadline: Option<DateTime<Utc>>,
+    /// Whether or not we've asked to be woken at the deadline.
+    timer_set: bool,
+}
+impl<'a> Future for AcquirePermit<'a> {
+    type Output = Result<RenderPermit>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // If the lock has been poisoned, we won't limit anything
+        let mut renders = match RENDERS.lock() {
+            Ok(renders) => renders,
+            Err(_) => {
+                return Poll::Ready(Ok(RenderPermit {
+                    template: self.template.to_string(),
+                }))
+            }
+        };
+        let template_renders = renders.entry(self.template.to_string()).or_default();
+        if template_renders.active < self.limit.max_renders {
+            template_renders.active += 1;
+            return Poll::Ready(Ok(RenderPermit {
+                template: self.template.to_string(),
+            }));
+        }
+        if self
+            .deadline
+            .map_or(false, |deadline| Utc::now() >= deadline)
+        {
+            return Poll::Ready(Err(ErrorKind::TooManyRenders(
+                self.template.to_string(),
+                self.limit.get_retry_after_secs(),
+            )
+            .into()));
+        }
+        template_renders.waiting.push(cx.waker().clone());
+        drop(renders);
+        if let (Some(deadline), false) = (self.deadline, self.timer_set) {
+            wake_at(deadline, cx.waker().clone());
+            self.timer_set = true;
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Waits until a page of the given template can be rendered under the given limit, returning a permit that should be held until the render
+/// has finished. If that takes longer than the limit allows, this will return an error that gives a `503` from `err_to_status_code`.
+pub(crate) async fn acquire_render_permit(
+    template: &str,
+    limit: &ConcurrencyLimit,
+) -> Result<RenderPermit> {
+    let deadline = chrono::Duration::from_std(limit.max_wait)
+        .ok()
+        .and_then(|max_wait| Utc::now().checked_add_signed(max_wait));
+    AcquirePermit {
+        template,
+        limit,
+        deadline,
+        timer_set: false,
+    }
+    .await
+}
+
+/// The wakers to wake at given times, along with a condition variable for telling the thread that wakes them about new ones.
+#[cfg(not(target_arch = "wasm32"))]
+static TIMERS: Lazy<(Mutex<Vec<(DateTime<Utc>, Waker)>>, std::sync::Condvar)> = Lazy::new(|| {
+    std::thread::spawn(run_timers);
+    (Mutex::new(Vec::new()), std::sync::Condvar::new())
+});
+
+/// Wakes the given waker at the given time, so that requests stop waiting when they've waited too long, even if no renders finish. This
+/// doesn't depend on any particular async runtime, so it uses a thread of its own.
+#[cfg(not(target_arch = "wasm32"))]
+fn wake_at(time: DateTime<Utc>, waker: Waker) {
+    let (timers, new_timer) = &*TIMERS;
+    if let Ok(mut timers) = timers.lock() {
+        timers.push((time, waker));
+        new_timer.notify_one();
+    }
+}
+/// Threads can't be spawned in WASM (e.g. on Cloudflare Workers), so requests there will only stop waiting when a render finishes.
+#[cfg(target_arch = "wasm32")]
+fn wake_at(_time: DateTime<Utc>, _waker: Waker) {}
+
+/// Wakes the wakers given to `wake_at` at their times, forever.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_timers() {
+    let (timers, new_timer) = &*TIMERS;
+    let mut timers = match timers.lock() {
+        Ok(timers) => timers,
+        Err(_) => return,
+    };
+    loop {
+        let now = Utc::now();
+        let (due, pending): (Vec<_>, Vec<_>) = timers.drain(..).partition(|(time, _)| *time <= now);
+        *timers = pending;
+        for (_, waker) in due {
+            waker.wake();
+        }
+        let next = timers.iter().map(|(time, _)| *time).min();
+        let res = match next {
+            Some(next) => new_timer
+                .wait_timeout(timers, (next - now).to_std().unwrap_or_default())
+                .map(|(timers, _)| timers),
+            None => new_timer.wait(timers),
+        };
+        timers = match res {
+            Ok(timers) => timers,
+            Err(_) => return,
+        };
+    }
+}
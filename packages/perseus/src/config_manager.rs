@@ -27,8 +27,9 @@ error_chain! {
 }
 
 /// A trait for systems that manage where to put configuration files. At simplest, we'll just write them to static files, but they're
-/// more likely to be stored on a CMS.
-#[async_trait::async_trait]
+/// more likely to be stored on a CMS. The futures returned don't need to be `Send`, so implementations can be backed by JavaScript APIs
+/// when the server is compiled to WASM (like Cloudflare's KV store).
+#[async_trait::async_trait(?Send)]
 pub trait ConfigManager: Clone {
     /// Reads data from the named asset.
     async fn read(&self, name: &str) -> Result<String>;
@@ -48,7 +49,7 @@ impl FsConfigManager {
         Self { root_path }
     }
 }
-#[async_trait::async_trait]
+#[async_trait::async_trait(?Send)]
 impl ConfigManager for FsConfigManager {
     async fn read(&self, name: &str) -> Result<String> {
         let asset_path = format!("{}/{}", self.root_path, name);
@@ -63,6 +64,11 @@ impl ConfigManager for FsConfigManager {
     }
     async fn write(&self, name: &str, content: &str) -> Result<()> {
         let asset_path = format!("{}/{}", self.root_path, name);
+        // Assets can be in directories of their own (like each locale's pages)
+        if let Some(parent) = std::path::Path::new(&asset_path).parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| ErrorKind::WriteFailed(asset_path.clone(), err.to_string()))?;
+        }
         fs::write(&asset_path, content)
             .map_err(|err| ErrorKind::WriteFailed(asset_path, err.to_string()).into())
     }
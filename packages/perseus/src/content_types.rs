@@ -0,0 +1,113 @@
+use crate::TRANSLATOR_FILE_EXT;
+use std::collections::HashMap;
+
+/// The content type sent for files with extensions that aren't known.
+pub const DFLT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Something a Perseus server sends, which determines the content type it's sent with.
+#[derive(Debug, Clone)]
+pub enum Artifact<'a> {
+    /// A page prerendered into the app shell (or the app shell on its own).
+    Html,
+    /// The data for a page (its content, head, and state), which is JSON.
+    PageData,
+    /// The app's WASM bundle.
+    Wasm,
+    /// A JavaScript bundle (like the app's main bundle or the script for embedding it).
+    Js,
+    /// The translations for a locale, which are in whatever format the app's translator uses.
+    Translations,
+    /// A static file at the given path, the content type of which is found from its extension.
+    Static(&'a str),
+}
+impl<'a> Artifact<'a> {
+    /// Gets the file extension this artifact's content type is found from.
+    fn get_ext(&self) -> &'a str {
+        match self {
+            Self::Html => "html",
+            Self::PageData => "json",
+            Self::Wasm => "wasm",
+            Self::Js => "js",
+            Self::Translations => TRANSLATOR_FILE_EXT,
+            Self::Static(path) => {
+                let path: &'a str = path;
+                // Extensions aren't case-sensitive, but we handle that when looking them up
+                let file_name = path.rsplit('/').next().unwrap_or(path);
+                match file_name.rsplit_once('.') {
+                    Some((_, ext)) => ext,
+                    None => "",
+                }
+            }
+        }
+    }
+}
+
+/// The content types a Perseus server sends its artifacts with, by file extension. Every server integration uses this, so that they send
+/// the same types (with the right `charset` parameters for text), and types can be overridden for particular extensions if a platform
+/// needs something different (e.g. `application/javascript` for `js`, which some older tools expect).
+#[derive(Debug, Clone, Default)]
+pub struct ContentTypes {
+    /// Content types to use instead of the defaults, by file extension (in lowercase, without a leading `.`).
+    overrides: HashMap<String, String>,
+}
+impl ContentTypes {
+    /// Creates a new set of content types that uses the defaults for everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Overrides the content type sent for files with the given extension (e.g. `ftl` or `.ftl`), which is also used for the artifacts
+    /// that have that extension (like `json` for page data).
+    pub fn with_override(mut self, ext: &str, content_type: impl Into<String>) -> Self {
+        self.overrides.insert(
+            ext.trim_start_matches('.').to_lowercase(),
+            content_type.into(),
+        );
+        self
+    }
+    /// Gets the content type the given artifact should be sent with.
+    pub fn get(&self, artifact: &Artifact) -> String {
+        self.get_for_ext(artifact.get_ext())
+    }
+    /// Gets the content type files with the given extension should be sent with, which will be `application/octet-stream` if the extension
+    /// isn't known.
+    pub fn get_for_ext(&self, ext: &str) -> String {
+        let ext = ext.trim_start_matches('.').to_lowercase();
+        match self.overrides.get(&ext) {
+            Some(content_type) => content_type.to_string(),
+            None => get_dflt_content_type(&ext).to_string(),
+        }
+    }
+}
+
+/// Gets the default content type for files with the given extension (in lowercase). Text types are always UTF-8, and types that define
+/// their own encoding (like JSON) don't get a `charset`.
+fn get_dflt_content_type(ext: &str) -> &'static str {
+    match ext {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "txt" | "ftl" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => DFLT_CONTENT_TYPE,
+    }
+}
@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+
+/// The class given to the element every embedded page is rendered into, which can be used to scope an app's styles to its embedded
+/// pages so that they don't leak into the host page (e.g. `.perseus-embed h1 { ... }`).
+pub const EMBED_CLASS: &str = "perseus-embed";
+/// The attribute given to the element every embedded page is rendered into, the value of which is the path of the embedded page.
+pub const EMBED_PATH_ATTR: &str = "data-perseus-embed";
+
+thread_local! {
+    /// The origin of the Perseus server the app is loading everything from if it's been embedded into a page that isn't part of it.
+    static EMBED_ORIGIN: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Puts the app shell into embed mode, in which pages are rendered into a host page that isn't part of the app (like a page of a legacy
+/// site that's being migrated to Perseus). Everything the app shell fetches (like page data and translations) will be loaded from the
+/// Perseus server at the given origin (e.g. `https://app.example.com`), and the app shell won't touch anything it doesn't own in the host
+/// page (like its `<head>` and its URL).
+pub fn set_embed_origin(origin: impl Into<String>) {
+    let origin = origin.into();
+    EMBED_ORIGIN.with(|embed_origin| {
+        *embed_origin.borrow_mut() = Some(origin.trim_end_matches('/').to_string())
+    });
+}
+
+/// Checks if the app shell is in embed mode.
+pub fn is_embedded() -> bool {
+    EMBED_ORIGIN.with(|embed_origin| embed_origin.borrow().is_some())
+}
+
+/// Resolves the given path on the Perseus server (like `/.perseus/page/en-US/about`) against the server's origin if the app shell is in
+/// embed mode, since the host page's origin is probably a different server. Anything else is returned as it is.
+pub(crate) fn resolve_url(url: &str) -> String {
+    EMBED_ORIGIN.with(|embed_origin| match &*embed_origin.borrow() {
+        Some(origin) if url.starts_with('/') && !url.starts_with("//") => {
+            format!("{}{}", origin, url)
+        }
+        _ => url.to_string(),
+    })
+}
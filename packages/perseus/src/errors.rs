@@ -1,5 +1,6 @@
 #![allow(missing_docs)]
 
+use crate::validation::ValidationErrors;
 pub use error_chain::bail;
 use error_chain::error_chain;
 
@@ -48,6 +49,12 @@ error_chain! {
             description("a template feature required by a function called was not present")
             display("the template '{}' is missing the feature '{}'", name, feature)
         }
+        /// For when a template uses rendering strategies that can't be used together (e.g. incremental generation without build paths).
+        /// This pertains only to rendering strategies, so it shouldn't ever be sensitive.
+        IncompatibleStrategies(name: String, problems: String) {
+            description("a template uses rendering strategies that can't be used together")
+            display("the template '{}' uses rendering strategies that can't be used together: {}", name, problems)
+        }
         /// For when the given path wasn't found, a 404 should never be sensitive.
         PageNotFound(path: String) {
             description("the requested page was not found")
@@ -59,6 +66,12 @@ error_chain! {
             description("invalid indicator in timestring")
             display("invalid indicator '{}' in timestring, must be one of: s, m, h, d, w, M, y", indicator)
         }
+        /// For when the user misconfigured their revalidation length in some other way (e.g. a number without an indicator, or an interval
+        /// too long to be represented). Like invalid indicators, this shouldn't be sensitive.
+        InvalidDatetimeInterval(interval: String) {
+            description("invalid revalidation interval")
+            display("invalid revalidation interval '{}', must be a timestring of a form like '1w' or '1d12h' that isn't absurdly long", interval)
+        }
         /// For when a template defined both build and request states when it can't amalgamate them sensibly, which indicates a misconfiguration.
         /// Revealing the rendering strategies of a template in this way should never be sensitive. Due to the execution context, this
         /// doesn't disclose the offending template.
@@ -66,12 +79,157 @@ error_chain! {
             description("both build and request states were defined for a template when only one or fewer were expected")
             display("both build and request states were defined for a template when only one or fewer were expected")
         }
+        /// For when a template's build and request states were to be merged as JSON, but they weren't both JSON objects. This is a
+        /// misconfiguration, and it shouldn't be sensitive.
+        StatesNotMergeable {
+            description("build and request states couldn't be merged because they weren't both json objects")
+            display("build and request states couldn't be merged because they weren't both json objects")
+        }
+        /// For when the client tried to mutate the state of a page that doesn't have any.
+        StateNotDefined {
+            description("the page has no state to mutate")
+            display("the page has no state to mutate")
+        }
+        /// For when the request sending a mutation to the server failed, meaning the page's state was rolled back.
+        MutationFailed(err: String) {
+            description("the mutation couldn't be sent to the server, and was rolled back")
+            display("the mutation couldn't be sent to the server, and was rolled back: '{}'", err)
+        }
+        /// For when a state (like a form's) failed its validation rules, either in the browser or on the server.
+        ValidationFailed(errors: ValidationErrors) {
+            description("the state failed validation")
+            display("the state failed validation: {}", errors)
+        }
+        /// For when the request sending a form to the server failed for a reason other than the form being invalid.
+        SubmissionFailed(err: String) {
+            description("the form couldn't be sent to the server")
+            display("the form couldn't be sent to the server: '{}'", err)
+        }
+        /// For when a build state function returned a redirect for a page that can't be redirected (because it's being built or
+        /// revalidated rather than generated for a particular request).
+        RedirectNotAllowed(path: String) {
+            description("a redirect was returned for a page that can't be redirected")
+            display("a redirect was returned for the page at path '{}', but redirects can only be returned when pages are generated incrementally (not when they're built or revalidated)", path)
+        }
+        /// For when internal links in the pages generated at build-time didn't resolve to any pages in the app.
+        BrokenLinks(links: String) {
+            description("some internal links didn't resolve to any pages")
+            display("the following internal links didn't resolve to any pages: {}", links)
+        }
+        /// For when user code panicked while rendering a page. The panic is caught so that other pages can still be rendered. Like errors from
+        /// render functions, these can be transmitted over the network, so **render code must not panic with sensitive information**.
+        RenderPanicked(template: String, path: String, msg: String) {
+            description("a panic occurred while rendering a page")
+            display("a panic occurred while rendering the page at path '{}' with template '{}': '{}'", path, template, msg)
+        }
+        /// For when translations failed while a page was being prerendered in strict i18n mode. This is only generated at build-time.
+        MissingTranslations(ids: Vec<String>) {
+            description("some translations failed while prerendering a page in strict i18n mode")
+            display("the following translation ids couldn't be translated: {}", ids.iter().map(|id| format!("'{}'", id)).collect::<Vec<String>>().join(", "))
+        }
+        /// For when the thread building a locale in a parallel build panicked outside of any page (pages that panic are caught and
+        /// reported in `PagesFailed` as usual). This is only generated at build-time.
+        LocaleBuildPanicked(locale: String) {
+            description("the thread building a locale panicked")
+            display("the thread building locale '{}' panicked", locale)
+        }
+        /// For when one or more pages failed to build (because they panicked, or for any reason with `BuildErrorMode::Aggregate`). Every
+        /// other page will still have been built. This displays a report of the failures grouped by template.
+        PagesFailed(failures: Vec<crate::build::PageFailure>) {
+            description("some pages failed to build")
+            display("{} page(s) failed to build:{}", failures.len(), crate::build::format_page_failures(failures))
+        }
+        /// For when a template couldn't be registered because another template already has the same path (or routing path). This is a
+        /// misconfiguration between the app and its plugins, so it shouldn't be sensitive.
+        TemplateConflict(path: String, existing_owner: String, new_owner: String) {
+            description("two templates were registered with the same path")
+            display("the template at path '{}' (registered by {}) conflicts with one registered by {}", path, existing_owner, new_owner)
+        }
+        /// For when a plugin tried to change a template that hasn't been registered.
+        TemplateNotRegistered(path: String, owner: String) {
+            description("the template to change hasn't been registered")
+            display("{} tried to change the template at path '{}', but no such template has been registered", owner, path)
+        }
+        /// For when a request made with the `ApiClient` couldn't be completed (e.g. the API couldn't be reached). This won't disclose the
+        /// credentials used, but it will disclose the URL.
+        ApiRequestFailed(url: String, err: String) {
+            description("a request to an api couldn't be completed")
+            display("the request to '{}' couldn't be completed: '{}'", url, err)
+        }
+        /// For when a request made with the `ApiClient` returned a non-success status code, even after any retries.
+        ApiResponseNotOk(url: String, status: u16, body: String) {
+            description("an api returned a non-success status code")
+            display("the request to '{}' returned status code '{}' with payload '{}'", url, status, body)
+        }
+        /// For when the environment variable that an `ApiClient` gets its authorization from isn't set.
+        ApiAuthNotSet(var: String) {
+            description("the environment variable to get api authorization from isn't set")
+            display("the environment variable '{}' to get api authorization from isn't set", var)
+        }
+        /// For when a GraphQL API returned errors for a query.
+        GraphQlErrors(errors: String) {
+            description("a graphql api returned errors for a query")
+            display("the graphql api returned the following errors for a query: {}", errors)
+        }
+        /// For when the field that should have been in the response to a GraphQL query (given as a JSON pointer) wasn't there, or was the
+        /// wrong type.
+        GraphQlFieldNotFound(pointer: String) {
+            description("a field expected in the response to a graphql query wasn't there")
+            display("the field at '{}' expected in the response to a graphql query wasn't there or was the wrong type", pointer)
+        }
         /// For when a render function failed. Only request-time functions can generate errors that will be transmitted over the network,
         /// so **render functions must not disclose sensitive information in errors**. Other information shouldn't be sensitive.
         RenderFnFailed(fn_name: String, template: String, cause: ErrorCause, err_str: String) {
             description("error while calling render function")
             display("an error caused by '{:?}' occurred while calling render function '{}' on template '{}': '{}'", cause, fn_name, template, err_str)
         }
+        /// For when the HTTP basic auth credentials in the given environment variable aren't in the form `username:password`.
+        InvalidBasicAuth(var: String) {
+            description("the http basic auth credentials given in the environment weren't in the form `username:password`")
+            display("the http basic auth credentials in the environment variable '{}' weren't in the form `username:password`", var)
+        }
+        /// For when an entry in an access control allowlist isn't a valid IP address or CIDR range.
+        InvalidAllowlistEntry(entry: String, err: String) {
+            description("an entry in an access control allowlist wasn't a valid ip address or cidr range")
+            display("the allowlist entry '{}' isn't a valid ip address or cidr range: {}", entry, err)
+        }
+        /// For when the number of trusted proxies in the given environment variable isn't `true`, `false`, or a number.
+        InvalidTrustedProxies(var: String, val: String) {
+            description("the number of trusted proxies given in the environment wasn't `true`, `false`, or a number")
+            display("the number of trusted proxies in the environment variable '{}' was '{}', which isn't `true`, `false`, or a number", var, val)
+        }
+        /// For when an upload wasn't a valid `multipart/form-data` body.
+        MalformedUpload(err: String) {
+            description("the upload wasn't a valid multipart/form-data body")
+            display("the upload wasn't a valid multipart/form-data body: {}", err)
+        }
+        /// For when an upload was over its limits (e.g. it had a file that was too large).
+        UploadTooLarge(limit: String) {
+            description("the upload was over its limits")
+            display("the upload was over its limits ({})", limit)
+        }
+        /// For when an upload contained a file with a content type that isn't allowed.
+        UploadTypeNotAllowed(content_type: String) {
+            description("the upload contained a file with a content type that isn't allowed")
+            display("the upload contained a file with the content type '{}', which isn't allowed", content_type)
+        }
+        /// For when too many pages of a template were being rendered at once for another to be rendered, even after waiting. This gives
+        /// the number of seconds the client should wait before trying again.
+        TooManyRenders(template: String, retry_after: u64) {
+            description("too many pages of a template were being rendered at once")
+            display("too many pages of the template '{}' are being rendered at once, try again in {} second(s)", template, retry_after)
+        }
+        /// For when a template's state functions have failed too many times in a row, so its circuit breaker has stopped calling them for
+        /// a while. This gives the number of seconds the client should wait before trying again.
+        CircuitOpen(template: String, retry_after: u64) {
+            description("a template's state functions have been failing, so they aren't being called for a while")
+            display("the state functions of the template '{}' have been failing, so its pages can't be rendered, try again in {} second(s)", template, retry_after)
+        }
+        /// For when a page's state didn't survive being serialized and deserialized again.
+        StateRoundTripFailed(err: String) {
+            description("state didn't survive being serialized and deserialized")
+            display("state didn't survive being serialized and deserialized: {}", err)
+        }
     }
     links {
         ConfigManager(crate::config_manager::Error, crate::config_manager::ErrorKind);
@@ -90,12 +248,20 @@ pub fn err_to_status_code(err: &Error) -> u16 {
     match err.kind() {
         // Misconfiguration
         ErrorKind::TemplateFeatureNotEnabled(_, _) => 500,
+        // Misconfiguration
+        ErrorKind::IncompatibleStrategies(_, _) => 500,
         // Bad request
         ErrorKind::PageNotFound(_) => 404,
         // Misconfiguration
         ErrorKind::InvalidDatetimeIntervalIndicator(_) => 500,
         // Misconfiguration
+        ErrorKind::InvalidDatetimeInterval(_) => 500,
+        // Misconfiguration
         ErrorKind::BothStatesDefined => 500,
+        // Misconfiguration
+        ErrorKind::StatesNotMergeable => 500,
+        // Bugs in the user's code
+        ErrorKind::RenderPanicked(_, _, _) => 500,
         // Ambiguous, we'll rely on the given cause
         ErrorKind::RenderFnFailed(_, _, cause, _) => match cause {
             ErrorCause::Client(code) => code.unwrap_or(400),
@@ -109,9 +275,28 @@ pub fn err_to_status_code(err: &Error) -> u16 {
         ErrorKind::ConfigManager(_) => 500,
         ErrorKind::Io(_) => 500,
         ErrorKind::ChronoParse(_) => 500,
+        // Bad uploads
+        ErrorKind::MalformedUpload(_) => 400,
+        ErrorKind::UploadTooLarge(_) => 413,
+        ErrorKind::UploadTypeNotAllowed(_) => 415,
+        // Bad submissions
+        ErrorKind::ValidationFailed(_) => 422,
+        // The server is too busy to render the page right now
+        ErrorKind::TooManyRenders(_, _) => 503,
+        ErrorKind::CircuitOpen(_, _) => 503,
         // JSON errors can be caused by the client, but we don't have enough information
         ErrorKind::Json(_) => 500,
         // Any other errors go to a 500
         _ => 500,
     }
 }
+
+/// Gets the number of seconds a client should wait before trying again after the given error, if it's one it should try again after
+/// (like too many pages of a template being rendered at once, or a template's circuit breaker being open). Server integrations should send this in a `Retry-After` header.
+pub fn err_to_retry_after(err: &Error) -> Option<u64> {
+    match err.kind() {
+        ErrorKind::TooManyRenders(_, retry_after) => Some(*retry_after),
+        ErrorKind::CircuitOpen(_, retry_after) => Some(*retry_after),
+        _ => None,
+    }
+}
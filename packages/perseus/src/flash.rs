@@ -0,0 +1,63 @@
+use crate::template::Redirect;
+use crate::Request;
+use http::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+use sycamore::rx::Signal;
+
+/// The name of the cookie flash messages are sent in.
+pub const FLASH_COOKIE: &str = "perseus_flash";
+
+/// The one-time flash message delivered to the current page, provided to templates through context. This will be `None` if no message
+/// was sent to this page.
+pub type FlashMessage = Signal<Option<String>>;
+
+/// Sets a one-time flash message that will be delivered to the next page the user loads, by adding a cookie to the given response
+/// headers. This can be used in `.set_headers_fn()` or in any other route on your server (e.g. one handling a form submission that then
+/// redirects the user to a page).
+pub fn set_flash(headers: &mut HeaderMap, message: &str) {
+    headers.append(
+        SET_COOKIE,
+        HeaderValue::from_str(&format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax",
+            FLASH_COOKIE,
+            urlencoding::encode(message)
+        ))
+        // The message is URL encoded, so this can't fail
+        .unwrap(),
+    );
+}
+
+/// Adds the flash message of the given redirect (if it has one) to the given response headers, so that it's delivered to the page the
+/// user is redirected to. This is done automatically by server integrations for redirects from state functions.
+pub fn set_redirect_flash(headers: &mut HeaderMap, redirect: &Redirect) {
+    if let Some(message) = &redirect.flash {
+        set_flash(headers, message);
+    }
+}
+
+/// Removes any flash message on the client by adding a cookie that expires it to the given response headers. This is done automatically
+/// by server integrations once a message has been delivered.
+pub fn clear_flash(headers: &mut HeaderMap) {
+    headers.append(
+        SET_COOKIE,
+        HeaderValue::from_str(&format!("{}=; Path=/; Max-Age=0", FLASH_COOKIE)).unwrap(),
+    );
+}
+
+/// Gets the flash message sent with the given request, if there is one.
+pub fn get_flash(req: &Request) -> Option<String> {
+    req.headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|cookie| {
+            let mut parts = cookie.trim().splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(val)) if name == FLASH_COOKIE && !val.is_empty() => {
+                    urlencoding::decode(val).ok().map(|val| val.to_string())
+                }
+                _ => None,
+            }
+        })
+        .next()
+}
@@ -0,0 +1,160 @@
+// This covers the common setup of getting content from a headless CMS over GraphQL, generating build paths and build state from queries
+
+use crate::api_client::ApiClient;
+use crate::errors::*;
+use crate::template::{BuildState, GetBuildPathsFn, GetBuildStateFn, StringResultWithCause};
+use crate::ErrorCause;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::rc::Rc;
+
+/// A GraphQL API to get content from, which can generate the build paths and build state of templates from queries. Queries are sent
+/// with the given [`ApiClient`], so they'll use its authorization, and, in development, its cache (keyed by each query and its variables,
+/// so that changing a query will fetch it again). This is only available with the `graphql` feature.
+#[derive(Debug, Clone)]
+pub struct GraphQlSource {
+    client: ApiClient,
+}
+impl GraphQlSource {
+    /// Creates a new GraphQL source that sends queries with the given client, the base URL of which should be the GraphQL endpoint (like
+    /// `https://cms.example.com/graphql`).
+    pub fn new(client: ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// Runs the given query with the given variables, returning its `data`. If the response has any `errors`, they'll be returned
+    /// instead.
+    pub async fn query(&self, query: &str, variables: Value) -> Result<Value> {
+        let body = json!({
+            "query": query,
+            "variables": variables
+        });
+        let cache_key = format!("{} {}", self.client.get_url(""), body);
+        let dev_cache = self.client.get_dev_cache();
+        let res = match dev_cache.as_ref().and_then(|cache| cache.get(&cache_key)) {
+            Some(res) => serde_json::from_str(&res)?,
+            None => {
+                let res: Value = self.client.post_json("", &body).await?;
+                // Responses with errors shouldn't be cached, since they're likely to be fixed on the next build
+                if let (Some(dev_cache), None) = (&dev_cache, res.get("errors")) {
+                    dev_cache.set(&cache_key, &res.to_string());
+                }
+                res
+            }
+        };
+
+        get_data(res)
+    }
+    /// Runs the given query with the given variables, deserializing its `data` into the given type.
+    pub async fn query_as<T: DeserializeOwned>(&self, query: &str, variables: Value) -> Result<T> {
+        let data = self.query(query, variables).await?;
+        Ok(serde_json::from_value(data)?)
+    }
+
+    /// Creates a function for the *build paths* strategy from the given query, which should list the pages to build. The list should be
+    /// at the given JSON pointer in the query's `data` (like `/posts`), and each item in it should have its path in the given field (like
+    /// `slug`).
+    pub fn build_paths_fn(
+        &self,
+        query: impl Into<String>,
+        list_pointer: impl Into<String>,
+        path_field: impl Into<String>,
+    ) -> GetBuildPathsFn {
+        let source = self.clone();
+        let query = query.into();
+        let list_pointer = list_pointer.into();
+        let path_field = path_field.into();
+        Rc::new(move || {
+            let source = source.clone();
+            let query = query.clone();
+            let list_pointer = list_pointer.clone();
+            let path_field = path_field.clone();
+            async move {
+                let data = source
+                    .query(&query, json!({}))
+                    .await
+                    .map_err(|err| err.to_string())?;
+                let list = match data.pointer(&list_pointer).and_then(|list| list.as_array()) {
+                    Some(list) => list,
+                    None => return Err(ErrorKind::GraphQlFieldNotFound(list_pointer).to_string()),
+                };
+                let mut paths = Vec::new();
+                for item in list {
+                    match item.get(&path_field).and_then(|path| path.as_str()) {
+                        Some(path) => paths.push(path.to_string()),
+                        None => {
+                            return Err(ErrorKind::GraphQlFieldNotFound(format!(
+                                "{}/*/{}",
+                                list_pointer, path_field
+                            ))
+                            .to_string())
+                        }
+                    }
+                }
+
+                Ok(paths)
+            }
+        })
+    }
+    /// Creates a function for the *build state* strategy from the given query, which will be given the `path` and `locale` of the page
+    /// as variables. The page's state will be whatever is at the given JSON pointer in the query's `data` (like `/post`), and, if that's
+    /// `null`, the page will be treated as not found (which is useful with incremental generation).
+    pub fn build_state_fn(
+        &self,
+        query: impl Into<String>,
+        state_pointer: impl Into<String>,
+    ) -> GetBuildStateFn {
+        let source = self.clone();
+        let query = query.into();
+        let state_pointer = state_pointer.into();
+        Rc::new(move |path: String, locale: String| {
+            let source = source.clone();
+            let query = query.clone();
+            let state_pointer = state_pointer.clone();
+            async move {
+                let data = source
+                    .query(
+                        &query,
+                        json!({
+                            "path": path,
+                            "locale": locale
+                        }),
+                    )
+                    .await
+                    .map_err(|err| (err.to_string(), ErrorCause::Server(None)))?;
+                let res: StringResultWithCause<BuildState> = match data.pointer(&state_pointer) {
+                    Some(Value::Null) => Ok(BuildState::not_found()),
+                    Some(state) => Ok(BuildState::new(state.to_string())),
+                    None => Err((
+                        ErrorKind::GraphQlFieldNotFound(state_pointer).to_string(),
+                        ErrorCause::Server(None),
+                    )),
+                };
+                res
+            }
+        })
+    }
+}
+
+/// Gets the `data` from the given GraphQL response, returning any `errors` in it instead.
+fn get_data(mut res: Value) -> Result<Value> {
+    if let Some(errors) = res.get("errors").and_then(|errors| errors.as_array()) {
+        let messages: Vec<String> = errors
+            .iter()
+            .map(
+                |err| match err.get("message").and_then(|msg| msg.as_str()) {
+                    Some(msg) => msg.to_string(),
+                    None => err.to_string(),
+                },
+            )
+            .collect();
+        if !messages.is_empty() {
+            bail!(ErrorKind::GraphQlErrors(messages.join("; ")))
+        }
+    }
+
+    match res.get_mut("data") {
+        Some(data) => Ok(data.take()),
+        None => bail!(ErrorKind::GraphQlFieldNotFound("/data".to_string())),
+    }
+}
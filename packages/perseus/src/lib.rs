@@ -30,34 +30,103 @@
  * Each of the available translations are feature-gated, and can be enabled with the `translator-[engine-name]` feature. You can set
  * the default translator by setting the `translator-dflt-[engine-name]` (you of course can't have more than one default translator).
  * You can read more about this system [here](https://arctic-hen7.github.io/perseus/i18n.html).
+ *
+ * If you don't need the full power of Fluent, you can disable the default features and use `translator-simple-json` and
+ * `translator-dflt-simple-json` instead, which use simple JSON files of translations and keep Fluent's parsing machinery out of your
+ * WASM bundle. Translations themselves are always fetched at runtime, so they're never compiled into the bundle.
+ *
+ * The `AccessControl` protection for whole apps is behind the `access-control` feature, which the server integrations enable for you,
+ * so its IP address and base64 parsing stays out of your WASM bundle.
  */
 
 #![deny(missing_docs)]
 #![recursion_limit = "256"]
 
+/// Utilities for protecting apps (like staging deployments) with HTTP basic auth or an IP allowlist.
+#[cfg(feature = "access-control")]
+pub mod access_control;
+/// Utilities for getting content from APIs in build and request state functions.
+#[cfg(feature = "api-client")]
+pub mod api_client;
+/// Utilities for auditing the HTML your app generates at build-time.
+pub mod audit;
+/// Utilities for running work after a response has been sent, like sending emails from request state.
+pub mod background;
 /// Utilities for building your app.
 pub mod build;
+mod circuit_breaker;
 mod client_translations_manager;
+/// Utilities for controlling the clock used for revalidation, so it can be tested deterministically.
+pub mod clock;
+mod concurrency;
 /// Utilities for creating custom config managers, as well as the default `FsConfigManager`.
 pub mod config_manager;
+/// Utilities for finding the content types that a server should send an app's artifacts with.
+pub mod content_types;
 mod decode_time_str;
+/// Utilities for embedding an app's pages into host pages that aren't part of it, like the pages of a legacy site being migrated to Perseus.
+pub mod embed;
 pub mod errors;
+/// Utilities for sending one-time flash messages to pages.
+pub mod flash;
+/// Utilities for generating build paths and build state from a GraphQL API.
+#[cfg(feature = "graphql")]
+pub mod graphql;
+mod link;
+/// Utilities for checking the internal links in your app after it's been built.
+pub mod link_check;
 mod locale_detector;
+mod locale_switcher;
 mod locales;
 mod log;
 mod macros;
+/// Utilities for optimistically mutating the state of a page on the client-side.
+pub mod mutation;
+mod path_equivalents;
+mod path_pattern;
+/// Utilities for writing plugins that contribute their own templates to apps.
+pub mod plugins;
+/// Utilities for serving parts of files in response to requests with a `Range` header (like for seeking through videos).
+pub mod ranges;
+mod redirects;
+mod render_panic;
+/// Utilities for sharing resources like database pools between the state functions of an app.
+pub mod resources;
+/// Utilities for finding the element the app is mounted into and managing auxiliary roots (like a container for modals) alongside it.
+pub mod roots;
+/// Utilities for getting the live route table of a running server (its templates, pages, and their freshness) for debugging in production.
+pub mod route_table;
 /// Utilities regarding routing.
 pub mod router;
+/// Utilities for sanitizing HTML that templates don't control (like rich text from a CMS) before rendering it. This is only available
+/// with the `sanitize` feature.
+#[cfg(feature = "sanitize")]
+pub mod sanitize;
+mod scoped_states;
 /// Utilities for serving your app. These are platform-agnostic, and you probably want an integration like [perseus-actix-web](https://crates.io/crates/perseus-actix-web).
 pub mod serve;
 /// Utilities to do with the app shell. You probably don't want to delve into here.
 pub mod shell;
+/// Utilities for checking that the artifacts generated at build-time aren't too large.
+pub mod size_budget;
+/// Serializable types for page state with stable formats (like dates and exact decimals), and diagnostics for state that doesn't survive
+/// being sent to the browser.
+pub mod state_types;
+/// Utilities for sending surrogate keys for CDN cache purging.
+pub mod surrogate_keys;
 /// Utilities to do with templating. This is where the bulk of designing apps lies.
 pub mod template;
 /// Utilities for creating custom translations managers, as well as the default `FsTranslationsManager`.
 pub mod translations_manager;
-/// Utilities regarding translators, including the default `FluentTranslator`.
+/// Utilities regarding translators, including the default `FluentTranslator` and the `SimpleJsonTranslator` for simple JSON translations.
 pub mod translator;
+/// Utilities for accepting file uploads in an app's own routes. This is only available with the `uploads` feature.
+#[cfg(feature = "uploads")]
+pub mod uploads;
+mod url_builder;
+mod url_strategy;
+/// Utilities for validating states (like the data sent by forms) with the same rules in the browser and on the server.
+pub mod validation;
 
 pub use http;
 pub use http::Request as HttpRequest;
@@ -66,14 +135,69 @@ pub type Request = HttpRequest<()>;
 pub use sycamore::{generic_node::GenericNode, DomNode, SsrNode};
 pub use sycamore_router::Route;
 
-pub use crate::build::{build_app, build_template, build_templates_for_locale};
+#[cfg(feature = "access-control")]
+pub use crate::access_control::{AccessControl, AccessVerdict};
+#[cfg(feature = "api-client")]
+pub use crate::api_client::ApiClient;
+pub use crate::audit::audit_html;
+pub use crate::background::spawn_after_response;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::build::build_app_in_parallel;
+pub use crate::build::{
+    build_app, build_template, build_templates_for_locale, plan_app, BuildErrorMode, BuildFilter,
+    BuildPlan, PageFailure, PlannedPage, DFLT_BUILD_CONCURRENCY,
+};
+pub use crate::circuit_breaker::CircuitBreaker;
 pub use crate::client_translations_manager::ClientTranslationsManager;
+pub use crate::concurrency::ConcurrencyLimit;
 pub use crate::config_manager::{ConfigManager, FsConfigManager};
-pub use crate::errors::{err_to_status_code, ErrorCause};
-pub use crate::locale_detector::detect_locale;
+pub use crate::content_types::{Artifact, ContentTypes};
+pub use crate::errors::{err_to_retry_after, err_to_status_code, ErrorCause};
+#[cfg(feature = "graphql")]
+pub use crate::graphql::GraphQlSource;
+pub use crate::link::{Link, LinkProps};
+pub use crate::link_check::{check_links, LinkCheckMode};
+pub use crate::locale_detector::{
+    detect_locale, detect_locale_from_header, detect_locale_with, LocaleRedirect,
+    LocaleRedirectView,
+};
+pub use crate::locale_switcher::{LocaleSwitcher, CURRENT_LOCALE_CLASS};
 pub use crate::locales::Locales;
-pub use crate::serve::{get_page, get_render_cfg};
-pub use crate::shell::{app_shell, ErrorPages};
-pub use crate::template::{States, StringResult, StringResultWithCause, Template, TemplateMap};
-pub use crate::translations_manager::{FsTranslationsManager, TranslationsManager};
+pub use crate::path_equivalents::{get_path_equivalents, PathEquivalents};
+pub use crate::plugins::{Plugin, TemplateRegistry};
+pub use crate::ranges::ByteRange;
+pub use crate::redirects::Redirects;
+pub use crate::resources::{get_resource, Resources};
+pub use crate::roots::{
+    clear_aux_root, get_app_root, get_aux_root, mount_aux_roots, render_to_aux_root,
+};
+pub use crate::route_table::{get_route_table, RouteTable};
+#[cfg(feature = "sanitize")]
+pub use crate::sanitize::{sanitize_html, SafeHtml, SanitizedHtml, Sanitizer};
+pub use crate::scoped_states::ScopedStates;
+pub use crate::serve::{
+    get_initial_load, get_page, get_page_for_template, get_render_cfg,
+    get_streamed_page_for_template, get_template_for_path, InitialLoad, InitialPage,
+    StreamedPageData,
+};
+pub use crate::shell::{
+    app_shell, follow_redirect, remove_initial_content, switch_locale, watch_translations,
+    ErrorPages, MountedTemplate,
+};
+pub use crate::size_budget::{check_artifact_sizes, SizeBudget};
+pub use crate::surrogate_keys::SurrogateKeyHeader;
+pub use crate::template::{
+    AmalgamationStrategy, BuildPath, BuildState, Outlet, OutletView, ReactiveState, Redirect,
+    RenderBackend, RenderCtx, RenderOutcome, RevalidationContext, SlowRender, States, StringResult,
+    StringResultWithCause, Template, TemplateMap, TemplateMapBuilder,
+};
+pub use crate::translations_manager::{
+    CachedTranslationsManager, FsTranslationsManager, TranslationsManager,
+};
+#[cfg(feature = "translator-fluent")]
+pub use crate::translator::checked_t;
 pub use crate::translator::{Translator, TRANSLATOR_FILE_EXT};
+#[cfg(feature = "uploads")]
+pub use crate::uploads::{UploadForm, UploadLimits, UploadedFile};
+pub use crate::url_builder::UrlBuilder;
+pub use crate::url_strategy::UrlStrategy;
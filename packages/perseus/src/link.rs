@@ -0,0 +1,72 @@
+use crate::embed::{is_embedded, resolve_url};
+use crate::Translator;
+use std::rc::Rc;
+use sycamore::prelude::{component, template, GenericNode, Template as SycamoreTemplate};
+use sycamore::rx::use_context;
+
+/// The properties of a [`Link`].
+pub struct LinkProps<G: GenericNode> {
+    /// The path to link to, without a locale (e.g. `/about`), which may have a query string or fragment.
+    pub href: String,
+    /// What should be rendered inside the link.
+    pub children: SycamoreTemplate<G>,
+    /// The class to give the link.
+    pub class: String,
+    /// The locale to link to the page in, if it's not the locale of the current page.
+    pub locale: Option<String>,
+}
+impl<G: GenericNode> LinkProps<G> {
+    /// Creates the properties of a link to the given path (without a locale) in the locale of the current page, which will render the
+    /// given children.
+    pub fn new(href: impl Into<String>, children: SycamoreTemplate<G>) -> Self {
+        Self {
+            href: href.into(),
+            children,
+            class: String::new(),
+            locale: None,
+        }
+    }
+    /// Sets the class to give the link.
+    pub fn class(mut self, val: impl Into<String>) -> Self {
+        self.class = val.into();
+        self
+    }
+    /// Sets the locale to link to the page in, instead of the locale of the current page.
+    pub fn locale(mut self, val: impl Into<String>) -> Self {
+        self.locale = Some(val.into());
+        self
+    }
+}
+
+/// Renders a link to the given path in the locale of the current page (or another locale), using the translator provided through context
+/// to localize it with the app's URL strategy. Links within the app are followed by the router like any other, and links that have to
+/// leave it (like those to a locale on its own domain, or from an embedded page to the full app) are given `rel="external"` so that they're
+/// loaded directly.
+///
+/// ```rust,ignore
+/// Link(LinkProps::new("/about", template! { "About" }).class("nav-link"))
+/// ```
+#[component(Link<G>)]
+pub fn link(props: LinkProps<G>) -> SycamoreTemplate<G> {
+    let translator = use_context::<Rc<Translator>>();
+    let href = match &props.locale {
+        Some(locale) if *locale != translator.get_locale() => translator
+            .url_builder(props.href)
+            .locale(locale.to_string())
+            .switching_locale(true)
+            .build(),
+        _ => translator.url(props.href),
+    };
+    // Embedded pages don't have the router, so links go to the full app on its own server
+    let href = resolve_url(&href);
+    let rel = match href.starts_with('/') && !href.starts_with("//") && !is_embedded() {
+        true => "",
+        false => "external",
+    };
+    let class = props.class;
+    let children = props.children;
+
+    template! {
+        a(href = href, class = class, rel = rel) { (children) }
+    }
+}
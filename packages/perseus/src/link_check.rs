@@ -0,0 +1,149 @@
+// This checks the internal links in the pages generated at build-time to catch broken links before deployment
+
+use crate::build::get_prerendered_pages;
+use crate::config_manager::ConfigManager;
+use crate::errors::*;
+use crate::serve::{get_render_cfg, get_template_name_for_path};
+use crate::Locales;
+use std::fmt;
+
+/// What to do about broken internal links found after the build process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkCheckMode {
+    /// Don't check links at all (the default).
+    Off,
+    /// Check links and report any broken ones, but don't fail the build.
+    Warn,
+    /// Check links and fail the build if any are broken.
+    Fail,
+}
+impl Default for LinkCheckMode {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+/// An internal link in a page generated at build-time that doesn't resolve to any page in the app.
+#[derive(Debug, Clone)]
+pub struct BrokenLink {
+    /// The locale of the page the link was found in.
+    pub locale: String,
+    /// The path of the page the link was found in (without the locale).
+    pub page: String,
+    /// The link itself, exactly as it appeared in the page.
+    pub href: String,
+}
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "broken link to '{}' in page '{}' (locale '{}')",
+            self.href, self.page, self.locale
+        )
+    }
+}
+
+/// Checks the internal links in every page prerendered at build-time, making sure each one resolves to a page in the app (either one
+/// that was rendered or one that a template could render on demand). Links to Perseus' own assets (under `/.perseus/`) are always
+/// considered valid, and external links are ignored. This must be run after the build process. If the mode is `LinkCheckMode::Fail`,
+/// this will return an error if any links are broken, otherwise they'll be returned for the caller to report.
+pub async fn check_links(
+    locales: &Locales,
+    mode: LinkCheckMode,
+    config_manager: &impl ConfigManager,
+) -> Result<Vec<BrokenLink>> {
+    if mode == LinkCheckMode::Off {
+        return Ok(Vec::new());
+    }
+    let render_cfg = get_render_cfg(config_manager).await?;
+
+    let mut broken_links = Vec::new();
+    for page in get_prerendered_pages(locales, config_manager).await? {
+        for href in get_hrefs(&page.html) {
+            let target = match resolve_internal_link(&href, &page.path, locales) {
+                Some(target) => target,
+                // External links are out of scope
+                None => continue,
+            };
+            if !target.starts_with(".perseus/")
+                && get_template_name_for_path(&target, &render_cfg).is_none()
+            {
+                broken_links.push(BrokenLink {
+                    locale: page.locale.clone(),
+                    page: page.path.clone(),
+                    href,
+                });
+            }
+        }
+    }
+
+    if mode == LinkCheckMode::Fail && !broken_links.is_empty() {
+        let links: Vec<String> = broken_links.iter().map(|link| link.to_string()).collect();
+        bail!(ErrorKind::BrokenLinks(links.join(", ")))
+    }
+
+    Ok(broken_links)
+}
+
+/// Extracts the values of all the `href` attributes in the given HTML. We don't need a full parser for this, because we only need to
+/// handle what Sycamore renders.
+fn get_hrefs(html: &str) -> Vec<String> {
+    let mut hrefs = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("href=\"") {
+        rest = &rest[(start + 6)..];
+        match rest.find('"') {
+            Some(end) => {
+                hrefs.push(rest[..end].to_string());
+                rest = &rest[end..];
+            }
+            None => break,
+        }
+    }
+
+    hrefs
+}
+
+/// Resolves the given link from the given page to the path of a page in the app (without the locale or a leading `/`), or `None` if the
+/// link is external.
+fn resolve_internal_link(href: &str, page: &str, locales: &Locales) -> Option<String> {
+    let href = href.trim();
+    // Anything with a scheme or a host is external, and fragments on the current page are always fine
+    if href.is_empty()
+        || href.contains("://")
+        || href.starts_with("//")
+        || href.starts_with('#')
+        || ["mailto:", "tel:", "javascript:", "data:"]
+            .iter()
+            .any(|scheme| href.starts_with(scheme))
+    {
+        return None;
+    }
+    // Remove any query string or fragment
+    let href = href.split(|c| c == '?' || c == '#').next().unwrap_or("");
+
+    // Relative links are resolved against the directory of the current page
+    let mut segments: Vec<&str> = match href.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => {
+            let mut segments: Vec<&str> = page.split('/').collect();
+            segments.pop();
+            segments
+        }
+    };
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    // Links can include the locale, which isn't part of the page's path (links without it will be redirected by locale detection)
+    if locales.using_i18n && segments.first().map_or(false, |s| locales.is_supported(s)) {
+        segments.remove(0);
+    }
+
+    Some(segments.join("/"))
+}
@@ -1,13 +1,186 @@
 use crate::DomNode;
 use crate::Locales;
-use sycamore::prelude::{template, Template as SycamoreTemplate};
+use crate::UrlBuilder;
+use std::rc::Rc;
+use std::time::Duration;
+use sycamore::prelude::{template, GenericNode, SsrNode, Template as SycamoreTemplate};
 use sycamore_router::navigate;
+use wasm_bindgen::prelude::Closure;
+use wasm_bindgen::JsCast;
+
+/// A view to show while the user is being redirected to their locale, which is given the locale they're being redirected to and the URL
+/// they're being redirected to.
+pub type LocaleRedirectView<G> = Rc<dyn Fn(String, String) -> SycamoreTemplate<G>>;
+
+/// How users who navigate to a page without a locale (like `/about`) are redirected to it in their locale, which can be set with
+/// `locale_redirect` in `define_app!`. By default, they're redirected straight away to the locale their browser prefers, and nothing is
+/// shown while that happens, but this can show a view of your own for a while first (e.g. a branded page that lets the user pick their
+/// locale themselves), or skip detection entirely and always redirect to the default locale.
+///
+/// Note that this only applies to navigations on the client-side, since the server redirects users who request a page without a locale
+/// on their first load with the `Accept-Language` header. For static hosts that can't do that, `.render_static()` creates a standalone
+/// HTML page that does the same thing.
+pub struct LocaleRedirect<G: GenericNode> {
+    view: Option<LocaleRedirectView<G>>,
+    delay: Duration,
+    detect: bool,
+}
+impl<G: GenericNode> Default for LocaleRedirect<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<G: GenericNode> LocaleRedirect<G> {
+    /// Creates a new locale redirection that redirects users to the locale their browser prefers straight away, without showing anything.
+    pub fn new() -> Self {
+        Self {
+            view: None,
+            delay: Duration::from_secs(0),
+            detect: true,
+        }
+    }
+    /// Sets the view to show while the user is being redirected, which is given the locale and the URL they're being redirected to (so
+    /// that it can link to it for the impatient).
+    pub fn view(mut self, val: impl Fn(String, String) -> SycamoreTemplate<G> + 'static) -> Self {
+        self.view = Some(Rc::new(val));
+        self
+    }
+    /// Sets how long the view should be shown for before the user is redirected. If the user navigates somewhere else before then (e.g.
+    /// by picking a locale themselves), they won't be redirected.
+    pub fn delay(mut self, val: Duration) -> Self {
+        self.delay = val;
+        self
+    }
+    /// Sets whether or not the user's locale should be detected from their browser's settings. If this is `false`, users will always be
+    /// redirected to the default locale.
+    pub fn detect(mut self, val: bool) -> Self {
+        self.detect = val;
+        self
+    }
+}
+impl LocaleRedirect<SsrNode> {
+    /// Renders a standalone HTML page that redirects users who load it to the page at the given path (without a leading `/`) in their
+    /// locale, for hosting the app's pages without a Perseus server (which redirects users itself). This detects the user's locale with
+    /// a small script, and shows the view (given the default locale and its URL) while it waits, linking every locale with `hreflang`
+    /// so that search engines can find them. Users without JavaScript are sent to the default locale.
+    pub fn render_static(&self, url: &str, locales: &Locales) -> String {
+        let urls: Vec<(String, String)> = locales
+            .get_all()
+            .into_iter()
+            .map(|locale| {
+                let to = UrlBuilder::new(url)
+                    .locale(locale.to_string())
+                    .switching_locale(true)
+                    .build_with(locales);
+                (locale.to_string(), to)
+            })
+            .collect();
+        let dflt_url = UrlBuilder::new(url)
+            .locale(locales.default.to_string())
+            .switching_locale(true)
+            .build_with(locales);
+
+        let alternates: String = urls
+            .iter()
+            .map(|(locale, to)| {
+                format!(
+                    r#"<link rel="alternate" hreflang="{}" href="{}">"#,
+                    escape_attr(locale),
+                    escape_attr(to)
+                )
+            })
+            .collect();
+        // Nothing in a script can close it early, even in a string
+        let urls_json = serde_json::to_string(&urls)
+            .unwrap_or_else(|_| "[]".to_string())
+            .replace("</", "<\\/");
+        let script = format!(
+            r#"(function(){{var urls={urls},to=urls[0][1];if({detect}){{var langs=navigator.languages&&navigator.languages.length?navigator.languages:[navigator.language||""];for(var i=0;i<langs.length;i++){{var lang=langs[i].toLowerCase(),exact=null,partial=null;for(var j=0;j<urls.length;j++){{var locale=urls[j][0].toLowerCase();if(locale===lang){{exact=urls[j][1];break}}if(!partial&&locale.split("-")[0]===lang.split("-")[0])partial=urls[j][1]}}if(exact||partial){{to=exact||partial;break}}}}}}setTimeout(function(){{location.replace(to)}},{delay})}})();"#,
+            urls = urls_json,
+            detect = self.detect,
+            delay = self.delay.as_millis()
+        );
+        let view = match &self.view {
+            Some(view) => {
+                let view = Rc::clone(view);
+                let locale = locales.default.to_string();
+                let dflt_url = dflt_url.clone();
+                sycamore::render_to_string(move || view(locale, dflt_url))
+            }
+            None => String::new(),
+        };
+
+        format!(
+            r#"<!DOCTYPE html><html><head><meta charset="utf-8"><meta name="robots" content="noindex">{alternates}<noscript><meta http-equiv="refresh" content="{delay};url={dflt_url}"></noscript><script>{script}</script></head><body>{view}</body></html>"#,
+            alternates = alternates,
+            delay = self.delay.as_secs(),
+            dflt_url = escape_attr(&dflt_url),
+            script = script,
+            view = view
+        )
+    }
+}
+
+/// Escapes the given text so that it can be put in an HTML attribute.
+fn escape_attr(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
 
 /// Detects which locale the user should be served and redirects appropriately. This should only be used when the user navigates to a
 /// page like `/about`, without a locale. This will only work on the client-side (needs access to browser i18n settings). Any pages
 /// that direct to this should be explicitly excluded from search engines (they don't show anything until redirected). This is guided
 /// by [RFC 4647](https://www.rfc-editor.org/rfc/rfc4647.txt), but is not yet fully compliant (only supports `xx-XX` form locales).
 pub fn detect_locale(url: String, locales: Locales) -> SycamoreTemplate<DomNode> {
+    detect_locale_with(url, locales, &LocaleRedirect::new())
+}
+
+/// Detects which locale the user should be served and redirects appropriately, like `detect_locale()`, but as the given locale
+/// redirection says (e.g. showing a view of its own for a while first).
+pub fn detect_locale_with(
+    url: String,
+    locales: Locales,
+    redirect: &LocaleRedirect<DomNode>,
+) -> SycamoreTemplate<DomNode> {
+    let locale = match redirect.detect {
+        true => get_browser_locale(&locales),
+        false => locales.default.clone(),
+    };
+    let to = UrlBuilder::new(url)
+        .locale(locale.to_string())
+        .build_with(&locales);
+    let view = redirect
+        .view
+        .as_ref()
+        .map(|view| view(locale, to.to_string()));
+
+    // Imperatively navigate to the localized route
+    if redirect.delay.as_millis() == 0 {
+        navigate(&to);
+    } else {
+        let window = web_sys::window().unwrap();
+        let from = window.location().href().ok();
+        let on_timeout = Closure::once(move || {
+            // The user might have gone somewhere else by themselves already
+            let current = web_sys::window().and_then(|window| window.location().href().ok());
+            if current == from {
+                navigate(&to);
+            }
+        });
+        let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+            on_timeout.as_ref().unchecked_ref(),
+            redirect.delay.as_millis().min(i32::MAX as u128) as i32,
+        );
+        on_timeout.forget();
+    }
+    // If there's no view, we'll never actually get here, but we need a sensible return type
+    view.unwrap_or_else(|| template! {})
+}
+
+/// Gets which of the given locales the user's browser prefers, falling back to the default locale if none of them match.
+fn get_browser_locale(locales: &Locales) -> String {
     // If nothing matches, we'll use the default locale
     let mut locale = locales.default.clone();
 
@@ -20,7 +193,7 @@ pub fn detect_locale(url: String, locales: Locales) -> SycamoreTemplate<DomNode>
         if let Some(lang) = navigator.language() {
             locale = match compare_locale(&lang, locales.get_all()) {
                 LocaleMatch::Exact(matched) | LocaleMatch::Language(matched) => matched,
-                LocaleMatch::None => locales.default,
+                LocaleMatch::None => locales.default.clone(),
             }
         }
     } else {
@@ -38,10 +211,45 @@ pub fn detect_locale(url: String, locales: Locales) -> SycamoreTemplate<DomNode>
         }
     }
 
-    // Imperatively navigate to the localized route
-    navigate(&format!("/{}/{}", locale, url));
-    // We'll never actually get here, but we need a sensible return type
-    template! {}
+    locale
+}
+
+/// Detects which of the app's locales the user should be served from the value of an `Accept-Language` header (like
+/// `fr-CH, fr;q=0.9, en;q=0.8, *;q=0.5`), falling back to the default locale if none of them match. The user's languages are tried in
+/// order of their quality values (ones with a quality of `0` are ignored), and the first that matches a supported locale exactly, or by
+/// its language alone (so `en-US` can be served `en` or `en-GB`), is used. A wildcard matches the default locale.
+///
+/// This is used on the server to redirect users who request a page without a locale on their first load.
+pub fn detect_locale_from_header(header: &str, locales: &Locales) -> String {
+    let mut langs: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|lang| {
+            let mut parts = lang.split(';').map(|part| part.trim());
+            let tag = parts.next().filter(|tag| !tag.is_empty())?;
+            // Anything without a valid quality value gets the default of 1
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((tag, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // This sort is stable, so languages with the same quality stay in the order the user gave them
+    langs.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    for (lang, _) in langs {
+        if lang == "*" {
+            break;
+        }
+        if let LocaleMatch::Exact(matched) | LocaleMatch::Language(matched) =
+            compare_locale(lang, locales.get_all())
+        {
+            return matched;
+        }
+    }
+
+    locales.default.clone()
 }
 
 /// The possible outcomes of trying to match a locale.
@@ -71,11 +279,11 @@ fn compare_locale(cmp: &str, locales: Vec<&String>) -> LocaleMatch {
     for locale in locales {
         // Split into language and region (e.g. `en-US`) if possible
         let parts: Vec<&str> = locale.split('-').collect();
-        if locale == cmp {
+        if locale.eq_ignore_ascii_case(cmp) {
             outcome = LocaleMatch::Exact(locale.to_string());
             // Any exact match voids anything after it (it'll be further down the list or only a partial match from here on)
             break;
-        } else if cmp_parts.get(0) == parts.get(0) {
+        } else if cmp_parts[0].eq_ignore_ascii_case(parts[0]) {
             // If we've already had a partial match higher up the chain, this is void
             // But we shouldn't break in case there's an exact match coming up
             if !matches!(outcome, LocaleMatch::Language(_)) {
@@ -87,3 +295,87 @@ fn compare_locale(cmp: &str, locales: Vec<&String>) -> LocaleMatch {
 
     outcome
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UrlStrategy;
+
+    fn detect(header: &str) -> String {
+        let locales = Locales {
+            default: "en-US".to_string(),
+            other: vec![
+                "en-GB".to_string(),
+                "fr-FR".to_string(),
+                "de-DE".to_string(),
+            ],
+            using_i18n: true,
+            url_strategy: UrlStrategy::PrefixAll,
+        };
+        detect_locale_from_header(header, &locales)
+    }
+
+    #[test]
+    fn matches_exact_locales() {
+        assert_eq!(detect("fr-FR"), "fr-FR");
+        assert_eq!(detect("en-GB, fr-FR"), "en-GB");
+    }
+
+    #[test]
+    fn is_case_insensitive() {
+        assert_eq!(detect("fr-fr"), "fr-FR");
+        assert_eq!(detect("EN-gb"), "en-GB");
+    }
+
+    #[test]
+    fn falls_back_to_region() {
+        assert_eq!(detect("fr-CH"), "fr-FR");
+        assert_eq!(detect("de"), "de-DE");
+        // The first supported locale with the language is used
+        assert_eq!(detect("en-AU"), "en-US");
+        // An exact match for the same language beats a partial one
+        assert_eq!(detect("en-GB"), "en-GB");
+    }
+
+    #[test]
+    fn orders_by_quality() {
+        assert_eq!(detect("fr-FR;q=0.5, de-DE;q=0.9"), "de-DE");
+        assert_eq!(detect("es-ES, fr-FR;q=0.1, de-DE;q=0.2"), "de-DE");
+        // Languages without a quality value have a quality of 1
+        assert_eq!(detect("fr-FR;q=0.9, de-DE"), "de-DE");
+        // Equal qualities keep the order the user gave them in
+        assert_eq!(detect("de-DE;q=0.8, fr-FR;q=0.8"), "de-DE");
+        assert_eq!(detect("fr-FR;q=0.8, de-DE;q=0.8"), "fr-FR");
+    }
+
+    #[test]
+    fn ignores_zero_quality() {
+        assert_eq!(detect("fr-FR;q=0, de-DE;q=0.1"), "de-DE");
+        assert_eq!(detect("fr-FR;q=0.0"), "en-US");
+    }
+
+    #[test]
+    fn wildcard_matches_default() {
+        assert_eq!(detect("*"), "en-US");
+        assert_eq!(detect("es-ES, *;q=0.5, fr-FR;q=0.1"), "en-US");
+        // Anything preferred to the wildcard is still used
+        assert_eq!(detect("*;q=0.5, fr-FR"), "fr-FR");
+    }
+
+    #[test]
+    fn falls_back_to_default() {
+        assert_eq!(detect("es-ES, ja-JP;q=0.8"), "en-US");
+    }
+
+    #[test]
+    fn handles_malformed_headers() {
+        assert_eq!(detect(""), "en-US");
+        assert_eq!(detect(" , ,,"), "en-US");
+        assert_eq!(detect(";q=0.9"), "en-US");
+        assert_eq!(detect(",, fr-FR ,"), "fr-FR");
+        // An invalid quality value is treated as the default of 1
+        assert_eq!(detect("de-DE;q=0.5, fr-FR;q=abc"), "fr-FR");
+        assert_eq!(detect("fr-FR;q=, de-DE;q=0.5"), "fr-FR");
+        assert_eq!(detect("fr-FR;level=1;q=0.1, de-DE;q=0.5"), "de-DE");
+    }
+}
@@ -0,0 +1,59 @@
+use crate::template::RenderCtx;
+use crate::{Locales, UrlBuilder};
+use sycamore::prelude::{component, template, GenericNode, Template as SycamoreTemplate};
+use sycamore::rx::{use_context, Signal};
+
+/// The class given to the link to the locale the current page is being rendered in by [`LocaleSwitcher`].
+pub const CURRENT_LOCALE_CLASS: &str = "perseus-locale-current";
+
+/// Renders a list of links to the current page in each of the given locales (which should be your app's locales, as returned by
+/// `get_locales()`), with the link to the locale the page is currently in given the class `perseus-locale-current` so that it can be
+/// highlighted. This can be used in any template (or any layout or parent template rendered around one), since it gets the current page
+/// from the render context, and it will be updated on the client-side when navigating between pages.
+///
+/// Links go to the same path in each locale, unless the page's path is different in some locales (see `BuildPath::key()`), and following
+/// them loads the page in that locale like any other navigation. If your app isn't using i18n, this will render nothing.
+#[component(LocaleSwitcher<G>)]
+pub fn locale_switcher(locales: Locales) -> SycamoreTemplate<G> {
+    let render_ctx = use_context::<Signal<RenderCtx>>();
+    template! {
+        ({
+            if locales.using_i18n {
+                let render_ctx = render_ctx.get();
+                let links = locales
+                    .get_all()
+                    .into_iter()
+                    .map(|locale| {
+                        // Pages with translated slugs have a different path in each locale
+                        let path = render_ctx
+                            .alternates
+                            .get(locale.as_str())
+                            .unwrap_or(&render_ctx.path);
+                        let href = UrlBuilder::new(path.to_string())
+                            .locale(locale.to_string())
+                            .switching_locale(true)
+                            .build_with(&locales);
+                        let class = if *locale == render_ctx.locale {
+                            CURRENT_LOCALE_CLASS
+                        } else {
+                            ""
+                        };
+                        let locale = locale.to_string();
+                        template! {
+                            li {
+                                a(href = href, hreflang = locale.clone(), class = class) { (locale) }
+                            }
+                        }
+                    })
+                    .collect();
+                template! {
+                    ul(class = "perseus-locale-switcher") {
+                        (SycamoreTemplate::new_fragment(links))
+                    }
+                }
+            } else {
+                SycamoreTemplate::empty()
+            }
+        })
+    }
+}
@@ -1,3 +1,5 @@
+use crate::UrlStrategy;
+
 /// Defines app information about i18n, specifically about which locales are supported.
 #[derive(Clone)]
 pub struct Locales {
@@ -7,6 +9,8 @@ pub struct Locales {
     pub other: Vec<String>,
     /// Whether or not the user is actually using i18n. This is set here because most things that need locale data also need it.
     pub using_i18n: bool,
+    /// How the locale of a page is put into its URL.
+    pub url_strategy: UrlStrategy,
 }
 impl Locales {
     /// Gets all the supported locales by combining the default, and other.
@@ -55,35 +55,198 @@ macro_rules! define_get_translations_manager {
         }
     };
 }
+/// An internal macro used for defining a function to get the user's preferred link checking mode after builds.
+#[macro_export]
+macro_rules! define_get_link_check_mode {
+    () => {
+        pub fn get_link_check_mode() -> $crate::LinkCheckMode {
+            $crate::LinkCheckMode::Off
+        }
+    };
+    ($check_links:expr) => {
+        pub fn get_link_check_mode() -> $crate::LinkCheckMode {
+            $check_links
+        }
+    };
+}
+/// An internal macro used for defining a function to get how the user wants pages that fail to build to be handled.
+#[macro_export]
+macro_rules! define_get_build_error_mode {
+    () => {
+        pub fn get_build_error_mode() -> $crate::BuildErrorMode {
+            $crate::BuildErrorMode::FailFast
+        }
+    };
+    ($build_error_mode:expr) => {
+        pub fn get_build_error_mode() -> $crate::BuildErrorMode {
+            $build_error_mode
+        }
+    };
+}
+/// An internal macro used for defining a function to get how many pages of each template the user wants built at once.
+#[macro_export]
+macro_rules! define_get_build_concurrency {
+    () => {
+        pub fn get_build_concurrency() -> usize {
+            $crate::DFLT_BUILD_CONCURRENCY
+        }
+    };
+    ($build_concurrency:expr) => {
+        pub fn get_build_concurrency() -> usize {
+            $build_concurrency
+        }
+    };
+}
+/// An internal macro used for defining a function to check if the user wants each locale built on a thread of its own.
+#[macro_export]
+macro_rules! define_builds_locales_in_parallel {
+    () => {
+        pub fn builds_locales_in_parallel() -> bool {
+            false
+        }
+    };
+    ($parallel_locales:literal) => {
+        pub fn builds_locales_in_parallel() -> bool {
+            $parallel_locales
+        }
+    };
+}
+/// An internal macro used for defining a function to check if the user wants builds to fail on any translations that fail.
+#[macro_export]
+macro_rules! define_is_i18n_strict {
+    () => {
+        pub fn is_i18n_strict() -> bool {
+            false
+        }
+    };
+    ($i18n_strict:literal) => {
+        pub fn is_i18n_strict() -> bool {
+            $i18n_strict
+        }
+    };
+}
+/// An internal macro used for defining a function to get the header the user wants surrogate keys sent in, if any.
+#[macro_export]
+macro_rules! define_get_surrogate_key_header {
+    () => {
+        pub fn get_surrogate_key_header() -> Option<$crate::SurrogateKeyHeader> {
+            None
+        }
+    };
+    ($surrogate_key_header:expr) => {
+        pub fn get_surrogate_key_header() -> Option<$crate::SurrogateKeyHeader> {
+            Some($surrogate_key_header)
+        }
+    };
+}
+/// An internal macro used for defining a function to get the HTML the user wants shown to users without JavaScript, if any.
+#[macro_export]
+macro_rules! define_get_noscript {
+    () => {
+        pub fn get_noscript() -> Option<String> {
+            None
+        }
+    };
+    ($noscript:expr) => {
+        pub fn get_noscript() -> Option<String> {
+            Some($noscript.to_string())
+        }
+    };
+}
+/// An internal macro used for defining a function to create the resources the user wants shared between their state functions in the
+/// app's resources cell (which `define_app!` defines as `APP_RESOURCES`).
+#[macro_export]
+macro_rules! define_init_resources {
+    () => {
+        pub async fn init_resources() -> std::sync::Arc<$crate::Resources> {
+            APP_RESOURCES
+                .get_or_init(|| async { $crate::Resources::new() })
+                .await
+        }
+    };
+    ($resources:expr) => {
+        pub async fn init_resources() -> std::sync::Arc<$crate::Resources> {
+            APP_RESOURCES.get_or_init($resources).await
+        }
+    };
+}
+/// An internal macro used for defining a function to set the translator backend the user wants for the current process.
+#[macro_export]
+macro_rules! define_init_translator_backend {
+    () => {
+        pub fn init_translator_backend() {
+            $crate::translator::set_translator_locales(get_locales());
+        }
+    };
+    ($translator_backend:ty) => {
+        pub fn init_translator_backend() {
+            $crate::translator::set_translator_locales(get_locales());
+            $crate::translator::set_translator_backend::<$translator_backend>()
+        }
+    };
+}
+/// An internal macro used for defining a function to check if the user wants their HTML audited after builds.
+#[macro_export]
+macro_rules! define_should_audit_html {
+    () => {
+        pub fn should_audit_html() -> bool {
+            false
+        }
+    };
+    ($audit_html:literal) => {
+        pub fn should_audit_html() -> bool {
+            $audit_html
+        }
+    };
+}
+/// An internal macro used for defining a function to get the size budget for the artifacts of the app's pages.
+#[macro_export]
+macro_rules! define_get_size_budget {
+    () => {
+        pub fn get_size_budget() -> $crate::SizeBudget {
+            $crate::SizeBudget::new()
+        }
+    };
+    ($size_budget:expr) => {
+        pub fn get_size_budget() -> $crate::SizeBudget {
+            $size_budget
+        }
+    };
+}
+/// An internal macro used for defining a function to get how users should be redirected to their locale.
+#[macro_export]
+macro_rules! define_get_locale_redirect {
+    () => {
+        pub fn get_locale_redirect<G: $crate::GenericNode>() -> $crate::LocaleRedirect<G> {
+            $crate::LocaleRedirect::new()
+        }
+    };
+    ($locale_redirect:expr) => {
+        pub fn get_locale_redirect<G: $crate::GenericNode>() -> $crate::LocaleRedirect<G> {
+            $locale_redirect
+        }
+    };
+}
 /// An internal macro used for defining locales data. This is abstracted because it needs multiple branches.
 #[macro_export]
 macro_rules! define_get_locales {
     {
         default: $default_locale:literal,
         other: [$($other_locale:literal),*]
+        $(, no_i18n: $no_i18n:literal)?
+        $(, url_strategy: $url_strategy:expr)?
     } => {
         pub fn get_locales() -> $crate::Locales {
+            #[allow(unused_mut)]
+            let mut url_strategy = $crate::UrlStrategy::default();
+            $(url_strategy = $url_strategy;)?
             $crate::Locales {
                 default: $default_locale.to_string(),
                 other: vec![
                     $($other_locale.to_string()),*
                 ],
-                using_i18n: true
-            }
-        }
-    };
-    {
-        default: $default_locale:literal,
-        other: [$($other_locale:literal),*],
-        no_i18n: $no_i18n:literal
-    } => {
-        pub fn get_locales() -> $crate::Locales {
-            $crate::Locales {
-                default: $default_locale.to_string(),
-                other: vec![
-                    $($other_locale.to_string()),*
-                ],
-                using_i18n: !$no_i18n
+                using_i18n: true $(&& !$no_i18n)?,
+                url_strategy,
             }
         }
     };
@@ -96,7 +259,9 @@ macro_rules! define_get_locales {
 /// a custom translations manager, the latter will override.
 ///
 /// Warning: all properties must currently be in the correct order (`root`, `error_pages`, `templates`, `locales`, `config_manager`,
-/// `translations_manager`).
+/// `translations_manager`, `check_links`, `audit_html`, `build_error_mode`, `build_concurrency`, `parallel_locales`, `i18n_strict`,
+/// `surrogate_key_header`,
+/// `resources`, `translator_backend`, `noscript`, `plugins`, `aux_roots`, `redirects`, `size_budget`, `locale_redirect`).
 // TODO make this syntax even more compact and beautiful? (error pages inside templates?)
 #[macro_export]
 macro_rules! define_app {
@@ -112,26 +277,70 @@ macro_rules! define_app {
             // The user doesn't have to define any other locales
             other: [$($other_locale:literal),*]
             $(,no_i18n: $no_i18n:literal)?
+            $(,url_strategy: $url_strategy:expr)?
         }
         $(,config_manager: $config_manager:expr)?
         $(,translations_manager: $translations_manager:expr)?
+        $(,check_links: $check_links:expr)?
+        $(,audit_html: $audit_html:literal)?
+        $(,build_error_mode: $build_error_mode:expr)?
+        $(,build_concurrency: $build_concurrency:expr)?
+        $(,parallel_locales: $parallel_locales:literal)?
+        $(,i18n_strict: $i18n_strict:literal)?
+        $(,surrogate_key_header: $surrogate_key_header:expr)?
+        $(,resources: $resources:expr)?
+        $(,translator_backend: $translator_backend:ty)?
+        $(,noscript: $noscript:expr)?
+        $(,plugins: [$($plugin:expr),*])?
+        $(,aux_roots: [$($aux_root:literal),*])?
+        $(,redirects: [$(($redirect_from:literal, $redirect_to:literal, $redirect_status:literal)),*])?
+        $(,size_budget: $size_budget:expr)?
+        $(,locale_redirect: $locale_redirect:expr)?
     } => {
         /// The CSS selector that will find the app root to render Perseus in.
         pub const APP_ROUTE: &str = $root_selector;
 
+        /// Gets the names of the auxiliary roots the app shell should manage alongside the app root (e.g. for modals).
+        pub fn get_aux_roots() -> Vec<&'static str> {
+            vec![$($($aux_root),*)?]
+        }
+
+        /// Gets the app's static redirects from old paths to new paths or URLs, which are followed by both the server and the router.
+        pub fn get_redirects() -> $crate::Redirects {
+            $crate::Redirects::new(vec![$($(($redirect_from, $redirect_to, $redirect_status)),*)?])
+        }
+
         /// Gets the routes for the app in Perseus' custom abstraction over Sycamore's routing logic. This enables tight coupling of
         /// the templates and the routing system. This can be used on the client or server side.
         pub fn get_routes<G: $crate::GenericNode>() -> $crate::router::Routes<G> {
             $crate::router::Routes::new(
-                vec![
-                    $(
-                        ($router_path.to_string(), $template)
-                    ),+
-                ],
-                get_locales()
+                get_template_registry().get_routes(),
+                get_locales(),
+                get_redirects()
             )
         }
 
+        /// Gets the registry of all the templates in the app, including those contributed by plugins.
+        /// # Panics
+        /// This will `panic!` if any of the templates conflict, or if a plugin fails to register its templates, which are both
+        /// misconfigurations that will be caught as soon as the app is built.
+        pub fn get_template_registry<G: $crate::GenericNode>() -> $crate::TemplateRegistry<G> {
+            let registry = $crate::TemplateRegistry::new(vec![
+                $(
+                    ($router_path.to_string(), $template)
+                ),+
+            ]);
+            $($(
+                let registry = registry.and_then(|registry| registry.apply(&$plugin));
+            )*)?
+            // Every template (including those from plugins) gets the app's resources for its state functions
+            let registry = registry.map(|registry| registry.provide_resources(&APP_RESOURCES));
+            match registry {
+                Ok(registry) => registry,
+                Err(err) => panic!("couldn't register the app's templates: {}", err),
+            }
+        }
+
         /// Gets the config manager to use. This allows the user to conveniently test production managers in development. If nothing is
         /// given, the filesystem will be used.
         $crate::define_get_config_manager!($($config_manager)?);
@@ -140,6 +349,50 @@ macro_rules! define_app {
         /// nothing is given, the filesystem will be used.
         $crate::define_get_translations_manager!(get_locales() $(, $no_i18n)? $(, $translations_manager)?);
 
+        /// Gets what should be done about broken internal links after the app is built. If nothing is given, links won't be checked.
+        $crate::define_get_link_check_mode!($($check_links)?);
+
+        /// Checks if the HTML generated at build-time should be audited for malformed HTML and accessibility problems.
+        $crate::define_should_audit_html!($($audit_html)?);
+
+        /// Gets the most bytes the artifacts generated for the app's pages at build-time should be. If nothing is given, the defaults
+        /// will be used.
+        $crate::define_get_size_budget!($($size_budget)?);
+
+        /// Gets how pages that fail to build should be handled. If nothing is given, the build will stop at the first error.
+        $crate::define_get_build_error_mode!($($build_error_mode)?);
+
+        /// Gets how many pages of each template should be built at once. If nothing is given, a sensible default will be used.
+        $crate::define_get_build_concurrency!($($build_concurrency)?);
+
+        /// Checks if each locale should be built on a thread of its own. If nothing is given, they'll all be built on the same thread.
+        $crate::define_builds_locales_in_parallel!($($parallel_locales)?);
+
+        /// Checks if the build should fail when any translations fail while pages are being prerendered (even if they're handled
+        /// gracefully), rather than leaving them to be found in the browser.
+        $crate::define_is_i18n_strict!($($i18n_strict)?);
+
+        /// Gets the header surrogate keys should be sent in for CDN cache purging. If nothing is given, they won't be sent.
+        $crate::define_get_surrogate_key_header!($($surrogate_key_header)?);
+
+        /// Where the resources shared between the app's state functions are kept once they've been created.
+        static APP_RESOURCES: $crate::resources::ResourcesCell = $crate::resources::ResourcesCell::new();
+        /// Creates the resources to share between the app's state functions, which should be done once by each engine process before
+        /// any of them are run (anything else that calls this while they're being created will wait for them). If nothing is given,
+        /// there won't be any.
+        $crate::define_init_resources!($($resources)?);
+
+        /// Sets the translator backend to use in this process (and gives translators the app's locales so they can localize URLs), which
+        /// should be done before any translators are created. If nothing is given, the default translator will be used.
+        $crate::define_init_translator_backend!($($translator_backend)?);
+
+        /// Gets the HTML to show at the top of every page to users without JavaScript. If nothing is given, nothing extra will be shown.
+        $crate::define_get_noscript!($($noscript)?);
+
+        /// Gets how users who navigate to a page without a locale should be redirected to their locale. If nothing is given, they'll be
+        /// redirected straight away to the locale their browser prefers.
+        $crate::define_get_locale_redirect!($($locale_redirect)?);
+
         /// Defines the locales the app should build for, specifying defaults and common locales (which will be built at build-time
         /// rather than on-demand).
         $crate::define_get_locales! {
@@ -148,20 +401,18 @@ macro_rules! define_app {
                 $($other_locale),*
             ]
             $(, no_i18n: $no_i18n)?
+            $(, url_strategy: $url_strategy)?
         }
 
         /// Gets a map of all the templates in the app by their root paths.
         pub fn get_templates_map<G: $crate::GenericNode>() -> $crate::TemplateMap<G> {
-            $crate::get_templates_map![
-                $($template),+
-            ]
+            get_template_registry().get_templates_map()
         }
 
         /// Gets a list of all the templates in the app in the order the user provided them.
         pub fn get_templates_vec<G: $crate::GenericNode>() -> Vec<$crate::Template<G>> {
-            vec![
-                $($template),+
-            ]
+            // Any nested templates are built in their own right
+            get_template_registry().get_templates_vec()
         }
 
         /// Gets the error pages (done here so the user doesn't have to worry about naming).
@@ -0,0 +1,91 @@
+use crate::errors::*;
+use crate::template::{ReactiveState, StringResult};
+use futures::Future;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Optimistically applies the given mutation to the given reactive page state, and then awaits the given request, which should send the
+/// same mutation to the server (e.g. through an API route). The page will show the mutated state straight away, and if the request fails,
+/// the state will be rolled back to what it was before and the request's error will be returned. Note that any other changes made to the
+/// state while the request is in flight will be lost in a rollback.
+pub async fn mutate<S, M, R>(state: &ReactiveState, mutation: M, request: R) -> Result<()>
+where
+    S: Serialize + DeserializeOwned,
+    M: FnOnce(&mut S),
+    R: Future<Output = StringResult<()>>,
+{
+    let prev_state = state.get().as_ref().clone();
+    let mut typed_state = match &prev_state {
+        Some(prev_state) => serde_json::from_str::<S>(prev_state)?,
+        None => bail!(ErrorKind::StateNotDefined),
+    };
+    mutation(&mut typed_state);
+    state.set(Some(serde_json::to_string(&typed_state)?));
+
+    if let Err(err) = request.await {
+        state.set(prev_state);
+        bail!(ErrorKind::MutationFailed(err));
+    }
+    Ok(())
+}
+
+/// Optimistically pushes the given item to the list in the given reactive page state that the given function points to. See [`mutate`]
+/// for details.
+pub async fn push<S, T, L, R>(state: &ReactiveState, list: L, item: T, request: R) -> Result<()>
+where
+    S: Serialize + DeserializeOwned,
+    L: FnOnce(&mut S) -> &mut Vec<T>,
+    R: Future<Output = StringResult<()>>,
+{
+    mutate(state, |state: &mut S| list(state).push(item), request).await
+}
+
+/// Optimistically removes every item matching the given predicate from the list in the given reactive page state that the given function
+/// points to. See [`mutate`] for details.
+pub async fn remove_where<S, T, L, P, R>(
+    state: &ReactiveState,
+    list: L,
+    predicate: P,
+    request: R,
+) -> Result<()>
+where
+    S: Serialize + DeserializeOwned,
+    L: FnOnce(&mut S) -> &mut Vec<T>,
+    P: Fn(&T) -> bool,
+    R: Future<Output = StringResult<()>>,
+{
+    mutate(
+        state,
+        |state: &mut S| list(state).retain(|item| !predicate(item)),
+        request,
+    )
+    .await
+}
+
+/// Optimistically applies the given update to every item matching the given predicate in the list in the given reactive page state that
+/// the given function points to. See [`mutate`] for details.
+pub async fn update_where<S, T, L, P, U, R>(
+    state: &ReactiveState,
+    list: L,
+    predicate: P,
+    update: U,
+    request: R,
+) -> Result<()>
+where
+    S: Serialize + DeserializeOwned,
+    L: FnOnce(&mut S) -> &mut Vec<T>,
+    P: Fn(&T) -> bool,
+    U: Fn(&mut T),
+    R: Future<Output = StringResult<()>>,
+{
+    mutate(
+        state,
+        |state: &mut S| {
+            list(state)
+                .iter_mut()
+                .filter(|item| predicate(item))
+                .for_each(update)
+        },
+        request,
+    )
+    .await
+}
@@ -0,0 +1,122 @@
+// This keeps track of which paths are the same page in different locales, for pages whose slugs are translated (so that the locale
+// switcher and `hreflang` links can go to the right path rather than a 404)
+
+use crate::build::get_page_path;
+use crate::errors::*;
+use crate::template::Template;
+use crate::ConfigManager;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use sycamore::prelude::SsrNode;
+
+/// The path equivalents of the app, which are loaded once by each process that renders pages.
+static PATH_EQUIVALENTS: OnceCell<PathEquivalents> = OnceCell::new();
+
+/// The paths of pages that are the same in different locales, as set by giving build paths the same key with `BuildPath::key()`. This
+/// is generated at build-time, and written to `path_equivalents.json` alongside the render configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathEquivalents {
+    /// The groups of equivalent paths of each template, by template path, each of which is a map of locales to the full paths (without
+    /// the locale) of the page in them. These are ordered so that `path_equivalents.json` is the same for the same build.
+    templates: BTreeMap<String, Vec<BTreeMap<String, String>>>,
+}
+impl PathEquivalents {
+    /// Works out the path equivalents of the given templates from their build paths. Templates that generate their paths along with their
+    /// states don't give keys to them, so they won't have any.
+    pub async fn from_templates(templates: &[&Template<SsrNode>]) -> Result<Self> {
+        let mut path_equivalents = Self::default();
+        for template in templates {
+            if !template.uses_build_paths() || template.uses_build_paths_with_state() {
+                continue;
+            }
+            // These are ordered by key so that builds are deterministic
+            let mut groups: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+            for path in template.get_build_paths().await? {
+                // Paths without keys or locales are the same in every locale anyway
+                if let (Some(key), Some(locales)) = (path.key, path.locales) {
+                    let group = groups.entry(key).or_default();
+                    let page_path = get_page_path(template, &path.path);
+                    for locale in locales {
+                        group.insert(locale, page_path.clone());
+                    }
+                }
+            }
+            if !groups.is_empty() {
+                path_equivalents.templates.insert(
+                    template.get_path(),
+                    groups.into_iter().map(|(_, group)| group).collect(),
+                );
+            }
+        }
+
+        Ok(path_equivalents)
+    }
+    /// Gets the full paths (without locales) of the page at the given path in the given locale in every locale it's been given a path in,
+    /// if it's been given a key. This will include the given path itself.
+    pub fn get_alternates(&self, path: &str, locale: &str) -> BTreeMap<String, String> {
+        self.templates
+            .values()
+            .flatten()
+            .find(|group| group.get(locale).map(|group_path| group_path.as_str()) == Some(path))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Gets the path equivalents generated at build-time.
+pub async fn get_path_equivalents(config_manager: &impl ConfigManager) -> Result<PathEquivalents> {
+    let content = config_manager.read("path_equivalents.json").await?;
+    let path_equivalents = serde_json::from_str::<PathEquivalents>(&content)?;
+
+    Ok(path_equivalents)
+}
+
+/// Writes the given path equivalents for the given templates, keeping those of any other templates from the last build (if there was
+/// one), and makes them the ones this process renders pages with.
+pub(crate) async fn write_path_equivalents(
+    templates: &[&Template<SsrNode>],
+    built: PathEquivalents,
+    config_manager: &impl ConfigManager,
+) -> Result<()> {
+    let mut path_equivalents = get_path_equivalents(config_manager)
+        .await
+        .unwrap_or_default();
+    for template in templates {
+        path_equivalents.templates.remove(&template.get_path());
+    }
+    path_equivalents
+        .templates
+        .extend(built.templates.into_iter());
+
+    config_manager
+        .write(
+            "path_equivalents.json",
+            &serde_json::to_string(&path_equivalents)?,
+        )
+        .await?;
+    let _ = PATH_EQUIVALENTS.set(path_equivalents);
+
+    Ok(())
+}
+
+/// Loads the path equivalents generated at build-time for this process, if they haven't been loaded (or built) already. Apps built
+/// before these were generated won't have any.
+pub(crate) async fn load_path_equivalents(config_manager: &impl ConfigManager) {
+    if PATH_EQUIVALENTS.get().is_none() {
+        let path_equivalents = get_path_equivalents(config_manager)
+            .await
+            .unwrap_or_default();
+        let _ = PATH_EQUIVALENTS.set(path_equivalents);
+    }
+}
+
+/// Gets the full paths (without locales) of the page at the given path in the given locale in every locale it's been given a path in,
+/// from the path equivalents loaded for this process. This will be empty if the page isn't linked to any others, or if the path
+/// equivalents haven't been loaded.
+pub(crate) fn get_alternates(path: &str, locale: &str) -> BTreeMap<String, String> {
+    PATH_EQUIVALENTS
+        .get()
+        .map(|path_equivalents| path_equivalents.get_alternates(path, locale))
+        .unwrap_or_default()
+}
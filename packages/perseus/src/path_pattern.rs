@@ -0,0 +1,177 @@
+// Template paths can contain dynamic segments, which are matched here against the paths of actual pages. The syntax is the same as that
+// used for routing paths, plus a bare `*` as a shorthand catch-all.
+/* Segment variants:
+    - <stuff..>     captures one or more segments (must be last)
+    - *             captures zero or more segments (must be last)
+    - <stuff>       captures exactly one segment
+    - stuff         verbatim stuff
+*/
+
+use std::cmp::Ordering;
+
+/// Checks if the given segment of a template path is dynamic (i.e. it will match more than one verbatim value).
+pub fn is_dynamic_segment(segment: &str) -> bool {
+    segment == "*" || (segment.starts_with('<') && segment.ends_with('>'))
+}
+
+/// Checks if the given template path contains any dynamic segments.
+pub fn is_path_pattern(pattern: &str) -> bool {
+    pattern.split('/').any(is_dynamic_segment)
+}
+
+/// Gets the root of the given template path, which is everything before the first dynamic segment. Any paths a template generates at
+/// build-time are relative to this. For a template path without any dynamic segments, this is the path itself.
+pub fn get_pattern_root(pattern: &str) -> String {
+    pattern
+        .split('/')
+        .take_while(|segment| !is_dynamic_segment(segment))
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+/// Gets how specific the given template path is, which is used to pick between multiple patterns that match the same path. Patterns with
+/// more verbatim segments are more specific, then those with more verbatim segments before their first dynamic one, and then those with
+/// more segments overall. Patterns that are still tied should be picked between with [`cmp_pattern_specificity`].
+pub fn get_pattern_specificity(pattern: &str) -> (usize, usize, usize) {
+    let segments: Vec<&str> = pattern.split('/').collect();
+    let verbatim = segments
+        .iter()
+        .filter(|segment| !is_dynamic_segment(segment))
+        .count();
+    let verbatim_prefix = segments
+        .iter()
+        .take_while(|segment| !is_dynamic_segment(segment))
+        .count();
+
+    (verbatim, verbatim_prefix, segments.len())
+}
+
+/// Compares how specific the given template paths are, with the more specific one being greater. Patterns that are equally specific are
+/// ordered lexically (with the first being greater), so that the same pattern is always picked for a path, whatever order the patterns
+/// are in.
+pub fn cmp_pattern_specificity(a: &str, b: &str) -> Ordering {
+    get_pattern_specificity(a)
+        .cmp(&get_pattern_specificity(b))
+        .then_with(|| b.cmp(a))
+}
+
+/// Checks if the given path matches the given template path. Neither should have leading or trailing `/`s.
+pub fn matches_path_pattern(pattern: &str, path: &str) -> bool {
+    let pattern_segments: Vec<&str> = pattern.split('/').collect();
+    let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    for (idx, pattern_segment) in pattern_segments.iter().enumerate() {
+        // Catch-alls take everything left, so we can return early
+        if *pattern_segment == "*" {
+            return true;
+        } else if pattern_segment.starts_with('<') && pattern_segment.ends_with("..>") {
+            return path_segments.len() > idx;
+        }
+
+        match path_segments.get(idx) {
+            // A single dynamic segment will match anything, as long as it's there
+            Some(_) if is_dynamic_segment(pattern_segment) => continue,
+            Some(path_segment) if path_segment == pattern_segment => continue,
+            _ => return false,
+        }
+    }
+    // If there are more segments in the path than in the pattern, it doesn't match
+    path_segments.len() == pattern_segments.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_literals() {
+        assert!(matches_path_pattern("about", "about"));
+        assert!(matches_path_pattern("blog/posts", "blog/posts"));
+        assert!(!matches_path_pattern("about", "about/team"));
+        assert!(!matches_path_pattern("blog/posts", "blog"));
+        assert!(!matches_path_pattern("blog/posts", "blog/drafts"));
+    }
+
+    #[test]
+    fn matches_params() {
+        assert!(matches_path_pattern(
+            "blog/<year>/<slug>",
+            "blog/2021/hello"
+        ));
+        assert!(matches_path_pattern("<lang>/docs", "en/docs"));
+        assert!(!matches_path_pattern("blog/<year>/<slug>", "blog/2021"));
+        assert!(!matches_path_pattern(
+            "blog/<year>/<slug>",
+            "blog/2021/hello/again"
+        ));
+        assert!(!matches_path_pattern("<lang>/docs", "en/api"));
+    }
+
+    #[test]
+    fn matches_catch_alls() {
+        // Bare catch-alls match nothing after them too, but named ones need at least one segment
+        assert!(matches_path_pattern("post/*", "post"));
+        assert!(matches_path_pattern("post/*", "post/a/b/c"));
+        assert!(!matches_path_pattern("post/<rest..>", "post"));
+        assert!(matches_path_pattern("post/<rest..>", "post/a"));
+        assert!(matches_path_pattern("post/<rest..>", "post/a/b/c"));
+        assert!(!matches_path_pattern("post/*", "posts/a"));
+    }
+
+    #[test]
+    fn gets_pattern_roots() {
+        assert_eq!(get_pattern_root("blog/<year>/<slug>"), "blog");
+        assert_eq!(get_pattern_root("post/*"), "post");
+        assert_eq!(get_pattern_root("<lang>/docs"), "");
+        assert_eq!(get_pattern_root("about"), "about");
+        assert!(!is_path_pattern("about"));
+        assert!(is_path_pattern("docs/<rest..>"));
+    }
+
+    /// Gets the most specific of the given patterns that matches the given path.
+    fn most_specific<'a>(patterns: &[&'a str], path: &str) -> Option<&'a str> {
+        patterns
+            .iter()
+            .copied()
+            .filter(|pattern| matches_path_pattern(pattern, path))
+            .max_by(|a, b| cmp_pattern_specificity(a, b))
+    }
+
+    #[test]
+    fn prefers_more_specific_patterns() {
+        let patterns = [
+            "blog/*",
+            "blog/<year>/<slug>",
+            "blog/2021/<slug>",
+            "<section>/2021/hello",
+        ];
+        assert_eq!(
+            most_specific(&patterns, "blog/2021/hello"),
+            Some("blog/2021/<slug>")
+        );
+        assert_eq!(
+            most_specific(&patterns, "blog/2020/hello"),
+            Some("blog/<year>/<slug>")
+        );
+        assert_eq!(
+            most_specific(&patterns, "blog/2020/hello/again"),
+            Some("blog/*")
+        );
+        assert_eq!(
+            most_specific(&patterns, "news/2021/hello"),
+            Some("<section>/2021/hello")
+        );
+    }
+
+    #[test]
+    fn breaks_ties_by_literal_prefix_then_lexically() {
+        // Both have two verbatim segments, but one has both before its parameter
+        let patterns = ["a/<x>/c", "a/b/<y>"];
+        assert_eq!(most_specific(&patterns, "a/b/c"), Some("a/b/<y>"));
+        // These are equally specific in every way, so the lexically first always wins, whatever order they're in
+        let patterns = ["docs/<slug>", "docs/<page>"];
+        assert_eq!(most_specific(&patterns, "docs/intro"), Some("docs/<page>"));
+        let patterns = ["docs/<page>", "docs/<slug>"];
+        assert_eq!(most_specific(&patterns, "docs/intro"), Some("docs/<page>"));
+    }
+}
@@ -0,0 +1,199 @@
+// This lets plugins contribute their own templates to an app (e.g. a sitemap page or an admin UI), or change the app's own, before the
+// templates are used anywhere
+
+use crate::errors::*;
+use crate::resources::ResourcesCell;
+use crate::template::{Template, TemplateMap};
+use sycamore::prelude::GenericNode;
+
+/// The name given to the app itself as the owner of the templates it registers directly, for error messages.
+const APP_OWNER: &str = "the app";
+
+/// A plugin that can add, modify, or remove templates before the app's templates are used by the build process, the server, or the
+/// router. Plugins are applied in the order they're given in `define_app!`, after the app's own templates have been registered.
+pub trait Plugin {
+    /// Gets the name of the plugin, which is used to report conflicts.
+    fn name(&self) -> String;
+    /// Makes whatever changes the plugin wants to the given registry of templates. This will be called once for templates on the server
+    /// and once for templates on the client, so it should do the same thing each time. The default implementation does nothing.
+    fn register_templates<G: GenericNode>(
+        &self,
+        _registry: &mut TemplateRegistry<G>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Validates the rendering strategies of the given template and any templates nested under it.
+fn validate_all<G: GenericNode>(template: &Template<G>) -> Result<()> {
+    for template in template.flatten_nested() {
+        template.validate()?;
+    }
+
+    Ok(())
+}
+
+/// A template in a [`TemplateRegistry`], along with where it's routed to and what registered it.
+struct RegisteredTemplate<G: GenericNode> {
+    /// The routing path of the template (like `/about`), which doesn't have to be the same as the template's path.
+    router_path: String,
+    template: Template<G>,
+    /// The plugin that registered the template, or `APP_OWNER` if the app itself did.
+    owner: String,
+}
+
+/// All the templates in an app, which plugins can add to, modify, or remove from. Any changes that would leave two templates with the same
+/// path (including the paths of any nested templates), or routed to from the same path, will be rejected with a `TemplateConflict` error,
+/// and any templates using rendering strategies that can't be used together will be rejected with an `IncompatibleStrategies` error.
+pub struct TemplateRegistry<G: GenericNode> {
+    templates: Vec<RegisteredTemplate<G>>,
+    /// The plugin currently making changes, or `APP_OWNER` if there isn't one.
+    owner: String,
+}
+impl<G: GenericNode> TemplateRegistry<G> {
+    /// Creates a new registry from the app's own templates, given as pairs of routing paths and templates (just like in `define_app!`).
+    pub fn new(routes: Vec<(String, Template<G>)>) -> Result<Self> {
+        let mut registry = Self {
+            templates: Vec::new(),
+            owner: APP_OWNER.to_string(),
+        };
+        for (router_path, template) in routes {
+            registry.add(router_path, template)?;
+        }
+
+        Ok(registry)
+    }
+    /// Lets the given plugin make its changes to the registry.
+    pub fn apply(mut self, plugin: &impl Plugin) -> Result<Self> {
+        self.owner = plugin.name();
+        plugin.register_templates(&mut self)?;
+        self.owner = APP_OWNER.to_string();
+
+        Ok(self)
+    }
+    /// Gives every template in the registry (and any templates nested under them) the given resources for their state functions.
+    pub fn provide_resources(mut self, resources: &'static ResourcesCell) -> Self {
+        self.templates = self
+            .templates
+            .into_iter()
+            .map(|registered| RegisteredTemplate {
+                template: registered.template.provide_resources(resources),
+                ..registered
+            })
+            .collect();
+
+        self
+    }
+    /// Adds the given template, routed to from the given path (like `/sitemap`).
+    pub fn add(&mut self, router_path: impl Into<String>, template: Template<G>) -> Result<()> {
+        let router_path = router_path.into();
+        validate_all(&template)?;
+        self.check_conflicts(&router_path, &template, None)?;
+        self.templates.push(RegisteredTemplate {
+            router_path,
+            template,
+            owner: self.owner.clone(),
+        });
+
+        Ok(())
+    }
+    /// Modifies the template with the given path using the given function, which is given the template and should return it with any
+    /// changes made using the usual builder methods (including changing its path).
+    pub fn modify(&mut self, path: &str, f: impl FnOnce(Template<G>) -> Template<G>) -> Result<()> {
+        let idx = self.get_idx(path)?;
+        let template = f(self.templates[idx].template.clone());
+        let router_path = self.templates[idx].router_path.clone();
+        validate_all(&template)?;
+        self.check_conflicts(&router_path, &template, Some(idx))?;
+        self.templates[idx].template = template;
+
+        Ok(())
+    }
+    /// Removes the template with the given path, returning it.
+    pub fn remove(&mut self, path: &str) -> Result<Template<G>> {
+        let idx = self.get_idx(path)?;
+        Ok(self.templates.remove(idx).template)
+    }
+    /// Gets the template with the given path, if it's been registered.
+    pub fn get(&self, path: &str) -> Option<&Template<G>> {
+        self.templates
+            .iter()
+            .find(|registered| registered.template.get_path() == path)
+            .map(|registered| &registered.template)
+    }
+
+    /// Gets the index of the template with the given path.
+    fn get_idx(&self, path: &str) -> Result<usize> {
+        match self
+            .templates
+            .iter()
+            .position(|registered| registered.template.get_path() == path)
+        {
+            Some(idx) => Ok(idx),
+            None => bail!(ErrorKind::TemplateNotRegistered(
+                path.to_string(),
+                self.owner.clone()
+            )),
+        }
+    }
+    /// Checks if registering the given template at the given routing path would conflict with any template already registered, ignoring
+    /// the one at the given index (if it's being replaced).
+    fn check_conflicts(
+        &self,
+        router_path: &str,
+        template: &Template<G>,
+        ignore: Option<usize>,
+    ) -> Result<()> {
+        let paths: Vec<String> = template
+            .flatten_nested()
+            .iter()
+            .map(|template| template.get_path())
+            .collect();
+        for (idx, registered) in self.templates.iter().enumerate() {
+            if Some(idx) == ignore {
+                continue;
+            }
+            if registered.router_path.trim_matches('/') == router_path.trim_matches('/') {
+                bail!(ErrorKind::TemplateConflict(
+                    router_path.to_string(),
+                    registered.owner.clone(),
+                    self.owner.clone()
+                ))
+            }
+            for registered_template in registered.template.flatten_nested() {
+                let registered_path = registered_template.get_path();
+                if paths.contains(&registered_path) {
+                    bail!(ErrorKind::TemplateConflict(
+                        registered_path,
+                        registered.owner.clone(),
+                        self.owner.clone()
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets the registered templates as pairs of routing paths and templates, for creating the app's routes.
+    pub fn get_routes(&self) -> Vec<(String, Template<G>)> {
+        self.templates
+            .iter()
+            .map(|registered| (registered.router_path.clone(), registered.template.clone()))
+            .collect()
+    }
+    /// Gets a list of all the registered templates in the order they were registered, including any nested templates.
+    pub fn get_templates_vec(&self) -> Vec<Template<G>> {
+        self.templates
+            .iter()
+            .flat_map(|registered| registered.template.flatten_nested())
+            .collect()
+    }
+    /// Gets a map of all the registered templates (including any nested templates) by their paths.
+    pub fn get_templates_map(&self) -> TemplateMap<G> {
+        self.get_templates_vec()
+            .into_iter()
+            .map(|template| (template.get_path(), template))
+            .collect()
+    }
+}
@@ -0,0 +1,169 @@
+/// The part of a file that a request asked for with its `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The whole file should be sent (the request didn't ask for a range, or asked for one we don't support, like several at once).
+    Full,
+    /// The bytes from `start` to `end` (inclusive) should be sent with a `206 Partial Content`.
+    Partial {
+        /// The first byte to send.
+        start: u64,
+        /// The last byte to send.
+        end: u64,
+    },
+    /// The range can't be satisfied (e.g. it starts after the end of the file), and a `416 Range Not Satisfiable` should be sent.
+    Unsatisfiable,
+}
+impl ByteRange {
+    /// Parses the given `Range` header (if the request had one) for a file of the given length in bytes. Only single ranges of bytes are
+    /// supported (like `bytes=0-499`, `bytes=500-`, or `bytes=-500`), and anything else is ignored (as HTTP allows), so the whole file
+    /// will be sent.
+    pub fn from_header(header: Option<&str>, len: u64) -> Self {
+        let spec = match header.and_then(|header| header.trim().strip_prefix("bytes=")) {
+            // Servers can always ignore requests for several ranges
+            Some(spec) if !spec.contains(',') => spec.trim(),
+            _ => return Self::Full,
+        };
+        let (start, end) = match spec.split_once('-') {
+            Some(parts) => parts,
+            None => return Self::Full,
+        };
+        let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+            // A suffix range, which asks for the last however many bytes
+            (Err(_), Ok(suffix_len)) if start.is_empty() => {
+                if suffix_len == 0 || len == 0 {
+                    return Self::Unsatisfiable;
+                }
+                (len.saturating_sub(suffix_len), len - 1)
+            }
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+            _ => return Self::Full,
+        };
+        if start >= len {
+            return Self::Unsatisfiable;
+        }
+
+        Self::Partial { start, end }
+    }
+    /// Gets the value of the `Content-Range` header that should be sent with this range of a file of the given length, if one should be.
+    pub fn content_range(&self, len: u64) -> Option<String> {
+        match self {
+            Self::Full => None,
+            Self::Partial { start, end } => Some(format!("bytes {}-{}/{}", start, end, len)),
+            Self::Unsatisfiable => Some(format!("bytes */{}", len)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(header: &str) -> ByteRange {
+        ByteRange::from_header(Some(header), 1000)
+    }
+
+    #[test]
+    fn no_header_is_full() {
+        assert_eq!(ByteRange::from_header(None, 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn parses_bounded_ranges() {
+        assert_eq!(
+            parse("bytes=0-499"),
+            ByteRange::Partial { start: 0, end: 499 }
+        );
+        assert_eq!(
+            parse("  bytes= 10-10 "),
+            ByteRange::Partial { start: 10, end: 10 }
+        );
+        // The end is clamped to the end of the file
+        assert_eq!(
+            parse("bytes=900-2000"),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(
+            parse("bytes=500-"),
+            ByteRange::Partial {
+                start: 500,
+                end: 999
+            }
+        );
+        assert_eq!(parse("bytes=0-"), ByteRange::Partial { start: 0, end: 999 });
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(
+            parse("bytes=-500"),
+            ByteRange::Partial {
+                start: 500,
+                end: 999
+            }
+        );
+        // Asking for more than the whole file gives the whole file
+        assert_eq!(
+            parse("bytes=-2000"),
+            ByteRange::Partial { start: 0, end: 999 }
+        );
+        assert_eq!(parse("bytes=-0"), ByteRange::Unsatisfiable);
+        assert_eq!(
+            ByteRange::from_header(Some("bytes=-10"), 0),
+            ByteRange::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn ignores_multiple_ranges() {
+        assert_eq!(parse("bytes=0-1,5-6"), ByteRange::Full);
+        assert_eq!(parse("bytes=0-1, -5"), ByteRange::Full);
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_ranges() {
+        assert_eq!(parse("bytes=1000-"), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=1000-1500"), ByteRange::Unsatisfiable);
+        assert_eq!(
+            ByteRange::from_header(Some("bytes=0-"), 0),
+            ByteRange::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_headers() {
+        for header in [
+            "",
+            "items=0-1",
+            "bytes=",
+            "bytes=-",
+            "bytes=abc",
+            "bytes=a-b",
+            "bytes=5-1",
+            "bytes=1-x",
+            "bytes=--5",
+        ] {
+            assert_eq!(parse(header), ByteRange::Full, "{}", header);
+        }
+    }
+
+    #[test]
+    fn gets_content_ranges() {
+        assert_eq!(ByteRange::Full.content_range(1000), None);
+        assert_eq!(
+            ByteRange::Partial { start: 0, end: 499 }.content_range(1000),
+            Some("bytes 0-499/1000".to_string())
+        );
+        assert_eq!(
+            ByteRange::Unsatisfiable.content_range(1000),
+            Some("bytes */1000".to_string())
+        );
+    }
+}
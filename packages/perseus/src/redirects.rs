@@ -0,0 +1,99 @@
+use crate::template::Redirect;
+use crate::Locales;
+use std::collections::HashMap;
+
+/// A static table of redirects from old paths to new paths or URLs, declared with `redirects` in `define_app!`. These are followed by
+/// the server (with the given status code) and by the app shell's router, so they work whether the user loads an old path directly or
+/// follows a link to it within the app.
+///
+/// Paths are matched exactly (ignoring leading and trailing slashes). If the app uses i18n, a path can be given without a locale, in
+/// which case it'll be matched with or without any locale in front of it, and a path the user is redirected to within the app (like
+/// `/blog`) will be kept in their locale.
+#[derive(Debug, Clone, Default)]
+pub struct Redirects {
+    /// The redirects by the paths they're from (without leading or trailing slashes).
+    redirects: HashMap<String, Redirect>,
+}
+impl Redirects {
+    /// Creates a new table of redirects from the given list of paths to redirect from, paths or URLs to redirect to, and HTTP status
+    /// codes (e.g. `301` for redirects that should be cached forever).
+    pub fn new(redirects: Vec<(&str, &str, u16)>) -> Self {
+        Self {
+            redirects: redirects
+                .into_iter()
+                .map(|(from, to, status)| {
+                    (
+                        from.trim_matches('/').to_string(),
+                        Redirect::new(to, status),
+                    )
+                })
+                .collect(),
+        }
+    }
+    /// Checks if there are no redirects.
+    pub fn is_empty(&self) -> bool {
+        self.redirects.is_empty()
+    }
+    /// Gets where the user should be redirected to from the given path (with its locale if the app uses i18n), if anywhere.
+    pub fn get(&self, path: &str, locales: &Locales) -> Option<Redirect> {
+        let path = path.trim_matches('/');
+        if let Some(redirect) = self.redirects.get(path) {
+            return Some(redirect.clone());
+        }
+        if !locales.using_i18n {
+            return None;
+        }
+        // Paths without a locale match in any locale, staying in it if they go somewhere else in the app
+        let (locale, path) = path.split_once('/').unwrap_or((path, ""));
+        if !locales.is_supported(locale) {
+            return None;
+        }
+        let redirect = self.redirects.get(path)?;
+        let to = match redirect.to.starts_with('/') && !redirect.to.starts_with("//") {
+            true => locales
+                .url_strategy
+                .localize_url(locale, &redirect.to, locales),
+            false => redirect.to.clone(),
+        };
+
+        Some(Redirect::new(to, redirect.status))
+    }
+    /// Gets where the user should be redirected to from the page at the given path in the given locale (as they're given separately when
+    /// page data is requested), if anywhere.
+    pub fn get_for_page(&self, path: &str, locale: &str, locales: &Locales) -> Option<Redirect> {
+        match locales.using_i18n {
+            true => self.get(
+                &format!("{}/{}", locale, path.trim_start_matches('/')),
+                locales,
+            ),
+            false => self.get(path, locales),
+        }
+    }
+    /// Formats the redirects as a `_redirects` file, which is understood by many static hosting platforms (like Netlify and Cloudflare
+    /// Pages), with a line for each redirect like `/old-path /new-path 301`. If the app uses i18n, paths without a locale will get a line
+    /// for each locale too.
+    pub fn to_redirects_file(&self, locales: &Locales) -> String {
+        let mut from_paths: Vec<&String> = self.redirects.keys().collect();
+        from_paths.sort();
+        let mut lines = Vec::new();
+        for from in from_paths {
+            let redirect = &self.redirects[from];
+            lines.push(format!("/{} {} {}", from, redirect.to, redirect.status));
+            if locales.using_i18n {
+                for locale in locales.get_all() {
+                    let localized_from = format!("{}/{}", locale, from);
+                    if let Some(redirect) = self.get(&localized_from, locales) {
+                        lines.push(format!(
+                            "/{} {} {}",
+                            localized_from.trim_end_matches('/'),
+                            redirect.to,
+                            redirect.status
+                        ));
+                    }
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,54 @@
+// Panics in user code (like state functions or templates) are caught around each page render, so that one broken page can't take down
+// the whole build or server
+
+use crate::errors::*;
+use futures::FutureExt;
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+
+/// Gets the message from a caught panic, which will be a string unless the panic was raised with a custom payload.
+fn get_panic_msg(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(msg) => msg.to_string(),
+            Err(_) => "panicked with a non-string payload".to_string(),
+        },
+    }
+}
+
+/// Runs the given future, which renders the page at the given path with the given template, converting any panic into a
+/// `RenderPanicked` error attributed to that page.
+pub async fn catch_render_panic<T>(
+    template: &str,
+    path: &str,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    // Nothing that the future borrows is used again if it panics, other than to report the error
+    match AssertUnwindSafe(fut).catch_unwind().await {
+        Ok(res) => res,
+        Err(payload) => bail!(ErrorKind::RenderPanicked(
+            template.to_string(),
+            path.to_string(),
+            get_panic_msg(payload)
+        )),
+    }
+}
+
+/// Runs the given function, which renders the page at the given path with the given template, converting any panic into a
+/// `RenderPanicked` error attributed to that page. This is [`catch_render_panic`] for renders that aren't asynchronous.
+pub fn catch_render_panic_sync<T>(
+    template: &str,
+    path: &str,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => res,
+        Err(payload) => bail!(ErrorKind::RenderPanicked(
+            template.to_string(),
+            path.to_string(),
+            get_panic_msg(payload)
+        )),
+    }
+}
@@ -0,0 +1,214 @@
+// This lets apps create resources like database pools once per engine process (the build process or the server), which their state
+// functions can then use, rather than creating them again for every page
+
+use futures::Future;
+use once_cell::sync::OnceCell;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+thread_local! {
+    /// The resources of the template whose state function is being run on this thread right now, if there is one.
+    static CURRENT_RESOURCES: RefCell<Option<Arc<Resources>>> = RefCell::new(None);
+}
+
+/// A set of shared resources (like database pools), which can hold one value of each type. These are created by the initializer given
+/// as `resources` in `define_app!`, and can be accessed with [`get_resource`] from build paths, build state, and request state functions.
+/// Because the server handles requests on many threads, resources must be `Send` and `Sync`.
+#[derive(Default)]
+pub struct Resources {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+impl Resources {
+    /// Creates a new, empty set of resources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds the given resource, replacing any existing resource of the same type.
+    pub fn insert<T: Any + Send + Sync>(mut self, val: T) -> Self {
+        self.map.insert(TypeId::of::<T>(), Arc::new(val));
+        self
+    }
+    /// Gets the resource of the given type, if there is one.
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|val| val.downcast_ref::<T>())
+    }
+    /// Gets a handle to the resource of the given type that can outlive these resources, if there is one.
+    fn get_shared<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .and_then(|val| val.downcast::<T>().ok())
+    }
+}
+
+/// Where an app's [`Resources`] are kept once they've been created, which `define_app!` makes one of for each app. Templates are given
+/// the cell of the app they're registered in, and make its resources available to their state functions while they're running, so
+/// different apps (or instances of the same app in tests) in one process never see each other's resources.
+#[derive(Default)]
+pub struct ResourcesCell {
+    resources: OnceCell<Arc<Resources>>,
+    /// Whether or not the initializer has been started, which it can only be once.
+    started: AtomicBool,
+    /// The tasks waiting for the initializer to finish.
+    waiting: OnceCell<Mutex<Vec<Waker>>>,
+}
+impl ResourcesCell {
+    /// Creates a new cell without any resources in it.
+    pub const fn new() -> Self {
+        Self {
+            resources: OnceCell::new(),
+            started: AtomicBool::new(false),
+            waiting: OnceCell::new(),
+        }
+    }
+    /// Gets the resources in this cell, if they've been created.
+    pub fn get(&self) -> Option<Arc<Resources>> {
+        self.resources.get().cloned()
+    }
+    /// Gets the resources in this cell, creating them with the given initializer if that hasn't been done yet. The initializer will only
+    /// ever be called once, so expensive resources (like database pools) aren't created and then thrown away, and anything that calls this
+    /// while it's running will wait for it to finish. This is what `init_resources()` from `define_app!` does.
+    pub async fn get_or_init<F, Fut>(&self, init: F) -> Arc<Resources>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Resources>,
+    {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return WaitForResources(self).await;
+        }
+        let resources = Arc::new(init().await);
+        let _ = self.resources.set(Arc::clone(&resources));
+        // Anything waiting will find the resources now that they're set
+        if let Ok(mut waiting) = self.get_waiting().lock() {
+            for waker in waiting.drain(..) {
+                waker.wake();
+            }
+        }
+
+        resources
+    }
+    fn get_waiting(&self) -> &Mutex<Vec<Waker>> {
+        self.waiting.get_or_init(Mutex::default)
+    }
+}
+
+/// A future that finishes once the initializer of the given cell has set its resources.
+struct WaitForResources<'a>(&'a ResourcesCell);
+impl Future for WaitForResources<'_> {
+    type Output = Arc<Resources>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(resources) = self.0.get() {
+            return Poll::Ready(resources);
+        }
+        // The initializer sets the resources before it wakes everything waiting, so we check again once we're in the list, otherwise we
+        // could miss it
+        let mut waiting = match self.0.get_waiting().lock() {
+            Ok(waiting) => waiting,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match self.0.get() {
+            Some(resources) => Poll::Ready(resources),
+            None => {
+                waiting.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Restores the resources available on this thread to what they were when it was created once it's dropped (even if a state function
+/// panics).
+struct RestoreResources(Option<Arc<Resources>>);
+impl Drop for RestoreResources {
+    fn drop(&mut self) {
+        let prev = self.0.take();
+        CURRENT_RESOURCES.with(|current| *current.borrow_mut() = prev);
+    }
+}
+
+/// A future that makes the given resources available to [`get_resource`] whenever it's being polled.
+pub(crate) struct WithResources<F> {
+    resources: Option<Arc<Resources>>,
+    fut: F,
+}
+impl<F: Future + Unpin> Future for WithResources<F> {
+    type Output = F::Output;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let prev = CURRENT_RESOURCES.with(|current| current.replace(self.resources.clone()));
+        let _restore = RestoreResources(prev);
+        Pin::new(&mut self.fut).poll(cx)
+    }
+}
+
+/// Runs the given future (a state function) with the resources in the given cell available to [`get_resource`].
+pub(crate) fn with_resources<F: Future + Unpin>(
+    cell: Option<&ResourcesCell>,
+    fut: F,
+) -> WithResources<F> {
+    WithResources {
+        resources: cell.and_then(|cell| cell.get()),
+        fut,
+    }
+}
+
+/// Gets the shared resource of the given type, if there is one. This can be used from build paths, build state, and request state
+/// functions (e.g. `get_resource::<PgPool>()`), which are given the resources of the app their template is registered in while they're
+/// running (but not from anything they spawn onto another task, so get the resource before doing that). There aren't any resources on the
+/// client-side.
+pub fn get_resource<T: Any + Send + Sync>() -> Option<Arc<T>> {
+    CURRENT_RESOURCES.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .and_then(|resources| resources.get_shared::<T>())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn initializer_only_runs_once_and_others_wait() {
+        static CELL: ResourcesCell = ResourcesCell::new();
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let init = || async {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            Resources::new().insert(42_u32)
+        };
+        let (first, second) = block_on(futures::future::join(
+            CELL.get_or_init(init),
+            CELL.get_or_init(init),
+        ));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(first.get::<u32>(), Some(&42));
+    }
+
+    #[test]
+    fn resources_are_only_available_inside_their_scope() {
+        static CELL: ResourcesCell = ResourcesCell::new();
+        block_on(CELL.get_or_init(|| async { Resources::new().insert("pool") }));
+        let in_scope = block_on(with_resources(
+            Some(&CELL),
+            Box::pin(async { get_resource::<&str>() }),
+        ));
+        assert_eq!(in_scope.as_deref(), Some(&"pool"));
+        assert!(get_resource::<&str>().is_none());
+        // Templates from an app without resources don't see any
+        let unscoped = block_on(with_resources(
+            None,
+            Box::pin(async { get_resource::<&str>() }),
+        ));
+        assert!(unscoped.is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use sycamore::prelude::Template as SycamoreTemplate;
+use sycamore::DomNode;
+use web_sys::Element;
+
+/// The name of the `<meta>` tag a host page can use to override the selector of the element Perseus is mounted into, which is useful
+/// when embedding an app into pages that aren't controlled by it (e.g. `<meta name="perseus-root" content="#widget">`).
+pub const ROOT_OVERRIDE_META: &str = "perseus-root";
+/// The attribute given to every auxiliary root the app shell manages, the value of which is the root's name.
+pub const AUX_ROOT_ATTR: &str = "data-perseus-aux-root";
+
+/// Gets the element the app should be mounted into, which is the first one matching the given selector (the `root` given to
+/// `define_app!`), unless the page the app has been loaded into overrides it with a `<meta name="perseus-root">` tag.
+/// # Panics
+/// This will `panic!` if there's no element matching the selector, since the app has nowhere to render.
+pub fn get_app_root(selector: &str) -> Element {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let selector = document
+        .query_selector(&format!("meta[name='{}']", ROOT_OVERRIDE_META))
+        .ok()
+        .flatten()
+        .and_then(|meta| meta.get_attribute("content"))
+        .unwrap_or_else(|| selector.to_string());
+    match document.query_selector(&selector) {
+        Ok(Some(root)) => root,
+        _ => panic!(
+            "couldn't find the element to mount the app into with selector '{}'",
+            selector
+        ),
+    }
+}
+
+/// Makes sure an auxiliary root exists for each of the given names (the `aux_roots` given to `define_app!`), creating empty `<div>`s at
+/// the end of the `<body>` for any that don't. Auxiliary roots are managed by the app shell alongside the main root, and they're useful
+/// for content that has to be rendered outside the page (like modals, which would otherwise be clipped by the page's layout).
+pub fn mount_aux_roots(names: &[&str]) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let body = document.query_selector("body").unwrap().unwrap();
+    for name in names {
+        if get_aux_root(name).is_none() {
+            let root = document.create_element("div").unwrap();
+            root.set_attribute(AUX_ROOT_ATTR, name).unwrap();
+            body.append_child(&root).unwrap();
+        }
+    }
+}
+
+/// Gets the auxiliary root with the given name, if it's been mounted.
+pub fn get_aux_root(name: &str) -> Option<Element> {
+    let document = web_sys::window().unwrap().document().unwrap();
+    document
+        .query_selector(&format!("[{}='{}']", AUX_ROOT_ATTR, name))
+        .ok()
+        .flatten()
+}
+
+/// Renders the given view into the auxiliary root with the given name, replacing anything that was there before. The app shell clears
+/// every auxiliary root when the user navigates to another page, since their contents belong to the page that rendered them.
+/// # Panics
+/// This will `panic!` if there's no auxiliary root with the given name, which should be declared in `aux_roots` in `define_app!`.
+pub fn render_to_aux_root(name: &str, view: impl FnOnce() -> SycamoreTemplate<DomNode>) {
+    let root = match get_aux_root(name) {
+        Some(root) => root,
+        None => panic!(
+            "no auxiliary root named '{}' (you should add it to `aux_roots` in `define_app!`)",
+            name
+        ),
+    };
+    root.set_inner_html("");
+    sycamore::render_to(view, &root);
+}
+
+/// Clears the auxiliary root with the given name (e.g. when a modal is closed), if it's been mounted.
+pub fn clear_aux_root(name: &str) {
+    if let Some(root) = get_aux_root(name) {
+        root.set_inner_html("");
+    }
+}
+
+/// Clears every auxiliary root, which is done by the app shell whenever a new page is loaded.
+pub(crate) fn clear_aux_roots() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let roots = document
+        .query_selector_all(&format!("[{}]", AUX_ROOT_ATTR))
+        .unwrap();
+    for idx in 0..roots.length() {
+        if let Some(root) = roots.item(idx) {
+            root.set_text_content(None);
+        }
+    }
+}
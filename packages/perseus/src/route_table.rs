@@ -0,0 +1,231 @@
+use crate::clock;
+use crate::config_manager::ConfigManager;
+use crate::path_pattern::is_path_pattern;
+use crate::serve::{get_page_artifact_name, read_page_artifact};
+use crate::template::TemplateMap;
+use crate::{Locales, SsrNode};
+use chrono::DateTime;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+
+/// The environment variable the token for reading the route table of a running server is read from by the server the CLI generates and
+/// by `perseus routes`. The route table won't be served at all if this isn't set (or is empty).
+pub const ROUTE_TABLE_TOKEN_VAR: &str = "PERSEUS_ROUTE_TABLE_TOKEN";
+
+/// The pages that this process has generated incrementally, as pairs of locales and paths. Config managers can't list what they've stored,
+/// so this is only what's been generated since the server started.
+static INCREMENTAL_PAGES: Lazy<Mutex<BTreeSet<(String, String)>>> =
+    Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+/// Records that the page at the given path (without the locale) was just generated incrementally in the given locale.
+pub(crate) fn record_incremental_page(locale: &str, path: &str) {
+    if let Ok(mut pages) = INCREMENTAL_PAGES.lock() {
+        pages.insert((locale.to_string(), path.to_string()));
+    }
+}
+
+/// Everything a running server knows about the routes of an app, which is useful for working out why a page is stale in production.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteTable {
+    /// The app's templates, ordered by their paths.
+    pub templates: Vec<TemplateRoute>,
+    /// The app's pages (those generated at build-time and those generated incrementally by this server since it started), ordered by
+    /// their paths and then their locales.
+    pub pages: Vec<PageRoute>,
+}
+
+/// A template in a [`RouteTable`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateRoute {
+    /// The template's path.
+    pub path: String,
+    /// The rendering strategies the template uses (like `build_state` or `incremental`).
+    pub strategies: Vec<String>,
+    /// How many seconds pages of the template revalidate after, if they revalidate with time (pages can override this in their build
+    /// state).
+    pub revalidate_after: Option<u64>,
+    /// Whether or not the template revalidates with custom logic.
+    pub revalidates_with_logic: bool,
+}
+
+/// A page in a [`RouteTable`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRoute {
+    /// The page's path (without the locale).
+    pub path: String,
+    /// The locale the page is in.
+    pub locale: String,
+    /// The path of the template the page is rendered with.
+    pub template: String,
+    /// Whether or not the page was generated incrementally (rather than at build-time).
+    pub incremental: bool,
+    /// When the page was last rendered on the server (RFC 3339), if it has been since it was built.
+    pub rendered_at: Option<String>,
+    /// When the page will next revalidate with time (RFC 3339), if it does.
+    pub revalidate_at: Option<String>,
+    /// Whether or not the page is due to be revalidated the next time it's requested.
+    pub stale: bool,
+}
+
+/// Gets the strategies the given template uses, by name.
+fn get_strategies(template: &crate::Template<SsrNode>) -> Vec<String> {
+    let strategies = [
+        ("build_paths", template.uses_build_paths()),
+        ("build_state", template.uses_build_state()),
+        ("request_state", template.uses_request_state()),
+        ("incremental", template.uses_incremental()),
+        ("revalidation", template.revalidates()),
+    ];
+    let strategies: Vec<String> = strategies
+        .iter()
+        .filter(|(_, uses)| *uses)
+        .map(|(strategy, _)| strategy.to_string())
+        .collect();
+    // Templates without any strategies are just rendered once at build-time
+    match strategies.is_empty() {
+        true => vec!["static".to_string()],
+        false => strategies,
+    }
+}
+
+/// Gets what the app's stored artifacts say about the page at the given path in the given locale, if it's been built in that locale.
+async fn get_page_route(
+    path: &str,
+    locale: &str,
+    template: &str,
+    incremental: bool,
+    config_manager: &impl ConfigManager,
+) -> Option<PageRoute> {
+    let path_encoded = get_page_artifact_name(locale, path);
+    // Build paths can be exclusive to some locales
+    read_page_artifact(config_manager, &path_encoded, "html")
+        .await
+        .ok()?;
+    let rendered_at = read_page_artifact(config_manager, &path_encoded, "rendered.txt")
+        .await
+        .ok();
+    let revalidate_at = read_page_artifact(config_manager, &path_encoded, "revld.txt")
+        .await
+        .ok();
+    let stale = revalidate_at
+        .as_ref()
+        .and_then(|revalidate_at| DateTime::parse_from_rfc3339(revalidate_at).ok())
+        .map_or(false, |revalidate_at| revalidate_at <= clock::now());
+
+    Some(PageRoute {
+        path: path.to_string(),
+        locale: locale.to_string(),
+        template: template.to_string(),
+        incremental,
+        rendered_at,
+        revalidate_at,
+        stale,
+    })
+}
+
+/// Gets the route table of the app with the given render configuration, templates, and locales, using what's been stored by the given
+/// config manager. This reads the prerendered HTML of every page to see which locales it exists in, so it shouldn't be called often.
+pub async fn get_route_table(
+    render_cfg: &HashMap<String, String>,
+    templates: &TemplateMap<SsrNode>,
+    locales: &Locales,
+    config_manager: &impl ConfigManager,
+) -> RouteTable {
+    let template_routes = templates
+        .values()
+        .map(|template| TemplateRoute {
+            path: template.get_path(),
+            strategies: get_strategies(template),
+            revalidate_after: template
+                .get_revalidate_interval()
+                .map(|interval| interval.as_secs()),
+            revalidates_with_logic: template.revalidates_with_logic(),
+        })
+        .collect();
+
+    // Patterns (for incremental generation and dynamic paths) aren't pages themselves
+    let mut pages: Vec<(String, String, bool)> = render_cfg
+        .iter()
+        .filter(|(path, _)| !is_path_pattern(path))
+        .map(|(path, template)| (path.to_string(), template.to_string(), false))
+        .collect();
+    let incremental_pages = match INCREMENTAL_PAGES.lock() {
+        Ok(incremental_pages) => incremental_pages.clone(),
+        Err(_) => BTreeSet::new(),
+    };
+    for (_, path) in incremental_pages.iter() {
+        if pages.iter().any(|(page_path, _, _)| page_path == path) {
+            continue;
+        }
+        if let Some(template) = crate::serve::get_template_name_for_path(path, render_cfg) {
+            pages.push((path.to_string(), template, true));
+        }
+    }
+    pages.sort();
+    let mut page_routes = Vec::new();
+    for (path, template, incremental) in pages {
+        for locale in locales.get_all() {
+            // Incremental pages are only generated in the locales they're requested in
+            if incremental && !incremental_pages.contains(&(locale.to_string(), path.clone())) {
+                continue;
+            }
+            if let Some(page_route) =
+                get_page_route(&path, locale, &template, incremental, config_manager).await
+            {
+                page_routes.push(page_route);
+            }
+        }
+    }
+
+    RouteTable {
+        templates: template_routes,
+        pages: page_routes,
+    }
+}
+
+/// Checks if the given `Authorization` header gives the given token as a bearer token. This takes the same time however much of the token
+/// is right, so it can't be guessed a character at a time. An empty token (or one that's only whitespace) never authorizes anything,
+/// since that would give anyone who sends `Bearer ` the route table.
+pub fn is_authorized(header: Option<&str>, token: &str) -> bool {
+    if token.trim().is_empty() {
+        return false;
+    }
+    match header.and_then(|header| header.strip_prefix("Bearer ")) {
+        Some(given) => constant_time_eq(given.trim().as_bytes(), token.as_bytes()),
+        None => false,
+    }
+}
+
+/// Compares the given bytes in time that only depends on their length, so that secrets (like the route table's token or access control
+/// credentials) can't be guessed by timing how long it takes for them to be rejected.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_bearer_tokens() {
+        assert!(is_authorized(Some("Bearer s3cret"), "s3cret"));
+        assert!(is_authorized(Some("Bearer  s3cret "), "s3cret"));
+        assert!(!is_authorized(Some("Bearer s3cre"), "s3cret"));
+        assert!(!is_authorized(Some("Bearer s3cret!"), "s3cret"));
+        assert!(!is_authorized(Some("Basic s3cret"), "s3cret"));
+        assert!(!is_authorized(None, "s3cret"));
+    }
+
+    #[test]
+    fn rejects_empty_tokens() {
+        for token in ["", " ", "\t\n"] {
+            assert!(!is_authorized(Some("Bearer "), token));
+            assert!(!is_authorized(Some(&format!("Bearer {}", token)), token));
+        }
+    }
+}
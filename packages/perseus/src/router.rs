@@ -1,4 +1,5 @@
 use crate::Locales;
+use crate::Redirects;
 use crate::Template;
 use std::rc::Rc;
 use sycamore::prelude::GenericNode;
@@ -15,11 +16,38 @@ pub struct Routes<G: GenericNode> {
     /// Whether or not the user is using i18n, which significantly impacts how we match routes (will there be a locale in front of
     /// everything).
     locales: Locales,
+    /// The app's static redirects, which take precedence over any templates.
+    redirects: Redirects,
 }
 impl<G: GenericNode> Routes<G> {
-    /// Creates a new instance of the routes. This takes a vector of key-value pairs of routing path to template functions.
-    pub fn new(raw_routes: Vec<(String, Template<G>)>, locales: Locales) -> Self {
-        let routes: Vec<(Vec<Segment>, Template<G>)> = raw_routes
+    /// Creates a new instance of the routes. This takes a vector of key-value pairs of routing path to template functions. Any templates
+    /// nested under those given will be routed to under their parent's routing path. Any paths in the given redirects will be redirected
+    /// rather than matched to a template.
+    pub fn new(
+        raw_routes: Vec<(String, Template<G>)>,
+        locales: Locales,
+        redirects: Redirects,
+    ) -> Self {
+        // Any nested templates get their own routes under their parent's, which we put first because they're more specific
+        let mut expanded_routes: Vec<(String, Template<G>)> = Vec::new();
+        for (router_path, template) in raw_routes {
+            let mut flattened = template.flatten_nested();
+            let parent = flattened.remove(0);
+            for nested in flattened {
+                let nested_path = nested
+                    .get_path()
+                    .strip_prefix(&format!("{}/", parent.get_path()))
+                    .map(|path| path.to_string())
+                    .unwrap_or_else(|| nested.get_path());
+                expanded_routes.push((
+                    format!("{}/{}", router_path.trim_end_matches('/'), nested_path),
+                    nested,
+                ));
+            }
+            expanded_routes.push((router_path, parent));
+        }
+
+        let routes: Vec<(Vec<Segment>, Template<G>)> = expanded_routes
             .iter()
             .map(|(router_path_str_raw, template_fn)| {
                 // Handle the landing page (because match systems don't tolerate empty strings well)
@@ -45,10 +73,11 @@ impl<G: GenericNode> Routes<G> {
                         // If you're familiar with Sycamore's routing system, we don't need to worry about capturing these segments in Perseus because we just return the actual path directly
                         /* Variants (in tested order):
                             - <stuff..>     segment that captures many parameters
+                            - *             shorthand for the above (as used in template paths)
                             - <stuff>       parameter that captures a single element
                             - stuff         verbatim stuff
                         */
-                        if part.starts_with('<') && part.ends_with("..>") {
+                        if part == "*" || (part.starts_with('<') && part.ends_with("..>")) {
                             Segment::DynSegments
                         } else if part.starts_with('<') && part.ends_with('>') {
                             Segment::DynParam
@@ -62,38 +91,60 @@ impl<G: GenericNode> Routes<G> {
             })
             .collect();
 
-        Self { routes, locales }
+        Self {
+            routes,
+            locales,
+            redirects,
+        }
     }
     /// Matches the given route to an instance of `RouteVerdict`.
     pub fn match_route(&self, raw_path: &[&str]) -> RouteVerdict<G> {
         let path: Vec<&str> = raw_path.to_vec();
         let path_joined = path.join("/"); // This should not have a leading forward slash, it's used for asset fetching by the app shell
 
+        // Redirects are checked first so that old paths can be moved even if a template still matches them
+        if let Some(redirect) = self.redirects.get(&path_joined, &self.locales) {
+            return RouteVerdict::Redirect(redirect.to);
+        }
+
         let mut verdict = RouteVerdict::NotFound;
         // There are different logic chains if we're using i18n, so we fork out early
         if self.locales.using_i18n {
+            // The app's URL strategy decides where the locale is (it might not be in the path at all)
+            let (host, query) = get_host_and_query();
+            let locale_and_path = self.locales.url_strategy.get_locale(
+                &path_joined,
+                host.as_deref(),
+                query.as_deref(),
+                &self.locales,
+            );
             for (segments, template_fn) in &self.routes {
-                let route_path_without_locale = RoutePath::new(segments.to_vec());
-                let route_path_with_locale = RoutePath::new({
-                    let mut vec = vec![Segment::DynParam];
-                    vec.extend(segments.to_vec());
-                    vec
-                });
+                let route_path = RoutePath::new(segments.to_vec());
 
                 // First, we'll see if the path matches a translated route
-                // If that fails, we'll see if it matches an untranslated route, which becomes a locale detector
-                if route_path_with_locale.match_path(&path).is_some() {
-                    verdict = RouteVerdict::Found(RouteInfo {
-                        // The asset fetching process deals with the locale separately, and doesn't need a leading `/`
-                        path: path[1..].to_vec().join("/"),
-                        template_fn: template_fn.clone(),
-                        locale: path[0].to_string(),
-                    });
-                    break;
-                } else if route_path_without_locale.match_path(&path).is_some() {
-                    // We've now matched that it fits without the locale, which means the user is trying to
-                    verdict = RouteVerdict::LocaleDetection(path_joined);
-                    break;
+                // If there's no locale, we'll see if it matches an untranslated route, which becomes a locale detector
+                match &locale_and_path {
+                    Some((locale, path_without_locale)) => {
+                        let path_without_locale: Vec<&str> = match path_without_locale.is_empty() {
+                            true => Vec::new(),
+                            false => path_without_locale.split('/').collect(),
+                        };
+                        if route_path.match_path(&path_without_locale).is_some() {
+                            verdict = RouteVerdict::Found(RouteInfo {
+                                // The asset fetching process deals with the locale separately, and doesn't need a leading `/`
+                                path: path_without_locale.join("/"),
+                                template_fn: template_fn.clone(),
+                                locale: locale.to_string(),
+                            });
+                            break;
+                        }
+                    }
+                    None => {
+                        if route_path.match_path(&path).is_some() {
+                            verdict = RouteVerdict::LocaleDetection(path_joined);
+                            break;
+                        }
+                    }
                 }
             }
         } else {
@@ -117,6 +168,23 @@ impl<G: GenericNode> Routes<G> {
     }
 }
 
+/// Gets the host and query string of the current page in the browser, which the app's URL strategy might get the locale from. These will
+/// both be `None` on the server.
+fn get_host_and_query() -> (Option<String>, Option<String>) {
+    #[cfg(target_arch = "wasm32")]
+    if let Some(location) = web_sys::window().map(|window| window.location()) {
+        return (
+            location.host().ok(),
+            location
+                .search()
+                .ok()
+                .map(|search| search.trim_start_matches('?').to_string()),
+        );
+    }
+
+    (None, None)
+}
+
 /// Information about a route, which, combined with error pages and a client-side translations manager, allows the initialization of
 /// the app shell and the rendering of a page.
 pub struct RouteInfo<G: GenericNode> {
@@ -138,6 +206,8 @@ pub enum RouteVerdict<G: GenericNode> {
     NotFound,
     /// The given route maps to the locale detector, which will redirect the user to the attached path (in the appropriate locale).
     LocaleDetection(String),
+    /// The given route is in the app's static redirects, and the user should be redirected to the attached path or URL.
+    Redirect(String),
 }
 impl<G: GenericNode> Route for RouteVerdict<G> {
     fn match_route(path: &[&str]) -> Self {
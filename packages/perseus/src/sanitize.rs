@@ -0,0 +1,102 @@
+// Templates often render HTML they don't control (like rich text from a CMS), which has to be sanitized before it's put into a page, so
+// this gives them one well-tested sanitizer (Ammonia) rather than leaving each template to pick its own or forget
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use sycamore::prelude::{component, template, GenericNode, Template as SycamoreTemplate};
+
+pub use ammonia;
+
+/// HTML that's been sanitized, which is the only thing [`SafeHtml`] will render. This can be put in a template's state, so HTML can be
+/// sanitized in a build or request state function on the server. Since state can come from anywhere (like a cached page or a tampered
+/// request), this is sanitized again with the default policy when it's deserialized, so anything that only a custom policy allows will
+/// be removed then (keep that HTML in a `String` and sanitize it in the template instead).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct SanitizedHtml(String);
+impl<'de> Deserialize<'de> for SanitizedHtml {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let html = String::deserialize(deserializer)?;
+        Ok(sanitize_html(&html))
+    }
+}
+impl SanitizedHtml {
+    /// Gets the sanitized HTML.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    /// Gets the sanitized HTML as an owned string.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+impl fmt::Display for SanitizedHtml {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A policy for sanitizing HTML. The default one keeps text formatting, links (adding `rel="noopener noreferrer"` to them), images, lists,
+/// and tables, and removes scripts, styles, event handlers, and anything else that could run code or take over the page. Policies can
+/// be customized by creating them from an [`ammonia::Builder`].
+#[derive(Debug, Clone)]
+pub struct Sanitizer {
+    builder: ammonia::Builder<'static>,
+}
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Sanitizer {
+    /// Creates a new sanitizer with the default policy.
+    pub fn new() -> Self {
+        Self {
+            builder: ammonia::Builder::default(),
+        }
+    }
+    /// Creates a new sanitizer with the policy of the given Ammonia builder (e.g. one that allows some extra tags or classes).
+    pub fn from_builder(builder: ammonia::Builder<'static>) -> Self {
+        Self { builder }
+    }
+    /// Sanitizes the given HTML with this sanitizer's policy.
+    pub fn sanitize(&self, html: &str) -> SanitizedHtml {
+        SanitizedHtml(self.builder.clean(html).to_string())
+    }
+}
+
+/// Sanitizes the given HTML with the default policy (see [`Sanitizer`]).
+pub fn sanitize_html(html: &str) -> SanitizedHtml {
+    Sanitizer::new().sanitize(html)
+}
+
+/// Renders the given sanitized HTML in a `<div>` with the class `perseus-sanitized`. This is the sanctioned alternative to setting
+/// `dangerously_set_inner_html` yourself, since it can only be given HTML that's been through a [`Sanitizer`].
+#[component(SafeHtml<G>)]
+pub fn safe_html(html: SanitizedHtml) -> SycamoreTemplate<G> {
+    let html = html.into_string();
+    template! {
+        div(class = "perseus-sanitized", dangerously_set_inner_html = &html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_on_deserialize() {
+        let html: SanitizedHtml =
+            serde_json::from_str(r#""<p onclick=\"steal()\">Hi<script>steal()</script></p>""#)
+                .unwrap();
+        assert_eq!(html.as_str(), "<p>Hi</p>");
+    }
+
+    #[test]
+    fn round_trips_sanitized_html() {
+        let html = sanitize_html("<p>Read <a href=\"https://example.com\">this</a></p>");
+        let json = serde_json::to_string(&html).unwrap();
+        let deserialized: SanitizedHtml = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, html);
+    }
+}
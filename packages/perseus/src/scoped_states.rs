@@ -0,0 +1,51 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use sycamore::rx::Signal;
+
+/// The states of named scopes, which are shared between every template subscribed to them with `.state_scope()` (e.g. a shopping cart
+/// shared between a few templates). On the client-side, one of these should be created for the whole app and given to the app shell,
+/// which will keep these states across navigations. On the server-side, scoped states always start out empty.
+///
+/// Templates can access this through Sycamore's context system, but they can only get the states of the scopes they're subscribed to.
+/// Like page states, these are stored as strings, so you can put whatever serializable data you like in them.
+#[derive(Clone, Default)]
+pub struct ScopedStates {
+    /// The signals behind the states of each scope.
+    states: Rc<RefCell<HashMap<String, Signal<Option<String>>>>>,
+    /// The scopes that can be accessed through this handle, or `None` if all of them can be.
+    subscribed: Option<Rc<Vec<String>>>,
+}
+impl ScopedStates {
+    /// Creates a new set of scoped states, all of which are empty.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Gets the signal behind the state of the given scope, which will be `None` if nothing has set it yet. This will panic if the
+    /// template this was provided to isn't subscribed to the given scope.
+    pub fn get(&self, scope: &str) -> Signal<Option<String>> {
+        if let Some(subscribed) = &self.subscribed {
+            if !subscribed
+                .iter()
+                .any(|subscribed_scope| subscribed_scope == scope)
+            {
+                panic!(
+                    "tried to access state scope '{}', which the current template isn't subscribed to",
+                    scope
+                );
+            }
+        }
+        self.states
+            .borrow_mut()
+            .entry(scope.to_string())
+            .or_insert_with(|| Signal::new(None))
+            .clone()
+    }
+    /// Creates a handle to the same states that can only access the given scopes.
+    pub(crate) fn for_scopes(&self, scopes: &[String]) -> Self {
+        Self {
+            states: Rc::clone(&self.states),
+            subscribed: Some(Rc::new(scopes.to_vec())),
+        }
+    }
+}
@@ -1,26 +1,104 @@
 // This file contains the universal logic for a serving process, regardless of framework
 
+use crate::circuit_breaker::{is_state_failure, Tripped};
+use crate::clock;
+use crate::concurrency::{acquire_render_permit, RenderPermit};
 use crate::config_manager::ConfigManager;
-use crate::decode_time_str::decode_time_str;
+use crate::decode_time_str::get_datetime_after;
 use crate::errors::*;
-use crate::template::{States, Template, TemplateMap};
-use crate::Request;
+use crate::flash::{clear_flash, get_flash};
+use crate::locale_detector::detect_locale_from_header;
+use crate::path_equivalents::{get_alternates, load_path_equivalents};
+use crate::path_pattern::{cmp_pattern_specificity, is_path_pattern, matches_path_pattern};
+use crate::render_panic::{catch_render_panic, catch_render_panic_sync};
+use crate::route_table::record_incremental_page;
+use crate::template::{
+    Redirect, RenderBackend, RenderCtx, RenderOutcome, RevalidationContext, States, Template,
+    TemplateMap,
+};
+use crate::translator::TextDirection;
+use crate::Locales;
+use crate::Redirects;
+use crate::ScopedStates;
 use crate::TranslationsManager;
 use crate::Translator;
+use crate::{HttpRequest, Request, UrlBuilder};
 use chrono::{DateTime, Utc};
+use http::header::HeaderMap;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::Mutex;
 use sycamore::prelude::SsrNode;
+use sycamore::rx::Signal;
 
 /// Represents the data necessary to render a page.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PageData {
     /// Prerendered HTML content.
     pub content: String,
+    /// Prerendered HTML to be injected into the document `<head>`.
+    pub head: String,
+    /// The one-time flash message sent to this page, if there is one. This is never cached.
+    pub flash: Option<String>,
     /// The state for hydration. This is kept as a string for ease of typing. Some pages may not need state or generate it in another way,
     /// so this might be `None`.
     pub state: Option<String>,
+    /// Whether or not this is just a placeholder for a page that's still being generated incrementally, in which case the real page
+    /// should be fetched again with `?fallback=false`.
+    pub fallback: bool,
+    /// Where the user should be redirected to instead of being shown this page, if a state function asked for that. If this is set,
+    /// nothing else will be.
+    pub redirect: Option<Redirect>,
+    /// The paths (without locales) of this same page in every locale it has a path of its own in, if its build path was given a key
+    /// (e.g. because its slug is translated).
+    #[serde(default)]
+    pub alternates: BTreeMap<String, String>,
+}
+impl PageData {
+    /// Creates page data that only redirects the user somewhere else.
+    pub fn for_redirect(redirect: Redirect) -> Self {
+        Self {
+            content: String::new(),
+            head: String::new(),
+            // Any flash message should be delivered to wherever the user ends up
+            flash: None,
+            state: None,
+            fallback: false,
+            redirect: Some(redirect),
+            alternates: BTreeMap::new(),
+        }
+    }
+}
+
+/// Gets the name (without an extension) the artifacts of the page at the given path (without the locale) in the given locale are stored
+/// under in `static/`. Each locale has a directory of its own, so that its artifacts can be found (or cleared) separately.
+pub fn get_page_artifact_name(locale: &str, path: &str) -> String {
+    format!("{}/{}", locale, urlencoding::encode(path))
+}
+
+/// Reads the artifact of a page with the given name (from [`get_page_artifact_name`]) and extension (like `html` or `json`) from
+/// `static/`. Builds from before each locale had a directory of its own stored artifacts as `{locale}-{path}`, so, if there isn't an
+/// artifact under the new name, it'll be looked for there, which lets an app be served from an older build.
+pub(crate) async fn read_page_artifact(
+    config_manager: &impl ConfigManager,
+    name: &str,
+    ext: &str,
+) -> crate::config_manager::Result<String> {
+    match config_manager
+        .read(&format!("static/{}.{}", name, ext))
+        .await
+    {
+        Err(crate::config_manager::Error(crate::config_manager::ErrorKind::NotFound(_), _)) => {
+            // Neither the locale nor the encoded path can contain a `/`, so the first one is the locale's directory
+            let legacy_name = name.replacen('/', "-", 1);
+            config_manager
+                .read(&format!("static/{}.{}", legacy_name, ext))
+                .await
+        }
+        res => res,
+    }
 }
 
 /// Gets the configuration of how to render each page.
@@ -33,49 +111,113 @@ pub async fn get_render_cfg(
     Ok(cfg)
 }
 
+/// Gets the artifact names of the pages that set their own revalidation intervals at build-time, mapped to their templates.
+pub(crate) async fn get_page_intervals(
+    config_manager: &impl ConfigManager,
+) -> Result<HashMap<String, String>> {
+    let content = config_manager.read("page_intervals.json").await?;
+    let page_intervals = serde_json::from_str::<HashMap<String, String>>(&content)?;
+
+    Ok(page_intervals)
+}
+
+/// What this process knows about which pages have their own revalidation intervals.
+enum PageIntervals {
+    /// The build's record hasn't been read yet.
+    Unread,
+    /// The build didn't record which pages have their own intervals (it's from before they were recorded), so any page might.
+    Unrecorded,
+    /// The artifact names of the pages that have their own intervals, from the build and from revalidations this process has done since.
+    Recorded(HashSet<String>),
+}
+/// Which pages have their own revalidation intervals, which is read from the build's record the first time it's needed.
+static PAGE_INTERVALS: Lazy<Mutex<PageIntervals>> = Lazy::new(|| Mutex::new(PageIntervals::Unread));
+
+/// Checks if the page with the given encoded path might have its own revalidation interval, which it can only have if one was recorded
+/// for it (or if nothing was recorded at all).
+async fn has_own_interval(path_encoded: &str, config_manager: &impl ConfigManager) -> bool {
+    match PAGE_INTERVALS.lock() {
+        Ok(page_intervals) => match &*page_intervals {
+            PageIntervals::Unread => (),
+            PageIntervals::Unrecorded => return true,
+            PageIntervals::Recorded(pages) => return pages.contains(path_encoded),
+        },
+        Err(_) => return true,
+    };
+    // We don't hold the lock while reading, so if that's happening somewhere else too, we'll just both read the same thing
+    let read = match get_page_intervals(config_manager).await {
+        Ok(page_intervals) => {
+            PageIntervals::Recorded(page_intervals.into_iter().map(|(page, _)| page).collect())
+        }
+        Err(_) => PageIntervals::Unrecorded,
+    };
+    if let Ok(mut page_intervals) = PAGE_INTERVALS.lock() {
+        if let PageIntervals::Unread = *page_intervals {
+            *page_intervals = read;
+        }
+    }
+    match PAGE_INTERVALS.lock() {
+        Ok(page_intervals) => match &*page_intervals {
+            PageIntervals::Recorded(pages) => pages.contains(path_encoded),
+            _ => true,
+        },
+        Err(_) => true,
+    }
+}
+
+/// Records whether or not the page with the given encoded path has its own revalidation interval after it's been revalidated, since its
+/// new build state might have changed that. This only affects this process.
+fn record_own_interval(path_encoded: &str, own_interval: bool) {
+    if let Ok(mut page_intervals) = PAGE_INTERVALS.lock() {
+        if let PageIntervals::Recorded(pages) = &mut *page_intervals {
+            match own_interval {
+                true => pages.insert(path_encoded.to_string()),
+                false => pages.remove(path_encoded),
+            };
+        }
+    }
+}
+
 /// Renders a template that uses state generated at build-time.
 async fn render_build_state(
     path_encoded: &str,
     config_manager: &impl ConfigManager,
-) -> Result<(String, Option<String>)> {
+) -> Result<(String, String, Option<String>)> {
     // Get the static HTML
-    let html = config_manager
-        .read(&format!("static/{}.html", path_encoded))
-        .await?;
+    let html = read_page_artifact(config_manager, path_encoded, "html").await?;
+    // Get the static head (it's fine for this to be empty)
+    let head = match read_page_artifact(config_manager, path_encoded, "head.html").await {
+        Ok(head) => head,
+        Err(_) => String::new(),
+    };
     // Get the static JSON
-    let state = match config_manager
-        .read(&format!("static/{}.json", path_encoded))
-        .await
-    {
+    let state = match read_page_artifact(config_manager, path_encoded, "json").await {
         Ok(state) => Some(state),
         Err(_) => None,
     };
 
-    Ok((html, state))
+    Ok((html, head, state))
 }
 /// Renders a template that generated its state at request-time. Note that revalidation and ISR have no impact on SSR-rendered pages.
-async fn render_request_state(
+fn render_request_state(
     template: &Template<SsrNode>,
     translator: Rc<Translator>,
     path: &str,
-    req: Request,
-) -> Result<(String, Option<String>)> {
-    // Generate the initial state (this may generate an error, but there's no file that can't exist)
-    let state = Some(template.get_request_state(path.to_string(), req).await?);
+    state: String,
+) -> (String, String, Option<String>) {
+    let state = Some(state);
     // Use that to render the static HTML
-    let html =
-        sycamore::render_to_string(|| template.render_for_template(state.clone(), translator));
+    let html = template.render_html_str(state.clone(), translator, path);
+    let head = template.render_head_str(state.clone());
 
-    Ok((html, state))
+    (html, head, state)
 }
 /// Checks if a template that uses ISR has already been cached.
 async fn get_incremental_cached(
     path_encoded: &str,
     config_manager: &impl ConfigManager,
 ) -> Option<String> {
-    let html_res = config_manager
-        .read(&format!("static/{}.html", path_encoded))
-        .await;
+    let html_res = read_page_artifact(config_manager, path_encoded, "html").await;
 
     // We should only treat it as cached if it can be accessed and if we aren't in development (when everything should constantly reload)
     match html_res {
@@ -83,33 +225,130 @@ async fn get_incremental_cached(
         Ok(_) | Err(_) => None,
     }
 }
-/// Checks if a template should revalidate by time.
+/// Clones the given request. `http` doesn't let us do this directly because extensions can't be cloned, but we never use them.
+fn clone_req(req: &Request) -> Request {
+    let mut cloned_req = HttpRequest::new(());
+    *cloned_req.method_mut() = req.method().clone();
+    *cloned_req.uri_mut() = req.uri().clone();
+    *cloned_req.version_mut() = req.version();
+    *cloned_req.headers_mut() = req.headers().clone();
+    cloned_req
+}
+/// Records that the page with the given encoded path was just rendered, so that custom revalidation logic can know how old it is.
+pub(crate) async fn write_render_time(
+    path_encoded: &str,
+    config_manager: &impl ConfigManager,
+) -> Result<()> {
+    config_manager
+        .write(
+            &format!("static/{}.rendered.txt", path_encoded),
+            &clock::now().to_rfc3339(),
+        )
+        .await?;
+
+    Ok(())
+}
+/// Records the shape of the given template's state alongside the state of the page with the given encoded path, if the template has
+/// declared one.
+pub(crate) async fn write_state_schema(
+    template: &Template<SsrNode>,
+    path_encoded: &str,
+    config_manager: &impl ConfigManager,
+) -> Result<()> {
+    if let Some(state_schema) = template.get_state_schema() {
+        config_manager
+            .write(
+                &format!("static/{}.schema.txt", path_encoded),
+                &state_schema,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+/// Checks if the cached state of the page with the given encoded path was generated with a different shape than the given template's
+/// state has now (e.g. by an older build), in which case the page has to be rendered again before it can be served.
+async fn has_stale_schema(
+    template: &Template<SsrNode>,
+    path_encoded: &str,
+    config_manager: &impl ConfigManager,
+) -> bool {
+    if !template.uses_build_state() {
+        return false;
+    }
+    match template.get_state_schema() {
+        // If nothing was recorded, the state was generated before the template declared its shape
+        Some(state_schema) => read_page_artifact(config_manager, path_encoded, "schema.txt")
+            .await
+            .map_or(true, |cached_schema| cached_schema != state_schema),
+        None => false,
+    }
+}
+/// Gets what's known about the currently cached version of the page with the given encoded path for custom revalidation logic.
+async fn get_revalidation_ctx(
+    path_encoded: &str,
+    config_manager: &impl ConfigManager,
+) -> RevalidationContext {
+    // Neither of these have to exist (the page might not have any state, or have been built before render times were recorded)
+    let last_rendered = read_page_artifact(config_manager, path_encoded, "rendered.txt")
+        .await
+        .ok()
+        .and_then(|last_rendered| DateTime::parse_from_rfc3339(&last_rendered).ok())
+        .map(|last_rendered| last_rendered.with_timezone(&Utc));
+    let state = read_page_artifact(config_manager, path_encoded, "json")
+        .await
+        .ok();
+
+    RevalidationContext {
+        last_rendered,
+        state,
+    }
+}
+/// Checks if a template should revalidate by time or by the user's custom logic (which is given the path, the request, and what's known
+/// about the cached page).
 async fn should_revalidate(
     template: &Template<SsrNode>,
+    path: &str,
     path_encoded: &str,
+    req: &Request,
     config_manager: &impl ConfigManager,
 ) -> Result<bool> {
     let mut should_revalidate = false;
     // If it revalidates after a certain period of time, we needd to check that BEFORE the custom logic
-    if template.revalidates_with_time() {
+    // Pages can set their own revalidation intervals from their build state, which the build records (but pages generated incrementally
+    // at request-time aren't in that record, so we always check those)
+    let may_have_own_interval = template.uses_build_state()
+        && (template.uses_incremental() || has_own_interval(path_encoded, config_manager).await);
+    if template.revalidates_with_time() || may_have_own_interval {
         // Get the time when it should revalidate (RFC 3339)
-        let datetime_to_revalidate_str = config_manager
-            .read(&format!("static/{}.revld.txt", path_encoded))
-            .await?;
-        let datetime_to_revalidate = DateTime::parse_from_rfc3339(&datetime_to_revalidate_str)?;
-        // Get the current time (UTC)
-        let now = Utc::now();
+        let datetime_to_revalidate_str =
+            read_page_artifact(config_manager, path_encoded, "revld.txt").await;
+        match datetime_to_revalidate_str {
+            Ok(datetime_to_revalidate_str) => {
+                let datetime_to_revalidate =
+                    DateTime::parse_from_rfc3339(&datetime_to_revalidate_str)?;
+                // Get the current time (UTC)
+                let now = clock::now();
 
-        // If the datetime to revalidate is still in the future, end with `false`
-        if datetime_to_revalidate > now {
-            return Ok(false);
+                // If the datetime to revalidate is still in the future, end with `false`
+                if datetime_to_revalidate > now {
+                    return Ok(false);
+                }
+                should_revalidate = true;
+            }
+            // If the template itself revalidates after a time, there must be a datetime
+            Err(err) if template.revalidates_with_time() => return Err(err.into()),
+            // Otherwise this page just doesn't revalidate after a time
+            Err(_) => (),
         }
-        should_revalidate = true;
     }
 
     // Now run the user's custom revalidation logic
     if template.revalidates_with_logic() {
-        should_revalidate = template.should_revalidate().await?;
+        let ctx = get_revalidation_ctx(path_encoded, config_manager).await;
+        should_revalidate = template
+            .should_revalidate(path.to_string(), clone_req(req), ctx)
+            .await?;
     }
     Ok(should_revalidate)
 }
@@ -120,21 +359,29 @@ async fn revalidate(
     path: &str,
     path_encoded: &str,
     config_manager: &impl ConfigManager,
-) -> Result<(String, Option<String>)> {
+) -> Result<(String, String, Option<String>)> {
     // We need to regenerate and cache this page for future usage (until the next revalidation)
-    let state = Some(
-        template
-            .get_build_state(format!("{}/{}", template.get_path(), path))
-            .await?,
-    );
-    let html =
-        sycamore::render_to_string(|| template.render_for_template(state.clone(), translator));
+    let build_state = template
+        .get_build_state(path.to_string(), translator.get_locale())
+        .await?;
+    if build_state.redirect.is_some() {
+        bail!(ErrorKind::RedirectNotAllowed(path.to_string()))
+    }
+    // The page might not exist anymore (it'll be checked again on the next request)
+    if build_state.not_found {
+        bail!(ErrorKind::PageNotFound(path.to_string()))
+    }
+    let revalidate_interval = template.get_revalidate_interval_for_page(&build_state)?;
+    record_own_interval(path_encoded, build_state.revalidate_after.is_some());
+    let state = Some(build_state.state);
+    let html = template.render_html_str(state.clone(), translator, path);
+    let head = template.render_head_str(state.clone());
     // Handle revalidation, we need to parse any given time strings into datetimes
     // We don't need to worry about revalidation that operates by logic, that's request-time only
-    if template.revalidates_with_time() {
+    if let Some(revalidate_interval) = revalidate_interval {
         // IMPORTANT: we set the new revalidation datetime to the interval from NOW, not from the previous one
         // So if you're revalidating many pages weekly, they will NOT revalidate simultaneously, even if they're all queried thus
-        let datetime_to_revalidate = decode_time_str(&template.get_revalidate_interval().unwrap())?;
+        let datetime_to_revalidate = get_datetime_after(revalidate_interval)?;
         config_manager
             .write(
                 &format!("static/{}.revld.txt", path_encoded),
@@ -151,13 +398,73 @@ async fn revalidate(
     config_manager
         .write(&format!("static/{}.html", path_encoded), &html)
         .await?;
+    config_manager
+        .write(&format!("static/{}.head.html", path_encoded), &head)
+        .await?;
+    write_state_schema(template, path_encoded, config_manager).await?;
+    write_render_time(path_encoded, config_manager).await?;
 
-    Ok((html, state))
+    Ok((html, head, state))
+}
+
+/// Gets the name of the template that should be used to render the given page path (which must not contain the locale) from the render
+/// configuration alone, if there is one.
+pub(crate) fn get_template_name_for_path(
+    raw_path: &str,
+    render_cfg: &HashMap<String, String>,
+) -> Option<String> {
+    let mut path = raw_path;
+    // If the path is empty, we're looking for the special `index` page
+    if path.is_empty() {
+        path = "index";
+    }
+
+    // We'll try a direct match first
+    if let Some(template_root_path) = render_cfg.get(path) {
+        return Some(template_root_path.to_string());
+    }
+    // Next, a pattern match (more complex), which we only want to run if we didn't get an exact match above
+    // That covers both ISR (`post/*`) and templates with dynamic paths (e.g. `blog/<year>/<slug>`)
+    // If several patterns match, we want the most specific one (which doesn't depend on the order of the render configuration)
+    render_cfg
+        .iter()
+        .filter(|(pattern, _)| is_path_pattern(pattern) && matches_path_pattern(pattern, path))
+        .max_by(|(a, _), (b, _)| cmp_pattern_specificity(a, b))
+        .map(|(_, template_root_path)| template_root_path.to_string())
+}
+
+/// Checks if the given request will accept a placeholder for a page that hasn't been generated yet. The app shell asks for the real page
+/// with `?fallback=false` after it's rendered the placeholder.
+fn allows_fallback(req: &Request) -> bool {
+    !req.uri().query().map_or(false, |query| {
+        query.split('&').any(|param| param == "fallback=false")
+    })
+}
+
+/// Gets the template that should be used to render the given page path (which must not contain the locale), using the given render
+/// configuration.
+pub fn get_template_for_path<'a>(
+    raw_path: &str,
+    render_cfg: &HashMap<String, String>,
+    templates: &'a TemplateMap<SsrNode>,
+) -> Result<&'a Template<SsrNode>> {
+    // Match the path to one of the templates
+    let template_name = match get_template_name_for_path(raw_path, render_cfg) {
+        Some(template_name) => template_name,
+        // If we have nothing, then the page doesn't exist
+        None => bail!(ErrorKind::PageNotFound(raw_path.to_string())),
+    };
+
+    // Get the template to use
+    let template = templates.get(&template_name);
+    match template {
+        Some(template) => Ok(template),
+        None => bail!(ErrorKind::PageNotFound(raw_path.to_string())),
+    }
 }
 
 /// Gets the HTML/JSON data for the given page path. This will call SSG/SSR/etc., whatever is needed for that page. Note that HTML generated
 /// at request-time will **always** replace anything generated at build-time, incrementally, revalidated, etc.
-// TODO possible further optimizations on this for futures?
 pub async fn get_page(
     // This must not contain the locale
     raw_path: &str,
@@ -168,58 +475,360 @@ pub async fn get_page(
     config_manager: &impl ConfigManager,
     translations_manager: &impl TranslationsManager,
 ) -> Result<PageData> {
-    let mut path = raw_path;
-    // If the path is empty, we're looking for the special `index` page
-    if path.is_empty() {
-        path = "index";
+    let template = get_template_for_path(raw_path, render_cfg, templates)?;
+    get_page_for_template(
+        raw_path,
+        locale,
+        template,
+        req,
+        config_manager,
+        translations_manager,
+    )
+    .await
+}
+
+/// Gets the HTML/JSON data for the given page path, which has already been matched to the given template (see
+/// [`get_template_for_path`]). This is useful for integrations that need the template afterwards (e.g. to get the page's headers).
+/// Any panics in the user's code while doing this will be returned as errors.
+pub async fn get_page_for_template(
+    // This must not contain the locale
+    raw_path: &str,
+    locale: &str,
+    template: &Template<SsrNode>,
+    req: Request,
+    config_manager: &impl ConfigManager,
+    translations_manager: &impl TranslationsManager,
+) -> Result<PageData> {
+    get_page_with_content(
+        raw_path,
+        locale,
+        template,
+        req,
+        config_manager,
+        translations_manager,
+        false,
+    )
+    .await
+    .map(|(page_data, _)| page_data)
+}
+
+/// Gets the data for the given page path like [`get_page_for_template`], but, if the template is streaming (see
+/// [`Template::is_streaming`]), leaves the page's content to be rendered once the rest of its data has been sent (see
+/// [`StreamedPageData::into_chunks`]).
+pub async fn get_streamed_page_for_template(
+    // This must not contain the locale
+    raw_path: &str,
+    locale: &str,
+    template: &Template<SsrNode>,
+    req: Request,
+    config_manager: &impl ConfigManager,
+    translations_manager: &impl TranslationsManager,
+) -> Result<StreamedPageData> {
+    // The template's post-processing hook (if it has one) is run on the content once it's been rendered
+    let hook_req = clone_req(&req);
+    let (page_data, pending) = get_page_with_content(
+        raw_path,
+        locale,
+        template,
+        req,
+        config_manager,
+        translations_manager,
+        template.is_streaming(),
+    )
+    .await?;
+    let content = match pending {
+        Some(pending) => {
+            let translator = Rc::new(
+                translations_manager
+                    .get_translator_for_locale(locale.to_string())
+                    .await?,
+            );
+            PageContent::Deferred(Box::new(DeferredContent {
+                template: template.clone(),
+                translator,
+                raw_path: raw_path.to_string(),
+                locale: locale.to_string(),
+                page_data: page_data.clone(),
+                hook_req,
+                pending,
+            }))
+        }
+        None => PageContent::Rendered(page_data.content.clone()),
+    };
+
+    Ok(StreamedPageData { page_data, content })
+}
+
+/// What's still needed to render the content of a page that [`get_page_with_content`] left to be rendered later: the render permit of its
+/// template (if it has a concurrency limit), which is held until the content has been rendered, and when the render started.
+struct PendingRender {
+    _permit: Option<RenderPermit>,
+    start: DateTime<Utc>,
+}
+
+/// Gets the data for the given page path like [`get_page_for_template`], but, if `defer_content` is `true`, leaves the content of a page
+/// rendered at request-time empty (and doesn't post-process it) so that it can be rendered later, while the rest of the page is sent. If
+/// that happens, what's needed to finish the render will be returned too.
+async fn get_page_with_content(
+    // This must not contain the locale
+    raw_path: &str,
+    locale: &str,
+    template: &Template<SsrNode>,
+    req: Request,
+    config_manager: &impl ConfigManager,
+    translations_manager: &impl TranslationsManager,
+    defer_content: bool,
+) -> Result<(PageData, Option<PendingRender>)> {
+    let template_path = template.get_path();
+    // The template's hook decides if the page should be served at all (e.g. if the user isn't logged in), so it has to come before
+    // anything that could serve it, or run the template's state functions
+    let path = match raw_path.is_empty() {
+        true => "index",
+        false => raw_path,
+    };
+    template
+        .before_render(path.to_string(), clone_req(&req))
+        .await?;
+    // Pages need to know their paths in other locales to link to themselves there
+    load_path_equivalents(config_manager).await;
+    // If the template's state functions have been failing, we won't call them again until the circuit closes
+    if let Some(breaker) = template.get_circuit_breaker() {
+        match breaker.check(&template_path, locale, raw_path) {
+            Some(Tripped::LastGood(page_data)) => return Ok((page_data, None)),
+            Some(Tripped::Degraded(state)) => {
+                return render_degraded(raw_path, locale, template, state, translations_manager)
+                    .await
+                    .map(|page_data| (page_data, None))
+            }
+            Some(Tripped::Unavailable(retry_after)) => {
+                bail!(ErrorKind::CircuitOpen(template_path, retry_after))
+            }
+            None => (),
+        }
     }
-    // Get a translator for this locale (for sanity we hope the manager is caching)
+    // Templates that are expensive to render can limit how many of their pages are rendered at once, in which case we hold a permit until
+    // we're done
+    let permit = match template.get_concurrency_limit() {
+        Some(limit) => Some(acquire_render_permit(&template_path, limit).await?),
+        None => None,
+    };
+    let start = Utc::now();
+    // A panic in one page shouldn't take down the whole server
+    let res = catch_render_panic(
+        &template_path,
+        raw_path,
+        render_page(
+            raw_path,
+            locale,
+            template,
+            req,
+            config_manager,
+            translations_manager,
+            defer_content,
+        ),
+    )
+    .await;
+    if let Some(breaker) = template.get_circuit_breaker() {
+        match &res {
+            // A page without its content is recorded once that's been rendered
+            Ok((_, true)) => (),
+            Ok((page_data, false)) => {
+                breaker.record_success(&template_path, locale, raw_path, page_data)
+            }
+            Err(err) if is_state_failure(err) => breaker.record_failure(&template_path),
+            Err(_) => (),
+        }
+    }
+
+    res.map(|(page_data, deferred)| match deferred {
+        true => (
+            page_data,
+            Some(PendingRender {
+                _permit: permit,
+                start,
+            }),
+        ),
+        false => (page_data, None),
+    })
+}
+
+/// Renders the given page with the given template and the degraded state of its circuit breaker, without calling any of its state
+/// functions.
+async fn render_degraded(
+    // This must not contain the locale
+    raw_path: &str,
+    locale: &str,
+    template: &Template<SsrNode>,
+    state: String,
+    translations_manager: &impl TranslationsManager,
+) -> Result<PageData> {
+    let path = match raw_path.is_empty() {
+        true => "index",
+        false => raw_path,
+    };
     let translator = Rc::new(
         translations_manager
             .get_translator_for_locale(locale.to_string())
             .await?,
     );
-    // Remove `/` from the path by encoding it as a URL (that's what we store) and add the locale
-    let path_encoded = format!("{}-{}", locale, urlencoding::encode(path).to_string());
+    let (html, head, state) = render_request_state(template, translator, path, state);
 
-    // Match the path to one of the templates
-    let mut template_name = String::new();
-    // We'll try a direct match first
-    if let Some(template_root_path) = render_cfg.get(path) {
-        template_name = template_root_path.to_string();
-    }
-    // Next, an ISR match (more complex), which we only want to run if we didn't get an exact match above
-    if template_name.is_empty() {
-        // We progressively look for more and more specificity of the path, adding each segment
-        // That way, we're searching forwards rather than backwards, which is more efficient
-        let path_segments: Vec<&str> = path.split('/').collect();
-        for (idx, _) in path_segments.iter().enumerate() {
-            // Make a path out of this and all the previous segments
-            let path_to_try = path_segments[0..(idx + 1)].join("/") + "/*";
-
-            // If we find something, keep going until we don't (maximise specificity)
-            if let Some(template_root_path) = render_cfg.get(&path_to_try) {
-                template_name = template_root_path.to_string();
-            } else {
-                break;
+    Ok(PageData {
+        content: html,
+        head,
+        flash: None,
+        state,
+        fallback: false,
+        redirect: None,
+        alternates: get_alternates(raw_path, locale),
+    })
+}
+
+/// The content of a page, which might not have been rendered yet.
+enum PageContent {
+    Rendered(String),
+    Deferred(Box<DeferredContent>),
+}
+impl PageContent {
+    /// Gets the content, rendering it if that hasn't been done yet.
+    fn render(self) -> Result<String> {
+        match self {
+            Self::Rendered(content) => Ok(content),
+            Self::Deferred(deferred) => deferred.render(),
+        }
+    }
+}
+
+/// The content of a streamed page, which is rendered once the rest of the page has been sent. Until then, this holds the render permit of
+/// the page's template, and the render is guarded and reported on like any other (panics become errors, the template's circuit breaker
+/// records the outcome, and the time of the whole render is reported).
+struct DeferredContent {
+    template: Template<SsrNode>,
+    translator: Rc<Translator>,
+    // This must not contain the locale
+    raw_path: String,
+    locale: String,
+    page_data: PageData,
+    hook_req: Request,
+    pending: PendingRender,
+}
+impl DeferredContent {
+    fn render(self) -> Result<String> {
+        let template_path = self.template.get_path();
+        let path = match self.raw_path.is_empty() {
+            true => "index",
+            false => self.raw_path.as_str(),
+        };
+        let state = self.page_data.state.clone();
+        let res = catch_render_panic_sync(&template_path, &self.raw_path, || {
+            let html =
+                self.template
+                    .render_html_str(state.clone(), Rc::clone(&self.translator), path);
+            Ok(self
+                .template
+                .after_render(html, path, state.as_deref(), &self.hook_req))
+        });
+        if let Some(breaker) = self.template.get_circuit_breaker() {
+            match &res {
+                Ok(content) => {
+                    let mut page_data = self.page_data.clone();
+                    page_data.content = content.to_string();
+                    breaker.record_success(&template_path, &self.locale, &self.raw_path, &page_data)
+                }
+                Err(_) => breaker.record_failure(&template_path),
             }
         }
+        let render_time = (Utc::now() - self.pending.start)
+            .to_std()
+            .unwrap_or_default();
+        self.template
+            .report_render_time(path, &self.locale, render_time);
+
+        res
     }
+}
 
-    // If we still have nothing, then the page doesn't exist
-    if template_name.is_empty() {
-        bail!(ErrorKind::PageNotFound(path.to_string()))
+/// The data for a page, the content of which might not have been rendered yet if its template is streaming, in which case the data
+/// should be sent in chunks (see [`StreamedPageData::into_chunks`]).
+pub struct StreamedPageData {
+    /// The data for the page, the content of which will be empty if it hasn't been rendered yet.
+    pub page_data: PageData,
+    content: PageContent,
+}
+impl StreamedPageData {
+    /// Gets the chunks of JSON this page's data should be streamed in, which are everything but the page's content, and then the
+    /// content. If the page's template is streaming, its content will only be rendered when the second chunk is needed, so integrations
+    /// should send the first chunk before asking for the next. If rendering the content fails (e.g. if it panics), the second chunk will
+    /// be an error, and integrations should abort the response, since its status code has already been sent.
+    pub fn into_chunks(self) -> impl Iterator<Item = Result<String>> {
+        let mut page_data = serde_json::to_value(&self.page_data).unwrap();
+        // The content goes at the end, so everything else can be sent before it's been rendered
+        if let Some(fields) = page_data.as_object_mut() {
+            fields.remove("content");
+        }
+        let mut start = page_data.to_string();
+        start.pop();
+        start.push_str(r#","content":"#);
+        let content = self.content;
+        std::iter::once(Ok(start)).chain(std::iter::once_with(move || {
+            content
+                .render()
+                .map(|content| format!("{}}}", serde_json::to_string(&content).unwrap()))
+        }))
+    }
+}
+impl std::fmt::Debug for StreamedPageData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamedPageData")
+            .field("page_data", &self.page_data)
+            .field(
+                "deferred",
+                &matches!(self.content, PageContent::Deferred(_)),
+            )
+            .finish()
     }
+}
 
-    // Get the template to use
-    let template = templates.get(&template_name);
-    let template = match template {
-        Some(template) => template,
-        None => bail!(ErrorKind::PageNotFound(path.to_string())),
+/// Renders the given page with the given template, which is the actual logic of [`get_page_for_template`]. If `defer_content` is `true`,
+/// the content of a page rendered at request-time will be left for the caller to render (and time), and this will say that it was.
+// TODO possible further optimizations on this for futures?
+async fn render_page(
+    // This must not contain the locale
+    raw_path: &str,
+    locale: &str,
+    template: &Template<SsrNode>,
+    req: Request,
+    config_manager: &impl ConfigManager,
+    translations_manager: &impl TranslationsManager,
+    defer_content: bool,
+) -> Result<(PageData, bool)> {
+    // We time everything so the template can report slow renders (`Instant` isn't available on every target we can serve from)
+    let render_start = Utc::now();
+    let mut path = raw_path;
+    // If the path is empty, we're looking for the special `index` page
+    if path.is_empty() {
+        path = "index";
+    }
+    // Get a translator for this locale (for sanity we hope the manager is caching)
+    let translator = Rc::new(
+        translations_manager
+            .get_translator_for_locale(locale.to_string())
+            .await?,
+    );
+    // Remove `/` from the path by encoding it as a URL (that's what we store) and put it in the locale's directory
+    let path_encoded = get_page_artifact_name(locale, path);
+    // Get any flash message sent to this page (the server integration will clear it on the client)
+    let flash = get_flash(&req);
+    // The request is consumed by the request state strategy, so we keep a copy for the template's post-processing hook if it has one
+    let hook_req = match template.has_render_hooks() {
+        true => Some(clone_req(&req)),
+        false => None,
     };
 
     // Only a single string of HTML is needed, and it will be overridden if necessary (priorities system)
     let mut html: String = String::new();
+    // The same applies to the HTML to be injected into the document `<head>`
+    let mut head: String = String::new();
     // Multiple rendering strategies may need to amalgamate different states
     let mut states: States = States::new();
 
@@ -232,9 +841,12 @@ pub async fn get_page(
             match html_opt {
                 // It's cached
                 Some(html_val) => {
-                    // Check if we need to revalidate
-                    if should_revalidate(template, &path_encoded, config_manager).await? {
-                        let (html_val, state) = revalidate(
+                    // Check if we need to revalidate (or re-render the page because its state is stale)
+                    if has_stale_schema(template, &path_encoded, config_manager).await
+                        || should_revalidate(template, path, &path_encoded, &req, config_manager)
+                            .await?
+                    {
+                        let (html_val, head_val, state) = revalidate(
                             template,
                             Rc::clone(&translator),
                             path,
@@ -244,37 +856,88 @@ pub async fn get_page(
                         .await?;
                         // Build-time generated HTML is the lowest priority, so we'll only set it if nothing else already has
                         if html.is_empty() {
-                            html = html_val
+                            html = html_val;
+                            head = head_val;
                         }
                         states.build_state = state;
                     } else {
                         // Build-time generated HTML is the lowest priority, so we'll only set it if nothing else already has
                         if html.is_empty() {
-                            html = html_val
+                            html = html_val;
+                            // Get the static head (it's fine for this to be empty)
+                            head = match read_page_artifact(
+                                config_manager,
+                                &path_encoded,
+                                "head.html",
+                            )
+                            .await
+                            {
+                                Ok(head) => head,
+                                Err(_) => String::new(),
+                            };
                         }
                         // Get the static JSON (if it exists, but it should)
-                        states.build_state = match config_manager
-                            .read(&format!("static/{}.json", path_encoded))
-                            .await
-                        {
-                            Ok(state) => Some(state),
-                            Err(_) => None,
-                        };
+                        states.build_state =
+                            match read_page_artifact(config_manager, &path_encoded, "json").await {
+                                Ok(state) => Some(state),
+                                Err(_) => None,
+                            };
                     }
                 }
+                // It's not cached, but the template has a placeholder to show while we generate it on a later request
+                None if template.has_incremental_fallback() && allows_fallback(&req) => {
+                    let render_ctx = Signal::new(RenderCtx {
+                        path: path.to_string(),
+                        locale: translator.get_locale(),
+                        backend: RenderBackend::Server,
+                        alternates: BTreeMap::new(),
+                    });
+                    let html = sycamore::render_to_string(|| {
+                        template.render_fallback(
+                            Rc::clone(&translator),
+                            ScopedStates::new(),
+                            Signal::new(None),
+                            render_ctx,
+                        )
+                    });
+                    // Any flash message will be delivered with the real page
+                    return Ok((
+                        PageData {
+                            content: html,
+                            head: String::new(),
+                            flash: None,
+                            state: None,
+                            fallback: true,
+                            redirect: None,
+                            alternates: BTreeMap::new(),
+                        },
+                        false,
+                    ));
+                }
                 // It's not cached
                 None => {
                     // We need to generate and cache this page for future usage
-                    let state = Some(template.get_build_state(path.to_string()).await?);
-                    let html_val = sycamore::render_to_string(|| {
-                        template.render_for_template(state.clone(), Rc::clone(&translator))
-                    });
+                    let build_state = template
+                        .get_build_state(path.to_string(), translator.get_locale())
+                        .await?;
+                    // If the page is a redirect or doesn't exist, there's nothing to cache (we'll ask again next time)
+                    if let Some(redirect) = build_state.redirect {
+                        return Ok((PageData::for_redirect(redirect), false));
+                    }
+                    if build_state.not_found {
+                        bail!(ErrorKind::PageNotFound(path.to_string()))
+                    }
+                    let revalidate_interval =
+                        template.get_revalidate_interval_for_page(&build_state)?;
+                    let state = Some(build_state.state);
+                    let html_val =
+                        template.render_html_str(state.clone(), Rc::clone(&translator), path);
+                    let head_val = template.render_head_str(state.clone());
                     // Handle revalidation, we need to parse any given time strings into datetimes
                     // We don't need to worry about revalidation that operates by logic, that's request-time only
                     // Obviously we don't need to revalidate now, we just created it
-                    if template.revalidates_with_time() {
-                        let datetime_to_revalidate =
-                            decode_time_str(&template.get_revalidate_interval().unwrap())?;
+                    if let Some(revalidate_interval) = revalidate_interval {
+                        let datetime_to_revalidate = get_datetime_after(revalidate_interval)?;
                         // Write that to a static file, we'll update it every time we revalidate
                         // Note that this runs for every path generated, so it's fully usable with ISR
                         config_manager
@@ -295,18 +958,35 @@ pub async fn get_page(
                     config_manager
                         .write(&format!("static/{}.html", path_encoded), &html_val)
                         .await?;
+                    config_manager
+                        .write(&format!("static/{}.head.html", path_encoded), &head_val)
+                        .await?;
+                    write_state_schema(template, &path_encoded, config_manager).await?;
+                    write_render_time(&path_encoded, config_manager).await?;
+                    record_incremental_page(locale, path);
 
                     states.build_state = state;
                     // Build-time generated HTML is the lowest priority, so we'll only set it if nothing else already has
                     if html.is_empty() {
-                        html = html_val
+                        html = html_val;
+                        head = head_val;
                     }
                 }
             }
         } else {
-            // Handle if we need to revalidate
-            if should_revalidate(template, &path_encoded, config_manager).await? {
-                let (html_val, state) = revalidate(
+            // Build paths can be exclusive to some locales, so a page that wasn't built for this one doesn't exist in it
+            if let Err(crate::config_manager::Error(
+                crate::config_manager::ErrorKind::NotFound(_),
+                _,
+            )) = read_page_artifact(config_manager, &path_encoded, "html").await
+            {
+                bail!(ErrorKind::PageNotFound(path.to_string()))
+            }
+            // Handle if we need to revalidate (or re-render the page because its state is stale)
+            if has_stale_schema(template, &path_encoded, config_manager).await
+                || should_revalidate(template, path, &path_encoded, &req, config_manager).await?
+            {
+                let (html_val, head_val, state) = revalidate(
                     template,
                     Rc::clone(&translator),
                     path,
@@ -316,46 +996,420 @@ pub async fn get_page(
                 .await?;
                 // Build-time generated HTML is the lowest priority, so we'll only set it if nothing else already has
                 if html.is_empty() {
-                    html = html_val
+                    html = html_val;
+                    head = head_val;
                 }
                 states.build_state = state;
             } else {
-                let (html_val, state) = render_build_state(&path_encoded, config_manager).await?;
+                let (html_val, head_val, state) =
+                    render_build_state(&path_encoded, config_manager).await?;
                 // Build-time generated HTML is the lowest priority, so we'll only set it if nothing else already has
                 if html.is_empty() {
-                    html = html_val
+                    html = html_val;
+                    head = head_val;
                 }
                 states.build_state = state;
             }
         }
     }
     // Handle request state
+    let mut request_state = None;
     if template.uses_request_state() {
-        let (html_val, state) =
-            render_request_state(template, Rc::clone(&translator), path, req).await?;
-        // Request-time HTML always overrides anything generated at build-time or incrementally (this has more information)
-        html = html_val;
-        states.request_state = state;
+        // Generate the initial state (this may generate an error, but there's no file that can't exist)
+        let outcome = template
+            .get_request_state(path.to_string(), translator.get_locale(), req)
+            .await?;
+        let state = match outcome {
+            RenderOutcome::Render(state) => state,
+            RenderOutcome::Redirect(redirect) => {
+                return Ok((PageData::for_redirect(redirect), false))
+            }
+        };
+        // We'll render with this once the template's hooks have been run
+        request_state = Some(state.clone());
+        states.request_state = Some(state);
     }
 
     // Amalgamate the states
-    // If the user has defined custom logic for this, we'll defer to that
-    // Otherwise we go as with HTML, request trumps build
+    // If the user has defined custom logic or a built-in strategy for this, we'll defer to that
+    // Otherwise we go as with HTML, request trumps build (templates are validated, so this should never actually happen)
     // Of course, if only one state was defined, we'll just use that regardless (so `None` prioritization is impossible)
     let state: Option<String>;
     if !states.both_defined() {
         state = states.get_defined()?;
     } else if template.can_amalgamate_states() {
-        state = template.amalgamate_states(states)?;
+        state = template.amalgamate_states(states).await?;
     } else {
         state = states.request_state;
     }
 
+    let mut deferred = false;
+    if let Some(request_state) = request_state {
+        // Request-time HTML always overrides anything generated at build-time or incrementally (this has more information)
+        if defer_content {
+            html = String::new();
+            head = template.render_head_str(Some(request_state));
+            deferred = true;
+        } else {
+            let (html_val, head_val, _) =
+                render_request_state(template, Rc::clone(&translator), path, request_state);
+            html = html_val;
+            head = head_val;
+        }
+    }
+    if let (Some(hook_req), false) = (&hook_req, deferred) {
+        html = template.after_render(html, path, state.as_deref(), hook_req);
+    }
+
     // Combine everything into one JSON object
     let res = PageData {
         content: html,
+        head,
+        flash,
         state,
+        fallback: false,
+        redirect: None,
+        alternates: get_alternates(raw_path, locale),
     };
+    if !deferred {
+        let render_time = (Utc::now() - render_start).to_std().unwrap_or_default();
+        template.report_render_time(path, locale, render_time);
+    }
+
+    Ok((res, deferred))
+}
+
+/// The `id` of the element a page is prerendered into in the app shell on the initial load, which the app moves the page out of once it's
+/// hydrating it.
+pub const INITIAL_CONTENT_ID: &str = "__perseus_initial";
+/// The `id` of the `<script>` element the data of the page prerendered into the app shell on the initial load is put in (without its
+/// content, which is already in the shell), so that the app can hydrate the page without fetching its data again. Its `data-page`
+/// attribute is the locale and path of the page.
+pub const INITIAL_PAGE_DATA_ID: &str = "__perseus_page_data";
+/// The `name` of the `<meta>` element that's put into the app shell on the initial load when the server was built in development, which
+/// tells the app to reload its translations whenever they're edited.
+pub const DEV_RELOAD_META: &str = "__perseus_dev_reload";
+
+/// The app shell with a page prerendered into it, which can be sent all at once, or streamed in chunks (see [`InitialPage::into_chunks`]).
+pub struct InitialPage {
+    /// Everything in the app shell before the page's content.
+    start: String,
+    content: PageContent,
+    /// Everything in the app shell after the page's content.
+    end: String,
+    /// The headers the page's template wants sent with it.
+    headers: HeaderMap,
+    /// The page's surrogate keys.
+    surrogate_keys: Vec<String>,
+}
+impl InitialPage {
+    /// Gets the HTTP headers the page's template wants sent with it (from `.set_headers_fn()`), which should be sent along with the app
+    /// shell so that they apply to the initial load of the page as well as to its data.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    /// Gets the surrogate keys of the page, which integrations configured with a `SurrogateKeyHeader` should send with it, like they do
+    /// with its data.
+    pub fn surrogate_keys(&self) -> &[String] {
+        &self.surrogate_keys
+    }
+    /// Gets the chunks this page should be streamed in, which are the app shell up to where the page's content goes, the content, and the
+    /// rest of the app shell. If the page's template is streaming, its content will only be rendered when the second chunk is needed, so
+    /// integrations should send the first chunk before asking for the next. If rendering the content fails (e.g. if it panics), the second
+    /// chunk will be an error, and integrations should abort the response, since its status code has already been sent.
+    pub fn into_chunks(self) -> impl Iterator<Item = Result<String>> {
+        let content = self.content;
+        std::iter::once(Ok(self.start))
+            .chain(std::iter::once_with(move || content.render()))
+            .chain(std::iter::once(Ok(self.end)))
+    }
+    /// Gets the whole page as a single string, rendering its content if that hasn't been done yet (which could fail).
+    pub fn into_string(self) -> Result<String> {
+        self.into_chunks().collect()
+    }
+}
+impl std::fmt::Debug for InitialPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InitialPage")
+            .field("start", &self.start)
+            .field(
+                "deferred",
+                &matches!(self.content, PageContent::Deferred(_)),
+            )
+            .field("end", &self.end)
+            .field("headers", &self.headers)
+            .field("surrogate_keys", &self.surrogate_keys)
+            .finish()
+    }
+}
+
+/// What should be sent in response to a request for a page itself (rather than its data), which is the initial load of the app.
+#[derive(Debug)]
+pub enum InitialLoad {
+    /// The app shell with the page prerendered into it.
+    Page(InitialPage),
+    /// A redirect in the app's static redirects, requested by the page's state functions, or to the user's locale if the path didn't have
+    /// one, which can be followed without JavaScript.
+    Redirect(Redirect),
+    /// The app shell without any page in it, and the status code to send it with. The app will render the appropriate error page once
+    /// it's running.
+    Shell(String, u16),
+}
+
+/// Gets what to send for the page at the given URL path (like `/en-US/about`) on the initial load. The page will be prerendered into
+/// the given app shell (the contents of the app's `index.html` file), so that it can be read and navigated with ordinary links even if
+/// JavaScript is disabled or the app's bundle fails to load. Any given `<noscript>` content will be put at the top of the body. If the app
+/// uses i18n, the locale is found with its URL strategy, and, if that needs one in the path and there isn't a supported one, the user will
+/// be redirected to the path in the locale negotiated from their `Accept-Language` header (or the default locale). Paths in the given static redirects are redirected before anything else. If the page
+/// can't be rendered, the app shell will be sent on its own.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_initial_load(
+    url_path: &str,
+    index: &str,
+    mut req: Request,
+    render_cfg: &HashMap<String, String>,
+    templates: &TemplateMap<SsrNode>,
+    locales: &Locales,
+    redirects: &Redirects,
+    noscript: Option<&str>,
+    config_manager: &impl ConfigManager,
+    translations_manager: &impl TranslationsManager,
+) -> InitialLoad {
+    let url_path = url_path.trim_matches('/');
+    if let Some(redirect) = redirects.get(url_path, locales) {
+        return InitialLoad::Redirect(redirect);
+    }
+    let (locale, path) = match locales.using_i18n {
+        // The app's URL strategy decides where the locale is (it might be in the host or the query string)
+        true => {
+            let host = req
+                .headers()
+                .get(http::header::HOST)
+                .and_then(|header| header.to_str().ok())
+                .or_else(|| req.uri().authority().map(|authority| authority.as_str()));
+            match locales
+                .url_strategy
+                .get_locale(url_path, host, req.uri().query(), locales)
+            {
+                Some((locale, path)) => (locale, path),
+                None => {
+                    let accept_language = req
+                        .headers()
+                        .get(http::header::ACCEPT_LANGUAGE)
+                        .and_then(|header| header.to_str().ok())
+                        .unwrap_or("");
+                    let locale = detect_locale_from_header(accept_language, locales);
+                    let to = match req.uri().query() {
+                        Some(query) => format!("{}?{}", url_path, query),
+                        None => url_path.to_string(),
+                    };
+                    let to = UrlBuilder::new(to).locale(locale).build_with(locales);
+                    return InitialLoad::Redirect(Redirect::new(to, 302));
+                }
+            }
+        }
+        false => (locales.default.to_string(), url_path),
+    };
+    // Without JavaScript, a placeholder would never be replaced with the real page
+    disallow_fallback(&mut req);
+    let template = match get_template_for_path(path, render_cfg, templates) {
+        Ok(template) => template,
+        Err(err) => return get_initial_shell(index, &locale, noscript, &err),
+    };
+    // A streaming template's content is rendered once the rest of the page has been sent
+    let page_data = get_streamed_page_for_template(
+        path,
+        &locale,
+        template,
+        req,
+        config_manager,
+        translations_manager,
+    )
+    .await;
+    let StreamedPageData { page_data, content } = match page_data {
+        Ok(StreamedPageData {
+            page_data:
+                PageData {
+                    redirect: Some(redirect),
+                    ..
+                },
+            ..
+        }) => return InitialLoad::Redirect(redirect),
+        Ok(streamed) => streamed,
+        Err(err) => return get_initial_shell(index, &locale, noscript, &err),
+    };
+
+    let mut headers = template.get_headers(page_data.state.clone());
+    // The app hydrates the page from the data we put in the shell, so any flash message is delivered with it
+    if page_data.flash.is_some() {
+        clear_flash(&mut headers);
+    }
+    let surrogate_keys = template.get_surrogate_keys(path, &locale, page_data.state.clone());
+
+    let (start, end) = interpolate_page(index, &locale, Some((path, &page_data)), noscript);
+    InitialLoad::Page(match end {
+        Some(end) => InitialPage {
+            start,
+            content,
+            end,
+            headers,
+            surrogate_keys,
+        },
+        // If the app shell has no body, there's nowhere to put the page
+        None => InitialPage {
+            start,
+            content: PageContent::Rendered(String::new()),
+            end: String::new(),
+            headers,
+            surrogate_keys,
+        },
+    })
+}
+
+/// Gets the app shell to send on its own on the initial load of a page that couldn't be rendered because of the given error.
+fn get_initial_shell(
+    index: &str,
+    locale: &str,
+    noscript: Option<&str>,
+    err: &Error,
+) -> InitialLoad {
+    let (start, end) = interpolate_page(index, locale, None, noscript);
+    InitialLoad::Shell(start + &end.unwrap_or_default(), err_to_status_code(err))
+}
+
+/// Makes the given request ask for the real version of a page that hasn't been generated yet, rather than its placeholder.
+fn disallow_fallback(req: &mut Request) {
+    let query = match req.uri().query() {
+        Some(query) => format!("{}&fallback=false", query),
+        None => "fallback=false".to_string(),
+    };
+    if let Ok(uri) = format!("{}?{}", req.uri().path(), query).parse() {
+        *req.uri_mut() = uri;
+    }
+}
+
+/// Removes the given attribute from the given tag (which should include everything between its `<` and `>`), if it's there.
+fn remove_attr(tag: &str, name: &str) -> String {
+    let mut tag = tag.to_string();
+    let start = tag
+        .to_ascii_lowercase()
+        .find(&format!(" {}=", name))
+        .map(|start| (start, start + name.len() + 2));
+    if let Some((start, val_start)) = start {
+        let val_end = match tag[val_start..].chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => tag[val_start + 1..]
+                .find(quote)
+                .map(|end| val_start + end + 2),
+            _ => tag[val_start..]
+                .find(|c: char| c.is_whitespace() || c == '>')
+                .map(|end| val_start + end),
+        };
+        let val_end = val_end.unwrap_or_else(|| tag.len());
+        tag.replace_range(start..val_end, "");
+    }
+
+    tag
+}
+
+/// Sets the `lang` and `dir` attributes of the `<html>` element in the given app shell for the given locale, replacing any that are
+/// already there (since `index.html` is shared by every locale).
+fn set_document_locale(html: &mut String, locale: &str) {
+    let tag_start = match html.find("<html") {
+        Some(tag_start) => tag_start,
+        None => return,
+    };
+    let tag_end = match html[tag_start..].find('>') {
+        Some(tag_end) => tag_start + tag_end,
+        None => return,
+    };
+    let tag = remove_attr(&remove_attr(&html[tag_start..tag_end], "lang"), "dir");
+    let tag = format!(
+        r#"{} lang="{}" dir="{}""#,
+        tag,
+        locale,
+        TextDirection::from_locale(locale)
+    );
+    html.replace_range(tag_start..tag_end, &tag);
+}
+
+/// Interpolates the given page at the given path (if there is one) and `<noscript>` content (if there is any) into the given app shell for
+/// the given locale. The page's head is put between the markers the app uses to replace it when navigating, its data is put after where
+/// its content goes so that the app can hydrate it straight away, and the document's `lang` and `dir` are set for the locale. This
+/// returns the app shell split at the top of its body, where the page's content goes, if it has a body.
+fn interpolate_page(
+    index: &str,
+    locale: &str,
+    page: Option<(&str, &PageData)>,
+    noscript: Option<&str>,
+) -> (String, Option<String>) {
+    let page_data = page.map(|(_, page_data)| page_data);
+    let mut html = index.to_string();
+    set_document_locale(&mut html, locale);
+    // Only development servers can tell the app when translations have been edited
+    if let (true, Some(head_end)) = (cfg!(debug_assertions), html.find("</head>")) {
+        html.insert_str(head_end, &format!(r#"<meta name="{}">"#, DEV_RELOAD_META));
+    }
+    if let (Some(page_data), Some(head_end)) = (page_data, html.find("</head>")) {
+        html.insert_str(
+            head_end,
+            &format!(
+                r#"<meta itemprop="__perseus_head_start">{}<meta itemprop="__perseus_head_end">"#,
+                page_data.head
+            ),
+        );
+    }
+    // Search engines should know where the page is in other locales if its path is different in them
+    if let (Some(page_data), Some(head_end)) = (page_data, html.find("</head>")) {
+        let links: String = page_data
+            .alternates
+            .iter()
+            .map(|(alt_locale, alt_path)| {
+                let href = UrlBuilder::new(alt_path.to_string())
+                    .locale(alt_locale.to_string())
+                    .switching_locale(true)
+                    .build();
+                format!(
+                    r#"<link rel="alternate" hreflang="{}" href="{}">"#,
+                    alt_locale,
+                    href.replace('&', "&amp;").replace('"', "&quot;")
+                )
+            })
+            .collect();
+        html.insert_str(head_end, &links);
+    }
+    let body_start = html
+        .find("<body")
+        .and_then(|start| html[start..].find('>').map(|end| start + end + 1));
+    let body_start = match body_start {
+        Some(body_start) => body_start,
+        None => return (html, None),
+    };
+    let mut end = html.split_off(body_start);
+    if let Some(noscript) = noscript {
+        html.push_str(&format!("<noscript>{}</noscript>", noscript));
+    }
+    if let Some((path, page_data)) = page {
+        html.push_str(&format!(r#"<div id="{}">"#, INITIAL_CONTENT_ID));
+        // The content is already in the shell, so there's no need to send it twice
+        let page_data = PageData {
+            content: String::new(),
+            ..page_data.clone()
+        };
+        // This is JSON, so `<` can only be in strings, where escaping it stops anything from closing the `<script>`
+        let page_data = serde_json::to_string(&page_data)
+            .unwrap()
+            .replace('<', "\\u003c");
+        let page_key = format!("{}/{}", locale, path)
+            .replace('&', "&amp;")
+            .replace('"', "&quot;");
+        end.insert_str(
+            0,
+            &format!(
+                r#"</div><script type="application/json" id="{}" data-page="{}">{}</script>"#,
+                INITIAL_PAGE_DATA_ID, page_key, page_data
+            ),
+        );
+    }
 
-    Ok(res)
+    (html, Some(end))
 }
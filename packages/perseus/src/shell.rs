@@ -1,13 +1,19 @@
+use crate::embed::{is_embedded, resolve_url};
 use crate::errors::*;
-use crate::serve::PageData;
-use crate::template::Template;
+use crate::flash::FlashMessage;
+use crate::roots::clear_aux_roots;
+use crate::serve::{PageData, DEV_RELOAD_META, INITIAL_CONTENT_ID, INITIAL_PAGE_DATA_ID};
+use crate::template::{RenderBackend, RenderCtx, Template};
 use crate::ClientTranslationsManager;
+use crate::ScopedStates;
 use crate::Translator;
+use crate::UrlBuilder;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 use sycamore::prelude::Template as SycamoreTemplate;
 use sycamore::prelude::*;
+use sycamore::rx::Signal;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
@@ -18,7 +24,9 @@ pub(crate) async fn fetch(url: &str) -> Result<Option<String>> {
     let mut opts = RequestInit::new();
     opts.method("GET").mode(RequestMode::Cors);
 
-    let request = Request::new_with_str_and_init(url, &opts).map_err(js_err_handler)?;
+    // Embedded apps load everything from their own server, not the host page's
+    let request =
+        Request::new_with_str_and_init(&resolve_url(url), &opts).map_err(js_err_handler)?;
 
     let window = web_sys::window().unwrap();
     // Get the response as a future and await it
@@ -53,6 +61,42 @@ pub(crate) async fn fetch(url: &str) -> Result<Option<String>> {
     }
 }
 
+/// The event dispatched on the `document` when the app shell starts loading a page, with the page's `path` (without the locale) and
+/// `locale` as its `detail`.
+pub const BEGIN_LOAD_EVENT: &str = "perseus:begin-load";
+/// The event dispatched on the `document` when a page has been rendered (or hydrated) and can be interacted with, with the page's `path`
+/// (without the locale) and `locale` as its `detail`. This is also dispatched when a reused template is updated with a new page's state.
+pub const PAGE_READY_EVENT: &str = "perseus:page-ready";
+/// The event dispatched on the `document` when the app shell renders an error page, with the `url` that caused it, the `status` code,
+/// and the error `message` as its `detail`.
+pub const ERROR_EVENT: &str = "perseus:error";
+
+/// Dispatches a `CustomEvent` with the given name and detail on the `document`, so that external scripts can follow what the app shell
+/// is doing.
+fn dispatch_shell_event(name: &str, detail: serde_json::Value) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let mut init = web_sys::CustomEventInit::new();
+    init.detail(&JsValue::from_serde(&detail).unwrap_or(JsValue::NULL));
+    // Nobody listening to shell events should be able to break the app, so failures are ignored
+    if let Ok(event) = web_sys::CustomEvent::new_with_event_init_dict(name, &init) {
+        let _ = document.dispatch_event(&event);
+    }
+}
+/// Dispatches the event for the given page starting to load.
+fn dispatch_begin_load(ctx: &RenderCtx) {
+    dispatch_shell_event(
+        BEGIN_LOAD_EVENT,
+        serde_json::json!({ "path": ctx.path, "locale": ctx.locale }),
+    );
+}
+/// Dispatches the event for the given page being ready.
+fn dispatch_page_ready(ctx: &RenderCtx) {
+    dispatch_shell_event(
+        PAGE_READY_EVENT,
+        serde_json::json!({ "path": ctx.path, "locale": ctx.locale }),
+    );
+}
+
 /// The callback to a template the user must provide for error pages. This is passed the status code, the error message, the URL of the
 /// problematic asset, and a translator if one is available . Many error pages are generated when a translator is not available or
 /// couldn't be instantiated, so you'll need to rely on symbols or the like in these cases.
@@ -97,6 +141,7 @@ impl ErrorPages {
             || template_fn(url, status, err, translator),
             &container.get::<DomNode>().inner_element(),
         );
+        dispatch_error(url, status, err);
     }
     /// Gets the template for a page without rendering it into a container.
     pub fn get_template_for_page(
@@ -112,13 +157,704 @@ impl ErrorPages {
             true => self.status_pages.get(status).unwrap(),
             false => &self.fallback,
         };
+        dispatch_error(url, status, err);
 
         template_fn(url, status, err, translator)
     }
 }
 
+/// Dispatches the event for an error page being rendered.
+fn dispatch_error(url: &str, status: &u16, err: &str) {
+    dispatch_shell_event(
+        ERROR_EVENT,
+        serde_json::json!({ "url": url, "status": status, "message": err }),
+    );
+}
+
+/// Keeps track of the template the app shell last mounted in *reuse component* mode, so that navigating to another page of that template
+/// can update its state in place rather than rendering it again. This also keeps track of the last parent template mounted around a
+/// nested template, so that navigating between its nested templates only swaps the view in its outlet, and of any listeners refetching the
+/// state of the current page. One of these should be created for the whole app and shared between
+/// every invocation of the app shell.
+#[derive(Default)]
+pub struct MountedTemplate {
+    /// The root path of the mounted template and the locale it was rendered in.
+    key: Option<(String, String)>,
+    /// The signal behind the reactive state provided to the mounted template.
+    state: Option<Signal<Option<String>>>,
+    /// The container the template was rendered into.
+    container: Option<NodeRef<DomNode>>,
+    /// The template holding that container, which we give back to the router so the mounted DOM is kept.
+    container_template: Option<SycamoreTemplate<DomNode>>,
+    /// The root path of the mounted parent template of a nested template and the locale it was rendered in.
+    parent_key: Option<(String, String)>,
+    /// The signal behind the view in the outlet of the mounted parent template.
+    outlet: Option<Signal<SycamoreTemplate<DomNode>>>,
+    /// The locale and path of the last page rendered in full and the template holding it, which parallel routes are rendered on top of.
+    page: Option<(String, SycamoreTemplate<DomNode>)>,
+    /// The parallel route currently being rendered as an overlay on top of that page, if there is one.
+    overlay: Option<Overlay>,
+    /// The locale and path of the page most recently navigated to.
+    current: Option<String>,
+    /// The listeners refetching the state of the current page, if its template asked for that.
+    refetcher: Option<StateRefetcher>,
+    /// The signal behind the flash message provided to every template, which is updated whenever a new page's data is fetched.
+    flash: Option<FlashMessage>,
+    /// The signal behind the render context provided to every template, which is updated along with the flash message.
+    render_ctx: Option<Signal<RenderCtx>>,
+}
+impl MountedTemplate {
+    /// Creates a new tracker with nothing mounted yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Forgets the template (or parent template) that was mounted, because its container has been replaced, so that a later navigation
+    /// doesn't try to reuse it.
+    fn unmount(&mut self) {
+        self.key = None;
+        self.state = None;
+        self.parent_key = None;
+        self.outlet = None;
+        self.container = None;
+        self.container_template = None;
+    }
+    /// Closes the overlay on top of the current page if there is one, clearing its container so that nothing rendered in it is left
+    /// behind (even if the page in it is still loading). This returns the render context of the page underneath it.
+    fn close_overlay(&mut self) -> Option<RenderCtx> {
+        let overlay = self.overlay.take()?;
+        if let Some(container) = overlay.container.try_get::<DomNode>() {
+            container
+                .unchecked_into::<web_sys::Element>()
+                .set_inner_html("");
+        }
+
+        Some(overlay.underlying_ctx)
+    }
+    /// Gets the template holding the page with the given key (its locale and path) if that's the page that was last rendered in full.
+    fn get_page(&self, page_key: &str) -> Option<SycamoreTemplate<DomNode>> {
+        self.page
+            .as_ref()
+            .filter(|(key, _)| key == page_key)
+            .map(|(_, page)| page.clone())
+    }
+    /// Gets the template holding the page that a parallel route with the given key (its locale and path) should be rendered on top of,
+    /// which is the page last rendered in full, unless that's the same route.
+    fn get_underlying_page(&self, page_key: &str) -> Option<SycamoreTemplate<DomNode>> {
+        self.page
+            .as_ref()
+            .filter(|(key, _)| key != page_key)
+            .map(|(_, page)| page.clone())
+    }
+}
+
+/// A parallel route being rendered as an overlay on top of the page last rendered in full.
+struct Overlay {
+    /// The container the parallel route is rendered into.
+    container: NodeRef<DomNode>,
+    /// The render context of the page underneath, which is given back to every template when the overlay is closed by going back to it.
+    underlying_ctx: RenderCtx,
+}
+
+/// Listeners that refetch the state of the current page when the window regains focus and/or on an interval, updating its reactive
+/// state in place. These are removed when this is dropped (which happens when the user navigates to another page).
+struct StateRefetcher {
+    on_focus: Option<Closure<dyn FnMut()>>,
+    on_interval: Option<(i32, Closure<dyn FnMut()>)>,
+}
+impl StateRefetcher {
+    /// Sets up refetching for the page at the given asset URL as the given template asks, feeding new states into the given signal.
+    fn new(template: &Template<DomNode>, asset_url: String, state: Signal<Option<String>>) -> Self {
+        let window = web_sys::window().unwrap();
+        let refetch = Rc::new(move || {
+            wasm_bindgen_futures::spawn_local(cloned!((asset_url, state) => async move {
+                refetch_state(&asset_url, &state).await;
+            }));
+        });
+
+        let on_focus = if template.refetches_on_focus() {
+            let on_focus = Closure::wrap(
+                Box::new(cloned!((refetch) => move || refetch())) as Box<dyn FnMut()>
+            );
+            window
+                .add_event_listener_with_callback("focus", on_focus.as_ref().unchecked_ref())
+                .unwrap();
+            Some(on_focus)
+        } else {
+            None
+        };
+        let on_interval = template.get_refetch_interval().map(|interval| {
+            let on_interval = Closure::wrap(Box::new(move || refetch()) as Box<dyn FnMut()>);
+            let id = window
+                .set_interval_with_callback_and_timeout_and_arguments_0(
+                    on_interval.as_ref().unchecked_ref(),
+                    interval as i32,
+                )
+                .unwrap();
+            (id, on_interval)
+        });
+
+        Self {
+            on_focus,
+            on_interval,
+        }
+    }
+}
+impl Drop for StateRefetcher {
+    fn drop(&mut self) {
+        let window = web_sys::window().unwrap();
+        if let Some(on_focus) = &self.on_focus {
+            // If this fails, the listener is already gone
+            let _ = window
+                .remove_event_listener_with_callback("focus", on_focus.as_ref().unchecked_ref());
+        }
+        if let Some((id, _)) = &self.on_interval {
+            window.clear_interval_with_handle(*id);
+        }
+    }
+}
+
+/// Refetches the state of the page at the given asset URL into the given signal. Failures are ignored, because the page is still showing
+/// a perfectly valid state and we'll try again next time.
+async fn refetch_state(asset_url: &str, state: &Signal<Option<String>>) {
+    if let Ok(Some(page_data_str)) = fetch(asset_url).await {
+        if let Ok(page_data) = serde_json::from_str::<PageData>(&page_data_str) {
+            // We'll only redirect the user on a navigation, not while they're looking at the page
+            if page_data.redirect.is_none() {
+                state.set(page_data.state);
+            }
+        }
+    }
+}
+
+/// Replaces whatever metadata the app shell last injected into the document `<head>` with the given HTML. Injected elements are kept
+/// between two marker `<meta>` tags so we can find them again on the next page.
+fn replace_head(head_str: &str) {
+    // An embedded page doesn't own the host page's metadata
+    if is_embedded() {
+        return;
+    }
+    let document = web_sys::window().unwrap().document().unwrap();
+    let end_selector = "meta[itemprop='__perseus_head_end']";
+    let end = match document.query_selector(end_selector).unwrap() {
+        Some(end) => end,
+        // This is the first page we've rendered, so we need to create the markers
+        None => {
+            let head_elem = document.query_selector("head").unwrap().unwrap();
+            head_elem
+                .insert_adjacent_html(
+                    "beforeend",
+                    r#"<meta itemprop="__perseus_head_start"><meta itemprop="__perseus_head_end">"#,
+                )
+                .unwrap();
+            document.query_selector(end_selector).unwrap().unwrap()
+        }
+    };
+    // Remove everything the last page injected
+    while let Some(prev) = end.previous_sibling() {
+        let is_start = prev
+            .dyn_ref::<web_sys::Element>()
+            .and_then(|elem| elem.get_attribute("itemprop"))
+            .map_or(false, |itemprop| itemprop == "__perseus_head_start");
+        if is_start {
+            break;
+        }
+        prev.parent_node().unwrap().remove_child(&prev).unwrap();
+    }
+    end.insert_adjacent_html("beforebegin", head_str).unwrap();
+}
+
+/// Redirects the user to the given path or URL. Paths within the app are navigated to with the router, and anything else is loaded
+/// directly.
+fn redirect(to: &str) {
+    // Embedded pages don't have the router, so paths within the app go to the full app on its own server
+    if to.starts_with('/') && !to.starts_with("//") && !is_embedded() {
+        sycamore_router::navigate(to);
+    } else {
+        web_sys::window()
+            .unwrap()
+            .location()
+            .set_href(&resolve_url(to))
+            .unwrap();
+    }
+}
+
+/// Redirects the user to the given path or URL from a path in the app's static redirects. This should only be used when the router
+/// matches one of those paths, since it renders nothing.
+pub fn follow_redirect(to: String) -> SycamoreTemplate<DomNode> {
+    redirect(&to);
+    // We'll never actually get here, but we need a sensible return type
+    template! {}
+}
+
+/// Fetches the page data from the given URL, rendering the appropriate error page into the given container if that fails. If the page
+/// redirects somewhere else, the user will be redirected and this will return `None`.
+async fn fetch_page_data(
+    asset_url: &str,
+    error_pages: &ErrorPages,
+    container: &NodeRef<DomNode>,
+) -> Option<PageData> {
+    // If this doesn't exist, then it's a 404 (we went here by explicit navigation, but it may be an unservable ISR page or the like)
+    let page_data_str = fetch(asset_url).await;
+    match page_data_str {
+        Ok(page_data_str) => match page_data_str {
+            Some(page_data_str) => {
+                // All good, deserialize the page data
+                let page_data = serde_json::from_str::<PageData>(&page_data_str);
+                match page_data {
+                    Ok(PageData {
+                        redirect: Some(redirect_to),
+                        ..
+                    }) => {
+                        redirect(&redirect_to.to);
+                        None
+                    }
+                    Ok(page_data) => Some(page_data),
+                    // If the page failed to serialize, an exception has occurred
+                    Err(err) => panic!("page data couldn't be serialized: '{}'", err),
+                }
+            }
+            // No translators ready yet
+            None => {
+                error_pages.render_page(asset_url, &404, "page not found", None, container);
+                None
+            }
+        },
+        Err(err) => match err.kind() {
+            // No translators ready yet
+            ErrorKind::AssetNotOk(url, status, _) => {
+                error_pages.render_page(url, status, &err.to_string(), None, container);
+                None
+            }
+            // No other errors should be returned
+            _ => panic!("expected 'AssetNotOk' error, found other unacceptable error"),
+        },
+    }
+}
+
+/// Gets a translator for the given locale from the given translations manager, rendering the appropriate error page into the given
+/// container if that fails. This will only fetch the translations with the given prefixes if the template has declared any.
+async fn get_translator(
+    locale: &str,
+    prefixes: Option<Vec<String>>,
+    translations_manager: &RefCell<ClientTranslationsManager>,
+    error_pages: &ErrorPages,
+    container: &NodeRef<DomNode>,
+) -> Option<Rc<Translator>> {
+    let mut translations_manager_mut = translations_manager.borrow_mut();
+    // This gets an `Rc<Translator>` that references the translations manager, meaning no cloning of translations
+    let translator = translations_manager_mut
+        .get_translator_for_prefixes(locale, prefixes.as_deref())
+        .await;
+    match translator {
+        Ok(translator) => {
+            // The server sets these on the initial load, but the user might have switched locales since then
+            if let Some(document_element) = web_sys::window()
+                .and_then(|window| window.document())
+                .and_then(|document| document.document_element())
+            {
+                let _ = document_element.set_attribute("lang", &translator.get_locale());
+                let _ = document_element.set_attribute("dir", translator.get_direction().as_str());
+            }
+            Some(translator)
+        }
+        Err(err) => {
+            match err.kind() {
+                // These errors happen because we couldn't get a translator, so they certainly don't get one
+                ErrorKind::AssetNotOk(url, status, _) => {
+                    error_pages.render_page(url, status, &err.to_string(), None, container)
+                }
+                ErrorKind::AssetSerFailed(url, _) => {
+                    error_pages.render_page(url, &500, &err.to_string(), None, container)
+                }
+                ErrorKind::LocaleNotSupported(locale) => error_pages.render_page(
+                    &format!("/{}/...", locale),
+                    &404,
+                    &err.to_string(),
+                    None,
+                    container,
+                ),
+                // No other errors should be returned
+                _ => panic!("expected 'AssetNotOk'/'AssetSerFailed'/'LocaleNotSupported' error, found other unacceptable error"),
+            };
+            None
+        }
+    }
+}
+
+/// Removes the page that was prerendered into the app shell on the initial load (for users without JavaScript). The app shell does this
+/// itself once it's rendered the first page it has to fetch, so that the prerendered page stays visible until then.
+pub fn remove_initial_content() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    if let Some(initial_content) = document.get_element_by_id(INITIAL_CONTENT_ID) {
+        initial_content.remove();
+    }
+}
+
+/// Takes the data the server put in the app shell for the page it prerendered on the initial load, if that was the page with the given
+/// key (its locale and path). This can only be done once, since any later loads of the page should get its latest data.
+fn take_initial_page_data(page_key: &str) -> Option<PageData> {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let script = document.get_element_by_id(INITIAL_PAGE_DATA_ID)?;
+    script.remove();
+    match script.get_attribute("data-page") {
+        Some(initial_key) if initial_key.trim_matches('/') == page_key.trim_matches('/') => {
+            serde_json::from_str(&script.text_content()?).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Moves the page that was prerendered into the app shell on the initial load into the given element, so that it can be hydrated there
+/// without ever disappearing.
+fn adopt_initial_content(container: &web_sys::Element) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    if let Some(initial_content) = document.get_element_by_id(INITIAL_CONTENT_ID) {
+        while let Some(child) = initial_content.first_child() {
+            // This can only fail if the container is inside the initial content, which it never is
+            let _ = container.append_child(&child);
+        }
+        initial_content.remove();
+    }
+}
+
+/// Renders the given view into the given element, hydrating the HTML that was rendered on the server unless the template is client-side
+/// only (in which case there won't be any).
+fn render_or_hydrate(
+    client_side_only: bool,
+    view: impl FnOnce() -> SycamoreTemplate<DomNode>,
+    parent: &web_sys::Node,
+) {
+    match client_side_only {
+        true => sycamore::render_to(view, parent),
+        false => sycamore::hydrate_to(view, parent),
+    }
+}
+
+/// Renders the given page of the given template into the given container, hydrating the HTML already in it unless told otherwise (or
+/// unless the template is client-side only), and records what was mounted so that later navigations can reuse it.
+#[allow(clippy::too_many_arguments)]
+fn mount_page(
+    template: &Template<DomNode>,
+    page_data: PageData,
+    translator: Rc<Translator>,
+    locale: &str,
+    asset_url: String,
+    page_key: &str,
+    container: NodeRef<DomNode>,
+    container_template: SycamoreTemplate<DomNode>,
+    as_overlay: bool,
+    hydrate: bool,
+    mounted_template: &RefCell<MountedTemplate>,
+    scoped_states: ScopedStates,
+    flash: FlashMessage,
+    render_ctx: Signal<RenderCtx>,
+) {
+    let key = (template.get_path(), locale.to_string());
+    let client_side_only = template.is_client_side_only() || !hydrate;
+    // Hydrate that static code using the acquired state
+    // BUG (Sycamore): this will double-render if the component is just text (no nodes)
+    // Templates rendered in an overlay are thrown away when it's closed, so they can't be reused
+    // Nested templates are always mounted in their parent's outlet, where their reactive state is kept instead
+    let reuse = template.reuses_component() && !as_overlay && !template.is_nested();
+    let reactive = template.uses_reactive_state() && !template.is_nested();
+    let refetch = template.refetches_state();
+    if reuse || reactive {
+        // We keep the signal behind the template's reactive state so we can update it on later navigations or refetches
+        let state = Signal::new(page_data.state.clone());
+        render_or_hydrate(
+            client_side_only,
+            || {
+                let view = template.render_for_template_reactive(
+                    page_data.state,
+                    state.clone(),
+                    Rc::clone(&translator),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                );
+                template.render_in_layout(
+                    view,
+                    Rc::clone(&translator),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                )
+            },
+            &container.get::<DomNode>().inner_element(),
+        );
+        let mut mounted = mounted_template.borrow_mut();
+        // The user might have navigated somewhere else while we were fetching everything, in which case this container is already gone
+        let is_current = mounted.current.as_deref() == Some(page_key);
+        if refetch && is_current {
+            mounted.refetcher = Some(StateRefetcher::new(template, asset_url, state.clone()));
+        }
+        if reuse && is_current {
+            // Only one template can be holding the container at a time
+            mounted.parent_key = None;
+            mounted.key = Some(key);
+            mounted.state = Some(state);
+            mounted.container = Some(container);
+            mounted.container_template = Some(container_template);
+        }
+    } else if let (Some(parent_path), false) = (template.get_parent_path(), as_overlay) {
+        // We keep the signal behind the parent template's outlet so we can swap the view on later navigations, and the one behind the
+        // nested template's reactive state so we can refetch it
+        let outlet = Signal::new(template! {});
+        let state = Signal::new(page_data.state.clone());
+        render_or_hydrate(
+            client_side_only,
+            || {
+                outlet.set(template.render_view_with_state(
+                    page_data.state,
+                    state.clone(),
+                    Rc::clone(&translator),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                ));
+                let view = template.render_in_parent(
+                    outlet.handle(),
+                    Rc::clone(&translator),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                );
+                template.render_in_layout(
+                    view,
+                    Rc::clone(&translator),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                )
+            },
+            &container.get::<DomNode>().inner_element(),
+        );
+        let mut mounted = mounted_template.borrow_mut();
+        // The user might have navigated somewhere else while we were fetching everything, in which case this container is already gone
+        if mounted.current.as_deref() == Some(page_key) {
+            if refetch {
+                mounted.refetcher = Some(StateRefetcher::new(template, asset_url, state));
+            }
+            // Only one template can be holding the container at a time
+            mounted.key = None;
+            mounted.parent_key = Some((parent_path, locale.to_string()));
+            mounted.outlet = Some(outlet);
+            mounted.container = Some(container);
+            mounted.container_template = Some(container_template);
+        }
+    } else {
+        render_or_hydrate(
+            client_side_only,
+            // This function provides translator context (and wraps the template in its layout) as needed
+            || {
+                template.render_for_template(
+                    page_data.state,
+                    Rc::clone(&translator),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                )
+            },
+            &container.get::<DomNode>().inner_element(),
+        );
+    }
+    dispatch_page_ready(&render_ctx.get());
+}
+
+thread_local! {
+    /// Switches the page the app shell last rendered into another locale, which is replaced whenever a new page is rendered.
+    static LOCALE_SWITCH: RefCell<Option<Rc<dyn Fn(String)>>> = RefCell::new(None);
+}
+
+/// Sets how the locale of the page the app shell is currently rendering should be switched.
+fn set_locale_switch(switch: Rc<dyn Fn(String)>) {
+    LOCALE_SWITCH.with(|locale_switch| *locale_switch.borrow_mut() = Some(switch));
+}
+
+/// Gets the URL of the page at the given path (without a leading `/`) in the given locale, with the app's URL strategy.
+fn get_locale_url(locale: &str, path: &str) -> String {
+    UrlBuilder::new(path)
+        .locale(locale)
+        .switching_locale(true)
+        .build()
+}
+
+/// Navigates to the given URL, which will be loaded from scratch if it's on another domain (like a locale's own domain).
+fn navigate_to_url(url: &str) {
+    if url.starts_with("//") {
+        let _ = web_sys::window().unwrap().location().set_href(url);
+    } else {
+        sycamore_router::navigate(url);
+    }
+}
+
+/// Switches the page the user is currently looking at into the given locale (which should be one of the app's locales) without reloading
+/// anything else. This fetches the page's state and translations in that locale, swaps the translator provided to its template, and
+/// re-renders the template in place, updating the URL so that the browser's back button returns to the previous locale. Templates will
+/// also see the new locale in their [`RenderCtx`]. If the page is being shown in an overlay as a parallel route, this will just navigate
+/// to it in the new locale.
+///
+/// This only works on the client-side, and does nothing if the app shell hasn't rendered a page yet.
+pub fn switch_locale(locale: &str) {
+    // Navigating renders a new page synchronously, which replaces the switch, so we can't be holding the cell when we call it
+    let switch = LOCALE_SWITCH.with(|locale_switch| locale_switch.borrow().clone());
+    if let Some(switch) = switch {
+        switch(locale.to_string());
+    }
+}
+
+/// Reloads the app's translations whenever they're edited, if the app was served by a development server (which says so in the app
+/// shell). This listens for new revisions of the translations from the server, and re-renders the current page with them in place, so
+/// nothing has to be rebuilt. This does nothing in production or in embedded pages.
+pub fn watch_translations(translations_manager: Rc<RefCell<ClientTranslationsManager>>) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let is_dev = matches!(
+        document.query_selector(&format!("meta[name={}]", DEV_RELOAD_META)),
+        Ok(Some(_))
+    );
+    if !is_dev || is_embedded() {
+        return;
+    }
+    let events = match web_sys::EventSource::new("/.perseus/dev/translations") {
+        Ok(events) => events,
+        Err(_) => return,
+    };
+    // The first revision we get is the one the app started with (the server sends it again if it restarts)
+    let mut revision: Option<String> = None;
+    let on_message = Closure::wrap(Box::new(move |ev: web_sys::MessageEvent| {
+        let new_revision = match ev.data().as_string() {
+            Some(new_revision) => new_revision,
+            None => return,
+        };
+        let prev_revision = revision.replace(new_revision.clone());
+        if prev_revision.is_none() || prev_revision == Some(new_revision) {
+            return;
+        }
+        // If translations are being fetched right now, we'll just let that finish
+        if let Ok(mut translations_manager) = translations_manager.try_borrow_mut() {
+            translations_manager.clear();
+        }
+        // The app shell always sets the document's language to the locale of the current page
+        let locale = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.document_element())
+            .and_then(|html| html.get_attribute("lang"));
+        if let Some(locale) = locale {
+            switch_locale(&locale);
+        }
+    }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+    events.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // This lives as long as the app does
+    on_message.forget();
+}
+
+/// Creates a function that re-renders the given page, which is mounted in the given container, in another locale.
+#[allow(clippy::too_many_arguments)]
+fn in_place_locale_switch(
+    path: String,
+    template: Template<DomNode>,
+    container: NodeRef<DomNode>,
+    container_template: SycamoreTemplate<DomNode>,
+    translations_manager: Rc<RefCell<ClientTranslationsManager>>,
+    error_pages: Rc<ErrorPages>,
+    mounted_template: Rc<RefCell<MountedTemplate>>,
+    scoped_states: ScopedStates,
+    flash: FlashMessage,
+    render_ctx: Signal<RenderCtx>,
+) -> Rc<dyn Fn(String)> {
+    Rc::new(move |locale: String| {
+        // Pages with translated slugs have a different path in each locale
+        let path = render_ctx
+            .get()
+            .alternates
+            .get(&locale)
+            .cloned()
+            .unwrap_or_else(|| path.clone());
+        // Locales on other domains can't be switched to in place
+        let locale_url = get_locale_url(&locale, &path);
+        if locale_url.starts_with("//") {
+            navigate_to_url(&locale_url);
+            return;
+        }
+        // If the user navigates somewhere else while we're fetching the page in the new locale, we'll leave them there
+        let prev_key = mounted_template.borrow().current.clone();
+        wasm_bindgen_futures::spawn_local(
+            cloned!((path, template, container, container_template, translations_manager, error_pages, mounted_template, scoped_states, flash, render_ctx) => async move {
+                let page_key = format!("{}/{}", locale, path);
+                let asset_url = format!("/.perseus/page/{}", page_key);
+                let ctx = RenderCtx {
+                    path,
+                    locale: locale.clone(),
+                    backend: RenderBackend::Client,
+                    alternates: BTreeMap::new(),
+                };
+                dispatch_begin_load(&ctx);
+                // The page stays as it is until the real page in the new locale is ready
+                let page_data = match fetch_page_data(&format!("{}?fallback=false", asset_url), &error_pages, &container).await {
+                    Some(page_data) => page_data,
+                    // The error page has already been rendered
+                    None => return,
+                };
+                let translator = match get_translator(&locale, template.get_translation_prefixes(), &translations_manager, &error_pages, &container).await {
+                    Some(translator) => translator,
+                    // The error page has already been rendered
+                    None => return,
+                };
+                {
+                    let mut mounted = mounted_template.borrow_mut();
+                    if mounted.current != prev_key {
+                        return;
+                    }
+                    // Whatever was mounted in this container is about to be replaced
+                    mounted.current = Some(page_key.clone());
+                    mounted.refetcher = None;
+                    mounted.unmount();
+                    mounted.page = Some((page_key.clone(), container_template.clone()));
+                }
+                // This doesn't trigger the router, which would render the page again from scratch (and the host page owns the URL of an
+                // embedded page)
+                // Re-rendering the page in the same locale (like when translations are reloaded) shouldn't add to the history
+                if !is_embedded() {
+                    let history = web_sys::window().unwrap().history().unwrap();
+                    if locale == render_ctx.get().locale {
+                        history.replace_state_with_url(&JsValue::NULL, "", Some(&locale_url)).unwrap();
+                    } else {
+                        history.push_state_with_url(&JsValue::NULL, "", Some(&locale_url)).unwrap();
+                    }
+                }
+                replace_head(&page_data.head);
+                flash.set(page_data.flash.clone());
+                render_ctx.set(RenderCtx {
+                    alternates: page_data.alternates.clone(),
+                    ..ctx
+                });
+                container.get::<DomNode>().unchecked_into::<web_sys::Element>().set_inner_html("");
+                mount_page(
+                    &template,
+                    page_data,
+                    translator,
+                    &locale,
+                    asset_url,
+                    &page_key,
+                    container,
+                    container_template,
+                    false,
+                    false,
+                    &mounted_template,
+                    scoped_states,
+                    flash,
+                    render_ctx,
+                );
+            }),
+        );
+    })
+}
+
 /// Fetches the information for the given page and renders it. This should be provided the actual path of the page to render (not just the
-/// broader template).
+/// broader template). If the template reuses its component and is already mounted, this will just fetch the new state and update the
+/// mounted template with it. If the template is a parallel route and another page has already been rendered, it will be rendered in an
+/// overlay on top of that page instead of replacing it. The given scoped states should be the same for every invocation, so that they're
+/// kept across navigations.
 // TODO handle exceptions higher up
 pub fn app_shell(
     path: String,
@@ -126,70 +862,338 @@ pub fn app_shell(
     locale: String,
     translations_manager: Rc<RefCell<ClientTranslationsManager>>,
     error_pages: Rc<ErrorPages>,
+    mounted_template: Rc<RefCell<MountedTemplate>>,
+    scoped_states: ScopedStates,
 ) -> SycamoreTemplate<DomNode> {
+    let key = (template.get_path(), locale.clone());
+    let page_key = format!("{}/{}", locale, path);
+    let asset_url = format!("/.perseus/page/{}/{}", locale, path.to_string());
+    // This will be given to the render context signal once the page's data has been fetched
+    let ctx = RenderCtx {
+        path: path.clone(),
+        locale: locale.clone(),
+        backend: RenderBackend::Client,
+        alternates: BTreeMap::new(),
+    };
+    dispatch_begin_load(&ctx);
+    // Anything the previous page rendered outside the main root belonged to it
+    clear_aux_roots();
+    // Whatever we were refetching state for before isn't being shown anymore
+    let (flash, render_ctx) = {
+        let mut mounted = mounted_template.borrow_mut();
+        mounted.current = Some(page_key.clone());
+        mounted.refetcher = None;
+        let flash = mounted
+            .flash
+            .get_or_insert_with(|| Signal::new(None))
+            .clone();
+        let render_ctx = mounted
+            .render_ctx
+            .get_or_insert_with(|| Signal::new(ctx.clone()))
+            .clone();
+        (flash, render_ctx)
+    };
+    // Until we know where this page will be mounted, switching its locale will just navigate to it in the new locale
+    set_locale_switch(Rc::new(cloned!((path) => move |locale: String| {
+        navigate_to_url(&get_locale_url(&locale, &path))
+    })));
+    // Any overlay is closed by navigating anywhere, and, if we're going back to the page underneath it, that's all we need to do
+    let underlying_ctx = mounted_template.borrow_mut().close_overlay();
+    if let Some(underlying_ctx) = &underlying_ctx {
+        let underlying_template = mounted_template.borrow().get_page(&page_key);
+        if let Some(underlying_template) = underlying_template {
+            render_ctx.set(underlying_ctx.clone());
+            dispatch_page_ready(underlying_ctx);
+            return underlying_template;
+        }
+    }
+    // Parallel routes are rendered on top of the previous page on client-side navigations (but as full pages on direct loads)
+    let underlying_template = if template.is_parallel_route() {
+        mounted_template.borrow().get_underlying_page(&page_key)
+    } else {
+        None
+    };
+    let as_overlay = underlying_template.is_some();
+    // Check if this template's parent is already mounted, in which case we only need to swap the view in its outlet
+    if let (Some(parent_path), false) = (template.get_parent_path(), as_overlay) {
+        let mut mounted = mounted_template.borrow_mut();
+        if mounted.parent_key == Some((parent_path, locale.clone())) {
+            if let (Some(outlet), Some(container), Some(container_template)) = (
+                mounted.outlet.clone(),
+                mounted.container.clone(),
+                mounted.container_template.clone(),
+            ) {
+                set_locale_switch(in_place_locale_switch(
+                    path.clone(),
+                    template.clone(),
+                    container.clone(),
+                    container_template.clone(),
+                    Rc::clone(&translations_manager),
+                    Rc::clone(&error_pages),
+                    Rc::clone(&mounted_template),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                ));
+                wasm_bindgen_futures::spawn_local(
+                    cloned!((mounted_template, page_key) => async move {
+                        // The parent template is still showing the previous page, so we'll wait for the real page rather than a placeholder
+                        let page_data = match fetch_page_data(&format!("{}?fallback=false", asset_url), &error_pages, &container).await {
+                            Some(page_data) => page_data,
+                            // An error page has replaced the parent template, so it can't be reused anymore
+                            None => {
+                                mounted_template.borrow_mut().parent_key = None;
+                                return;
+                            }
+                        };
+                        let translator = match get_translator(&locale, template.get_translation_prefixes(), &translations_manager, &error_pages, &container).await {
+                            Some(translator) => translator,
+                            None => {
+                                mounted_template.borrow_mut().parent_key = None;
+                                return;
+                            }
+                        };
+                        replace_head(&page_data.head);
+                        flash.set(page_data.flash);
+                        render_ctx.set(RenderCtx {
+                            alternates: page_data.alternates.clone(),
+                            ..ctx
+                        });
+                        let state = Signal::new(page_data.state.clone());
+                        outlet.set(template.render_view_with_state(page_data.state, state.clone(), translator, scoped_states, flash, render_ctx.clone()));
+                        if template.refetches_state() {
+                            let mut mounted = mounted_template.borrow_mut();
+                            // The user might have navigated somewhere else while we were fetching everything
+                            if mounted.current.as_ref() == Some(&page_key) {
+                                mounted.refetcher = Some(StateRefetcher::new(&template, asset_url, state));
+                            }
+                        }
+                        dispatch_page_ready(&render_ctx.get());
+                    }),
+                );
+                // We give the router the same container, so the parent template stays where it is
+                mounted.page = Some((page_key, container_template.clone()));
+                return container_template;
+            }
+        }
+    }
+    // Check if we can reuse an already mounted template
+    if template.reuses_component() && !as_overlay {
+        let mut mounted = mounted_template.borrow_mut();
+        if mounted.key.as_ref() == Some(&key) {
+            if let (Some(state), Some(container), Some(container_template)) = (
+                mounted.state.clone(),
+                mounted.container.clone(),
+                mounted.container_template.clone(),
+            ) {
+                set_locale_switch(in_place_locale_switch(
+                    path.clone(),
+                    template.clone(),
+                    container.clone(),
+                    container_template.clone(),
+                    Rc::clone(&translations_manager),
+                    Rc::clone(&error_pages),
+                    Rc::clone(&mounted_template),
+                    scoped_states.clone(),
+                    flash.clone(),
+                    render_ctx.clone(),
+                ));
+                wasm_bindgen_futures::spawn_local(
+                    cloned!((mounted_template, asset_url, state) => async move {
+                        // The mounted template is still showing the previous page, so we'll wait for the real page rather than a placeholder
+                        match fetch_page_data(&format!("{}?fallback=false", asset_url), &error_pages, &container).await {
+                            // The template will update itself reactively
+                            Some(page_data) => {
+                                replace_head(&page_data.head);
+                                flash.set(page_data.flash);
+                                render_ctx.set(RenderCtx {
+                                    alternates: page_data.alternates.clone(),
+                                    ..ctx
+                                });
+                                state.set(page_data.state);
+                                dispatch_page_ready(&render_ctx.get());
+                            }
+                            // An error page has replaced the template, so it can't be reused anymore
+                            None => mounted_template.borrow_mut().key = None,
+                        }
+                    }),
+                );
+                if template.refetches_state() {
+                    mounted.refetcher = Some(StateRefetcher::new(&template, asset_url, state));
+                }
+                // We give the router the same container, so the mounted template stays where it is
+                mounted.page = Some((page_key, container_template.clone()));
+                return container_template;
+            }
+        }
+    }
+
     // Get the container as a DOM element
     let container = NodeRef::new();
+    // This is where the static content will be rendered
+    // BUG: white flash of death until Sycamore can suspend the router until the static content is ready
+    let container_template = template! {
+        div(ref = container)
+    };
+    // Pages rendered in an overlay are thrown away when it's closed, so switching their locale just navigates
+    if !as_overlay {
+        // Whatever was mounted before is being replaced by this container (an overlay keeps the page underneath it mounted though)
+        mounted_template.borrow_mut().unmount();
+        set_locale_switch(in_place_locale_switch(
+            path.clone(),
+            template.clone(),
+            container.clone(),
+            container_template.clone(),
+            Rc::clone(&translations_manager),
+            Rc::clone(&error_pages),
+            Rc::clone(&mounted_template),
+            scoped_states.clone(),
+            flash.clone(),
+            render_ctx.clone(),
+        ));
+    }
     // Spawn a Rust futures thread in the background to fetch the static HTML/JSON
-    wasm_bindgen_futures::spawn_local(cloned!((container) => async move {
-        // Get the static page data
-        let asset_url = format!("/.perseus/page/{}/{}", locale, path.to_string());
-        // If this doesn't exist, then it's a 404 (we went here by explicit navigation, but it may be an unservable ISR page or the like)
-        let page_data_str = fetch(&asset_url).await;
-        match page_data_str {
-            Ok(page_data_str) => match page_data_str {
-                Some(page_data_str) => {
-                    // All good, deserialize the page data
-                    let page_data = serde_json::from_str::<PageData>(&page_data_str);
-                    match page_data {
-                        Ok(page_data) => {
-                            // We have the page data ready, render everything
-                            // Interpolate the HTML directly into the document (we'll hydrate it later)
-                            let container_elem = container.get::<DomNode>().unchecked_into::<web_sys::Element>();
-                            container_elem.set_inner_html(&page_data.content);
-
-                            // Now that the user can see something, we can get the translator
-                            let mut translations_manager_mut = translations_manager.borrow_mut();
-                            // This gets an `Rc<Translator>` that references the translations manager, meaning no cloning of translations
-                            let translator = translations_manager_mut.get_translator_for_locale(&locale).await;
-                            let translator = match translator {
-                                Ok(translator) => translator,
-                                Err(err) => match err.kind() {
-                                    // These errors happen because we couldn't get a translator, so they certainly don't get one
-                                    ErrorKind::AssetNotOk(url, status, _) => return error_pages.render_page(url, status, &err.to_string(), None, &container),
-                                    ErrorKind::AssetSerFailed(url, _) => return error_pages.render_page(url, &500, &err.to_string(), None, &container),
-                                    ErrorKind::LocaleNotSupported(locale) => return error_pages.render_page(&format!("/{}/...", locale), &404, &err.to_string(),None,  &container),
-                                    // No other errors should be returned
-                                    _ => panic!("expected 'AssetNotOk'/'AssetSerFailed'/'LocaleNotSupported' error, found other unacceptable error")
-                                }
-                            };
-
-                            // Hydrate that static code using the acquired state
-                            // BUG (Sycamore): this will double-render if the component is just text (no nodes)
-                            sycamore::hydrate_to(
-                                // This function provides translator context as needed
-                                || template.render_for_template(page_data.state, Rc::clone(&translator)),
-                                &container.get::<DomNode>().inner_element()
-                            );
-                        },
-                        // If the page failed to serialize, an exception has occurred
-                        Err(err) => panic!("page data couldn't be serialized: '{}'", err)
+    wasm_bindgen_futures::spawn_local(
+        cloned!((container, container_template, page_key) => async move {
+            let container_elem = container.get::<DomNode>().unchecked_into::<web_sys::Element>();
+            let page_data = match take_initial_page_data(&page_key) {
+                // The server has already rendered this page into the app shell, so we can hydrate that without fetching anything
+                Some(page_data) => {
+                    adopt_initial_content(&container_elem);
+                    page_data
+                }
+                None => {
+                    // Get the static page data
+                    let page_data = fetch_page_data(&asset_url, &error_pages, &container).await;
+                    // Anything the server prerendered is for another page, which we've kept showing until now
+                    remove_initial_content();
+                    let mut page_data = match page_data {
+                        Some(page_data) => page_data,
+                        // The error page has already been rendered
+                        None => return,
                     };
-                },
-                // No translators ready yet
-                None => error_pages.render_page(&asset_url, &404, "page not found", None, &container),
-            },
-            Err(err) => match err.kind() {
-                // No translators ready yet
-                ErrorKind::AssetNotOk(url, status, _) => error_pages.render_page(url, status, &err.to_string(), None, &container),
-                // No other errors should be returned
-                _ => panic!("expected 'AssetNotOk' error, found other unacceptable error")
+                    // If the page is still being generated, we'll show its placeholder until the real page is ready
+                    if page_data.fallback {
+                        container_elem.set_inner_html(&page_data.content);
+                        page_data = match fetch_page_data(&format!("{}?fallback=false", asset_url), &error_pages, &container).await {
+                            Some(page_data) => page_data,
+                            // The error page has already been rendered
+                            None => return,
+                        };
+                    }
+                    // Interpolate the HTML directly into the document (we'll hydrate it later)
+                    container_elem.set_inner_html(&page_data.content);
+                    page_data
+                }
+            };
+            // We have the page data ready, render everything
+            replace_head(&page_data.head);
+            flash.set(page_data.flash.clone());
+            render_ctx.set(RenderCtx {
+                alternates: page_data.alternates.clone(),
+                ..ctx
+            });
+            // Templates that opt out of hydration are just left as their prerendered HTML
+            if template.skips_hydration() {
+                dispatch_page_ready(&render_ctx.get());
+                return;
             }
-        };
-    }));
 
-    // This is where the static content will be rendered
-    // BUG: white flash of death until Sycamore can suspend the router until the static content is ready
-    template! {
-        div(ref = container)
+            // Now that the user can see something, we can get the translator
+            let translator = match get_translator(&locale, template.get_translation_prefixes(), &translations_manager, &error_pages, &container).await {
+                Some(translator) => translator,
+                // The error page has already been rendered
+                None => return,
+            };
+
+            mount_page(
+                &template,
+                page_data,
+                translator,
+                &locale,
+                asset_url,
+                &page_key,
+                container,
+                container_template,
+                as_overlay,
+                true,
+                &mounted_template,
+                scoped_states,
+                flash,
+                render_ctx,
+            );
+        }),
+    );
+
+    let mut mounted = mounted_template.borrow_mut();
+    match underlying_template {
+        // We keep the previous page where it is and render this one on top of it
+        Some(underlying_template) => {
+            // If we're replacing another overlay, the page underneath still has the context it had before that one was opened
+            let underlying_ctx = underlying_ctx.unwrap_or_else(|| (*render_ctx.get()).clone());
+            mounted.overlay = Some(Overlay {
+                container,
+                underlying_ctx,
+            });
+            SycamoreTemplate::new_fragment(vec![
+                underlying_template,
+                template! {
+                    div(class = "perseus-overlay") {
+                        (container_template)
+                    }
+                },
+            ])
+        }
+        None => {
+            mounted.page = Some((page_key, container_template.clone()));
+            container_template
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(path: &str) -> RenderCtx {
+        RenderCtx {
+            path: path.to_string(),
+            locale: "en-US".to_string(),
+            backend: RenderBackend::Client,
+            alternates: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn overlays_are_closed_on_any_navigation() {
+        let mut mounted = MountedTemplate::new();
+        mounted.page = Some(("en-US/posts".to_string(), template! {}));
+        // A parallel route is rendered on top of the page, but not on top of itself
+        assert!(mounted.get_underlying_page("en-US/login").is_some());
+        assert!(mounted.get_underlying_page("en-US/posts").is_none());
+        mounted.overlay = Some(Overlay {
+            container: NodeRef::new(),
+            underlying_ctx: ctx("posts"),
+        });
+
+        // The page underneath gets its context back when we go back to it
+        assert_eq!(mounted.close_overlay(), Some(ctx("posts")));
+        assert!(mounted.get_page("en-US/posts").is_some());
+        assert!(mounted.overlay.is_none());
+        // Closing it again does nothing
+        assert_eq!(mounted.close_overlay(), None);
+    }
+
+    #[test]
+    fn pages_are_only_returned_to_by_their_keys() {
+        let mut mounted = MountedTemplate::new();
+        assert!(mounted.get_page("en-US/posts").is_none());
+        mounted.page = Some(("en-US/posts".to_string(), template! {}));
+        assert!(mounted.get_page("en-US/posts").is_some());
+        assert!(mounted.get_page("fr-FR/posts").is_none());
+        // The page is replaced when another one is rendered in full
+        mounted.page = Some(("en-US/about".to_string(), template! {}));
+        assert!(mounted.get_page("en-US/posts").is_none());
+        assert!(mounted.get_underlying_page("en-US/login").is_some());
     }
 }
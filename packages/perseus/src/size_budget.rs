@@ -0,0 +1,140 @@
+// This checks the artifacts generated at build-time against size budgets, since large pages (especially large inline state, which has to
+// be parsed before a page can be hydrated) slow everything down without anything else noticing
+
+use crate::build::get_prerendered_pages;
+use crate::config_manager::ConfigManager;
+use crate::errors::*;
+use crate::serve::{get_page_artifact_name, read_page_artifact};
+use crate::template::TemplateMap;
+use crate::Locales;
+use std::fmt;
+use sycamore::prelude::SsrNode;
+
+/// The default most bytes a page's prerendered HTML should be.
+pub const DFLT_MAX_HTML_SIZE: usize = 200 * 1024;
+/// The default most bytes a page's state should be.
+pub const DFLT_MAX_STATE_SIZE: usize = 100 * 1024;
+
+/// The most bytes the artifacts generated for each page at build-time should be. Pages over these won't fail to build, but they'll be
+/// reported after the build. This can be set for a whole app with `size_budget` in `define_app!`, and for a single template with
+/// `.size_budget()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeBudget {
+    max_html_size: Option<usize>,
+    max_state_size: Option<usize>,
+}
+impl Default for SizeBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl SizeBudget {
+    /// Creates a new budget with the default limits (200KiB for HTML and 100KiB for state).
+    pub fn new() -> Self {
+        Self {
+            max_html_size: Some(DFLT_MAX_HTML_SIZE),
+            max_state_size: Some(DFLT_MAX_STATE_SIZE),
+        }
+    }
+    /// Sets the most bytes a page's prerendered HTML should be, or `None` to not check it at all.
+    pub fn max_html_size(mut self, val: Option<usize>) -> Self {
+        self.max_html_size = val;
+        self
+    }
+    /// Sets the most bytes a page's state should be, or `None` to not check it at all.
+    pub fn max_state_size(mut self, val: Option<usize>) -> Self {
+        self.max_state_size = val;
+        self
+    }
+}
+
+/// An artifact generated for a page at build-time that can be over its budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizedArtifact {
+    /// The page's prerendered HTML.
+    Html,
+    /// The page's state, which is inlined into the HTML of the initial load.
+    State,
+}
+impl fmt::Display for SizedArtifact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Html => write!(f, "HTML"),
+            Self::State => write!(f, "state"),
+        }
+    }
+}
+
+/// An artifact generated at build-time that's over its budget.
+#[derive(Debug, Clone)]
+pub struct SizeWarning {
+    /// The template that rendered the page.
+    pub template: String,
+    /// The locale of the page.
+    pub locale: String,
+    /// The path of the page (without the locale).
+    pub page: String,
+    /// The artifact that's over its budget.
+    pub artifact: SizedArtifact,
+    /// The size of the artifact in bytes.
+    pub size: usize,
+    /// The budget for the artifact in bytes.
+    pub budget: usize,
+}
+impl fmt::Display for SizeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] page '{}' (locale '{}'): {} is {:.1}KiB, which is over its budget of {:.1}KiB",
+            self.template,
+            self.page,
+            self.locale,
+            self.artifact,
+            self.size as f64 / 1024.0,
+            self.budget as f64 / 1024.0
+        )
+    }
+}
+
+/// Checks the HTML and state of every page prerendered at build-time against the budget of the template that rendered it (or the given
+/// budget for the whole app, if that template doesn't have its own). This must be run after the build process. The warnings will be
+/// ordered by the template that rendered them.
+pub async fn check_artifact_sizes(
+    locales: &Locales,
+    templates: &TemplateMap<SsrNode>,
+    app_budget: &SizeBudget,
+    config_manager: &impl ConfigManager,
+) -> Result<Vec<SizeWarning>> {
+    let mut warnings = Vec::new();
+    for page in get_prerendered_pages(locales, config_manager).await? {
+        let budget = templates
+            .get(&page.template)
+            .and_then(|template| template.get_size_budget())
+            .unwrap_or(app_budget);
+        let mut check = |artifact: SizedArtifact, size: usize, budget: Option<usize>| {
+            if let Some(budget) = budget {
+                if size > budget {
+                    warnings.push(SizeWarning {
+                        template: page.template.clone(),
+                        locale: page.locale.clone(),
+                        page: page.path.clone(),
+                        artifact,
+                        size,
+                        budget,
+                    });
+                }
+            }
+        };
+        check(SizedArtifact::Html, page.html.len(), budget.max_html_size);
+        // Only pages with build state have any state written for them
+        let path_encoded = get_page_artifact_name(&page.locale, &page.path);
+        if let Ok(state) = read_page_artifact(config_manager, &path_encoded, "json").await {
+            check(SizedArtifact::State, state.len(), budget.max_state_size);
+        }
+    }
+    // Group everything by template so it can be reported that way
+    warnings
+        .sort_by(|a, b| (&a.template, &a.page, &a.locale).cmp(&(&b.template, &b.page, &b.locale)));
+
+    Ok(warnings)
+}
@@ -0,0 +1,429 @@
+// Page state is serialized on the server and deserialized again in the browser, so anything with a format that could differ between
+// targets (like floats or timezone offsets) needs a stable string representation
+
+use crate::errors::*;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::value::{BorrowedStrDeserializer, Error as TraceError};
+use serde::de::{
+    DeserializeOwned, DeserializeSeed, Error as DeError, MapAccess, SeqAccess, Visitor,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// A point in time for page state, which is always serialized as an RFC 3339 string in UTC with millisecond precision (like
+/// `2021-09-01T12:00:00.000Z`), so it's formatted identically on the server and in the browser. Any RFC 3339 string (with any offset) can
+/// be deserialized into this, and it will be converted to UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StateDateTime(pub DateTime<Utc>);
+impl From<DateTime<Utc>> for StateDateTime {
+    fn from(time: DateTime<Utc>) -> Self {
+        Self(time)
+    }
+}
+impl fmt::Display for StateDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.to_rfc3339_opts(SecondsFormat::Millis, true))
+    }
+}
+impl Serialize for StateDateTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for StateDateTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let time_str = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&time_str)
+            .map(|time| Self(time.with_timezone(&Utc)))
+            .map_err(D::Error::custom)
+    }
+}
+
+/// An exact decimal number for page state (like a price), which is stored as an integer mantissa and a number of decimal places, and
+/// serialized as a string (like `"12.50"`). Unlike an `f64`, this will never lose precision or be formatted differently on different
+/// targets, and the number of decimal places is preserved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StateDecimal {
+    mantissa: i128,
+    scale: u32,
+}
+impl StateDecimal {
+    /// Creates a new decimal of `mantissa * 10^-scale` (e.g. `StateDecimal::new(1250, 2)` is `12.50`).
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        Self { mantissa, scale }
+    }
+    /// Gets the integer mantissa of this decimal.
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+    /// Gets the number of decimal places in this decimal.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+}
+impl fmt::Display for StateDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        // Pad with leading zeroes so there's always at least one digit before the point
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, int, frac)
+    }
+}
+impl FromStr for StateDecimal {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("'{}' isn't a valid decimal", s);
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s),
+        };
+        let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if int.is_empty() || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let mantissa = format!("{}{}", int, frac)
+            .parse::<i128>()
+            .map_err(|_| invalid())?;
+
+        Ok(Self {
+            mantissa: if negative { -mantissa } else { mantissa },
+            scale: frac.len() as u32,
+        })
+    }
+}
+impl Serialize for StateDecimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for StateDecimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let decimal_str = String::deserialize(deserializer)?;
+        decimal_str.parse().map_err(D::Error::custom)
+    }
+}
+
+/// An exact amount of some unit for page state, like a price in a currency (which should be given as its ISO 4217 code, like `EUR`) or a
+/// measurement (like `kg`). This is serialized as an object with `amount` (a string, like [`StateDecimal`]) and `unit` fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Quantity {
+    /// The amount of the unit.
+    pub amount: StateDecimal,
+    /// The unit, like a currency code or a unit of measurement.
+    pub unit: String,
+}
+impl Quantity {
+    /// Creates a new quantity of the given amount of the given unit.
+    pub fn new(amount: StateDecimal, unit: impl Into<String>) -> Self {
+        Self {
+            amount,
+            unit: unit.into(),
+        }
+    }
+}
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.unit)
+    }
+}
+
+/// Checks that the given state survives being serialized and deserialized again, like it will be when it's sent from the server to the
+/// browser, returning an error describing the difference if it doesn't. This will catch things like non-finite floats (which become
+/// `null`) and custom serialization that isn't symmetrical, and it's a good idea to call this in your app's tests with some example
+/// states for each template.
+pub fn check_round_trip<S: Serialize + DeserializeOwned>(state: &S) -> Result<()> {
+    let serialized = serde_json::to_string(state)?;
+    let deserialized = serde_json::from_str::<S>(&serialized).map_err(|err| {
+        ErrorKind::StateRoundTripFailed(format!(
+            "state '{}' couldn't be deserialized: {}",
+            serialized, err
+        ))
+    })?;
+    let reserialized = serde_json::to_string(&deserialized)?;
+    if serialized != reserialized {
+        bail!(ErrorKind::StateRoundTripFailed(format!(
+            "state was serialized as '{}', but became '{}' after being deserialized",
+            serialized, reserialized
+        )))
+    }
+
+    Ok(())
+}
+
+/// The longest a traced state schema can get before we stop tracing it, which stops recursive types from being traced forever.
+const MAX_SCHEMA_LEN: usize = 16 * 1024;
+
+/// Gets a hash of the shape of the given state type, as far as it can be worked out from the type's `Deserialize` implementation (the
+/// type's name, and the names and types of its fields, recursively). Tracing stops at anything that can only be deserialized from
+/// self-describing data (like most enums), but it always stops in the same place, so the hash is still stable.
+///
+/// This is recorded alongside the state of every page of templates that use `.typed_template()`, so that the server can re-render pages
+/// whose state was generated from an older version of their template's state type, rather than sending the browser state that it can't
+/// deserialize.
+pub fn state_schema<S: DeserializeOwned>() -> String {
+    let mut schema = std::any::type_name::<S>().to_string();
+    // Deserializing from the tracer will almost always fail eventually (it can't make up valid data), but that's fine
+    let _ = S::deserialize(SchemaTracer {
+        schema: &mut schema,
+    });
+
+    format!("{:016x}", fnv1a(schema.as_bytes()))
+}
+
+/// Hashes the given bytes with 64-bit FNV-1a, which (unlike the standard library's hasher) is guaranteed to give the same result in the
+/// build process and the server.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+/// A deserializer that records what a type asks to be deserialized from into a schema, giving it placeholder values so that it will keep
+/// asking (e.g. one element for sequences, and every field for structs).
+struct SchemaTracer<'a> {
+    schema: &'a mut String,
+}
+impl<'a> SchemaTracer<'a> {
+    /// Records the given part of the type's shape, failing if the schema has become too long.
+    fn record(&mut self, token: &str) -> std::result::Result<(), TraceError> {
+        if self.schema.len() > MAX_SCHEMA_LEN {
+            return Err(TraceError::custom("state schema too long to trace"));
+        }
+        self.schema.push(';');
+        self.schema.push_str(token);
+
+        Ok(())
+    }
+}
+
+/// Implements the methods of the schema tracer for types that can be given a placeholder value straight away.
+macro_rules! trace_simple {
+    ($($method:ident => $visit:ident($($val:expr)?)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+                self.record(stringify!($method))?;
+                visitor.$visit($($val)?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for SchemaTracer<'a> {
+    type Error = TraceError;
+
+    trace_simple! {
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i8(0),
+        deserialize_i16 => visit_i16(0),
+        deserialize_i32 => visit_i32(0),
+        deserialize_i64 => visit_i64(0),
+        deserialize_i128 => visit_i128(0),
+        deserialize_u8 => visit_u8(0),
+        deserialize_u16 => visit_u16(0),
+        deserialize_u32 => visit_u32(0),
+        deserialize_u64 => visit_u64(0),
+        deserialize_u128 => visit_u128(0),
+        deserialize_f32 => visit_f32(0.0),
+        deserialize_f64 => visit_f64(0.0),
+        deserialize_char => visit_char('0'),
+        deserialize_str => visit_str(""),
+        deserialize_string => visit_string(String::new()),
+        deserialize_bytes => visit_bytes(&[]),
+        deserialize_byte_buf => visit_byte_buf(Vec::new()),
+        deserialize_unit => visit_unit(),
+        deserialize_identifier => visit_str(""),
+        deserialize_ignored_any => visit_unit(),
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(
+        mut self,
+        _: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record("any")?;
+        Err(TraceError::custom("self-describing types can't be traced"))
+    }
+    fn deserialize_option<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record("option")?;
+        visitor.visit_some(self)
+    }
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record(&format!("unit_struct {}", name))?;
+        visitor.visit_unit()
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record(&format!("newtype_struct {}", name))?;
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_seq<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record("seq")?;
+        visitor.visit_seq(ElementsTracer {
+            schema: self.schema,
+            remaining: 1,
+        })
+    }
+    fn deserialize_tuple<V: Visitor<'de>>(
+        mut self,
+        len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record(&format!("tuple {}", len))?;
+        visitor.visit_seq(ElementsTracer {
+            schema: self.schema,
+            remaining: len,
+        })
+    }
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record(&format!("tuple_struct {} {}", name, len))?;
+        visitor.visit_seq(ElementsTracer {
+            schema: self.schema,
+            remaining: len,
+        })
+    }
+    fn deserialize_map<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record("map")?;
+        visitor.visit_map(EntriesTracer {
+            schema: self.schema,
+            remaining: 1,
+        })
+    }
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record(&format!("struct {}", name))?;
+        visitor.visit_map(FieldsTracer {
+            schema: self.schema,
+            fields: fields.iter(),
+        })
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        _: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.record(&format!("enum {} {}", name, variants.join(",")))?;
+        Err(TraceError::custom("enum variants can't be traced"))
+    }
+}
+
+/// Gives a sequence the given number of traced elements.
+struct ElementsTracer<'a> {
+    schema: &'a mut String,
+    remaining: usize,
+}
+impl<'de, 'a> SeqAccess<'de> for ElementsTracer<'a> {
+    type Error = TraceError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<Option<T::Value>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(SchemaTracer {
+            schema: &mut *self.schema,
+        })
+        .map(Some)
+    }
+}
+
+/// Gives a map the given number of traced keys and values.
+struct EntriesTracer<'a> {
+    schema: &'a mut String,
+    remaining: usize,
+}
+impl<'de, 'a> MapAccess<'de> for EntriesTracer<'a> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(SchemaTracer {
+            schema: &mut *self.schema,
+        })
+        .map(Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        seed.deserialize(SchemaTracer {
+            schema: &mut *self.schema,
+        })
+    }
+}
+
+/// Gives a struct each of its fields, with traced values.
+struct FieldsTracer<'a> {
+    schema: &'a mut String,
+    fields: std::slice::Iter<'static, &'static str>,
+}
+impl<'de, 'a> MapAccess<'de> for FieldsTracer<'a> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, Self::Error> {
+        match self.fields.next() {
+            Some(field) => {
+                self.schema.push_str(&format!(";field {}", field));
+                seed.deserialize(BorrowedStrDeserializer::<Self::Error>::new(field))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        seed.deserialize(SchemaTracer {
+            schema: &mut *self.schema,
+        })
+    }
+}
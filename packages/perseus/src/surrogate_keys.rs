@@ -0,0 +1,56 @@
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// The header that surrogate keys are sent in, which CDNs use to purge exactly the cached pages that depend on some content when it
+/// changes. Different CDNs expect different headers.
+#[derive(Debug, Clone)]
+pub enum SurrogateKeyHeader {
+    /// The `Surrogate-Key` header, with keys separated by spaces (used by Fastly, among others).
+    SurrogateKey,
+    /// The `Cache-Tag` header, with keys separated by commas (used by Cloudflare).
+    CacheTag,
+    /// A custom header, with keys separated by the given separator.
+    Custom {
+        /// The name of the header.
+        name: String,
+        /// The separator between keys.
+        separator: String,
+    },
+}
+impl SurrogateKeyHeader {
+    /// Gets the name of the header and the separator between keys.
+    fn get_name_and_separator(&self) -> (&str, &str) {
+        match self {
+            Self::SurrogateKey => ("Surrogate-Key", " "),
+            Self::CacheTag => ("Cache-Tag", ","),
+            Self::Custom { name, separator } => (name, separator),
+        }
+    }
+}
+
+/// Makes the given surrogate key safe to send in a header, since keys can't contain whitespace or commas (which separate keys), or any
+/// characters that aren't visible ASCII.
+fn sanitize_key(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            c if c.is_ascii_graphic() && c != ',' => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+/// Adds the given surrogate keys to the given response headers in the given header. This is done automatically by server integrations
+/// when they're configured with a [`SurrogateKeyHeader`], but it can also be used in any other route on your server.
+pub fn set_surrogate_keys(headers: &mut HeaderMap, keys: &[String], header: &SurrogateKeyHeader) {
+    if keys.is_empty() {
+        return;
+    }
+    let (name, separator) = header.get_name_and_separator();
+    let keys: Vec<String> = keys.iter().map(|key| sanitize_key(key)).collect();
+    // An invalid custom header name is a misconfiguration, but it shouldn't stop the page being served
+    if let Ok(name) = HeaderName::from_bytes(name.as_bytes()) {
+        // The keys have been sanitized, so this can only fail if the separator is invalid
+        if let Ok(val) = HeaderValue::from_str(&keys.join(separator)) {
+            headers.append(name, val);
+        }
+    }
+}
@@ -1,25 +1,66 @@
 // This file contains logic to define how templates are rendered
 
+use crate::circuit_breaker::CircuitBreaker;
+use crate::clock;
+use crate::concurrency::ConcurrencyLimit;
+use crate::decode_time_str::parse_time_str;
 use crate::errors::*;
+use crate::flash::FlashMessage;
+use crate::path_equivalents::get_alternates;
+use crate::path_pattern::{get_pattern_root, is_path_pattern, matches_path_pattern};
+use crate::plugins::{Plugin, TemplateRegistry};
+use crate::resources::{with_resources, ResourcesCell};
+use crate::size_budget::SizeBudget;
+use crate::state_types::state_schema;
 use crate::Request;
+use crate::ScopedStates;
 use crate::Translator;
+use chrono::{DateTime, Utc};
 use futures::Future;
-use std::collections::HashMap;
+use http::header::HeaderMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::pin::Pin;
 use std::rc::Rc;
-use sycamore::prelude::{template, GenericNode, Template as SycamoreTemplate};
-use sycamore::rx::{ContextProvider, ContextProviderProps};
+use std::time::Duration;
+use sycamore::prelude::{component, template, GenericNode, SsrNode, Template as SycamoreTemplate};
+use sycamore::rx::{use_context, ContextProvider, ContextProviderProps, Signal, StateHandle};
+
+/// A built-in strategy for amalgamating build and request states, for when both are defined and custom amalgamation logic isn't needed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AmalgamationStrategy {
+    /// Uses the request state, ignoring the build state.
+    PreferRequest,
+    /// Uses the build state, ignoring the request state.
+    PreferBuild,
+    /// Merges the two states, which must both be JSON objects, with properties in the request state overriding those in the build
+    /// state. This is a shallow merge, so nested objects won't be merged.
+    MergeJson,
+}
 
 /// Represents all the different states that can be generated for a single template, allowing amalgamation logic to be run with the knowledge
-/// of what did what (rather than blindly working on a vector).
-#[derive(Default)]
-pub struct States {
+/// of what did what (rather than blindly working on a vector). By default, the states are serialized strings, but amalgamation functions
+/// given with `.typed_amalgamate_states_fn()` will be given them already deserialized.
+pub struct States<T = String> {
     /// Any state generated by the *build state* strategy.
-    pub build_state: Option<String>,
+    pub build_state: Option<T>,
     /// Any state generated by the *request state* strategy.
-    pub request_state: Option<String>,
+    pub request_state: Option<T>,
+    /// The built-in strategy to use to amalgamate the states if both are defined, if there is one.
+    pub strategy: Option<AmalgamationStrategy>,
+}
+// This is implemented manually so that `T` doesn't have to implement `Default`
+impl<T> Default for States<T> {
+    fn default() -> Self {
+        Self {
+            build_state: None,
+            request_state: None,
+            strategy: None,
+        }
+    }
 }
-impl States {
+impl<T> States<T> {
     /// Creates a new instance of the states, setting both to `None`.
     pub fn new() -> Self {
         Self::default()
@@ -28,11 +69,18 @@ impl States {
     pub fn both_defined(&self) -> bool {
         self.build_state.is_some() && self.request_state.is_some()
     }
+}
+impl States<String> {
     /// Gets the only defined state if only one is defined. If no states are defined, this will just return `None`. If both are defined,
-    /// this will return an error.
+    /// they'll be amalgamated with the built-in strategy if there is one, and otherwise this will return an error.
     pub fn get_defined(&self) -> Result<Option<String>> {
         if self.both_defined() {
-            bail!(ErrorKind::BothStatesDefined)
+            return match self.strategy {
+                Some(AmalgamationStrategy::PreferRequest) => Ok(self.request_state.clone()),
+                Some(AmalgamationStrategy::PreferBuild) => Ok(self.build_state.clone()),
+                Some(AmalgamationStrategy::MergeJson) => self.merge_json().map(Some),
+                None => bail!(ErrorKind::BothStatesDefined),
+            };
         }
 
         if self.build_state.is_some() {
@@ -43,6 +91,188 @@ impl States {
             Ok(None)
         }
     }
+    /// Shallowly merges the request state into the build state, both of which must be defined and must be JSON objects.
+    fn merge_json(&self) -> Result<String> {
+        let build_state: serde_json::Value =
+            serde_json::from_str(self.build_state.as_ref().unwrap())?;
+        let request_state: serde_json::Value =
+            serde_json::from_str(self.request_state.as_ref().unwrap())?;
+        match (build_state, request_state) {
+            (
+                serde_json::Value::Object(mut build_state),
+                serde_json::Value::Object(request_state),
+            ) => {
+                build_state.extend(request_state);
+                Ok(serde_json::Value::Object(build_state).to_string())
+            }
+            _ => bail!(ErrorKind::StatesNotMergeable),
+        }
+    }
+}
+
+/// A path generated by the *build paths* strategy, along with the locales it should be built for. Build paths functions can return either
+/// this or just a `String` of the path, which will be built for every locale.
+#[derive(Debug, Clone)]
+pub struct BuildPath {
+    /// The path itself (relative to the template's root path).
+    pub path: String,
+    /// The locales the page should be built for, or `None` if it should be built for all of them.
+    pub locales: Option<Vec<String>>,
+    /// An identifier shared by the paths of this same page in other locales (e.g. when its slug is translated), if it has any.
+    pub key: Option<String>,
+}
+impl BuildPath {
+    /// Creates a new build path that will be built for every locale.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            locales: None,
+            key: None,
+        }
+    }
+    /// Restricts this path to only being built for the given locales (e.g. for a page that only exists in `fr-FR`). In any other
+    /// locale, the page won't exist.
+    pub fn locales<L: Into<String>>(mut self, val: Vec<L>) -> Self {
+        self.locales = Some(val.into_iter().map(|locale| locale.into()).collect());
+        self
+    }
+    /// Marks this path as the same page as every other path of the template with the same key (e.g. the page's ID in a CMS), which is
+    /// how the paths of a page with a translated slug in different locales (like `about` in `en-US` and `a-propos` in `fr-FR`) are linked
+    /// together, so that switching locales goes to the right path.
+    pub fn key(mut self, val: impl Into<String>) -> Self {
+        self.key = Some(val.into());
+        self
+    }
+    /// Checks if this path should be built for the given locale.
+    pub fn is_for_locale(&self, locale: &str) -> bool {
+        match &self.locales {
+            Some(locales) => locales.iter().any(|path_locale| path_locale == locale),
+            None => true,
+        }
+    }
+}
+impl From<String> for BuildPath {
+    fn from(path: String) -> Self {
+        Self::new(path)
+    }
+}
+
+/// The state generated by the *build state* strategy for a single page, along with any settings specific to that page. Build state
+/// functions can return either this or just a `String` of the state.
+#[derive(Debug, Clone)]
+pub struct BuildState {
+    /// The state itself.
+    pub state: String,
+    /// An interval after which this page should be revalidated, which overrides the template's `revalidate_after` for this page alone.
+    /// This uses the same syntax as `revalidate_after`.
+    pub revalidate_after: Option<String>,
+    /// Where to redirect the user instead of rendering this page. This is only possible when the page is being generated at
+    /// request-time (i.e. with incremental generation), and the state will be ignored.
+    pub redirect: Option<Redirect>,
+    /// Whether or not this page turned out not to exist (e.g. a path being generated incrementally that doesn't correspond to any real
+    /// content), in which case the state will be ignored and a 404 will be returned instead.
+    pub not_found: bool,
+}
+impl BuildState {
+    /// Creates a new build state with no page-specific settings.
+    pub fn new(state: String) -> Self {
+        Self {
+            state,
+            revalidate_after: None,
+            redirect: None,
+            not_found: false,
+        }
+    }
+    /// Creates a build state that redirects the user to the given path or URL with the given HTTP status code (e.g. `302`) rather than
+    /// rendering the page. This can only be used when the page is generated incrementally, it'll cause an error at build-time.
+    pub fn redirect(to: impl Into<String>, status: u16) -> Self {
+        Self {
+            state: String::new(),
+            revalidate_after: None,
+            redirect: Some(Redirect::new(to, status)),
+            not_found: false,
+        }
+    }
+    /// Creates a build state for a page that doesn't exist, which will make the server return a 404 (and the app's 404 error page be
+    /// shown) rather than rendering it. Pages that don't exist at build-time will just be skipped.
+    pub fn not_found() -> Self {
+        Self {
+            state: String::new(),
+            revalidate_after: None,
+            redirect: None,
+            not_found: true,
+        }
+    }
+    /// Sets the interval after which this page should be revalidated, overriding the template's interval for this page alone. This
+    /// takes a time string of a form like `1w` for one week, just like `revalidate_after` on `Template`.
+    pub fn revalidate_after(mut self, val: impl Into<String>) -> Self {
+        self.revalidate_after = Some(val.into());
+        self
+    }
+}
+impl From<String> for BuildState {
+    fn from(state: String) -> Self {
+        Self::new(state)
+    }
+}
+
+/// A redirect returned by a state function instead of a state.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Redirect {
+    /// The path (e.g. `/other-page`) or full URL to redirect to.
+    pub to: String,
+    /// The HTTP status code of the redirect (e.g. `302`).
+    pub status: u16,
+    /// A one-time flash message to deliver to the page the user is redirected to, if there is one.
+    #[serde(default)]
+    pub flash: Option<String>,
+}
+impl Redirect {
+    /// Creates a new redirect to the given path or URL.
+    pub fn new(to: impl Into<String>, status: u16) -> Self {
+        Self {
+            to: to.into(),
+            status,
+            flash: None,
+        }
+    }
+    /// Sets a one-time flash message to deliver to the page the user is redirected to (e.g. to tell them a form they submitted in a
+    /// request state function was saved). This is sent in the same cookie as messages set with [`set_flash`](crate::flash::set_flash).
+    pub fn with_flash(mut self, message: impl Into<String>) -> Self {
+        self.flash = Some(message.into());
+        self
+    }
+}
+
+/// The outcome of a request state function, which can either be a state to render the page with, or a redirect to somewhere else.
+/// Request state functions can return either this or just a `String` of the state.
+#[derive(Debug, Clone)]
+pub enum RenderOutcome {
+    /// The page should be rendered with the given state.
+    Render(String),
+    /// The user should be redirected instead of the page being rendered.
+    Redirect(Redirect),
+}
+impl RenderOutcome {
+    /// Creates an outcome that redirects the user to the given path or URL with the given HTTP status code (e.g. `302`).
+    pub fn redirect(to: impl Into<String>, status: u16) -> Self {
+        Self::Redirect(Redirect::new(to, status))
+    }
+    /// Creates an outcome that redirects the user to the given path or URL with the given HTTP status code, delivering the given one-time
+    /// flash message to the page they end up on. This is the usual way to confirm a form submission handled in a request state function
+    /// (post/redirect/get).
+    pub fn redirect_with_flash(
+        to: impl Into<String>,
+        status: u16,
+        message: impl Into<String>,
+    ) -> Self {
+        Self::Redirect(Redirect::new(to, status).with_flash(message))
+    }
+}
+impl From<String> for RenderOutcome {
+    fn from(state: String) -> Self {
+        Self::Render(state)
+    }
 }
 
 /// A generic error type that mandates a string error. This sidesteps horrible generics while maintaining DX.
@@ -50,6 +280,22 @@ pub type StringResult<T> = std::result::Result<T, String>;
 /// A generic error type that mandates a string errorr and a statement of causation (client or server) for status code generation.
 pub type StringResultWithCause<T> = std::result::Result<T, (String, ErrorCause)>;
 
+/// Serializes the given typed state into a string, treating any failure as the server's fault.
+fn serialize_state<S: Serialize>(state: &S) -> StringResultWithCause<String> {
+    serde_json::to_string(state).map_err(|err| (err.to_string(), ErrorCause::Server(None)))
+}
+/// Deserializes the given state string (if there is one) into a typed state, treating any failure as the server's fault.
+fn deserialize_state<S: DeserializeOwned>(
+    state: Option<String>,
+) -> StringResultWithCause<Option<S>> {
+    match state {
+        Some(state) => serde_json::from_str(&state)
+            .map(Some)
+            .map_err(|err| (err.to_string(), ErrorCause::Server(None))),
+        None => Ok(None),
+    }
+}
+
 /// A generic return type for asynchronous functions that we need to store in a struct.
 type AsyncFnReturn<T> = Pin<Box<dyn Future<Output = T>>>;
 
@@ -94,27 +340,141 @@ macro_rules! make_async_trait {
 }
 
 // A series of asynchronous closure traits that prevent the user from having to pin their functions
-make_async_trait!(GetBuildPathsFnType, StringResult<Vec<String>>);
+// This is written out manually so that functions can return either plain strings or full `BuildPath`s
+#[doc(hidden)]
+pub trait GetBuildPathsFnType {
+    fn call(&self) -> AsyncFnReturn<StringResult<Vec<BuildPath>>>;
+}
+impl<T, F, P> GetBuildPathsFnType for T
+where
+    T: Fn() -> F,
+    F: Future<Output = StringResult<Vec<P>>> + 'static,
+    P: Into<BuildPath> + 'static,
+{
+    fn call(&self) -> AsyncFnReturn<StringResult<Vec<BuildPath>>> {
+        let fut = self();
+        Box::pin(async move {
+            fut.await
+                .map(|paths| paths.into_iter().map(|path| path.into()).collect())
+        })
+    }
+}
 // The build state strategy needs an error cause if it's invoked from incremental
+// This is written out manually so that functions can return either a plain string or a full `BuildState`
+#[doc(hidden)]
+pub trait GetBuildStateFnType {
+    fn call(
+        &self,
+        path: String,
+        locale: String,
+    ) -> AsyncFnReturn<StringResultWithCause<BuildState>>;
+}
+impl<T, F, S> GetBuildStateFnType for T
+where
+    T: Fn(String, String) -> F,
+    F: Future<Output = StringResultWithCause<S>> + 'static,
+    S: Into<BuildState> + 'static,
+{
+    fn call(
+        &self,
+        path: String,
+        locale: String,
+    ) -> AsyncFnReturn<StringResultWithCause<BuildState>> {
+        let fut = self(path, locale);
+        Box::pin(async move { fut.await.map(|state| state.into()) })
+    }
+}
+// This is written out manually for the same reason as above
+#[doc(hidden)]
+pub trait GetBuildPathsWithStateFnType {
+    fn call(&self, locale: String) -> AsyncFnReturn<StringResult<Vec<(String, BuildState)>>>;
+}
+impl<T, F, S> GetBuildPathsWithStateFnType for T
+where
+    T: Fn(String) -> F,
+    F: Future<Output = StringResult<Vec<(String, S)>>> + 'static,
+    S: Into<BuildState> + 'static,
+{
+    fn call(&self, locale: String) -> AsyncFnReturn<StringResult<Vec<(String, BuildState)>>> {
+        let fut = self(locale);
+        Box::pin(async move {
+            fut.await.map(|paths| {
+                paths
+                    .into_iter()
+                    .map(|(path, state)| (path, state.into()))
+                    .collect()
+            })
+        })
+    }
+}
+// This is written out manually so that functions can return either a plain string or a full `RenderOutcome`
+#[doc(hidden)]
+pub trait GetRequestStateFnType {
+    fn call(
+        &self,
+        path: String,
+        locale: String,
+        req: Request,
+    ) -> AsyncFnReturn<StringResultWithCause<RenderOutcome>>;
+}
+impl<T, F, S> GetRequestStateFnType for T
+where
+    T: Fn(String, String, Request) -> F,
+    F: Future<Output = StringResultWithCause<S>> + 'static,
+    S: Into<RenderOutcome> + 'static,
+{
+    fn call(
+        &self,
+        path: String,
+        locale: String,
+        req: Request,
+    ) -> AsyncFnReturn<StringResultWithCause<RenderOutcome>> {
+        let fut = self(path, locale, req);
+        Box::pin(async move { fut.await.map(|state| state.into()) })
+    }
+}
 make_async_trait!(
-    GetBuildStateFnType,
-    StringResultWithCause<String>,
-    path: String
+    ShouldRevalidateFnType,
+    StringResultWithCause<bool>,
+    path: String,
+    req: Request,
+    ctx: RevalidationContext
 );
 make_async_trait!(
-    GetRequestStateFnType,
-    StringResultWithCause<String>,
+    BeforeRenderFnType,
+    StringResultWithCause<()>,
     path: String,
     req: Request
 );
-make_async_trait!(ShouldRevalidateFnType, StringResultWithCause<bool>);
+make_async_trait!(
+    AmalgamateStatesFnType,
+    StringResultWithCause<Option<String>>,
+    states: States
+);
 
 // A series of closure types that should not be typed out more than once
 /// The type of functions that are given a state and render a page. If you've defined state for your page, it's safe to `.unwrap()` the
 /// given `Option`. If you're using i18n, an `Rc<Translator>` will also be made available through Sycamore's [context system](https://sycamore-rs.netlify.app/docs/advanced/advanced_reactivity).
 pub type TemplateFn<G> = Rc<dyn Fn(Option<String>) -> SycamoreTemplate<G>>;
+/// The type of functions that render a placeholder (like a loading skeleton) for a page whose state is still being generated.
+pub type FallbackFn<G> = Rc<dyn Fn() -> SycamoreTemplate<G>>;
+/// A type alias for a layout, which wraps the views of every template registered under it (e.g. with a navbar and a footer). This is
+/// given the template's view, and should render it somewhere in its own.
+pub type LayoutFn<G> = Rc<dyn Fn(SycamoreTemplate<G>) -> SycamoreTemplate<G>>;
+/// The type of functions that are given a state and render the metadata for a page (like its `<title>`), which will be injected into the
+/// document `<head>`. This is always rendered to a string on the server, so it doesn't need to be generic over a node type.
+pub type HeadFn = Rc<dyn Fn(Option<String>) -> SycamoreTemplate<SsrNode>>;
+/// The type of functions that are given a state and return the HTTP headers that should be sent with a page.
+pub type SetHeadersFn = Rc<dyn Fn(Option<String>) -> HeaderMap>;
+/// The type of functions that are given a page's state and return the surrogate keys for the content it depends on (e.g. `post-123`),
+/// which CDNs can use to purge it when that content changes.
+pub type SurrogateKeysFn = Rc<dyn Fn(Option<String>) -> Vec<String>>;
+/// The type of functions that are called when a page takes longer to render on the server than its template's slow render threshold.
+pub type SlowRenderHook = Rc<dyn Fn(SlowRender)>;
 /// The type of functions that get build paths.
 pub type GetBuildPathsFn = Rc<dyn GetBuildPathsFnType>;
+/// The type of functions that get build paths along with their build states.
+pub type GetBuildPathsWithStateFn = Rc<dyn GetBuildPathsWithStateFnType>;
 /// The type of functions that get build state.
 pub type GetBuildStateFn = Rc<dyn GetBuildStateFnType>;
 /// The type of functions that get request state.
@@ -122,7 +482,100 @@ pub type GetRequestStateFn = Rc<dyn GetRequestStateFnType>;
 /// The type of functions that check if a template sghould revalidate.
 pub type ShouldRevalidateFn = Rc<dyn ShouldRevalidateFnType>;
 /// The type of functions that amalgamate build and request states.
-pub type AmalgamateStatesFn = Rc<dyn Fn(States) -> StringResultWithCause<Option<String>>>;
+pub type AmalgamateStatesFn = Rc<dyn AmalgamateStatesFnType>;
+/// The type of functions that are run on the server before a page is rendered for a request, which are given the path of the page, its
+/// state, and the request. If these return an error, the page won't be rendered, and the error will be returned instead.
+pub type BeforeRenderFn = Rc<dyn BeforeRenderFnType>;
+/// The type of functions that are run on the server after a page has been rendered for a request, which are given the page's HTML, its
+/// path, its state, and the request, and return the HTML that should actually be sent.
+pub type AfterRenderFn = Rc<dyn Fn(String, &str, Option<&str>, &Request) -> String>;
+/// Where a page is being rendered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderBackend {
+    /// On the server, at build-time or request-time.
+    Server,
+    /// In the browser, by the app shell.
+    Client,
+}
+/// Information about the page being rendered, provided through context (as a `Signal<RenderCtx>`) to templates, and to any layouts and
+/// parent templates they're rendered in. On the client-side, this will be updated whenever a page of a template that stays mounted
+/// across navigations (like one that reuses its component) is navigated to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderCtx {
+    /// The path of the page, without the locale (e.g. `post/my-post`).
+    pub path: String,
+    /// The locale the page is being rendered for.
+    pub locale: String,
+    /// Where the page is being rendered.
+    pub backend: RenderBackend,
+    /// The paths (without locales) of the page in every locale it has a path of its own in, if its build path was given a key (e.g.
+    /// because its slug is translated). This is empty for pages that have the same path in every locale.
+    pub alternates: BTreeMap<String, String>,
+}
+
+/// The reactive state provided through context to templates that reuse their component, refetch their state, or have mutable state.
+/// When navigating between pages of such a template, this will be updated with the new page's state rather than the template being
+/// rendered again. Templates with mutable state can update this themselves (see the [`mutation`](crate::mutation) module).
+pub type ReactiveState = Signal<Option<String>>;
+/// The view rendered by the [`Outlet`] of a template with nested templates, provided through context. On the client-side, this will be
+/// updated when navigating between the nested templates of the same parent, so that the parent stays mounted.
+pub type OutletView<G> = StateHandle<SycamoreTemplate<G>>;
+
+/// Information about a page that took longer to render on the server than its template's slow render threshold.
+#[derive(Debug, Clone)]
+pub struct SlowRender {
+    /// The root path of the template the page belongs to.
+    pub template: String,
+    /// The path of the page.
+    pub path: String,
+    /// The locale the page was rendered in.
+    pub locale: String,
+    /// How long the page took to render.
+    pub duration: Duration,
+    /// The threshold the render exceeded.
+    pub threshold: Duration,
+}
+impl std::fmt::Display for SlowRender {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "slow render: page '{}' (template '{}', locale '{}') took {}ms to render, exceeding the threshold of {}ms",
+            self.path,
+            self.template,
+            self.locale,
+            self.duration.as_millis(),
+            self.threshold.as_millis()
+        )
+    }
+}
+
+/// Information about the currently cached version of a page, which is given to custom revalidation logic.
+#[derive(Debug, Clone)]
+pub struct RevalidationContext {
+    /// When the page was last rendered (at build-time, or when it was last revalidated or generated incrementally). This will be `None`
+    /// if that isn't known (e.g. if the page was built by an older version of Perseus).
+    pub last_rendered: Option<DateTime<Utc>>,
+    /// The cached state of the page, if it has one.
+    pub state: Option<String>,
+}
+impl RevalidationContext {
+    /// Gets how long ago the page was last rendered (according to the revalidation clock), if that's known.
+    pub fn age(&self) -> Option<Duration> {
+        self.last_rendered
+            .and_then(|last_rendered| (clock::now() - last_rendered).to_std().ok())
+    }
+}
+
+/// Renders the view of whichever of a template's nested templates is currently active. This should be placed in the template function of
+/// any template that has nested templates registered with `.nested()`, and it will render nothing when the parent template is rendered
+/// for its own path.
+#[component(Outlet<G>)]
+pub fn outlet() -> SycamoreTemplate<G> {
+    let view = use_context::<OutletView<G>>();
+    template! {
+        (view.get().as_ref().clone())
+    }
+}
 
 /// This allows the specification of all the template templates in an app and how to render them. If no rendering logic is provided at all,
 /// the template will be prerendered at build-time with no state. All closures are stored on the heap to avoid hellish lifetime specification.
@@ -137,14 +590,42 @@ pub struct Template<G: GenericNode> {
     /// This will be executed inside `sycamore::render_to_string`, and should return a `Template<SsrNode>`. This takes an `Option<Props>`
     /// because otherwise efficient typing is almost impossible for templates without any properties (solutions welcome in PRs!).
     template: TemplateFn<G>,
+    /// A function that will render the metadata for your template's pages (like their `<title>`) from their state. This will be rendered
+    /// to a string at build-time or request-time along with the template itself, and injected into the document `<head>` by the app shell.
+    head: HeadFn,
+    /// A function that returns the HTTP headers that should be sent with your template's pages (like `Cache-Control`), given their state.
+    set_headers: SetHeadersFn,
+    /// A function that returns the surrogate keys for the content your template's pages depend on, given their state. These are sent
+    /// along with keys for the template and the page themselves.
+    surrogate_keys: Option<SurrogateKeysFn>,
+    /// How long a page of this template can take to render on the server before it's reported as a slow render, if at all.
+    slow_render_threshold: Option<Duration>,
+    /// Only one in every this many slow renders will be reported, so that busy pages don't flood the logs.
+    slow_render_sample_rate: u32,
+    /// The number of slow renders since the last one was reported, which is shared between clones of this template.
+    slow_renders_since_report: Rc<Cell<u32>>,
+    /// A function that will be called with slow renders, without which they won't be reported anywhere.
+    slow_render_hook: Option<SlowRenderHook>,
+    /// An identifier for the shape of this template's state, which is recorded alongside the state of its pages so that pages generated
+    /// with an older shape can be re-rendered rather than served.
+    state_schema: Option<String>,
     /// A function that gets the paths to render for at built-time. This is equivalent to `get_static_paths` in NextJS. If
     /// `incremental_path_rendering` is `true`, more paths can be rendered at request time on top of these.
     get_build_paths: Option<GetBuildPathsFn>,
+    /// A function that gets the paths to render for at build-time along with their build states, for when getting the paths and getting
+    /// the states would involve the same work (like querying a CMS). This will be passed the locale being built for. This enables both
+    /// the *build paths* and *build state* strategies, though a separate `get_build_state` is still needed for incremental generation and
+    /// revalidation.
+    get_build_paths_with_state: Option<GetBuildPathsWithStateFn>,
     /// Defines whether or not any new paths that match this template will be prerendered and cached in production. This allows you to
     /// have potentially billions of templates and retain a super-fast build process. The first user will have an ever-so-slightly slower
     /// experience, and everyone else gets the beneftis afterwards. This requires `get_build_paths`. Note that the template root will NOT
     /// be rendered on demand, and must be explicitly defined if it's wanted. It can uuse a different template.
     incremental_path_rendering: bool,
+    /// A function that renders a placeholder for pages being generated incrementally. If this is set, the first request for a page that
+    /// hasn't been generated yet will get this immediately, and the app shell will then fetch the real page, which waits for it to be
+    /// generated. This is equivalent to `fallback: true` in NextJS.
+    incremental_fallback: Option<FallbackFn<G>>,
     /// A function that gets the initial state to use to prerender the template at build time. This will be passed the path of the template, and
     /// will be run for any sub-paths. This is equivalent to `get_static_props` in NextJS.
     get_build_state: Option<GetBuildStateFn>,
@@ -155,52 +636,421 @@ pub struct Template<G: GenericNode> {
     /// to revalidation after a time in NextJS, with the improvement of custom logic. If used with `revalidate_after`, this function will
     /// only be run after that time period. This function will not be parsed anything specific to the request that invoked it.
     should_revalidate: Option<ShouldRevalidateFn>,
-    /// A length of time after which to prerender the template again. This is equivalent to revalidating in NextJS. This should specify a
-    /// string interval to revalidate after. That will be converted into a datetime to wait for, which will be updated after every revalidation.
+    /// A length of time after which to prerender the template again. This is equivalent to revalidating in NextJS. This will be converted
+    /// into a datetime to wait for, which will be updated after every revalidation.
+    /// Individual pages can override this by returning a [`BuildState`] with their own interval from `get_build_state`.
     /// Note that, if this is used with incremental generation, the counter will only start after the first render (meaning if you expect
     /// a weekly re-rendering cycle for all pages, they'd likely all be out of sync, you'd need to manually implement that with
     /// `should_revalidate`).
-    revalidate_after: Option<String>,
+    revalidate_after: Option<Duration>,
     /// Custom logic to amalgamate potentially different states generated at build and request time. This is only necessary if your template
     /// uses both `build_state` and `request_state`. If not specified and both are generated, request state will be prioritized.
     amalgamate_states: Option<AmalgamateStatesFn>,
+    /// A built-in strategy to amalgamate build and request states with, which will be used if there's no custom logic to do so.
+    amalgamation_strategy: Option<AmalgamationStrategy>,
+    /// Whether or not the mounted template should be kept when navigating between pages of this template on the client-side. If this is
+    /// `true`, the app shell will feed the new page's state into the [`ReactiveState`] provided through context instead of rendering the
+    /// template again, which preserves any layout-level state (like scroll containers). Templates using this should read their state
+    /// from that context rather than only from the initial props they're given.
+    reuse_component: bool,
+    /// Whether or not this template is a parallel route. If `true`, pages of this template will be rendered in an overlay on top of the
+    /// current page when navigated to on the client-side (e.g. a photo viewer over a gallery), but as full pages when loaded directly.
+    parallel_route: bool,
+    /// Whether or not this template is only rendered on the client-side. If `true`, its pages will never be prerendered on the server
+    /// (though their states and heads still will be), and the app shell will render them from scratch rather than hydrating them. This
+    /// is useful for things like dashboards that need browser APIs like `window`. Templates nested under this one will be client-side only
+    /// too.
+    client_side_only: bool,
+    /// Whether or not this template's pages should be left as their prerendered HTML on the client-side, rather than being hydrated.
+    /// This saves the work of hydration for purely static pages, but they won't be interactive at all. This is ignored for client-side
+    /// only templates, which have nothing to leave.
+    no_hydration: bool,
+    /// Whether or not this template's pages should be streamed, sending everything but the page's content as soon as its state has been
+    /// generated, and the content once it's been rendered. This only makes a difference for pages rendered at request-time, since
+    /// anything else was rendered before the request.
+    streaming: bool,
+    /// Whether or not the client should refetch the state of this template's pages whenever the window regains focus.
+    refetch_on_focus: bool,
+    /// The interval in milliseconds at which the client should refetch the state of this template's pages, if any.
+    refetch_interval: Option<u32>,
+    /// Whether or not this template's pages can mutate their own state on the client-side through the [`ReactiveState`] provided
+    /// through context.
+    mutable_state: bool,
+    /// The named state scopes this template is subscribed to, the states of which will be provided through context as [`ScopedStates`].
+    /// Nested templates are also subscribed to their parent's scopes.
+    state_scopes: Vec<String>,
+    /// The prefixes of the translation IDs this template's pages use, if they've been declared, in which case the app shell will only
+    /// fetch those translations from the server for them. Nested templates also use their parent's prefixes.
+    translation_prefixes: Option<Vec<String>>,
+    /// The most bytes the artifacts generated for this template's pages at build-time should be, if it has a budget different from the
+    /// app's.
+    size_budget: Option<SizeBudget>,
+    /// How many of this template's pages can be rendered at once on the server, if that's limited.
+    concurrency_limit: Option<ConcurrencyLimit>,
+    /// The circuit breaker for this template's state functions, if it has one.
+    circuit_breaker: Option<CircuitBreaker>,
+    /// The resources of the app this template is registered in, which its state functions can access with `get_resource()`. Templates
+    /// nested under this one get the same resources.
+    resources: Option<&'static ResourcesCell>,
+    /// Templates nested under this one, which will be rendered inside this template's [`Outlet`]. Their paths are relative to this
+    /// template's path.
+    nested: Vec<Template<G>>,
+    /// The template function of the template this one is nested under, if any, which renders that template inside any template it's
+    /// nested under in turn. This is only set on the flattened templates produced by `.flatten_nested()`.
+    parent_template: Option<TemplateFn<G>>,
+    /// The path of the template this one is nested under, if any.
+    parent_path: Option<String>,
+    /// The layout this template is registered under, which will wrap its view on both the server and the client. Templates nested
+    /// under this one will be rendered in the same layout.
+    layout: Option<LayoutFn<G>>,
+    /// A function that will be run on the server before a page of this template is rendered for a request (e.g. to check that the user
+    /// is authorized to see it). Templates nested under this one will inherit this if they don't have their own.
+    before_render: Option<BeforeRenderFn>,
+    /// A function that will be run on the server after a page of this template is rendered for a request, which can post-process its
+    /// HTML. Templates nested under this one will inherit this if they don't have their own.
+    after_render: Option<AfterRenderFn>,
 }
 impl<G: GenericNode> Template<G> {
-    /// Creates a new template definition.
+    /// Creates a new template definition. The path can contain dynamic segments like `<slug>` (exactly one segment), `<slug..>` (one or
+    /// more segments), or `*` (any number of segments), the last two of which must come at the end (e.g. `blog/<year>/<slug>` or `docs/*`).
     pub fn new(path: impl Into<String> + std::fmt::Display) -> Self {
         Self {
             path: path.to_string(),
             template: Rc::new(|_: Option<String>| sycamore::template! {}),
+            head: Rc::new(|_: Option<String>| sycamore::template! {}),
+            set_headers: Rc::new(|_: Option<String>| HeaderMap::new()),
+            surrogate_keys: None,
+            slow_render_threshold: None,
+            slow_render_sample_rate: 1,
+            slow_renders_since_report: Rc::new(Cell::new(0)),
+            slow_render_hook: None,
+            state_schema: None,
             get_build_paths: None,
+            get_build_paths_with_state: None,
             incremental_path_rendering: false,
+            incremental_fallback: None,
             get_build_state: None,
             get_request_state: None,
             should_revalidate: None,
             revalidate_after: None,
             amalgamate_states: None,
+            amalgamation_strategy: None,
+            reuse_component: false,
+            parallel_route: false,
+            client_side_only: false,
+            no_hydration: false,
+            streaming: false,
+            refetch_on_focus: false,
+            refetch_interval: None,
+            mutable_state: false,
+            state_scopes: Vec::new(),
+            translation_prefixes: None,
+            size_budget: None,
+            concurrency_limit: None,
+            circuit_breaker: None,
+            resources: None,
+            nested: Vec::new(),
+            parent_template: None,
+            parent_path: None,
+            layout: None,
+            before_render: None,
+            after_render: None,
         }
     }
+    /// Creates a new template definition at the given path that extends the given base template, inheriting all its rendering strategies
+    /// and settings (e.g. revalidation and state amalgamation, its layout, and its template and head functions). These can be overridden
+    /// with the usual builder methods. The base template's nested templates aren't inherited, since they're registered under its path.
+    /// This is useful for sharing settings between many templates without having to repeat them, and the base template can be defined
+    /// with any path (it doesn't need to be registered with the app).
+    pub fn extending(path: impl Into<String> + std::fmt::Display, base: &Template<G>) -> Self {
+        let mut template = base.clone();
+        template.path = path.to_string();
+        // Slow renders are sampled separately for each template
+        template.slow_renders_since_report = Rc::new(Cell::new(0));
+        template.nested = Vec::new();
+        template.parent_template = None;
+        template.parent_path = None;
+
+        template
+    }
 
     // Render executors
-    /// Executes the user-given function that renders the template on the server-side (build or request time).
+    /// Executes the user-given function that renders the template on the server-side (build or request time). If this template is
+    /// nested under another, this will render that parent template with this one in its outlet. All of that will be wrapped in the
+    /// template's layout, if it has one.
     // TODO possibly duplicate routes context here to avoid disappearance issues?
     pub fn render_for_template(
         &self,
         props: Option<String>,
         translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+    ) -> SycamoreTemplate<G> {
+        let view = self.render_view(
+            props,
+            Rc::clone(&translator),
+            scoped_states.clone(),
+            flash.clone(),
+            render_ctx.clone(),
+        );
+        let view = match self.parent_template {
+            Some(_) => self.render_in_parent(
+                Signal::new(view).handle(),
+                Rc::clone(&translator),
+                scoped_states.clone(),
+                flash.clone(),
+                render_ctx.clone(),
+            ),
+            None => view,
+        };
+
+        self.render_in_layout(view, translator, scoped_states, flash, render_ctx)
+    }
+    /// Renders the placeholder for a page of this template that's still being generated incrementally, which will be rendered inside
+    /// any parent template this one is nested under. This will panic if the template doesn't have a fallback.
+    pub fn render_fallback(
+        &self,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
     ) -> SycamoreTemplate<G> {
+        let fallback = Rc::clone(self.incremental_fallback.as_ref().unwrap());
+        let view = self.provide_context(
+            Rc::clone(&translator),
+            scoped_states.clone(),
+            flash.clone(),
+            render_ctx.clone(),
+            move || fallback(),
+        );
+        let view = match self.parent_template {
+            Some(_) => self.render_in_parent(
+                Signal::new(view).handle(),
+                Rc::clone(&translator),
+                scoped_states.clone(),
+                flash.clone(),
+                render_ctx.clone(),
+            ),
+            None => view,
+        };
+
+        self.render_in_layout(view, translator, scoped_states, flash, render_ctx)
+    }
+    /// Renders only this template's own view, without any parent template it may be nested under.
+    pub fn render_view(
+        &self,
+        props: Option<String>,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+    ) -> SycamoreTemplate<G> {
+        let state = Signal::new(props.clone());
+        self.render_view_with_state(props, state, translator, scoped_states, flash, render_ctx)
+    }
+    /// Renders only this template's own view, like [`Template::render_view`], but with the given signal behind its reactive state (if it
+    /// has one), so that the app shell can update it later (e.g. when refetching the state of a nested template's page).
+    pub fn render_view_with_state(
+        &self,
+        props: Option<String>,
+        state: ReactiveState,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+    ) -> SycamoreTemplate<G> {
+        // Templates with reactive state expect it in context, even when rendering on the server
+        if self.uses_reactive_state() {
+            return self.render_for_template_reactive(
+                props,
+                state,
+                translator,
+                scoped_states,
+                flash,
+                render_ctx,
+            );
+        }
+
+        self.render_for_template_inner(props, translator, scoped_states, flash, render_ctx)
+    }
+    /// Renders the template this one is nested under, with the given view in its outlet. If this template isn't nested, the view will
+    /// just be rendered directly.
+    pub fn render_in_parent(
+        &self,
+        outlet: OutletView<G>,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+    ) -> SycamoreTemplate<G> {
+        match &self.parent_template {
+            // Nested templates are subscribed to their parent's scopes as well, so we can use this template's context here
+            Some(parent_template) => {
+                self.provide_context(translator, scoped_states, flash, render_ctx, || {
+                    template! {
+                        ContextProvider(ContextProviderProps {
+                            value: outlet,
+                            // Parent templates can't have state (`.validate()` makes sure of that)
+                            children: || parent_template(None)
+                        })
+                    }
+                })
+            }
+            None => outlet.get().as_ref().clone(),
+        }
+    }
+    /// Wraps the given view in the layout this template is registered under, with the template's context provided so the layout can use
+    /// the translator and the like. If the template doesn't have a layout, the view will just be rendered directly.
+    pub fn render_in_layout(
+        &self,
+        view: SycamoreTemplate<G>,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+    ) -> SycamoreTemplate<G> {
+        match &self.layout {
+            Some(layout) => {
+                self.provide_context(translator, scoped_states, flash, render_ctx, || {
+                    layout(view)
+                })
+            }
+            None => view,
+        }
+    }
+    /// Renders the template with its context provided. If the template has nested templates, an empty outlet will also be provided, since
+    /// it's being rendered for its own path.
+    fn render_for_template_inner(
+        &self,
+        props: Option<String>,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+    ) -> SycamoreTemplate<G> {
+        self.provide_context(translator, scoped_states, flash, render_ctx, || {
+            if self.nested.is_empty() {
+                (self.template)(props)
+            } else {
+                let outlet = Signal::new(template! {});
+                template! {
+                    ContextProvider(ContextProviderProps {
+                        value: outlet.handle(),
+                        children: || (self.template)(props)
+                    })
+                }
+            }
+        })
+    }
+    /// Renders the given children with the translator, the states of the scopes this template is subscribed to, the flash message for
+    /// the page, and the [`RenderCtx`] for the page provided through context.
+    fn provide_context(
+        &self,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+        children: impl FnOnce() -> SycamoreTemplate<G>,
+    ) -> SycamoreTemplate<G> {
+        let scoped_states = scoped_states.for_scopes(&self.state_scopes);
         template! {
             // We provide the translator through context, which avoids having to define a separate variable for every translation due to Sycamore's `template!` macro taking ownership with `move` closures
             ContextProvider(ContextProviderProps {
-                value: Rc::clone(&translator),
-                children: || (self.template)(props)
+                value: translator,
+                children: || template! {
+                    ContextProvider(ContextProviderProps {
+                        value: scoped_states,
+                        children: || template! {
+                            ContextProvider(ContextProviderProps {
+                                value: flash,
+                                children: || template! {
+                                    ContextProvider(ContextProviderProps {
+                                        value: render_ctx,
+                                        children
+                                    })
+                                }
+                            })
+                        }
+                    })
+                }
+            })
+        }
+    }
+    /// Executes the user-given function that renders the template, additionally providing the given reactive state through context. This
+    /// is used on the client-side for templates that reuse their component, and the app shell keeps the `Signal` behind that handle so it
+    /// can update the state on later navigations.
+    pub fn render_for_template_reactive(
+        &self,
+        props: Option<String>,
+        state: ReactiveState,
+        translator: Rc<Translator>,
+        scoped_states: ScopedStates,
+        flash: FlashMessage,
+        render_ctx: Signal<RenderCtx>,
+    ) -> SycamoreTemplate<G> {
+        template! {
+            ContextProvider(ContextProviderProps {
+                value: state,
+                children: || self.render_for_template_inner(props, translator, scoped_states, flash, render_ctx)
             })
         }
     }
-    /// Gets the list of templates that should be prerendered for at build-time.
-    pub async fn get_build_paths(&self) -> Result<Vec<String>> {
+    /// Renders the metadata for a page of this template from the given state to a string, which can be injected into the document `<head>`.
+    pub fn render_head_str(&self, props: Option<String>) -> String {
+        sycamore::render_to_string(|| (self.head)(props))
+    }
+    /// Gets the HTTP headers that should be sent with a page of this template, given its state.
+    pub fn get_headers(&self, state: Option<String>) -> HeaderMap {
+        (self.set_headers)(state)
+    }
+    /// Gets the surrogate keys for the page at the given path (without the locale) in the given locale, given its state. These will
+    /// always include keys for the template (`template:<name>`) and the page (`page:<locale>/<path>`), followed by any keys for the
+    /// content the page depends on from the function given to `.surrogate_keys_fn()`.
+    pub fn get_surrogate_keys(
+        &self,
+        path: &str,
+        locale: &str,
+        state: Option<String>,
+    ) -> Vec<String> {
+        let mut keys = vec![
+            format!("template:{}", self.get_path()),
+            format!("page:{}/{}", locale, path),
+        ];
+        if let Some(surrogate_keys) = &self.surrogate_keys {
+            keys.extend(surrogate_keys(state));
+        }
+
+        keys
+    }
+    /// Reports that the given page took the given time to render on the server to the user's hook if that exceeds this template's slow
+    /// render threshold. Slow renders are sampled as the template specifies, and nothing is done if the template doesn't have a hook.
+    pub fn report_render_time(&self, path: &str, locale: &str, duration: Duration) {
+        let (threshold, hook) = match (self.slow_render_threshold, &self.slow_render_hook) {
+            (Some(threshold), Some(hook)) if duration > threshold => (threshold, hook),
+            _ => return,
+        };
+        // We report the first slow render, and then one in every `slow_render_sample_rate` after that
+        let since_report = self.slow_renders_since_report.get();
+        if since_report % self.slow_render_sample_rate.max(1) != 0 {
+            self.slow_renders_since_report.set(since_report + 1);
+            return;
+        }
+        self.slow_renders_since_report.set(1);
+
+        let slow_render = SlowRender {
+            template: self.get_path(),
+            path: path.to_string(),
+            locale: locale.to_string(),
+            duration,
+            threshold,
+        };
+        hook(slow_render);
+    }
+    /// Gets the list of templates that should be prerendered for at build-time, along with the locales they should be prerendered for.
+    pub async fn get_build_paths(&self) -> Result<Vec<BuildPath>> {
         if let Some(get_build_paths) = &self.get_build_paths {
-            let res = get_build_paths.call().await;
+            let res = with_resources(self.resources, get_build_paths.call()).await;
             match res {
                 Ok(res) => Ok(res),
                 Err(err) => bail!(ErrorKind::RenderFnFailed(
@@ -217,11 +1067,34 @@ impl<G: GenericNode> Template<G> {
             ))
         }
     }
+    /// Gets the list of templates that should be prerendered for at build-time for the given locale, along with their build states.
+    pub async fn get_build_paths_with_state(
+        &self,
+        locale: String,
+    ) -> Result<Vec<(String, BuildState)>> {
+        if let Some(get_build_paths_with_state) = &self.get_build_paths_with_state {
+            let res = with_resources(self.resources, get_build_paths_with_state.call(locale)).await;
+            match res {
+                Ok(res) => Ok(res),
+                Err(err) => bail!(ErrorKind::RenderFnFailed(
+                    "get_build_paths_with_state".to_string(),
+                    self.get_path(),
+                    ErrorCause::Server(None),
+                    err
+                )),
+            }
+        } else {
+            bail!(ErrorKind::TemplateFeatureNotEnabled(
+                self.path.clone(),
+                "build_paths_with_state".to_string()
+            ))
+        }
+    }
     /// Gets the initial state for a template. This needs to be passed the full path of the template, which may be one of those generated by
-    /// `.get_build_paths()`.
-    pub async fn get_build_state(&self, path: String) -> Result<String> {
+    /// `.get_build_paths()`, and the locale the page is being built for. This may include a revalidation interval specific to the page.
+    pub async fn get_build_state(&self, path: String, locale: String) -> Result<BuildState> {
         if let Some(get_build_state) = &self.get_build_state {
-            let res = get_build_state.call(path).await;
+            let res = with_resources(self.resources, get_build_state.call(path, locale)).await;
             match res {
                 Ok(res) => Ok(res),
                 Err((err, cause)) => bail!(ErrorKind::RenderFnFailed(
@@ -239,11 +1112,17 @@ impl<G: GenericNode> Template<G> {
         }
     }
     /// Gets the request-time state for a template. This is equivalent to SSR, and will not be performed at build-time. Unlike
-    /// `.get_build_paths()` though, this will be passed information about the request that triggered the render. Errors here can be caused
-    /// by either the server or the client, so the user must specify an [`ErrorCause`].
-    pub async fn get_request_state(&self, path: String, req: Request) -> Result<String> {
+    /// `.get_build_paths()` though, this will be passed information about the request that triggered the render (as well as the locale
+    /// it's for). Errors here can be caused by either the server or the client, so the user must specify an [`ErrorCause`].
+    pub async fn get_request_state(
+        &self,
+        path: String,
+        locale: String,
+        req: Request,
+    ) -> Result<RenderOutcome> {
         if let Some(get_request_state) = &self.get_request_state {
-            let res = get_request_state.call(path, req).await;
+            let res =
+                with_resources(self.resources, get_request_state.call(path, locale, req)).await;
             match res {
                 Ok(res) => Ok(res),
                 Err((err, cause)) => bail!(ErrorKind::RenderFnFailed(
@@ -260,11 +1139,11 @@ impl<G: GenericNode> Template<G> {
             ))
         }
     }
-    /// Amalagmates given request and build states. Errors here can be caused by either the server or the client, so the user must specify
-    /// an [`ErrorCause`].
-    pub fn amalgamate_states(&self, states: States) -> Result<Option<String>> {
+    /// Amalagmates given request and build states, using custom logic if there is any, and the built-in strategy otherwise. Errors from
+    /// custom logic can be caused by either the server or the client, so the user must specify an [`ErrorCause`].
+    pub async fn amalgamate_states(&self, mut states: States) -> Result<Option<String>> {
         if let Some(amalgamate_states) = &self.amalgamate_states {
-            let res = amalgamate_states(states);
+            let res = with_resources(self.resources, amalgamate_states.call(states)).await;
             match res {
                 Ok(res) => Ok(res),
                 Err((err, cause)) => bail!(ErrorKind::RenderFnFailed(
@@ -274,6 +1153,9 @@ impl<G: GenericNode> Template<G> {
                     err
                 )),
             }
+        } else if self.amalgamation_strategy.is_some() {
+            states.strategy = self.amalgamation_strategy;
+            states.get_defined()
         } else {
             bail!(ErrorKind::TemplateFeatureNotEnabled(
                 self.path.clone(),
@@ -281,12 +1163,50 @@ impl<G: GenericNode> Template<G> {
             ))
         }
     }
-    /// Checks, by the user's custom logic, if this template should revalidate. This function isn't presently parsed anything, but has
-    /// network access etc., and can really do whatever it likes. Errors here can be caused by either the server or the client, so the
-    /// user must specify an [`ErrorCause`].
-    pub async fn should_revalidate(&self) -> Result<bool> {
+    /// Runs the user's hook for before a page of this template is rendered for a request, if there is one. This is given the path of the
+    /// page and the request, and errors can be caused by either the server or the client (e.g. a `401` for a user who isn't logged in),
+    /// so the user must specify an [`ErrorCause`].
+    pub async fn before_render(&self, path: String, req: Request) -> Result<()> {
+        if let Some(before_render) = &self.before_render {
+            let res = with_resources(self.resources, before_render.call(path, req)).await;
+            if let Err((err, cause)) = res {
+                bail!(ErrorKind::RenderFnFailed(
+                    "before_render".to_string(),
+                    self.get_path(),
+                    cause,
+                    err
+                ))
+            }
+        }
+
+        Ok(())
+    }
+    /// Runs the user's hook for after a page of this template has been rendered for a request on the given HTML, if there is one,
+    /// returning the HTML that should be sent.
+    pub fn after_render(
+        &self,
+        html: String,
+        path: &str,
+        state: Option<&str>,
+        req: &Request,
+    ) -> String {
+        match &self.after_render {
+            Some(after_render) => after_render(html, path, state, req),
+            None => html,
+        }
+    }
+    /// Checks, by the user's custom logic, if this template should revalidate. This function is given the path of the page being
+    /// requested, the request itself (so it can check headers like `If-Modified-Since`), and a [`RevalidationContext`] with when the page
+    /// was last rendered and its cached state, but has network access etc., and can really do whatever it likes. Errors here can be caused
+    /// by either the server or the client, so the user must specify an [`ErrorCause`].
+    pub async fn should_revalidate(
+        &self,
+        path: String,
+        req: Request,
+        ctx: RevalidationContext,
+    ) -> Result<bool> {
         if let Some(should_revalidate) = &self.should_revalidate {
-            let res = should_revalidate.call().await;
+            let res = with_resources(self.resources, should_revalidate.call(path, req, ctx)).await;
             match res {
                 Ok(res) => Ok(res),
                 Err((err, cause)) => bail!(ErrorKind::RenderFnFailed(
@@ -310,9 +1230,158 @@ impl<G: GenericNode> Template<G> {
     pub fn get_path(&self) -> String {
         self.path.clone()
     }
+    /// Gets the root path of the template, which is everything in its path before the first dynamic segment (e.g. `blog` for
+    /// `blog/<year>/<slug>`). Any paths generated at build-time are relative to this. If the template's path has no dynamic segments,
+    /// this will be the same as `.get_path()`.
+    pub fn get_root_path(&self) -> String {
+        get_pattern_root(&self.path)
+    }
+    /// Checks if the given path (without the locale or a leading `/`) could be a page of this template. For templates without any
+    /// dynamic segments in their paths, this only checks that the path is the template's path or underneath it.
+    pub fn matches_path(&self, path: &str) -> bool {
+        match self.has_dynamic_path() {
+            true => matches_path_pattern(&self.path, path),
+            false => path == self.path || path.starts_with(&format!("{}/", self.path)),
+        }
+    }
+    /// Gets the path of the template this one is nested under, if there is one.
+    pub fn get_parent_path(&self) -> Option<String> {
+        self.parent_path.clone()
+    }
+    /// Gets the template function that renders this template inside any template it's nested under (with the outlet of the innermost one
+    /// provided by whatever calls it).
+    fn get_template_in_parents(&self) -> TemplateFn<G> {
+        let template = Rc::clone(&self.template);
+        match &self.parent_template {
+            Some(parent_template) => {
+                let parent_template = Rc::clone(parent_template);
+                Rc::new(move |_: Option<String>| {
+                    // This template uses the outlet it's been given, and then it becomes the outlet of its own parent
+                    let view = template(None);
+                    let parent_template = Rc::clone(&parent_template);
+                    template! {
+                        ContextProvider(ContextProviderProps {
+                            value: Signal::new(view).handle(),
+                            children: move || parent_template(None)
+                        })
+                    }
+                })
+            }
+            None => template,
+        }
+    }
+    /// Gets this template along with all the templates nested under it (and under those, and so on), which will have their paths
+    /// prefixed with this template's path. This template will always be first, and the templates nested under each nested template will
+    /// come before it. This should be used wherever templates are registered so that nested templates can be rendered and routed to like
+    /// any other.
+    pub fn flatten_nested(&self) -> Vec<Template<G>> {
+        let mut templates = vec![self.clone()];
+        for nested in &self.nested {
+            let mut nested = nested.clone();
+            nested.path = format!("{}/{}", self.path, nested.path);
+            nested.parent_template = Some(self.get_template_in_parents());
+            nested.parent_path = Some(self.get_path());
+            nested.layout = self.layout.clone();
+            nested.resources = self.resources;
+            // The parent is rendered around the nested template, so it has to be rendered wherever the parent is
+            nested.client_side_only |= self.client_side_only;
+            nested.before_render = nested.before_render.or_else(|| self.before_render.clone());
+            nested.after_render = nested.after_render.or_else(|| self.after_render.clone());
+            nested
+                .state_scopes
+                .extend(self.state_scopes.iter().cloned());
+            // The parent is rendered around the nested template, so its translations are needed too (and all of them if it hasn't
+            // declared which it uses)
+            nested.translation_prefixes =
+                match (nested.translation_prefixes, &self.translation_prefixes) {
+                    (Some(mut prefixes), Some(parent_prefixes)) => {
+                        prefixes.extend(parent_prefixes.iter().cloned());
+                        Some(prefixes)
+                    }
+                    _ => None,
+                };
+            // Anything nested under this template gets everything it inherited from us too, and it comes first since it's more specific
+            let mut flattened = nested.flatten_nested();
+            let nested = flattened.remove(0);
+            templates.extend(flattened);
+            templates.push(nested);
+        }
+
+        templates
+    }
     /// Gets the interval after which the template will next revalidate.
-    pub fn get_revalidate_interval(&self) -> Option<String> {
-        self.revalidate_after.clone()
+    pub fn get_revalidate_interval(&self) -> Option<Duration> {
+        self.revalidate_after
+    }
+    /// Gets the interval after which the page with the given build state will next revalidate, preferring any interval specific to
+    /// that page over the template's. This will fail if the page's interval is invalid.
+    pub fn get_revalidate_interval_for_page(
+        &self,
+        build_state: &BuildState,
+    ) -> Result<Option<Duration>> {
+        match &build_state.revalidate_after {
+            Some(interval) => Ok(Some(parse_time_str(interval)?)),
+            None => Ok(self.get_revalidate_interval()),
+        }
+    }
+    /// Checks that the rendering strategies this template uses can be used together, returning an `IncompatibleStrategies` error that
+    /// describes every problem if they can't. This is done automatically whenever templates are registered, so misconfigurations are
+    /// caught as soon as the app starts rather than when a page is first requested.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        // Incremental generation renders unknown paths beneath the ones generated at build-time (unless the template's path is dynamic, in
+        // which case it can generate every page), and it needs a state for each of them
+        if self.uses_incremental() && !self.uses_build_paths() && !self.has_dynamic_path() {
+            problems.push("incremental generation requires build paths (`.build_paths_fn()`)");
+        }
+        if self.uses_incremental() && self.get_build_state.is_none() {
+            problems.push(
+                "incremental generation requires build state for each page (`.build_state_fn()`)",
+            );
+        }
+        if self.has_incremental_fallback() && !self.uses_incremental() {
+            problems.push("a fallback for incremental generation requires incremental generation (`.incremental_path_rendering(true)`)");
+        }
+        // Revalidation regenerates the build state of a single page
+        if self.revalidates() && self.get_build_state.is_none() {
+            problems.push("revalidation requires build state for each page (`.build_state_fn()`)");
+        }
+        if self.can_amalgamate_states() && !(self.uses_build_state() && self.uses_request_state()) {
+            problems.push("amalgamating states requires both build state and request state");
+        }
+        // The last good pages of a circuit breaker are served to everyone, which would leak the state of pages personalized for one user
+        let serves_last_good = self
+            .circuit_breaker
+            .as_ref()
+            .map_or(false, |breaker| breaker.serves_last_good());
+        if serves_last_good && (self.uses_request_state() || self.can_amalgamate_states()) {
+            problems.push("serving the last good pages of a circuit breaker can't be used with request state, since they're served to every user (`.serve_last_good(false)`)");
+        }
+        // Parents are rendered around their nested templates' pages, which don't have states for them
+        if !self.nested.is_empty()
+            && (self.uses_build_state()
+                || self.uses_request_state()
+                || self.uses_build_paths_with_state())
+        {
+            problems.push("templates with nested templates can't have state of their own, since they're rendered around pages that don't have it (put it in the nested templates instead)");
+        }
+        if !self.nested.is_empty() && self.refetches_state() {
+            problems.push("templates with nested templates can't refetch their state, since they don't have any (refetch the state of the nested templates instead)");
+        }
+        if !self.nested.is_empty() && self.has_mutable_state() {
+            problems.push("templates with nested templates can't mutate their state, since they don't have any (mutate the state of the nested templates instead)");
+        }
+        if self.uses_build_state() && self.uses_request_state() && !self.can_amalgamate_states() {
+            problems.push("using both build state and request state requires a way to amalgamate them (`.amalgamate_states_fn()` or `.amalgamation_strategy()`)");
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => bail!(ErrorKind::IncompatibleStrategies(
+                self.get_path(),
+                problems.join("; ")
+            )),
+        }
     }
 
     // Render characteristic checkers
@@ -332,9 +1401,17 @@ impl<G: GenericNode> Template<G> {
     pub fn uses_incremental(&self) -> bool {
         self.incremental_path_rendering
     }
+    /// Checks if this template renders a placeholder for pages that are still being generated incrementally.
+    pub fn has_incremental_fallback(&self) -> bool {
+        self.incremental_fallback.is_some()
+    }
     /// Checks if this template is a template to generate paths beneath it.
     pub fn uses_build_paths(&self) -> bool {
-        self.get_build_paths.is_some()
+        self.get_build_paths.is_some() || self.get_build_paths_with_state.is_some()
+    }
+    /// Checks if this template gets its build paths along with their states.
+    pub fn uses_build_paths_with_state(&self) -> bool {
+        self.get_build_paths_with_state.is_some()
     }
     /// Checks if this template needs to do anything on requests for it.
     pub fn uses_request_state(&self) -> bool {
@@ -342,11 +1419,92 @@ impl<G: GenericNode> Template<G> {
     }
     /// Checks if this template needs to do anything at build time.
     pub fn uses_build_state(&self) -> bool {
-        self.get_build_state.is_some()
+        self.get_build_state.is_some() || self.get_build_paths_with_state.is_some()
     }
-    /// Checks if this template has custom logic to amalgamate build and reqquest states if both are generated.
+    /// Checks if this template has custom logic or a built-in strategy to amalgamate build and reqquest states if both are generated.
     pub fn can_amalgamate_states(&self) -> bool {
-        self.amalgamate_states.is_some()
+        self.amalgamate_states.is_some() || self.amalgamation_strategy.is_some()
+    }
+    /// Checks if this template keeps its mounted component when navigating between its pages on the client-side.
+    pub fn reuses_component(&self) -> bool {
+        self.reuse_component
+    }
+    /// Gets the identifier for the shape of this template's state, if it has one.
+    pub fn get_state_schema(&self) -> Option<String> {
+        self.state_schema.clone()
+    }
+    /// Checks if this template's path contains any dynamic segments (e.g. `docs/*` or `blog/<year>/<slug>`).
+    pub fn has_dynamic_path(&self) -> bool {
+        is_path_pattern(&self.path)
+    }
+    /// Checks if this template is rendered on top of the current page when navigated to on the client-side.
+    pub fn is_parallel_route(&self) -> bool {
+        self.parallel_route
+    }
+    /// Checks if this template is only rendered on the client-side.
+    pub fn is_client_side_only(&self) -> bool {
+        self.client_side_only
+    }
+    /// Checks if this template's pages will be left as their prerendered HTML on the client-side instead of being hydrated.
+    pub fn skips_hydration(&self) -> bool {
+        self.no_hydration && !self.client_side_only
+    }
+    /// Checks if this template's pages will be streamed, which is only done for pages rendered at request-time.
+    pub fn is_streaming(&self) -> bool {
+        self.streaming && self.uses_request_state() && !self.client_side_only
+    }
+    /// Checks if the client should refetch the state of this template's pages whenever the window regains focus.
+    pub fn refetches_on_focus(&self) -> bool {
+        self.refetch_on_focus
+    }
+    /// Gets the interval in milliseconds at which the client should refetch the state of this template's pages, if it should at all.
+    pub fn get_refetch_interval(&self) -> Option<u32> {
+        self.refetch_interval
+    }
+    /// Checks if the client should refetch the state of this template's pages at all. If so, it will be rendered with a [`ReactiveState`].
+    pub fn refetches_state(&self) -> bool {
+        self.refetches_on_focus() || self.refetch_interval.is_some()
+    }
+    /// Checks if this template's pages can mutate their own state on the client-side.
+    pub fn has_mutable_state(&self) -> bool {
+        self.mutable_state
+    }
+    /// Checks if this template is provided a [`ReactiveState`] through context when it's rendered.
+    pub fn uses_reactive_state(&self) -> bool {
+        self.reuses_component() || self.refetches_state() || self.has_mutable_state()
+    }
+    /// Gets the named state scopes this template is subscribed to.
+    pub fn get_state_scopes(&self) -> Vec<String> {
+        self.state_scopes.clone()
+    }
+    /// Gets the prefixes of the translation IDs this template's pages use, or `None` if it hasn't declared them (meaning it needs all
+    /// of them).
+    pub fn get_translation_prefixes(&self) -> Option<Vec<String>> {
+        self.translation_prefixes.clone()
+    }
+    /// Gets the size budget for this template's pages, if it has one different from the app's.
+    pub fn get_size_budget(&self) -> Option<&SizeBudget> {
+        self.size_budget.as_ref()
+    }
+    /// Gets the limit on how many of this template's pages can be rendered at once on the server, if it has one.
+    pub fn get_concurrency_limit(&self) -> Option<&ConcurrencyLimit> {
+        self.concurrency_limit.as_ref()
+    }
+    /// Gets the circuit breaker for this template's state functions, if it has one.
+    pub fn get_circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.circuit_breaker.as_ref()
+    }
+    /// Checks if this template is nested under another template.
+    pub fn is_nested(&self) -> bool {
+        self.parent_template.is_some()
+    }
+    /// Checks if this template is registered under a layout.
+    pub fn has_layout(&self) -> bool {
+        self.layout.is_some()
+    }
+    /// Checks if this template has hooks that should be run around rendering its pages for requests.
+    pub fn has_render_hooks(&self) -> bool {
+        self.before_render.is_some() || self.after_render.is_some()
     }
     /// Checks if this template defines no rendering logic whatsoever. Such templates will be rendered using SSG.
     pub fn is_basic(&self) -> bool {
@@ -363,6 +1521,76 @@ impl<G: GenericNode> Template<G> {
         self.template = val;
         self
     }
+    /// Sets the template rendering function to use, deserializing any state into the given type before it's passed to that function. This
+    /// avoids having to manually deserialize the stringified state in every template.
+    /// # Panics
+    /// The created template function will `panic!` if the state can't be deserialized into the given type, which indicates that it was
+    /// generated with a different type (make sure to use the same type with `.typed_build_state_fn()` etc.), or that it doesn't
+    /// deserialize to what it was serialized from (which can be checked with [`check_round_trip`](crate::state_types::check_round_trip)).
+    pub fn typed_template<S, F>(self, val: F) -> Template<G>
+    where
+        S: DeserializeOwned + 'static,
+        F: Fn(Option<S>) -> SycamoreTemplate<G> + 'static,
+    {
+        let path = self.get_path();
+        // The shape of the state is recorded with every page, so stale state from an older version of `S` is never served
+        let template = self.state_schema(state_schema::<S>());
+        template.template(Rc::new(move |props: Option<String>| {
+            let props = props.map(|props| {
+                serde_json::from_str::<S>(&props).unwrap_or_else(|err| {
+                    panic!(
+                        "state for template '{}' couldn't be deserialized (make sure it round-trips, and use the types in `perseus::state_types` for dates and decimals): '{}'",
+                        path, err
+                    )
+                })
+            });
+            val(props)
+        }))
+    }
+    /// Sets the identifier for the shape of this template's state (e.g. a version number that you change whenever you change your state),
+    /// which is recorded alongside the state of its pages. If a page's state was generated with a different identifier (e.g. by an older
+    /// build), it will be re-rendered before it's served, rather than sending the browser state that it might not be able to deserialize.
+    /// This is set automatically by `.typed_template()`, from the fields of its state type.
+    pub fn state_schema(mut self, val: String) -> Template<G> {
+        self.state_schema = Some(val);
+        self
+    }
+    /// Sets the function that renders the metadata for this template's pages from their state, which will be injected into the document
+    /// `<head>` (e.g. to set a `<title>` or `<meta>` tags).
+    pub fn head(mut self, val: HeadFn) -> Template<G> {
+        self.head = val;
+        self
+    }
+    /// Sets the function that returns the HTTP headers that should be sent with this template's pages, given their state (e.g. to set
+    /// `Cache-Control` or `X-Frame-Options`).
+    pub fn set_headers_fn(mut self, val: SetHeadersFn) -> Template<G> {
+        self.set_headers = val;
+        self
+    }
+    /// Sets the function that declares what content each page of this template depends on as surrogate keys (e.g. `post-123`), given
+    /// the page's state. If the server is configured with a surrogate key header, these will be sent with every page so that CDNs can
+    /// purge exactly the pages that depend on some content when it changes.
+    pub fn surrogate_keys_fn(mut self, val: SurrogateKeysFn) -> Template<G> {
+        self.surrogate_keys = Some(val);
+        self
+    }
+    /// Sets how long a page of this template can take to render on the server before it's reported as a slow render. Slow renders are
+    /// reported to the hook given with `.slow_render_hook()`, and aren't reported at all without one.
+    pub fn slow_render_threshold(mut self, val: Duration) -> Template<G> {
+        self.slow_render_threshold = Some(val);
+        self
+    }
+    /// Makes only one in every given number of slow renders be reported, so that busy pages don't flood the logs. By default, every slow
+    /// render is reported.
+    pub fn slow_render_sample_rate(mut self, val: u32) -> Template<G> {
+        self.slow_render_sample_rate = val;
+        self
+    }
+    /// Sets the function that will be called with slow renders (e.g. to log them, or to send them to a monitoring service).
+    pub fn slow_render_hook(mut self, val: SlowRenderHook) -> Template<G> {
+        self.slow_render_hook = Some(val);
+        self
+    }
     /// Enables the *build paths* strategy with the given function.
     pub fn build_paths_fn(mut self, val: GetBuildPathsFn) -> Template<G> {
         self.get_build_paths = Some(val);
@@ -373,16 +1601,57 @@ impl<G: GenericNode> Template<G> {
         self.incremental_path_rendering = val;
         self
     }
+    /// Sets the placeholder to render immediately for pages that haven't been generated incrementally yet, rather than making the first
+    /// user wait for the server to generate them. The real page will be fetched and rendered as soon as it's ready.
+    pub fn incremental_fallback(mut self, val: FallbackFn<G>) -> Template<G> {
+        self.incremental_fallback = Some(val);
+        self
+    }
+    /// Enables both the *build paths* and *build state* strategies with the given function, which should return the paths to render along
+    /// with their states. This avoids having to do the same work twice when getting the paths and their states. Note that incremental
+    /// generation and revalidation still require `.build_state_fn()`, because they need to get the state of a single page.
+    pub fn build_paths_with_state_fn(mut self, val: GetBuildPathsWithStateFn) -> Template<G> {
+        self.get_build_paths_with_state = Some(val);
+        self
+    }
     /// Enables the *build state* strategy with the given function.
     pub fn build_state_fn(mut self, val: GetBuildStateFn) -> Template<G> {
         self.get_build_state = Some(val);
         self
     }
+    /// Enables the *build state* strategy with the given function, which can return any serializable type as its state. This state will
+    /// be serialized automatically, and should be deserialized with `.typed_template()`.
+    pub fn typed_build_state_fn<S, F, Fut>(self, val: F) -> Template<G>
+    where
+        S: Serialize + 'static,
+        F: Fn(String, String) -> Fut + 'static,
+        Fut: Future<Output = StringResultWithCause<S>> + 'static,
+    {
+        self.build_state_fn(Rc::new(move |path: String, locale: String| {
+            let fut = val(path, locale);
+            async move { fut.await.and_then(|state| serialize_state(&state)) }
+        }))
+    }
     /// Enables the *request state* strategy with the given function.
     pub fn request_state_fn(mut self, val: GetRequestStateFn) -> Template<G> {
         self.get_request_state = Some(val);
         self
     }
+    /// Enables the *request state* strategy with the given function, which can return any serializable type as its state. This state
+    /// will be serialized automatically, and should be deserialized with `.typed_template()`.
+    pub fn typed_request_state_fn<S, F, Fut>(self, val: F) -> Template<G>
+    where
+        S: Serialize + 'static,
+        F: Fn(String, String, Request) -> Fut + 'static,
+        Fut: Future<Output = StringResultWithCause<S>> + 'static,
+    {
+        self.request_state_fn(Rc::new(
+            move |path: String, locale: String, req: Request| {
+                let fut = val(path, locale, req);
+                async move { fut.await.and_then(|state| serialize_state(&state)) }
+            },
+        ))
+    }
     /// Enables the *revalidation* strategy (logic variant) with the given function.
     pub fn should_revalidate_fn(mut self, val: ShouldRevalidateFn) -> Template<G> {
         self.should_revalidate = Some(val);
@@ -390,37 +1659,283 @@ impl<G: GenericNode> Template<G> {
     }
     /// Enables the *revalidation* strategy (time variant). This takes a time string of a form like `1w` for one week. More details are available
     /// [in the book](https://arctic-hen7.github.io/perseus/strategies/revalidation.html#time-syntax).
-    pub fn revalidate_after(mut self, val: String) -> Template<G> {
+    /// # Panics
+    /// This will `panic!` if the given time string is invalid, so that typos are caught as soon as the template is defined. If you need to
+    /// handle arbitrary time strings, use `.revalidate_after_checked()` instead, or use `.revalidate_after_duration()` to avoid them entirely.
+    pub fn revalidate_after(self, val: String) -> Template<G> {
+        match self.revalidate_after_checked(val) {
+            Ok(template) => template,
+            Err(err) => panic!("invalid revalidation interval for template: {}", err),
+        }
+    }
+    /// Enables the *revalidation* strategy (time variant), returning an error if the given time string is invalid.
+    pub fn revalidate_after_checked(mut self, val: String) -> Result<Template<G>> {
+        self.revalidate_after = Some(parse_time_str(&val)?);
+        Ok(self)
+    }
+    /// Enables the *revalidation* strategy (time variant) with the given interval.
+    pub fn revalidate_after_duration(mut self, val: Duration) -> Template<G> {
         self.revalidate_after = Some(val);
         self
     }
+    /// Sets a function to be run on the server before each page of this template is rendered for a request, which can reject the request
+    /// by returning an error (e.g. for authorization checks) or just observe it (e.g. for logging). This is run before anything else,
+    /// including the template's state functions and its circuit breaker, so a page it rejects is never generated or served in any form
+    /// (not even as a placeholder or a redirect). Pages prerendered at build-time (or revalidated or generated incrementally) aren't
+    /// rendered again for each request, but this will still be run before their HTML is sent.
+    pub fn before_render_fn(mut self, val: BeforeRenderFn) -> Template<G> {
+        self.before_render = Some(val);
+        self
+    }
+    /// Sets a function to be run on the server after each page of this template is rendered for a request, which can post-process the
+    /// page's HTML. This isn't run on the HTML the app shell renders on the client-side.
+    pub fn after_render_fn(mut self, val: AfterRenderFn) -> Template<G> {
+        self.after_render = Some(val);
+        self
+    }
     /// Enables state amalgamation with the given function.
     pub fn amalgamate_states_fn(mut self, val: AmalgamateStatesFn) -> Template<G> {
         self.amalgamate_states = Some(val);
         self
     }
+    /// Enables state amalgamation with the given function, which will be given the build and request states deserialized into the given
+    /// type, and can return any state of that type, which will be serialized automatically. This should be used with the typed
+    /// state functions (like `.typed_build_state_fn()`).
+    pub fn typed_amalgamate_states_fn<S, F, Fut>(self, val: F) -> Template<G>
+    where
+        S: Serialize + DeserializeOwned + 'static,
+        F: Fn(States<S>) -> Fut + 'static,
+        Fut: Future<Output = StringResultWithCause<Option<S>>> + 'static,
+    {
+        let val = Rc::new(val);
+        self.amalgamate_states_fn(Rc::new(move |states: States| {
+            let val = Rc::clone(&val);
+            async move {
+                let states = States {
+                    build_state: deserialize_state(states.build_state)?,
+                    request_state: deserialize_state(states.request_state)?,
+                    strategy: states.strategy,
+                };
+                match val(states).await? {
+                    Some(state) => serialize_state(&state).map(Some),
+                    None => Ok(None),
+                }
+            }
+        }))
+    }
+    /// Enables state amalgamation with the given built-in strategy. If a custom function is also given with `.amalgamate_states_fn()`,
+    /// that will be used instead.
+    pub fn amalgamation_strategy(mut self, val: AmalgamationStrategy) -> Template<G> {
+        self.amalgamation_strategy = Some(val);
+        self
+    }
+    /// Sets whether or not the template should be kept mounted when navigating between its pages, with only its [`ReactiveState`] being
+    /// updated.
+    pub fn reuse_component(mut self, val: bool) -> Template<G> {
+        self.reuse_component = val;
+        self
+    }
+    /// Sets whether or not the template is a parallel route, which will be rendered in an overlay on top of the current page on client-side
+    /// navigations rather than replacing it. Going back to the page underneath will close the overlay.
+    pub fn parallel_route(mut self, val: bool) -> Template<G> {
+        self.parallel_route = val;
+        self
+    }
+    /// Sets whether or not the template is only rendered on the client-side, which skips prerendering its pages on the server entirely.
+    /// The server will only send the page's state and head, and the app shell will render it in the browser.
+    pub fn client_side_only(mut self, val: bool) -> Template<G> {
+        self.client_side_only = val;
+        self
+    }
+    /// Sets whether or not the template's pages should skip hydration on the client-side, leaving just their prerendered HTML. This is
+    /// useful for purely static pages that don't need any interactivity.
+    pub fn no_hydration(mut self, val: bool) -> Template<G> {
+        self.no_hydration = val;
+        self
+    }
+    /// Sets whether or not this template's pages should be streamed. If they are, the server will send everything but a page's content
+    /// (like its head and state, or the app shell on the initial load) as soon as its state has been generated, and then the content
+    /// once it's been rendered. This only makes a difference for pages that are rendered at request-time. Note that the status code has
+    /// to be sent before the content is rendered, so, if that fails, the response will be aborted rather than being sent as an error.
+    pub fn streaming(mut self, val: bool) -> Template<G> {
+        self.streaming = val;
+        self
+    }
+    /// Sets whether or not the client should refetch the state of this template's pages whenever the window regains focus. New states
+    /// will be fed into the [`ReactiveState`] provided through context, so the template should read its state from that.
+    pub fn refetch_on_focus(mut self, val: bool) -> Template<G> {
+        self.refetch_on_focus = val;
+        self
+    }
+    /// Makes the client refetch the state of this template's pages every given number of milliseconds while they're being shown. New
+    /// states will be fed into the [`ReactiveState`] provided through context, so the template should read its state from that.
+    pub fn refetch_interval(mut self, val: u32) -> Template<G> {
+        self.refetch_interval = Some(val);
+        self
+    }
+    /// Sets whether or not this template's pages can mutate their own state on the client-side. If `true`, the template will be provided
+    /// a [`ReactiveState`] through context, which can be updated with the utilities in the [`mutation`](crate::mutation) module.
+    pub fn mutable_state(mut self, val: bool) -> Template<G> {
+        self.mutable_state = val;
+        self
+    }
+    /// Sets the most bytes the artifacts generated for this template's pages at build-time should be, instead of the app's budget (e.g.
+    /// for a template that's expected to have large pages, like a changelog). Pages over it will be reported after the build.
+    pub fn size_budget(mut self, val: SizeBudget) -> Template<G> {
+        self.size_budget = Some(val);
+        self
+    }
+    /// Limits how many of this template's pages can be rendered at once on the server (across every worker), which protects whatever its
+    /// state functions depend on (like an upstream API or a database) from being hammered when a page is expensive to render. Requests
+    /// over the limit will wait for a render to finish, and will be sent a `503 Service Unavailable` if they wait too long.
+    pub fn concurrency_limit(mut self, val: ConcurrencyLimit) -> Template<G> {
+        self.concurrency_limit = Some(val);
+        self
+    }
+    /// Stops calling this template's state functions for a while when rendering its pages on the server keeps failing (like when an
+    /// upstream API is down), serving the last good version of each page (or a degraded one) instead. See [`CircuitBreaker`] for the
+    /// details.
+    pub fn circuit_breaker(mut self, val: CircuitBreaker) -> Template<G> {
+        self.circuit_breaker = Some(val);
+        self
+    }
+    /// Gives this template's state functions the resources in the given cell. This is done by the [`TemplateRegistry`] of the app the
+    /// template is registered in.
+    pub(crate) fn provide_resources(mut self, val: &'static ResourcesCell) -> Template<G> {
+        self.resources = Some(val);
+        self
+    }
+    /// Subscribes this template to the named state scope, the state of which is shared with every other template subscribed to it and
+    /// kept across navigations on the client-side. This can be called multiple times to subscribe to several scopes.
+    pub fn state_scope(mut self, val: impl Into<String>) -> Template<G> {
+        self.state_scopes.push(val.into());
+        self
+    }
+    /// Declares that this template's pages use translation IDs starting with the given prefix (e.g. `about-` for `about-title` and
+    /// `about-intro`). Once a template has declared any prefixes, the app shell will only fetch the translations starting with them for
+    /// its pages, fetching more lazily as the user navigates to pages that need them. Any translations used by this template's layout
+    /// or parent template must be covered too, and Fluent terms (like `-brand`) are always sent.
+    pub fn translation_prefix(mut self, val: impl Into<String>) -> Template<G> {
+        self.translation_prefixes
+            .get_or_insert_with(Vec::new)
+            .push(val.into());
+        self
+    }
+    /// Nests the given template under this one, to be rendered inside this template's [`Outlet`]. The given template's path should be
+    /// relative to this template's path (e.g. `profile` for a template nested under `settings`), and it can have templates nested under
+    /// it in turn. Templates with nested templates can't have state of their own (which `.validate()` checks), since they're rendered
+    /// around pages of their nested templates that don't have any for them.
+    pub fn nested(mut self, val: Template<G>) -> Template<G> {
+        self.nested.push(val);
+        self
+    }
+    /// Registers this template under the given layout, which will wrap its view (e.g. with a navbar and a footer). The same layout can be
+    /// shared between any number of templates by cloning the `Rc`. Any templates nested under this one will use this layout too.
+    pub fn layout(mut self, val: LayoutFn<G>) -> Template<G> {
+        self.layout = Some(val);
+        self
+    }
+}
+impl Template<SsrNode> {
+    /// Renders the page at the given path (without the locale) of this template from the given state to a string of HTML on the
+    /// server-side. If the template is client-side only, this will be empty, since the app shell will render the page in the browser.
+    pub fn render_html_str(
+        &self,
+        props: Option<String>,
+        translator: Rc<Translator>,
+        path: &str,
+    ) -> String {
+        if self.client_side_only {
+            return String::new();
+        }
+        let render_ctx = Signal::new(RenderCtx {
+            path: path.to_string(),
+            locale: translator.get_locale(),
+            backend: RenderBackend::Server,
+            alternates: get_alternates(path, &translator.get_locale()),
+        });
+        // Sycamore can only render whole templates to strings, so streaming happens around this (see `StreamedPageData` and `InitialPage`)
+        sycamore::render_to_string(|| {
+            self.render_for_template(
+                props,
+                translator,
+                ScopedStates::new(),
+                Signal::new(None),
+                render_ctx,
+            )
+        })
+    }
 }
 
-/// Gets a `HashMap` of the given templates by their paths for serving. This should be manually wrapped for the pages your app provides
-/// for convenience.
+/// Gets a map of the given templates by their paths for serving, ordered by their paths so that identical apps always produce identical
+/// code. This should be manually wrapped for the pages your app provides for convenience. This is shorthand for adding each of the
+/// templates to a [`TemplateMapBuilder`], and it will `panic!` if any of them conflict. If you need to register some templates
+/// conditionally, use the builder directly.
 #[macro_export]
 macro_rules! get_templates_map {
     [
         $($template:expr),+
     ] => {
-        {
-            let mut map = ::std::collections::HashMap::new();
-            $(
-                map.insert(
-                    $template.get_path(),
-                    $template
-                );
-            )+
+        $crate::template::TemplateMapBuilder::new()
+            $(.add($template))+
+            .build()
+            .unwrap_or_else(|err| panic!("couldn't create templates map: {}", err))
+    };
+}
 
-            map
+/// A builder for a map of templates by their paths, which can register templates conditionally (e.g. admin pages that should only exist
+/// with some environment variable set) and let plugins make their changes. Any templates nested under the given ones are registered in
+/// their own right. Errors are reported when the map is built, so two templates with the same path (including nested ones) will be a
+/// `TemplateConflict` error, and templates using rendering strategies that can't be used together will be an `IncompatibleStrategies`
+/// error.
+pub struct TemplateMapBuilder<G: GenericNode> {
+    /// The templates registered so far, or the first error that occurred while registering them.
+    registry: Result<TemplateRegistry<G>>,
+}
+impl<G: GenericNode> Default for TemplateMapBuilder<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<G: GenericNode> TemplateMapBuilder<G> {
+    /// Creates a new builder without any templates.
+    pub fn new() -> Self {
+        Self {
+            registry: TemplateRegistry::new(Vec::new()),
         }
-    };
+    }
+    /// Adds the given template.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, template: Template<G>) -> Self {
+        let res = match &mut self.registry {
+            Ok(registry) => registry.add(template.get_path(), template),
+            // We only report the first error
+            Err(_) => Ok(()),
+        };
+        if let Err(err) = res {
+            self.registry = Err(err);
+        }
+        self
+    }
+    /// Adds the template created by the given function, but only if the given condition is true (the template won't be created at all
+    /// otherwise).
+    pub fn add_if(self, cond: bool, template: impl FnOnce() -> Template<G>) -> Self {
+        match cond {
+            true => self.add(template()),
+            false => self,
+        }
+    }
+    /// Lets the given plugin add, modify, or remove templates.
+    pub fn apply(mut self, plugin: &impl Plugin) -> Self {
+        self.registry = self.registry.and_then(|registry| registry.apply(plugin));
+        self
+    }
+    /// Builds the map of templates, returning the first error that occurred while registering them, if there was one.
+    pub fn build(self) -> Result<TemplateMap<G>> {
+        Ok(self.registry?.get_templates_map())
+    }
 }
 
-/// A type alias for a `HashMap` of `Template`s.
-pub type TemplateMap<G> = HashMap<String, Template<G>>;
+/// A type alias for a map of `Template`s by their paths. This is ordered by path (rather than being a `HashMap`) so that iterating over it
+/// is the same in every build, which keeps builds (and the diffs between them) reproducible.
+pub type TemplateMap<G> = BTreeMap<String, Template<G>>;
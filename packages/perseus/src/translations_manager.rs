@@ -2,12 +2,17 @@
 // At simplest, this is just a filesystem interface, but it might be something like a database in production
 // This has its own error management logic because the user may implement it separately
 
+use crate::clock;
 use crate::Translator;
+use chrono::{DateTime, Duration, Utc};
 pub use error_chain::bail;
 use error_chain::error_chain;
 use futures::future::join_all;
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::UNIX_EPOCH;
 
 // This has no foreign links because everything to do with config management should be isolated and generic
 error_chain! {
@@ -32,22 +37,82 @@ error_chain! {
 }
 
 /// A trait for systems that manage where to put translations. At simplest, we'll just write them to static files, but they might also
-/// be stored in a CMS. It is **strongly** advised that any implementations use some form of caching, guided by `FsTranslationsManager`.
-#[async_trait::async_trait]
+/// be stored in a CMS, a database, or a bucket like S3, or fetched from a translation service over HTTP. Only
+/// `get_translations_str_for_locale` has to be implemented, and it is **strongly** advised that any implementations that don't read from
+/// the filesystem use some form of caching, which can be added to any of them by wrapping them in a `CachedTranslationsManager`. Like
+/// `ConfigManager`, the futures returned don't need to be `Send`.
+#[async_trait::async_trait(?Send)]
 pub trait TranslationsManager: Clone {
-    /// Gets a translator for the given locale.
-    async fn get_translator_for_locale(&self, locale: String) -> Result<Translator>;
-    /// Gets the translations in string format for the given locale (avoids deserialize-then-serialize).
+    /// Gets the translations in string format for the given locale (avoids deserialize-then-serialize). If the locale's translations are
+    /// split into several resources, this should give them all joined with `Translator::join_resources()`.
     async fn get_translations_str_for_locale(&self, locale: String) -> Result<String>;
+    /// Gets the resources the translations for the given locale are split into (like the files in a locale's directory). By default, this
+    /// gives the translations from `get_translations_str_for_locale` as a single resource.
+    async fn get_translation_resources_for_locale(&self, locale: String) -> Result<Vec<String>> {
+        Ok(vec![self.get_translations_str_for_locale(locale).await?])
+    }
+    /// Gets a translator for the given locale. By default, this creates one from the resources given by
+    /// `get_translation_resources_for_locale`, so that errors in them can say which resource they're in.
+    async fn get_translator_for_locale(&self, locale: String) -> Result<Translator> {
+        let resources = self
+            .get_translation_resources_for_locale(locale.clone())
+            .await?;
+        // We expect the translations defined there, but not the locale itself
+        let translator = Translator::with_resources(locale.clone(), resources)
+            .map_err(|err| ErrorKind::SerializationFailed(locale.clone(), err.to_string()))?;
+
+        Ok(translator)
+    }
+    /// Gets the translations in string format for the given locale, filtered down to those with IDs starting with any of the given
+    /// prefixes (if any are given). By default, this filters the full translations with the app's translator backend, but storage systems
+    /// that can filter translations more efficiently themselves may want to override this.
+    async fn get_translations_str_for_prefixes(
+        &self,
+        locale: String,
+        prefixes: Option<Vec<String>>,
+    ) -> Result<String> {
+        let translations_str = self.get_translations_str_for_locale(locale).await?;
+        match prefixes {
+            Some(prefixes) => Ok(Translator::filter_translations(
+                &translations_str,
+                &prefixes,
+            )),
+            None => Ok(translations_str),
+        }
+    }
+    /// Gets something that identifies the current version of the translations (like when they were last edited), which changes whenever
+    /// they do. In development, the server uses this to tell the app shell to reload its translations without anything being rebuilt. By
+    /// default, this returns `None`, which means translations won't be reloaded.
+    async fn get_translations_revision(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Gets the translation ID prefixes requested in the given query string of a request for translations (e.g. `prefixes=about-,nav-`), if
+/// there are any. The app shell requests only the translations a template has declared it uses like this.
+pub fn get_translation_prefixes(query: &str) -> Option<Vec<String>> {
+    let prefixes = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("prefixes="))?;
+    let prefixes = prefixes
+        .split(',')
+        .filter(|prefix| !prefix.is_empty())
+        .map(|prefix| match urlencoding::decode(prefix) {
+            Ok(prefix) => prefix.to_string(),
+            Err(_) => prefix.to_string(),
+        })
+        .collect();
+
+    Some(prefixes)
 }
 
-/// A utility function for allowing parallel futures execution. This returns a tuple of the locale and the translations as a JSON string.
+/// A utility function for allowing parallel futures execution. This returns a tuple of the locale and the resources of its translations.
 async fn get_translations_str_and_cache(
     locale: String,
     manager: &FsTranslationsManager,
-) -> (String, String) {
-    let translations_str = manager
-        .get_translations_str_for_locale(locale.to_string())
+) -> (String, Vec<String>) {
+    let resources = manager
+        .get_translation_resources_for_locale(locale.to_string())
         .await
         .unwrap_or_else(|_| {
             panic!(
@@ -56,18 +121,21 @@ async fn get_translations_str_and_cache(
             )
         });
 
-    (locale, translations_str)
+    (locale, resources)
 }
 
 /// The default translations manager. This will store static files in the specified location on disk. This should be suitable for
 /// nearly all development and serverful use-cases. Serverless is another matter though (more development needs to be done). This
-/// mandates that translations be stored as files named as the locale they describe (e.g. 'en-US.ftl', 'en-US.json', etc.).
+/// mandates that translations be stored as files named as the locale they describe (e.g. 'en-US.ftl', 'en-US.json', etc.), or, for
+/// locales with lots of translations, as several files in a directory named as the locale (e.g. `en-US/nav.ftl` and `en-US/about.ftl`),
+/// which will be used together in the order of their names.
 #[derive(Clone)]
 pub struct FsTranslationsManager {
     root_path: String,
-    /// A map of locales to cached translations. This decreases the number of file reads significantly for the locales specified. This
-    /// does NOT cache dynamically, and will only cache the requested locales. Translators can be created when necessary from these.
-    cached_translations: HashMap<String, String>,
+    /// A map of locales to the resources of their cached translations. This decreases the number of file reads significantly for the
+    /// locales specified. This does NOT cache dynamically, and will only cache the requested locales. Translators can be created when
+    /// necessary from these.
+    cached_translations: HashMap<String, Vec<String>>,
     /// The locales being cached for easier access.
     cached_locales: Vec<String>,
     /// The file extension expected (e.g. JSON, FTL, etc). This allows for greater flexibility of translation engines (future).
@@ -99,42 +167,174 @@ impl FsTranslationsManager {
 
         manager
     }
+    /// Reads the resources of the translations for the given locale from disk, which are either every file with the right extension in a
+    /// directory named as the locale (in the order of their names), or a single file named as the locale.
+    fn read_resources(&self, locale: &str) -> Result<Vec<String>> {
+        let dir_path = format!("{}/{}", self.root_path, locale);
+        if Path::new(&dir_path).is_dir() {
+            let mut paths: Vec<_> = fs::read_dir(&dir_path)
+                .map_err(|err| ErrorKind::ReadFailed(locale.to_string(), err.to_string()))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file() && self.is_translations_file(path))
+                .collect();
+            // Filesystems don't list files in any particular order, but resources should always be added in the same one
+            paths.sort();
+            return paths
+                .iter()
+                .map(|path| {
+                    fs::read_to_string(path).map_err(|err| {
+                        Error::from(ErrorKind::ReadFailed(
+                            path.to_string_lossy().to_string(),
+                            err.to_string(),
+                        ))
+                    })
+                })
+                .collect();
+        }
+
+        // The file must be named as the locale it describes
+        let asset_path = format!("{}/{}.{}", self.root_path, locale, self.file_ext);
+        let translations_str = match fs::metadata(&asset_path) {
+            Ok(_) => fs::read_to_string(&asset_path)
+                .map_err(|err| ErrorKind::ReadFailed(asset_path, err.to_string()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                bail!(ErrorKind::NotFound(asset_path))
+            }
+            Err(err) => bail!(ErrorKind::ReadFailed(locale.to_string(), err.to_string())),
+        };
+        Ok(vec![translations_str])
+    }
+    /// Checks if the given path is a translations file (rather than anything else that's been put next to them).
+    fn is_translations_file(&self, path: &Path) -> bool {
+        path.extension()
+            .map_or(false, |ext| ext == self.file_ext.as_str())
+    }
 }
-#[async_trait::async_trait]
+#[async_trait::async_trait(?Send)]
 impl TranslationsManager for FsTranslationsManager {
     async fn get_translations_str_for_locale(&self, locale: String) -> Result<String> {
+        let resources = self.get_translation_resources_for_locale(locale).await?;
+        Ok(Translator::join_resources(resources))
+    }
+    async fn get_translation_resources_for_locale(&self, locale: String) -> Result<Vec<String>> {
         // Check if the locale is cached for
         // No dynamic caching, so if it isn't cached it stays that way
-        if self.cached_locales.contains(&locale) {
-            Ok(self.cached_translations.get(&locale).unwrap().to_string())
+        // In development, we always read from disk so that edited translations can be reloaded
+        if self.cached_locales.contains(&locale) && !cfg!(debug_assertions) {
+            Ok(self.cached_translations.get(&locale).unwrap().clone())
         } else {
-            // The file must be named as the locale it describes
-            let asset_path = format!("{}/{}.{}", self.root_path, locale, self.file_ext);
-            let translations_str = match fs::metadata(&asset_path) {
-                Ok(_) => fs::read_to_string(&asset_path)
-                    .map_err(|err| ErrorKind::ReadFailed(asset_path, err.to_string()))?,
-                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-                    bail!(ErrorKind::NotFound(asset_path))
-                }
-                Err(err) => bail!(ErrorKind::ReadFailed(locale.to_string(), err.to_string())),
+            self.read_resources(&locale)
+        }
+    }
+    async fn get_translations_revision(&self) -> Option<String> {
+        // Editing any translations file (including those in a locale's directory) will change the most recent time one was modified
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&self.root_path)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+        {
+            let path = entry.path();
+            match path.is_dir() {
+                true => paths.extend(
+                    fs::read_dir(&path)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path()),
+                ),
+                false => paths.push(path),
+            }
+        }
+        let last_modified = paths
+            .iter()
+            .filter(|path| self.is_translations_file(path))
+            .filter_map(|path| fs::metadata(path).ok()?.modified().ok())
+            .max()?;
+        let last_modified = last_modified.duration_since(UNIX_EPOCH).ok()?;
+
+        Some(last_modified.as_nanos().to_string())
+    }
+}
+
+/// A translations manager that caches the translations of another one in memory, which is useful for managers that get translations
+/// from somewhere slow (like a database or a translation service over HTTP). Unlike `FsTranslationsManager`, this caches every locale
+/// as it's requested, and the cache is shared between all clones of the manager. By default, translations are cached until the server
+/// restarts, but they can be refetched after some time with `.with_max_age()`, and they'll be refetched whenever the revision of the
+/// translations changes (if the other manager can tell).
+#[derive(Clone)]
+pub struct CachedTranslationsManager<T: TranslationsManager> {
+    /// The translations manager the translations are actually fetched from.
+    inner: T,
+    /// The cached translations and when they were fetched, by locale.
+    cache: Arc<RwLock<HashMap<String, (String, DateTime<Utc>)>>>,
+    /// How long translations are cached for, if they aren't cached forever.
+    max_age: Option<Duration>,
+    /// The last revision of the translations we've seen, which the cache is cleared for whenever it changes.
+    revision: Arc<RwLock<Option<String>>>,
+}
+impl<T: TranslationsManager> CachedTranslationsManager<T> {
+    /// Creates a new caching translations manager around the given one.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+            max_age: None,
+            revision: Arc::new(RwLock::new(None)),
+        }
+    }
+    /// Sets how long translations are cached for before they're fetched again.
+    pub fn with_max_age(mut self, val: Duration) -> Self {
+        self.max_age = Some(val);
+        self
+    }
+    /// Clears the cache, so that all translations will be fetched again the next time they're needed (e.g. after they've been updated in
+    /// a translation service).
+    pub fn clear(&self) {
+        if let Ok(mut cache) = self.cache.write() {
+            cache.clear();
+        }
+    }
+}
+#[async_trait::async_trait(?Send)]
+impl<T: TranslationsManager> TranslationsManager for CachedTranslationsManager<T> {
+    async fn get_translations_str_for_locale(&self, locale: String) -> Result<String> {
+        let cached = self
+            .cache
+            .read()
+            .ok()
+            .and_then(|cache| cache.get(&locale).cloned());
+        if let Some((translations_str, fetched)) = cached {
+            let is_fresh = match self.max_age {
+                Some(max_age) => clock::now() < fetched + max_age,
+                None => true,
             };
-            Ok(translations_str)
+            if is_fresh {
+                return Ok(translations_str);
+            }
+        }
+        let translations_str = self
+            .inner
+            .get_translations_str_for_locale(locale.clone())
+            .await?;
+        // If the lock has been poisoned, we just won't cache anything
+        if let Ok(mut cache) = self.cache.write() {
+            cache.insert(locale, (translations_str.clone(), clock::now()));
         }
+
+        Ok(translations_str)
     }
-    async fn get_translator_for_locale(&self, locale: String) -> Result<Translator> {
-        // Check if the locale is cached for
-        // No dynamic caching, so if it isn't cached it stays that way
-        let translations_str;
-        if self.cached_locales.contains(&locale) {
-            translations_str = self.cached_translations.get(&locale).unwrap().to_string();
-        } else {
-            translations_str = self.get_translations_str_for_locale(locale.clone()).await?;
+    async fn get_translations_revision(&self) -> Option<String> {
+        let revision = self.inner.get_translations_revision().await;
+        // Anything we've cached is out of date if the translations have changed
+        if let Ok(mut last_revision) = self.revision.write() {
+            if *last_revision != revision {
+                self.clear();
+                *last_revision = revision.clone();
+            }
         }
-        // We expect the translations defined there, but not the locale itself
-        let translator = Translator::new(locale.clone(), translations_str)
-            .map_err(|err| ErrorKind::SerializationFailed(locale.clone(), err.to_string()))?;
 
-        Ok(translator)
+        revision
     }
 }
 
@@ -148,15 +348,9 @@ impl DummyTranslationsManager {
         Self::default()
     }
 }
-#[async_trait::async_trait]
+#[async_trait::async_trait(?Send)]
 impl TranslationsManager for DummyTranslationsManager {
     async fn get_translations_str_for_locale(&self, _locale: String) -> Result<String> {
         Ok(String::new())
     }
-    async fn get_translator_for_locale(&self, locale: String) -> Result<Translator> {
-        let translator = Translator::new(locale.clone(), String::new())
-            .map_err(|err| ErrorKind::SerializationFailed(locale.clone(), err.to_string()))?;
-
-        Ok(translator)
-    }
 }
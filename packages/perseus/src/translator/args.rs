@@ -0,0 +1,79 @@
+#[cfg(feature = "translator-dflt-fluent")]
+use crate::translator::IntoFluentArgs;
+use crate::translator::TranslationArgs;
+
+/// The default translator's arguments with the given lifetime (Fluent's arguments can borrow their values, but the simple JSON
+/// translator's can't).
+#[cfg(feature = "translator-dflt-fluent")]
+pub(crate) type ArgsWithLifetime<'a> = TranslationArgs<'a>;
+#[cfg(feature = "translator-dflt-simple-json")]
+pub(crate) type ArgsWithLifetime<'a> = TranslationArgs;
+
+/// Something that can be given to a [`Translator`](crate::Translator) as the arguments for a translation: `None`, the default
+/// translator's arguments (directly or in `Some`), or, with Fluent, anything that implements `IntoFluentArgs` (like a struct that derives
+/// it). This means `translator.translate("greeting", args! { "name" => name })` and `translator.translate("about", None)` both work.
+pub trait IntoTranslationArgs<'a> {
+    /// Converts this into the default translator's arguments, if there are any.
+    fn into_translation_args(self) -> Option<ArgsWithLifetime<'a>>;
+}
+#[cfg(feature = "translator-dflt-fluent")]
+impl<'a, T: IntoFluentArgs> IntoTranslationArgs<'a> for T {
+    fn into_translation_args(self) -> Option<ArgsWithLifetime<'a>> {
+        Some(self.into_fluent_args())
+    }
+}
+#[cfg(feature = "translator-dflt-fluent")]
+impl<'a> IntoTranslationArgs<'a> for Option<TranslationArgs<'a>> {
+    fn into_translation_args(self) -> Option<ArgsWithLifetime<'a>> {
+        self
+    }
+}
+#[cfg(feature = "translator-dflt-simple-json")]
+impl<'a> IntoTranslationArgs<'a> for TranslationArgs {
+    fn into_translation_args(self) -> Option<ArgsWithLifetime<'a>> {
+        Some(self)
+    }
+}
+#[cfg(feature = "translator-dflt-simple-json")]
+impl<'a> IntoTranslationArgs<'a> for Option<TranslationArgs> {
+    fn into_translation_args(self) -> Option<ArgsWithLifetime<'a>> {
+        self
+    }
+}
+
+/// Creates arguments for a translation with the default translator from pairs of names and values (e.g.
+/// `args! { "name" => name, "unread" => 3 }`), which can be given straight to a translator's `.translate()`. With Fluent, each value
+/// can be anything that implements `IntoFluentValue`.
+#[cfg(feature = "translator-dflt-fluent")]
+#[macro_export]
+macro_rules! args {
+    {$($key:literal => $value:expr),* $(,)?} => {
+        {
+            #[allow(unused_mut)]
+            let mut args = $crate::translator::FluentArgs::new();
+            $(
+                args.set($key, $crate::translator::IntoFluentValue::into_fluent_value($value));
+            )*
+
+            args
+        }
+    };
+}
+/// Creates arguments for a translation with the default translator from pairs of names and values (e.g.
+/// `args! { "name" => name, "unread" => 3 }`), which can be given straight to a translator's `.translate()`. With the simple JSON
+/// translator, each value can be anything that implements `ToString`.
+#[cfg(feature = "translator-dflt-simple-json")]
+#[macro_export]
+macro_rules! args {
+    {$($key:literal => $value:expr),* $(,)?} => {
+        {
+            #[allow(unused_mut)]
+            let mut args = $crate::translator::SimpleJsonArgs::new();
+            $(
+                args.set($key, $value);
+            )*
+
+            args
+        }
+    };
+}
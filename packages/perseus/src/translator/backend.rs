@@ -0,0 +1,309 @@
+use crate::translator::errors::*;
+use crate::translator::formatting::{self, DateStyle};
+use crate::translator::strict::{is_recording_failed_translations, record_failed_translation};
+use crate::translator::IntoTranslationArgs;
+use crate::translator::TextDirection;
+use crate::translator::TranslationArgs;
+use crate::{Locales, UrlBuilder};
+use chrono::Datelike;
+use once_cell::sync::OnceCell;
+
+/// The function that creates translators with the app's translator backend, if it isn't using the default one.
+static BACKEND_FN: OnceCell<fn(String, String) -> Result<Translator>> = OnceCell::new();
+/// The function that creates translators from several resources with the app's translator backend, if it isn't using the default one.
+static RESOURCES_FN: OnceCell<fn(String, Vec<String>) -> Result<Translator>> = OnceCell::new();
+/// The function that filters translations with the app's translator backend, if it isn't using the default one.
+static FILTER_FN: OnceCell<fn(&str, &[String]) -> String> = OnceCell::new();
+/// The function that joins resources of translations with the app's translator backend, if it isn't using the default one.
+static JOIN_FN: OnceCell<fn(Vec<String>) -> String> = OnceCell::new();
+/// The app's locales, which translators use to localize URLs with the app's URL strategy.
+static LOCALES: OnceCell<Locales> = OnceCell::new();
+
+/// A translation system that can be used behind a [`Translator`]. Perseus' own translators implement this, and apps can implement it
+/// to use another system (like gettext) without forking Perseus, setting it as their backend with `translator_backend` in
+/// `define_app!`.
+///
+/// Backends are given translation arguments in the form of the default translator's arguments (`FluentArgs` with the
+/// `translator-dflt-fluent` feature, or `SimpleJsonArgs` with `translator-dflt-simple-json`), since those are what apps create with the
+/// `t!` macro. If you don't need Fluent, using the simple JSON translator as the default will keep Fluent out of your app's bundle.
+pub trait TranslatorBackend {
+    /// Creates a new backend for the given locale from the given translations, which will be the contents of that locale's translations
+    /// file.
+    fn new(locale: String, translations: String) -> Result<Self>
+    where
+        Self: Sized;
+    /// Creates a new backend for the given locale from several resources of translations (like the files in a locale's directory), which
+    /// should all be used together. By default, this creates one from the resources joined with `.join_resources()`.
+    fn with_resources(locale: String, resources: Vec<String>) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::new(locale, Self::join_resources(resources))
+    }
+    /// Gets the locale this backend is translating into.
+    fn get_locale(&self) -> String;
+    /// Translates the given ID, interpolating any given arguments, returning an error if that isn't possible.
+    fn translate_checked(&self, id: &str, args: Option<TranslationArgs>) -> Result<String>;
+    /// Translates the given ID, interpolating any given arguments. By default, this will `panic!` if the ID can't be translated, but
+    /// backends can handle missing translations differently (like the `FluentTranslator` with a `MissingTranslationHandler`).
+    fn translate(&self, id: &str, args: Option<TranslationArgs>) -> String {
+        match self.translate_checked(id, args) {
+            Ok(translation) => translation,
+            Err(_) => panic!("translation id '{}' not found for locale '{}' (if you're not hardcoding the id, use `.translate_checked()` instead)", id, self.get_locale())
+        }
+    }
+    /// Gets the path to the given URL in this backend's locale, which is only used if the app's locales haven't been given to translators
+    /// (otherwise they're localized with the app's URL strategy). By default, this just puts the locale before it.
+    fn url(&self, url: &str) -> String {
+        format!("/{}{}", self.get_locale(), url)
+    }
+    /// Filters the given translations (the contents of a translations file) down to those with IDs starting with any of the given
+    /// prefixes, which is used to send templates only the translations they've declared they need. By default, this doesn't filter
+    /// anything out.
+    fn filter_translations(translations: &str, _prefixes: &[String]) -> String
+    where
+        Self: Sized,
+    {
+        translations.to_string()
+    }
+    /// Joins several resources of translations into one, which is what the browser is sent for a locale that has more than one. By
+    /// default, they're just put one after another on separate lines.
+    fn join_resources(resources: Vec<String>) -> String
+    where
+        Self: Sized,
+    {
+        resources.join("\n")
+    }
+}
+
+/// Creates a translator with the given backend for the given locale from the given translations.
+fn new_with_backend<B: TranslatorBackend + 'static>(
+    locale: String,
+    translations: String,
+) -> Result<Translator> {
+    Ok(Translator::from_backend(B::new(locale, translations)?))
+}
+/// Creates a translator with the given backend for the given locale from the given resources of translations.
+fn with_resources_with_backend<B: TranslatorBackend + 'static>(
+    locale: String,
+    resources: Vec<String>,
+) -> Result<Translator> {
+    Ok(Translator::from_backend(B::with_resources(
+        locale, resources,
+    )?))
+}
+
+/// Sets the backend used for all translators created with [`Translator::new`] in this process, instead of the default translator. This is
+/// done automatically with the backend given as `translator_backend` in `define_app!`, and only the first call will have any effect.
+pub fn set_translator_backend<B: TranslatorBackend + 'static>() {
+    let _ = BACKEND_FN.set(new_with_backend::<B>);
+    let _ = RESOURCES_FN.set(with_resources_with_backend::<B>);
+    let _ = FILTER_FN.set(B::filter_translations);
+    let _ = JOIN_FN.set(B::join_resources);
+}
+
+/// Gives every translator in this process the app's locales, so that they localize URLs with its URL strategy. This is done
+/// automatically by `define_app!`, and only the first call will have any effect.
+pub fn set_translator_locales(locales: Locales) {
+    let _ = LOCALES.set(locales);
+}
+
+/// Gets the app's locales, if they've been given to translators.
+pub(crate) fn get_translator_locales() -> Option<&'static Locales> {
+    LOCALES.get()
+}
+
+/// Manages translations for a single locale with the app's translator backend. This should generally be placed into an `Rc<T>` and
+/// referred to by every template in an app.
+pub struct Translator {
+    backend: Box<dyn TranslatorBackend>,
+}
+impl Translator {
+    /// Creates a new translator for the given locale from the given translations, using the app's translator backend if it's set one, or
+    /// the default translator otherwise.
+    pub fn new(locale: String, translations: String) -> Result<Self> {
+        match BACKEND_FN.get() {
+            Some(new_with_backend) => new_with_backend(locale, translations),
+            None => new_with_backend::<super::DefaultTranslator>(locale, translations),
+        }
+    }
+    /// Creates a new translator for the given locale from several resources of translations (like the files in a locale's directory),
+    /// using the app's translator backend if it's set one, or the default translator otherwise.
+    pub fn with_resources(locale: String, resources: Vec<String>) -> Result<Self> {
+        match RESOURCES_FN.get() {
+            Some(with_resources_with_backend) => with_resources_with_backend(locale, resources),
+            None => with_resources_with_backend::<super::DefaultTranslator>(locale, resources),
+        }
+    }
+    /// Joins several resources of translations into one that a translator can be created from with [`Translator::new`], using the app's
+    /// translator backend if it's set one, or the default translator otherwise. A single resource is given back as it is.
+    pub fn join_resources(mut resources: Vec<String>) -> String {
+        if resources.len() == 1 {
+            return resources.remove(0);
+        }
+        match JOIN_FN.get() {
+            Some(join_fn) => join_fn(resources),
+            None => super::DefaultTranslator::join_resources(resources),
+        }
+    }
+    /// Filters the given translations down to those with IDs starting with any of the given prefixes, using the app's translator backend
+    /// if it's set one, or the default translator otherwise.
+    pub fn filter_translations(translations: &str, prefixes: &[String]) -> String {
+        match FILTER_FN.get() {
+            Some(filter_fn) => filter_fn(translations, prefixes),
+            None => super::DefaultTranslator::filter_translations(translations, prefixes),
+        }
+    }
+    /// Creates a new translator that uses the given backend.
+    pub fn from_backend(backend: impl TranslatorBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
+    /// Gets the path to the given URL in whatever locale the instance is configured for. This uses the app's URL strategy if the app's
+    /// locales have been given to translators, and the backend otherwise.
+    pub fn url<S: Into<String> + std::fmt::Display>(&self, url: S) -> String {
+        match LOCALES.get() {
+            Some(locales) => self.url_builder(url).build_with(locales),
+            None => self.backend.url(&url.to_string()),
+        }
+    }
+    /// Creates a builder for the URL of the given path in whatever locale the instance is configured for, which can add query
+    /// parameters, a fragment, the app's base path, or its origin to it. Note that this always localizes the URL with the app's URL
+    /// strategy, even if the backend localizes URLs differently.
+    pub fn url_builder(&self, url: impl Into<String>) -> UrlBuilder {
+        UrlBuilder::new(url).locale(self.get_locale())
+    }
+    /// Gets the locale for which this instancce is configured.
+    pub fn get_locale(&self) -> String {
+        self.backend.get_locale()
+    }
+    /// Gets the direction text is written in for this translator's locale (e.g. right-to-left for `ar` or `he`), which is set as the
+    /// `dir` attribute of the document automatically.
+    pub fn get_direction(&self) -> TextDirection {
+        TextDirection::from_locale(&self.get_locale())
+    }
+    /// Translates the given ID. This additionally takes any arguments that should be interpolated, which can be `None`, arguments made
+    /// with `args!`, or anything else that implements [`IntoTranslationArgs`].
+    /// # Panics
+    /// Unless the backend handles missing translations differently, this will `panic!` if any errors occur while trying to translate the
+    /// given ID. Therefore, this method should only be used for hardcoded IDs that can be confirmed as valid. If you need to parse
+    /// arbitrary IDs, use `.translate_checked()` instead. While pages are being prerendered in strict i18n mode, this never panics, and
+    /// gives the ID itself for anything that can't be translated, since those pages will fail the build anyway.
+    pub fn translate<'a, I: Into<String> + std::fmt::Display>(
+        &self,
+        id: I,
+        args: impl IntoTranslationArgs<'a>,
+    ) -> String {
+        let id = id.to_string();
+        let args = args.into_translation_args();
+        // In strict i18n mode, translations that the backend would handle gracefully (e.g. by falling back to another locale) still need to
+        // be reported, and ones it would panic on mustn't stop the build from reporting every other failure too
+        if is_recording_failed_translations() {
+            return match self.backend.translate_checked(&id, args) {
+                Ok(translation) => translation,
+                Err(_) => {
+                    record_failed_translation(&id);
+                    // The page will fail the build, so this only has to make it obvious what's missing
+                    id
+                }
+            };
+        }
+        self.backend.translate(&id, args)
+    }
+    /// Translates the given ID, returning graceful errors. This additionally takes any arguments that should be interpolated (like
+    /// `.translate()`).
+    pub fn translate_checked<'a, I: Into<String> + std::fmt::Display>(
+        &self,
+        id: I,
+        args: impl IntoTranslationArgs<'a>,
+    ) -> Result<String> {
+        let id = id.to_string();
+        let res = self
+            .backend
+            .translate_checked(&id, args.into_translation_args());
+        if res.is_err() {
+            record_failed_translation(&id);
+        }
+
+        res
+    }
+    /// Formats the given number in this translator's locale, with up to three decimal places (e.g. `1,234.5` in `en-US` or `1.234,5` in
+    /// `de-DE`).
+    pub fn format_number(&self, number: f64) -> String {
+        formatting::format_number(&self.get_locale(), number)
+    }
+    /// Formats the given amount of the currency with the given ISO 4217 code (like `USD` or `EUR`) in this translator's locale (e.g.
+    /// `€1,234.50` in `en-US` or `1.234,50 €` in `de-DE`).
+    pub fn format_currency(&self, amount: f64, currency: &str) -> String {
+        formatting::format_currency(&self.get_locale(), amount, currency)
+    }
+    /// Formats the given date (like a `chrono::NaiveDate`) in this translator's locale with the given style (e.g. `3/14/2021` or
+    /// `March 14, 2021` in `en-US`).
+    pub fn format_date(&self, date: &impl Datelike, style: DateStyle) -> String {
+        formatting::format_date(&self.get_locale(), date, style)
+    }
+    /// Gets the backend behind this translator.
+    pub fn get_backend(&self) -> &dyn TranslatorBackend {
+        self.backend.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::record_failed_translations;
+
+    /// A backend that can only translate `hello`, and so panics on anything else (like the default translator without a
+    /// `MissingTranslationHandler`).
+    struct HelloBackend;
+    impl TranslatorBackend for HelloBackend {
+        fn new(_locale: String, _translations: String) -> Result<Self> {
+            Ok(Self)
+        }
+        fn get_locale(&self) -> String {
+            "en-US".to_string()
+        }
+        fn translate_checked(&self, id: &str, args: Option<TranslationArgs>) -> Result<String> {
+            match (id, args) {
+                ("hello", None) => Ok("Hello!".to_string()),
+                ("hello", Some(_)) => Ok("Hello with arguments!".to_string()),
+                _ => bail!(ErrorKind::TranslationIdNotFound(
+                    id.to_string(),
+                    self.get_locale()
+                )),
+            }
+        }
+    }
+
+    #[test]
+    fn strict_mode_records_missing_translations_without_panicking() {
+        let translator = Translator::from_backend(HelloBackend);
+        let (translations, failed) = record_failed_translations(|| {
+            vec![
+                translator.translate("hello", None),
+                translator.translate("missing", None),
+                translator.translate("hello", Some(TranslationArgs::new())),
+                translator.translate("also-missing", None),
+                translator.translate("missing", None),
+            ]
+        });
+        assert_eq!(
+            translations,
+            vec![
+                "Hello!",
+                "missing",
+                "Hello with arguments!",
+                "also-missing",
+                "missing"
+            ]
+        );
+        assert_eq!(failed, vec!["missing", "also-missing"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_on_missing_translations_outside_strict_mode() {
+        Translator::from_backend(HelloBackend).translate("missing", None);
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// Languages that are written right-to-left in their usual script.
+const RTL_LANGS: [&str; 12] = [
+    "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ps", "sd", "ug", "ur", "yi",
+];
+/// Scripts that are written right-to-left (as ISO 15924 codes in lowercase), which override the usual direction of a locale's language
+/// (e.g. `pa-Arab` is right-to-left, but `pa` isn't).
+const RTL_SCRIPTS: [&str; 8] = [
+    "adlm", "arab", "hebr", "nkoo", "rohg", "syrc", "thaa", "yezi",
+];
+
+/// The direction text is written in, which is set as the `dir` attribute of a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextDirection {
+    /// Left-to-right (like English).
+    Ltr,
+    /// Right-to-left (like Arabic or Hebrew).
+    Rtl,
+}
+impl TextDirection {
+    /// Gets the direction text is written in for the given locale (like `ar-EG` or `pa-Arab`). A script subtag takes precedence over the
+    /// language, and anything that isn't known to be right-to-left is treated as left-to-right.
+    pub fn from_locale(locale: &str) -> Self {
+        let mut subtags = locale.split(|c| c == '-' || c == '_');
+        let lang = subtags.next().unwrap_or_default().to_lowercase();
+        // Scripts are the only subtags with four letters
+        let script = subtags
+            .find(|subtag| subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+            .map(|script| script.to_lowercase());
+        let is_rtl = match script {
+            Some(script) => RTL_SCRIPTS.contains(&script.as_str()),
+            None => RTL_LANGS.contains(&lang.as_str()),
+        };
+
+        match is_rtl {
+            true => Self::Rtl,
+            false => Self::Ltr,
+        }
+    }
+    /// Gets the value of the `dir` attribute for this direction (`ltr` or `rtl`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ltr => "ltr",
+            Self::Rtl => "rtl",
+        }
+    }
+}
+impl fmt::Display for TextDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -14,6 +14,17 @@ error_chain! {
             description("given translations string couldn't be parsed")
             display("given translations string for locale '{}' couldn't be parsed: '{}'", locale, err)
         }
+        /// For when one of several resources of translations given for a locale couldn't be parsed, or couldn't be added with the others.
+        ResourceSerFailed(locale: String, resource: usize, err: String) {
+            description("given translations resource couldn't be parsed")
+            display("translations resource at index {} for locale '{}' couldn't be parsed or added: '{}'", resource, locale, err)
+        }
+        /// For when a custom function couldn't be registered with the Fluent bundle for a locale (e.g. because two were given the same
+        /// name).
+        FunctionRegistrationFailed(name: String, locale: String, err: String) {
+            description("custom function couldn't be registered")
+            display("custom function '{}' couldn't be registered for locale '{}': '{}'", name, locale, err)
+        }
         /// For when the given locale was invalid. This takes an error because different i18n systems may have different requirements.
         InvalidLocale(locale: String, err: String) {
             description("given locale was invalid")
@@ -1,51 +1,176 @@
 use crate::translator::errors::*;
-use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource, FluentValue};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::Arc;
 use unic_langid::{LanguageIdentifier, LanguageIdentifierError};
 
 /// The file extension used by the Fluent translator, which expects FTL files.
 pub const FLUENT_TRANSLATOR_FILE_EXT: &str = "ftl";
 
+/// A custom function that can be called from FTL (like `UPPERCASE($name)`), which is given the positional and named arguments it was
+/// called with. This is an `Arc` so that the same function can be registered with a translator's fallbacks too.
+pub type FluentFunction =
+    Arc<dyn for<'a> Fn(&[FluentValue<'a>], &FluentArgs) -> FluentValue<'a> + Send + Sync>;
+
+/// A function that's given the IDs a [`FluentTranslator`] can't translate, along with its locale (e.g. to send them to wherever the app's
+/// logs go on the server).
+pub type MissingTranslationReporter = Rc<dyn Fn(&str, &str)>;
+
+/// What a [`FluentTranslator`] should do when it's asked to `.translate()` an ID it can't translate (including from any of its
+/// fallbacks). By default, it'll panic, which will make sure a missing translation is noticed in development, but which will also take
+/// down the whole page in production.
+#[derive(Clone)]
+pub enum MissingTranslationHandler {
+    /// The translator will `panic!`.
+    Panic,
+    /// The translator will return the ID itself, so that the page still renders and the missing translation is obvious on it.
+    ReturnId,
+    /// The translator will use the given translator for the app's default locale instead (with the same arguments), returning the ID if
+    /// that can't translate it either.
+    DefaultLocale(Rc<FluentTranslator>),
+    /// The translator will log the ID and its locale to the browser console and return an empty string. There's no console on the
+    /// server, so nothing will be logged there (use `ReportAndEmpty` to handle these yourself on both).
+    LogAndEmpty,
+    /// The translator will give the ID and its locale to the given function and return an empty string.
+    ReportAndEmpty(MissingTranslationReporter),
+}
+impl Default for MissingTranslationHandler {
+    fn default() -> Self {
+        Self::Panic
+    }
+}
+
 /// Manages translations on the client-side for a single locale using Mozilla's [Fluent](https://projectfluent.org/) syntax. This
 /// should generally be placed into an `Rc<T>` and referred to by every template in an app. You do NOT want to be cloning potentially
 /// thousands of translations!
 ///
 /// Fluent supports compound messages, with many variants, which can specified here using the form `[id].[variant]` in a translation ID,
-/// as a `.` is not valid in an ID anyway, and so can be used as a delimiter. More than one dot will result in an error.
+/// as a `.` is not valid in an ID anyway, and so can be used as a delimiter. Structured IDs with more dots are supported too, by mapping
+/// them onto Fluent's hyphenated IDs: `nav.menu.settings` is the variant `settings` of the message `nav-menu` if there is one, and
+/// otherwise the message `nav-menu-settings` (see [`get_fluent_id_candidates`]).
+///
+/// A locale's translations can be split across several FTL resources (like `common.ftl` and `blog.ftl`), which can be given to
+/// `.with_resources()`. If a message is missing from this translator's locale, it can fall back to the translations of other locales (like `fr` and then
+/// the app's default locale for `fr-FR`), which can be set up with `.with_fallbacks()`. What `.translate()` does when a message can't be
+/// translated at all can be set with `.with_missing_handler()`, and custom functions can be registered with
+/// `FluentTranslator::with_functions()`.
+///
+/// Messages translated without any arguments are only formatted once, after which they're cached by their IDs (including any variant).
+/// A translator's bundles can't be changed, so a new translator (with an empty cache) should be created whenever translations are. This
+/// isn't done if any custom functions have been registered, since they might not give the same result every time.
 pub struct FluentTranslator {
     /// Stores the internal Fluent data for translating. This bundle directly owns its attached resources (translations).
     bundle: Rc<FluentBundle<FluentResource>>,
+    /// The bundles for the locales to fall back to when a message is missing from the main bundle, in the order they should be tried.
+    fallbacks: Vec<Rc<FluentBundle<FluentResource>>>,
     /// The locale for which translations are being managed by this instance.
     locale: String,
+    /// What to do when `.translate()` is given an ID that can't be translated.
+    missing_handler: MissingTranslationHandler,
+    /// Messages that have been translated without arguments, by their IDs (including any variant), if they can be cached.
+    cache: Option<RefCell<HashMap<String, String>>>,
 }
 impl FluentTranslator {
     /// Creates a new translator for a given locale, passing in translations in FTL syntax form.
     pub fn new(locale: String, ftl_string: String) -> Result<Self> {
-        let resource = FluentResource::try_new(ftl_string)
-            // If this errors, we get it still and a vector of errors (wtf.)
-            .map_err(|(_, errs)| {
-                ErrorKind::TranslationsStrSerFailed(
-                    locale.clone(),
-                    errs.iter().map(|e| e.to_string()).collect(),
-                )
-            })?;
-        let lang_id: LanguageIdentifier =
-            locale.parse().map_err(|err: LanguageIdentifierError| {
-                ErrorKind::InvalidLocale(locale.clone(), err.to_string())
-            })?;
-        let mut bundle = FluentBundle::new(vec![lang_id]);
-        bundle.add_resource(resource).map_err(|errs| {
-            ErrorKind::TranslationsStrSerFailed(
-                locale.clone(),
-                errs.iter().map(|e| e.to_string()).collect(),
-            )
-        })?;
+        Self::with_fallbacks(locale, ftl_string, Vec::new())
+    }
+    /// Creates a new translator for a given locale, passing in several resources of translations in FTL syntax form, which will all be
+    /// added to the same bundle. Any errors will say which resource caused them (by its index in the given list), and defining the same
+    /// message in more than one resource is an error.
+    pub fn with_resources(locale: String, resources: Vec<String>) -> Result<Self> {
+        Self::with_functions(locale, resources, Vec::new(), Vec::new())
+    }
+    /// Creates a new translator for a given locale, passing in translations in FTL syntax form, along with an ordered list of other
+    /// locales and the resources of their translations to fall back to when a message is missing (e.g. `fr` and then `en-US` for
+    /// `fr-FR`). Each fallback is tried in turn, and a message will only be reported as not found if none of them have it. A fallback's
+    /// resources are all added to its bundle, just like with [`FluentTranslator::with_resources`].
+    pub fn with_fallbacks(
+        locale: String,
+        ftl_string: String,
+        fallbacks: Vec<(String, Vec<String>)>,
+    ) -> Result<Self> {
+        Self::with_functions(locale, vec![ftl_string], fallbacks, Vec::new())
+    }
+    /// Creates a new translator for a given locale, passing in several resources of translations in FTL syntax form, an ordered list of
+    /// other locales and the resources of their translations to fall back to, and custom functions that can be called from FTL (like
+    /// `PLATFORM()`), by name. The functions are registered with every bundle (including the fallbacks') before any translations are added to it.
+    pub fn with_functions(
+        locale: String,
+        resources: Vec<String>,
+        fallbacks: Vec<(String, Vec<String>)>,
+        functions: Vec<(String, FluentFunction)>,
+    ) -> Result<Self> {
+        let bundle = Self::new_bundle(&locale, resources, &functions)?;
+        // Custom functions might not give the same result every time they're called
+        let cache = match functions.is_empty() {
+            true => Some(RefCell::new(HashMap::new())),
+            false => None,
+        };
+        let fallbacks = fallbacks
+            .into_iter()
+            .map(|(fallback_locale, fallback_resources)| {
+                Self::new_bundle(&fallback_locale, fallback_resources, &functions).map(Rc::new)
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(Self {
             bundle: Rc::new(bundle),
+            fallbacks,
             locale,
+            missing_handler: MissingTranslationHandler::default(),
+            cache,
         })
     }
+    /// Sets what `.translate()` should do when it's given an ID that can't be translated, instead of panicking (e.g.
+    /// `FluentTranslator::new(locale, ftl_string)?.with_missing_handler(MissingTranslationHandler::ReturnId)`).
+    pub fn with_missing_handler(mut self, val: MissingTranslationHandler) -> Self {
+        self.missing_handler = val;
+        self
+    }
+    /// Creates a Fluent bundle for the given locale with the given custom functions from the given resources of translations in FTL
+    /// syntax form. If there's more than one resource, errors will say which one caused them.
+    fn new_bundle(
+        locale: &str,
+        resources: Vec<String>,
+        functions: &[(String, FluentFunction)],
+    ) -> Result<FluentBundle<FluentResource>> {
+        let lang_id: LanguageIdentifier =
+            locale.parse().map_err(|err: LanguageIdentifierError| {
+                ErrorKind::InvalidLocale(locale.to_string(), err.to_string())
+            })?;
+        let mut bundle = FluentBundle::new(vec![lang_id]);
+        for (name, function) in functions {
+            let function = Arc::clone(function);
+            bundle
+                .add_function(name, move |positional, named| function(positional, named))
+                .map_err(|err| {
+                    ErrorKind::FunctionRegistrationFailed(
+                        name.to_string(),
+                        locale.to_string(),
+                        err.to_string(),
+                    )
+                })?;
+        }
+        let attribute = resources.len() > 1;
+        for (idx, ftl_string) in resources.into_iter().enumerate() {
+            let ser_failed = |err: String| match attribute {
+                true => ErrorKind::ResourceSerFailed(locale.to_string(), idx, err),
+                false => ErrorKind::TranslationsStrSerFailed(locale.to_string(), err),
+            };
+            let resource = FluentResource::try_new(ftl_string)
+                // If this errors, we get it still and a vector of errors (wtf.)
+                .map_err(|(_, errs)| ser_failed(errs.iter().map(|e| e.to_string()).collect()))?;
+            // This will fail if a message in this resource was already defined by another one
+            bundle
+                .add_resource(resource)
+                .map_err(|errs| ser_failed(errs.iter().map(|e| e.to_string()).collect()))?;
+        }
+
+        Ok(bundle)
+    }
     /// Gets the path to the given URL in whatever locale the instance is configured for.
     pub fn url<S: Into<String> + std::fmt::Display>(&self, url: S) -> String {
         format!("/{}{}", self.locale, url)
@@ -57,17 +182,34 @@ impl FluentTranslator {
     /// Translates the given ID. This additionally takes any arguments that should be interpolated. If your i18n system also has variants,
     /// they should be specified somehow in the ID.
     /// # Panics
-    /// This will `panic!` if any errors occur while trying to prepare the given ID. Therefore, this method should only be used for
-    /// hardcoded IDs that can be confirmed as valid. If you need to parse arbitrary IDs, use `.translate_checked()` instead.
+    /// Unless a different `MissingTranslationHandler` has been set, this will `panic!` if any errors occur while trying to prepare the
+    /// given ID. Therefore, this method should only be used for hardcoded IDs that can be confirmed as valid. If you need to parse
+    /// arbitrary IDs, use `.translate_checked()` instead.
     pub fn translate<I: Into<String> + std::fmt::Display>(
         &self,
         id: I,
         args: Option<FluentArgs>,
     ) -> String {
-        let translation_res = self.translate_checked(&id.to_string(), args);
-        match translation_res {
-            Ok(translation) => translation,
-            Err(_) => panic!("translation id '{}' not found for locale '{}' (if you're not hardcoding the id, use `.translate_checked()` instead)", id, self.locale)
+        let id = id.to_string();
+        if let Ok(translation) = self.translate_with_args(&id, args.as_ref()) {
+            return translation;
+        }
+        match &self.missing_handler {
+            MissingTranslationHandler::Panic => panic!("translation id '{}' not found for locale '{}' (if you're not hardcoding the id, use `.translate_checked()` instead)", id, self.locale),
+            MissingTranslationHandler::ReturnId => id,
+            MissingTranslationHandler::DefaultLocale(dflt_translator) => dflt_translator
+                .translate_with_args(&id, args.as_ref())
+                .unwrap_or(id),
+            MissingTranslationHandler::LogAndEmpty => {
+                // The console only exists in the browser, and translators are also used on the server
+                #[cfg(target_arch = "wasm32")]
+                crate::web_log!("translation id '{}' not found for locale '{}'", id, self.locale);
+                String::new()
+            }
+            MissingTranslationHandler::ReportAndEmpty(report) => {
+                report(&id, &self.locale);
+                String::new()
+            }
         }
     }
     /// Translates the given ID, returning graceful errors. This additionally takes any arguments that should be interpolated. If your
@@ -77,71 +219,405 @@ impl FluentTranslator {
         id: I,
         args: Option<FluentArgs>,
     ) -> Result<String> {
-        let id_str = id.to_string();
-        // Deal with the possibility of a specified variant
-        let id_vec: Vec<&str> = id_str.split('.').collect();
-        let id_str = id_vec[0].to_string();
-        let variant = id_vec.get(1);
-
-        // This is the message in the Fluent system, an unformatted translation (still needs variables etc.)
-        // This may also be compound, which means it has multiple variants
-        let msg = self.bundle.get_message(&id_str);
-        let msg = match msg {
-            Some(msg) => msg,
-            None => bail!(ErrorKind::TranslationIdNotFound(
-                id_str,
-                self.locale.clone()
-            )),
+        self.translate_with_args(&id.to_string(), args.as_ref())
+    }
+    /// Translates the given ID with a reference to any arguments, so that they can be used again if the translation is missing. Messages
+    /// without arguments are cached.
+    fn translate_with_args(&self, id_str: &str, args: Option<&FluentArgs>) -> Result<String> {
+        let cache = match args {
+            Some(_) => None,
+            None => self.cache.as_ref(),
         };
-        // This module accumulates errors in a provided buffer, we'll handle them later
-        let mut errors = Vec::new();
-        let value = msg.value();
-        let mut translation = None; // If it's compound, the requested variant may not exist
-        if let Some(value) = value {
-            // Non-compound, just one variant
-            translation = Some(
-                self.bundle
-                    .format_pattern(value, args.as_ref(), &mut errors),
-            );
-        } else {
-            // Compound, many variants, one should be specified
-            if let Some(variant) = variant {
-                for attr in msg.attributes() {
-                    // Once we find the requested variant, we don't need to continue (they should all be unique)
-                    if &attr.id() == variant {
-                        translation = Some(self.bundle.format_pattern(
-                            attr.value(),
-                            args.as_ref(),
-                            &mut errors,
-                        ));
-                        break;
-                    }
-                }
-            } else {
+        if let Some(translation) = cache.and_then(|cache| cache.borrow().get(id_str).cloned()) {
+            return Ok(translation);
+        }
+        let translation = self.format_message(id_str, args)?;
+        if let Some(cache) = cache {
+            cache
+                .borrow_mut()
+                .insert(id_str.to_string(), translation.clone());
+        }
+
+        Ok(translation)
+    }
+    /// Formats the message with the given ID (and possibly variant) with any given arguments.
+    fn format_message(&self, id_str: &str, args: Option<&FluentArgs>) -> Result<String> {
+        // Structured IDs could refer to a few different messages (or variants of them), which we try in order
+        let candidates = get_fluent_id_candidates(id_str);
+        // This is the pattern in the Fluent system, an unformatted translation (still needs variables etc.)
+        // Messages may also be compound, which means they have multiple variants, one of which should be specified
+        // If it's not in our locale, we walk the fallback chain and use the first bundle that has it
+        let bundle_and_pattern = std::iter::once(&self.bundle)
+            .chain(self.fallbacks.iter())
+            .find_map(|bundle| {
+                candidates.iter().find_map(|(msg_id, variant)| {
+                    let msg = bundle.get_message(msg_id)?;
+                    let pattern = match variant {
+                        Some(variant) => {
+                            msg.attributes().find(|attr| attr.id() == variant)?.value()
+                        }
+                        None => msg.value()?,
+                    };
+                    Some((bundle, pattern))
+                })
+            });
+        let (bundle, pattern) = match bundle_and_pattern {
+            Some(bundle_and_pattern) => bundle_and_pattern,
+            // A compound message without a variant exists, but it doesn't have anything to translate to
+            None if candidates.len() == 1 && self.bundle.has_message(id_str) => {
                 bail!(ErrorKind::TranslationFailed(
-                    id_str,
+                    id_str.to_string(),
                     self.locale.clone(),
                     "no variant provided for compound message".to_string()
                 ))
             }
-        }
+            None if candidates
+                .iter()
+                .any(|(msg_id, _)| self.bundle.has_message(msg_id)) =>
+            {
+                bail!(ErrorKind::NoTranslationDerived(
+                    id_str.to_string(),
+                    self.locale.clone()
+                ))
+            }
+            None => bail!(ErrorKind::TranslationIdNotFound(
+                id_str.to_string(),
+                self.locale.clone()
+            )),
+        };
+        // This module accumulates errors in a provided buffer, we'll handle them later
+        let mut errors = Vec::new();
+        let translation = bundle.format_pattern(pattern, args, &mut errors);
         // Check for any errors
         // TODO apparently these aren't all fatal, but how do we know?
         if !errors.is_empty() {
             bail!(ErrorKind::TranslationFailed(
-                id_str,
+                id_str.to_string(),
                 self.locale.clone(),
                 errors.iter().map(|e| e.to_string()).collect()
             ))
         }
-        // Make sure we've actually got a translation
-        match translation {
-            Some(translation) => Ok(translation.to_string()),
-            None => bail!(ErrorKind::NoTranslationDerived(id_str, self.locale.clone())),
-        }
+
+        Ok(translation.to_string())
     }
     /// Gets the Fluent bundle for more advanced translation requirements.
     pub fn get_bundle(&self) -> Rc<FluentBundle<FluentResource>> {
         Rc::clone(&self.bundle)
     }
+    /// Gets the Fluent bundles this translator falls back to, in the order they're tried.
+    pub fn get_fallback_bundles(&self) -> Vec<Rc<FluentBundle<FluentResource>>> {
+        self.fallbacks.iter().map(Rc::clone).collect()
+    }
+    /// Filters the given FTL down to the messages with IDs in any of the given prefixes, which match an ID that's either exactly the prefix
+    /// or starts with it followed by a hyphen (so `about` and `about-` both match `about-title`, but neither matches `aboutus`). Messages
+    /// referenced by those messages (even indirectly) are kept too, so that they can still be formatted. Terms (like `-brand`) are always
+    /// kept, since any message might reference them, but comments are stripped out.
+    pub fn filter_translations(ftl_string: &str, prefixes: &[String]) -> String {
+        // Each entry is its ID and its lines (its attributes and multiline values are indented under it)
+        let mut entries: Vec<(&str, Vec<&str>)> = Vec::new();
+        // Whether or not we're in the middle of an entry (rather than a comment)
+        let mut in_entry = false;
+        for line in ftl_string.lines() {
+            if line.starts_with('#') {
+                in_entry = false;
+            } else if !line.is_empty()
+                && !line.starts_with(char::is_whitespace)
+                // The end of a multiline placeable doesn't have to be indented
+                && !line.starts_with('}')
+            {
+                let id = line.split('=').next().unwrap_or_default().trim();
+                entries.push((id, vec![line]));
+                in_entry = true;
+            } else if in_entry {
+                // This can't be the first line, so there'll be an entry to add it to
+                if let Some((_, lines)) = entries.last_mut() {
+                    lines.push(line);
+                }
+            }
+        }
+
+        // Terms are always kept, so the messages they reference need to be too
+        let mut kept: HashSet<&str> = HashSet::new();
+        let mut to_check: Vec<&str> = entries
+            .iter()
+            .map(|(id, _)| *id)
+            .filter(|id| {
+                id.starts_with('-')
+                    || prefixes
+                        .iter()
+                        .any(|prefix| is_in_translation_prefix(id, prefix))
+            })
+            .collect();
+        while let Some(id) = to_check.pop() {
+            if !kept.insert(id) {
+                continue;
+            }
+            for (_, lines) in entries.iter().filter(|(entry_id, _)| *entry_id == id) {
+                // The ID itself shouldn't be treated as text that could reference anything
+                let body = lines.join("\n");
+                let body = body.splitn(2, '=').nth(1).unwrap_or_default();
+                for reference in get_message_references(body) {
+                    if let Some((reference_id, _)) = entries
+                        .iter()
+                        .find(|(entry_id, _)| *entry_id == reference.as_str())
+                    {
+                        to_check.push(*reference_id);
+                    }
+                }
+            }
+        }
+
+        let mut filtered = String::new();
+        for (_, lines) in entries.iter().filter(|(id, _)| kept.contains(id)) {
+            for line in lines {
+                filtered.push_str(line);
+                filtered.push('\n');
+            }
+        }
+
+        filtered
+    }
+}
+
+/// Checks if the given message ID is in the given translation prefix, which it is if it's exactly the prefix or if it starts with the
+/// prefix followed by a hyphen (a trailing hyphen on the prefix is ignored). An empty prefix includes every message.
+fn is_in_translation_prefix(id: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('-');
+    prefix.is_empty()
+        || id == prefix
+        || (id.starts_with(prefix) && id[prefix.len()..].starts_with('-'))
+}
+
+/// Gets the IDs of the messages referenced in the given Fluent pattern (like `about` in `{ about }` or `{ about.title }`). Variables,
+/// terms, functions, and their named arguments aren't messages, so they aren't included, and neither is the text of select variants
+/// (though any placeables in them are checked).
+fn get_message_references(pattern: &str) -> Vec<String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut refs = Vec::new();
+    let mut i = 0;
+    scan_pattern(&chars, &mut i, &mut refs, false);
+
+    refs
+}
+/// Scans text in a Fluent pattern for placeables, collecting any messages they reference. If this is the text of select variants, this
+/// stops at the `}` that closes the select expression (without consuming it), and variant keys (like `*[other]`) are skipped.
+fn scan_pattern(chars: &[char], i: &mut usize, refs: &mut Vec<String>, in_variants: bool) {
+    while *i < chars.len() {
+        match chars[*i] {
+            '{' => {
+                *i += 1;
+                scan_expression(chars, i, refs);
+            }
+            '}' if in_variants => return,
+            '[' if in_variants => {
+                while *i < chars.len() && chars[*i] != ']' {
+                    *i += 1;
+                }
+                *i += 1;
+            }
+            _ => *i += 1,
+        }
+    }
+}
+/// Scans the inside of a Fluent placeable (after its `{`), collecting any messages it references, and consuming everything up to and
+/// including its closing `}`.
+fn scan_expression(chars: &[char], i: &mut usize, refs: &mut Vec<String>) {
+    while *i < chars.len() {
+        match chars[*i] {
+            '}' => {
+                *i += 1;
+                return;
+            }
+            '{' => {
+                *i += 1;
+                scan_expression(chars, i, refs);
+            }
+            '"' => {
+                // String literals can contain anything, including escaped quotes
+                *i += 1;
+                while *i < chars.len() && chars[*i] != '"' {
+                    if chars[*i] == '\\' {
+                        *i += 1;
+                    }
+                    *i += 1;
+                }
+                *i += 1;
+            }
+            '-' if chars.get(*i + 1) == Some(&'>') => {
+                // Everything after this is the variants of a select expression
+                *i += 2;
+                scan_pattern(chars, i, refs, true);
+                *i += 1;
+                return;
+            }
+            // Variables and terms are never messages
+            '$' | '-' => {
+                *i += 1;
+                skip_identifier(chars, i);
+            }
+            c if c.is_ascii_alphabetic() => {
+                let start = *i;
+                skip_identifier(chars, i);
+                let ident: String = chars[start..*i].iter().collect();
+                // Attributes (like `.title` in `about.title`) belong to the message
+                if chars.get(*i) == Some(&'.') {
+                    *i += 1;
+                    skip_identifier(chars, i);
+                }
+                // Functions are followed by their arguments, and named arguments by their values
+                let next = chars[*i..].iter().find(|c| !c.is_whitespace());
+                if next != Some(&'(') && next != Some(&':') {
+                    refs.push(ident);
+                }
+            }
+            _ => *i += 1,
+        }
+    }
+}
+/// Skips over a Fluent identifier (stopping before a `->`, which could follow it without a space).
+fn skip_identifier(chars: &[char], i: &mut usize) {
+    while *i < chars.len()
+        && (chars[*i].is_ascii_alphanumeric() || chars[*i] == '_' || chars[*i] == '-')
+        && !(chars[*i] == '-' && chars.get(*i + 1) == Some(&'>'))
+    {
+        *i += 1;
+    }
+}
+
+/// Gets the messages (and the variants of them) that the given translation ID could refer to in Fluent, in the order they should be
+/// tried. An ID without any dots is just a message, and an ID like `nav.about` is the variant `about` of the message `nav`. Fluent IDs can't
+/// contain dots, so structured IDs with more of them are mapped onto hyphenated IDs: `nav.menu.settings` is the variant `settings` of the
+/// message `nav-menu`, or, if there's no such variant, the message `nav-menu-settings`.
+pub fn get_fluent_id_candidates(id: &str) -> Vec<(String, Option<String>)> {
+    let segments: Vec<&str> = id.split('.').collect();
+    match segments.split_last() {
+        Some((variant, msg_segments)) if !msg_segments.is_empty() => vec![
+            (msg_segments.join("-"), Some(variant.to_string())),
+            (segments.join("-"), None),
+        ],
+        _ => vec![(id.to_string(), None)],
+    }
+}
+// Backends are given the default translator's arguments, so this can only be a backend if it's the default
+#[cfg(feature = "translator-dflt-fluent")]
+impl crate::translator::TranslatorBackend for FluentTranslator {
+    fn new(locale: String, translations: String) -> Result<Self> {
+        FluentTranslator::new(locale, translations)
+    }
+    fn with_resources(locale: String, resources: Vec<String>) -> Result<Self> {
+        FluentTranslator::with_resources(locale, resources)
+    }
+    fn get_locale(&self) -> String {
+        self.get_locale()
+    }
+    fn translate_checked(&self, id: &str, args: Option<FluentArgs>) -> Result<String> {
+        self.translate_checked(id, args)
+    }
+    fn translate(&self, id: &str, args: Option<FluentArgs>) -> String {
+        self.translate(id, args)
+    }
+    fn url(&self, url: &str) -> String {
+        self.url(url)
+    }
+    fn filter_translations(translations: &str, prefixes: &[String]) -> String {
+        FluentTranslator::filter_translations(translations, prefixes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(ftl: &str, prefixes: &[&str]) -> String {
+        let prefixes: Vec<String> = prefixes.iter().map(|prefix| prefix.to_string()).collect();
+        FluentTranslator::filter_translations(ftl, &prefixes)
+    }
+
+    #[test]
+    fn prefixes_match_on_segment_boundaries() {
+        let ftl = "about = About\nabout-title = About us\naboutus = Not about\nblog-title = Blog\n";
+        assert_eq!(
+            filter(ftl, &["about"]),
+            "about = About\nabout-title = About us\n"
+        );
+        assert_eq!(
+            filter(ftl, &["about-"]),
+            "about = About\nabout-title = About us\n"
+        );
+        assert_eq!(
+            filter(ftl, &["blog", "about-title"]),
+            "about-title = About us\nblog-title = Blog\n"
+        );
+    }
+
+    #[test]
+    fn empty_prefix_keeps_everything() {
+        let ftl = "about = About\nblog-title = Blog\n";
+        assert_eq!(filter(ftl, &[""]), ftl);
+    }
+
+    #[test]
+    fn keeps_terms_and_strips_comments() {
+        let ftl =
+            "# A comment\n-brand = Perseus\nabout-title = About { -brand }\nblog-title = Blog\n";
+        assert_eq!(
+            filter(ftl, &["about"]),
+            "-brand = Perseus\nabout-title = About { -brand }\n"
+        );
+    }
+
+    #[test]
+    fn keeps_referenced_messages_transitively() {
+        let ftl = "about-title = About { site-name }\nsite-name = { site-owner }'s site\nsite-owner = { site-owner-name.full }\nsite-owner-name = Jane\n    .full = Jane Doe\nunused = Unused\n";
+        assert_eq!(
+            filter(ftl, &["about"]),
+            "about-title = About { site-name }\nsite-name = { site-owner }'s site\nsite-owner = { site-owner-name.full }\nsite-owner-name = Jane\n    .full = Jane Doe\n"
+        );
+    }
+
+    #[test]
+    fn keeps_messages_referenced_by_terms() {
+        let ftl = "-brand = { brand-name }\nbrand-name = Perseus\nunused = Unused\n";
+        assert_eq!(
+            filter(ftl, &["about"]),
+            "-brand = { brand-name }\nbrand-name = Perseus\n"
+        );
+    }
+
+    #[test]
+    fn ignores_variables_functions_and_variant_text() {
+        let ftl = "about-count = { NUMBER($count, minimumFractionDigits: 2) ->\n    [one] One item\n   *[other] { $count } items in { about-place }\n}\nabout-place = Here\nitems = Items\none = One\ncount = Count\nNUMBER = Number\nminimumFractionDigits = Digits\n";
+        assert_eq!(
+            filter(ftl, &["about"]),
+            "about-count = { NUMBER($count, minimumFractionDigits: 2) ->\n    [one] One item\n   *[other] { $count } items in { about-place }\n}\nabout-place = Here\n"
+        );
+    }
+
+    #[test]
+    fn finds_message_references() {
+        assert_eq!(
+            get_message_references(
+                " { a } and { b.attr } but not { $c }, { -d }, { e(f: \"{ g }\") }"
+            ),
+            vec!["a".to_string(), "b".to_string()]
+        );
+    }
+
+    #[test]
+    fn report_and_empty_gives_missing_ids_to_the_reporter() {
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let handler_reported = Rc::clone(&reported);
+        let translator = FluentTranslator::new("en-US".to_string(), "hello = Hello\n".to_string())
+            .unwrap()
+            .with_missing_handler(MissingTranslationHandler::ReportAndEmpty(Rc::new(
+                move |id: &str, locale: &str| {
+                    handler_reported
+                        .borrow_mut()
+                        .push(format!("{} ({})", id, locale))
+                },
+            )));
+        assert_eq!(translator.translate("hello", None), "Hello");
+        assert_eq!(translator.translate("missing", None), "");
+        assert_eq!(*reported.borrow(), vec!["missing (en-US)".to_string()]);
+    }
 }
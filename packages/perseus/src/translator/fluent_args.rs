@@ -0,0 +1,64 @@
+use fluent_bundle::{FluentArgs, FluentValue};
+
+/// A value that can be given to a Fluent translation as an argument. This is implemented for numbers, strings, `bool` (which becomes the
+/// string `true` or `false`, so it can be matched in selectors), and `Option`s of those (where `None` is Fluent's empty value).
+pub trait IntoFluentValue {
+    /// Converts this into a Fluent value.
+    fn into_fluent_value(self) -> FluentValue<'static>;
+}
+
+/// Implements `IntoFluentValue` for types Fluent can already convert into its values.
+macro_rules! impl_into_fluent_value {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoFluentValue for $ty {
+                fn into_fluent_value(self) -> FluentValue<'static> {
+                    FluentValue::from(self)
+                }
+            }
+        )*
+    };
+}
+impl_into_fluent_value!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, String);
+
+impl IntoFluentValue for &str {
+    fn into_fluent_value(self) -> FluentValue<'static> {
+        FluentValue::from(self.to_string())
+    }
+}
+impl IntoFluentValue for &String {
+    fn into_fluent_value(self) -> FluentValue<'static> {
+        FluentValue::from(self.clone())
+    }
+}
+impl IntoFluentValue for bool {
+    fn into_fluent_value(self) -> FluentValue<'static> {
+        FluentValue::from(self.to_string())
+    }
+}
+impl<T: IntoFluentValue> IntoFluentValue for Option<T> {
+    fn into_fluent_value(self) -> FluentValue<'static> {
+        match self {
+            Some(val) => val.into_fluent_value(),
+            None => FluentValue::None,
+        }
+    }
+}
+impl IntoFluentValue for FluentValue<'static> {
+    fn into_fluent_value(self) -> FluentValue<'static> {
+        self
+    }
+}
+
+/// A type that can be turned into the full set of arguments for a Fluent translation, which can be given to `t!` in place of listing
+/// each argument. This can be derived for structs with named fields with `#[derive(IntoFluentArgs)]`, which will give each field as the
+/// argument of the same name (every field's type must implement [`IntoFluentValue`]).
+pub trait IntoFluentArgs {
+    /// Converts this into Fluent arguments.
+    fn into_fluent_args(self) -> FluentArgs<'static>;
+}
+impl IntoFluentArgs for FluentArgs<'static> {
+    fn into_fluent_args(self) -> FluentArgs<'static> {
+        self
+    }
+}
@@ -0,0 +1,323 @@
+// This formats numbers, currencies, and dates for a locale with a small table of conventions, rather than the browser's `Intl`, so that
+// pages are formatted identically on the server and the client (otherwise they wouldn't hydrate cleanly)
+
+use chrono::Datelike;
+
+/// A non-breaking space, which separates groups of digits in some locales.
+const NBSP: &str = "\u{a0}";
+/// A narrow non-breaking space, which separates groups of digits in French.
+const NNBSP: &str = "\u{202f}";
+
+/// How a date should be formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateStyle {
+    /// Only numbers, in the locale's order (e.g. `3/14/2021` in `en-US` or `14.03.2021` in `de-DE`).
+    Short,
+    /// With the month's name (e.g. `March 14, 2021` in `en-US` or `14. März 2021` in `de-DE`). Locales without month names known to
+    /// Perseus will fall back to the short style.
+    Long,
+}
+
+/// The order of the parts of a numeric date.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    Dmy,
+    Mdy,
+    Ymd,
+}
+
+/// The conventions for formatting things in a locale.
+struct LocaleFormat {
+    decimal: &'static str,
+    group: &'static str,
+    /// The fewest digits a number must have before the decimal point for them to be grouped (e.g. `5` in Spanish, where `1000` isn't
+    /// grouped but `10.000` is).
+    min_grouping: usize,
+    /// Whether the currency symbol goes after the amount (with a non-breaking space) rather than before it.
+    currency_after: bool,
+    date_order: DateOrder,
+    date_sep: &'static str,
+}
+
+/// Gets the formatting conventions for the given locale (like `en-US`), falling back to those of its language, and then to unambiguous
+/// international conventions (`1,234.5` and ISO 8601 dates).
+fn get_locale_format(locale: &str) -> LocaleFormat {
+    let (lang, region) = match locale.split_once('-') {
+        Some((lang, region)) => (lang, region),
+        None => (locale, ""),
+    };
+    let mut format = LocaleFormat {
+        decimal: ".",
+        group: ",",
+        min_grouping: 4,
+        currency_after: false,
+        date_order: DateOrder::Ymd,
+        date_sep: "-",
+    };
+    match lang {
+        "en" => {
+            format.date_order = match region {
+                "US" | "" => DateOrder::Mdy,
+                "CA" => DateOrder::Ymd,
+                _ => DateOrder::Dmy,
+            };
+            format.date_sep = match region {
+                "CA" => "-",
+                _ => "/",
+            };
+        }
+        "de" | "es" | "it" | "pt" | "nl" | "ru" | "pl" | "sv" | "fr" => {
+            format.decimal = ",";
+            format.group = match lang {
+                "fr" => NNBSP,
+                "ru" | "pl" | "sv" => NBSP,
+                "pt" if region == "PT" => NBSP,
+                _ => ".",
+            };
+            format.min_grouping = match lang {
+                "es" | "pl" => 5,
+                _ => 4,
+            };
+            // Dutch and Brazilian Portuguese put the currency first
+            format.currency_after = !matches!((lang, region), ("nl", _) | ("pt", "BR"));
+            format.date_order = match lang {
+                "sv" => DateOrder::Ymd,
+                _ => DateOrder::Dmy,
+            };
+            format.date_sep = match lang {
+                "de" | "ru" | "pl" => ".",
+                "nl" | "sv" => "-",
+                _ => "/",
+            };
+            // Swiss German uses the conventions of Switzerland
+            if (lang, region) == ("de", "CH") {
+                format.decimal = ".";
+                format.group = "’";
+                format.currency_after = false;
+            }
+            // Mexican Spanish uses the same separators as American English
+            if (lang, region) == ("es", "MX") {
+                format.decimal = ".";
+                format.group = ",";
+                format.currency_after = false;
+            }
+        }
+        "ja" | "zh" | "ko" => {
+            format.date_sep = "/";
+        }
+        _ => (),
+    }
+
+    format
+}
+
+/// Gets the names of the months in the given language, if they're known.
+fn get_month_names(lang: &str) -> Option<[&'static str; 12]> {
+    let names = match lang {
+        "en" => [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+        "de" => [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        "fr" => [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        "es" => [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        "it" => [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+        "pt" => [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+        "nl" => [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+        _ => return None,
+    };
+
+    Some(names)
+}
+
+/// Formats the given number with exactly the given number of decimal places in the given locale.
+fn format_fixed(locale: &str, number: f64, decimals: usize) -> String {
+    let format = get_locale_format(locale);
+    let formatted = format!("{:.*}", decimals, number.abs());
+    let (int_part, frac_part) = match formatted.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+        None => (formatted.as_str(), None),
+    };
+    // Digits are grouped in threes from the decimal point
+    let mut grouped = String::new();
+    let should_group = int_part.len() >= format.min_grouping;
+    for (idx, digit) in int_part.chars().enumerate() {
+        if should_group && idx > 0 && (int_part.len() - idx) % 3 == 0 {
+            grouped.push_str(format.group);
+        }
+        grouped.push(digit);
+    }
+    if let Some(frac_part) = frac_part {
+        grouped.push_str(format.decimal);
+        grouped.push_str(frac_part);
+    }
+    // Rounding can make tiny negative numbers zero, which shouldn't have a sign
+    let is_negative = number < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0');
+
+    match is_negative {
+        true => format!("-{}", grouped),
+        false => grouped,
+    }
+}
+
+/// Formats the given number in the given locale, with up to three decimal places (without trailing zeroes).
+pub fn format_number(locale: &str, number: f64) -> String {
+    let format = get_locale_format(locale);
+    let formatted = format_fixed(locale, number, 3);
+    match formatted.contains(format.decimal) {
+        true => formatted
+            .trim_end_matches('0')
+            .trim_end_matches(format.decimal)
+            .to_string(),
+        false => formatted,
+    }
+}
+
+/// Formats the given amount of the currency with the given ISO 4217 code (like `USD` or `EUR`) in the given locale. Common currencies
+/// are shown with their symbols, and others with their codes.
+pub fn format_currency(locale: &str, amount: f64, currency: &str) -> String {
+    let format = get_locale_format(locale);
+    let currency = currency.to_uppercase();
+    let symbol = match currency.as_str() {
+        "USD" => "$",
+        "EUR" => "€",
+        "GBP" => "£",
+        "JPY" | "CNY" => "¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        "BRL" => "R$",
+        _ => currency.as_str(),
+    };
+    // These currencies don't have minor units
+    let decimals = match currency.as_str() {
+        "JPY" | "KRW" => 0,
+        _ => 2,
+    };
+    let formatted = format_fixed(locale, amount.abs(), decimals);
+    let sign = match amount < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        true => "-",
+        false => "",
+    };
+    // Codes always need a space to separate them from the number, but symbols only do when they go after it
+    let is_code = symbol.chars().all(|c| c.is_ascii_alphabetic());
+
+    match (format.currency_after, is_code) {
+        (true, _) => format!("{}{}{}{}", sign, formatted, NBSP, symbol),
+        (false, true) => format!("{}{}{}{}", sign, symbol, NBSP, formatted),
+        (false, false) => format!("{}{}{}", sign, symbol, formatted),
+    }
+}
+
+/// Formats the given date in the given locale with the given style.
+pub fn format_date(locale: &str, date: &impl Datelike, style: DateStyle) -> String {
+    let format = get_locale_format(locale);
+    let lang = locale.split('-').next().unwrap_or(locale);
+    let (year, month, day) = (date.year(), date.month(), date.day());
+    if let (DateStyle::Long, Some(month_names)) = (style, get_month_names(lang)) {
+        let month_name = month_names[month as usize - 1];
+        return match lang {
+            "en" if format.date_order == DateOrder::Mdy => {
+                format!("{} {}, {}", month_name, day, year)
+            }
+            "en" => format!("{} {} {}", day, month_name, year),
+            "de" => format!("{}. {} {}", day, month_name, year),
+            "es" | "pt" => format!("{} de {} de {}", day, month_name, year),
+            _ => format!("{} {} {}", day, month_name, year),
+        };
+    }
+
+    let sep = format.date_sep;
+    match format.date_order {
+        DateOrder::Dmy => format!("{:02}{}{:02}{}{}", day, sep, month, sep, year),
+        DateOrder::Mdy => format!("{}{}{}{}{}", month, sep, day, sep, year),
+        DateOrder::Ymd => format!("{}{}{:02}{}{:02}", year, sep, month, sep, day),
+    }
+}
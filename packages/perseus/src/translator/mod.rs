@@ -1,25 +1,71 @@
 /// Errors for translators. These are separate so new translators can easily be created in a modular fashion.
 pub mod errors;
 
+mod args;
+mod backend;
+mod direction;
+pub use args::IntoTranslationArgs;
+pub(crate) use backend::get_translator_locales;
+pub use backend::{set_translator_backend, set_translator_locales, Translator, TranslatorBackend};
+pub use direction::TextDirection;
+/// Utilities for formatting numbers, currencies, and dates in a locale, which are also available on every `Translator`.
+pub mod formatting;
+pub use formatting::DateStyle;
+mod pseudo;
+pub use pseudo::{pseudo_localize, PseudoTranslator};
+mod strict;
+pub(crate) use strict::record_failed_translations;
+
 // We export each translator by name
 #[cfg(feature = "translator-fluent")]
 mod fluent;
 #[cfg(feature = "translator-fluent")]
-pub use fluent::{FluentTranslator, FLUENT_TRANSLATOR_FILE_EXT};
+pub use fluent::{
+    get_fluent_id_candidates, FluentFunction, FluentTranslator, MissingTranslationHandler,
+    MissingTranslationReporter, FLUENT_TRANSLATOR_FILE_EXT,
+};
+#[cfg(feature = "translator-fluent")]
+mod fluent_args;
+#[cfg(feature = "translator-fluent")]
+pub use fluent_args::{IntoFluentArgs, IntoFluentValue};
+#[cfg(feature = "translator-fluent")]
+pub use fluent_bundle::{FluentArgs, FluentValue};
+#[cfg(feature = "translator-fluent")]
+pub use perseus_macro::{checked_t, IntoFluentArgs};
+#[cfg(feature = "translator-simple-json")]
+mod simple_json;
+#[cfg(feature = "translator-simple-json")]
+pub use simple_json::{SimpleJsonArgs, SimpleJsonTranslator, SIMPLE_JSON_TRANSLATOR_FILE_EXT};
+
+// Only one translator can be the default
+#[cfg(all(
+    feature = "translator-dflt-fluent",
+    feature = "translator-dflt-simple-json"
+))]
+compile_error!("only one `translator-dflt-*` feature can be enabled at a time (if you're using the simple JSON translator, you may need to disable Perseus' default features)");
 
 // And then we export defaults using feature gates
 #[cfg(feature = "translator-dflt-fluent")]
-pub use FluentTranslator as Translator;
+pub use fluent_bundle::FluentArgs as TranslationArgs;
+#[cfg(feature = "translator-dflt-fluent")]
+use FluentTranslator as DefaultTranslator;
+#[cfg(feature = "translator-dflt-simple-json")]
+pub use SimpleJsonArgs as TranslationArgs;
+#[cfg(feature = "translator-dflt-simple-json")]
+use SimpleJsonTranslator as DefaultTranslator;
 #[cfg(feature = "translator-dflt-fluent")]
 pub use FLUENT_TRANSLATOR_FILE_EXT as TRANSLATOR_FILE_EXT;
+#[cfg(feature = "translator-dflt-simple-json")]
+pub use SIMPLE_JSON_TRANSLATOR_FILE_EXT as TRANSLATOR_FILE_EXT;
 
-/// Translates the given ID conveniently, taking arguments for interpolation as required.
+/// Translates the given ID conveniently, taking arguments for interpolation as required. This gets the translator for the current page from
+/// context, so it can be used anywhere in a template (or its layout) without having to get the translator yourself.
 #[macro_export]
 macro_rules! t {
     // When there are no arguments to interpolate
     ($id:expr) => {
         {
-            let translator = ::sycamore::rx::use_context::<Rc<Translator>>();
+            let translator = ::sycamore::rx::use_context::<::std::rc::Rc<$crate::Translator>>();
             translator.translate($id, None)
         }
     };
@@ -28,8 +74,8 @@ macro_rules! t {
         $($key:literal: $value:expr),+
     }) => {
         {
-            let translator = ::sycamore::rx::use_context::<Rc<Translator>>();
-            let mut args = fluent_bundle::FluentArgs::new();
+            let translator = ::sycamore::rx::use_context::<::std::rc::Rc<$crate::Translator>>();
+            let mut args = $crate::translator::TranslationArgs::new();
             $(
                 args.set($key, $value);
             )+
@@ -37,13 +83,39 @@ macro_rules! t {
             translator.translate($id, Some(args))
         }
     };
+    // When the arguments are given as something that can be converted into them (like `args!` or, with Fluent, a struct deriving
+    // `IntoFluentArgs`)
+    ($id:expr, $args:expr) => {
+        {
+            let translator = ::sycamore::rx::use_context::<::std::rc::Rc<$crate::Translator>>();
+            translator.translate($id, $args)
+        }
+    };
 }
 
-/// Gets the link to the given resource in internationalized form conveniently.
+/// Gets the path to the given URL in the locale of the current page (e.g. `url!("/about")` might give `/en-US/about`), using the translator
+/// provided through context. Query parameters can be given after the URL as pairs of keys and values (e.g.
+/// `url!("/search", "q" => query)`), which will be percent-encoded.
 #[macro_export]
-macro_rules! link {
+macro_rules! url {
     ($url:expr) => {{
-        let translator = ::sycamore::rx::use_context::<Rc<Translator>>();
+        let translator = ::sycamore::rx::use_context::<::std::rc::Rc<$crate::Translator>>();
         translator.url($url)
     }};
+    ($url:expr, $($key:expr => $val:expr),+ $(,)?) => {{
+        let translator = ::sycamore::rx::use_context::<::std::rc::Rc<$crate::Translator>>();
+        translator
+            .url_builder($url)
+            $(.query($key, $val))+
+            .build()
+    }};
+}
+
+/// Gets the link to the given resource in internationalized form conveniently. This is the same as `url!` (see `Link` for a component
+/// that renders the whole link).
+#[macro_export]
+macro_rules! link {
+    ($($args:tt)+) => {
+        $crate::url!($($args)+)
+    };
 }
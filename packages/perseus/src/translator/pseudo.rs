@@ -0,0 +1,146 @@
+use crate::translator::errors::*;
+use crate::translator::{TranslationArgs, TranslatorBackend};
+
+/// How much longer pseudo-localized text is made than the original, as a fraction of its length. Translations are often this much longer
+/// than English (especially in German), so layouts that can't handle this will probably break in some real locales.
+const EXPANSION: f32 = 0.4;
+
+/// Gets the accented version of the given ASCII letter, or the character itself if it isn't one.
+fn accent(c: char) -> char {
+    match c {
+        'a' => 'á',
+        'b' => 'ƀ',
+        'c' => 'ç',
+        'd' => 'ð',
+        'e' => 'é',
+        'f' => 'ƒ',
+        'g' => 'ĝ',
+        'h' => 'ĥ',
+        'i' => 'í',
+        'j' => 'ĵ',
+        'k' => 'ķ',
+        'l' => 'ĺ',
+        'm' => 'ɱ',
+        'n' => 'ñ',
+        'o' => 'ó',
+        'p' => 'þ',
+        'q' => 'ǫ',
+        'r' => 'ŕ',
+        's' => 'š',
+        't' => 'ţ',
+        'u' => 'ú',
+        'v' => 'ṽ',
+        'w' => 'ŵ',
+        'x' => 'ẋ',
+        'y' => 'ý',
+        'z' => 'ž',
+        'A' => 'Á',
+        'B' => 'Ɓ',
+        'C' => 'Ç',
+        'D' => 'Ð',
+        'E' => 'É',
+        'F' => 'Ƒ',
+        'G' => 'Ĝ',
+        'H' => 'Ĥ',
+        'I' => 'Í',
+        'J' => 'Ĵ',
+        'K' => 'Ķ',
+        'L' => 'Ĺ',
+        'M' => 'Ṁ',
+        'N' => 'Ñ',
+        'O' => 'Ó',
+        'P' => 'Þ',
+        'Q' => 'Ǫ',
+        'R' => 'Ŕ',
+        'S' => 'Š',
+        'T' => 'Ţ',
+        'U' => 'Ú',
+        'V' => 'Ṽ',
+        'W' => 'Ŵ',
+        'X' => 'Ẋ',
+        'Y' => 'Ý',
+        'Z' => 'Ž',
+        _ => c,
+    }
+}
+
+/// Pseudo-localizes the given text, accenting its letters, padding it to be about 40% longer, and wrapping it in brackets (so that text
+/// that's been cut off is obvious). HTML tags and entities are left alone, as are any arguments Fluent interpolated (which it wraps in
+/// Unicode isolation marks), since those didn't come from the translations.
+pub fn pseudo_localize(text: &str) -> String {
+    let mut localized = String::from("[");
+    let mut in_tag = false;
+    let mut in_entity = false;
+    let mut in_arg = false;
+    let mut letters = 0;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            '&' => in_entity = true,
+            // A lone `&` (like in `this & that`) isn't an entity
+            ';' | ' ' => in_entity = false,
+            // Fluent's first strong isolate and pop directional isolate
+            '\u{2068}' => in_arg = true,
+            '\u{2069}' => in_arg = false,
+            _ => (),
+        }
+        if in_tag || in_entity || in_arg || !c.is_ascii_alphabetic() {
+            localized.push(c);
+        } else {
+            localized.push(accent(c));
+            letters += 1;
+        }
+    }
+    let padding = (letters as f32 * EXPANSION).ceil() as usize;
+    localized.push_str(&"~".repeat(padding));
+    localized.push(']');
+
+    localized
+}
+
+/// A translator backend that pseudo-localizes everything another backend translates, which makes it easy to spot hardcoded strings
+/// (which won't be accented) and layouts that can't handle longer text in development. This can be set as an app's backend with
+/// `translator_backend: PseudoTranslator<FluentTranslator>` in `define_app!`, and it'll read the same translations files as the
+/// backend it wraps.
+pub struct PseudoTranslator<B: TranslatorBackend> {
+    backend: B,
+}
+impl<B: TranslatorBackend> PseudoTranslator<B> {
+    /// Creates a new pseudo-localizing translator that wraps the given backend.
+    pub fn from_backend(backend: B) -> Self {
+        Self { backend }
+    }
+    /// Gets the backend this translator wraps.
+    pub fn get_backend(&self) -> &B {
+        &self.backend
+    }
+}
+impl<B: TranslatorBackend> TranslatorBackend for PseudoTranslator<B> {
+    fn new(locale: String, translations: String) -> Result<Self> {
+        Ok(Self::from_backend(B::new(locale, translations)?))
+    }
+    fn with_resources(locale: String, resources: Vec<String>) -> Result<Self> {
+        Ok(Self::from_backend(B::with_resources(locale, resources)?))
+    }
+    fn get_locale(&self) -> String {
+        self.backend.get_locale()
+    }
+    fn translate_checked(&self, id: &str, args: Option<TranslationArgs>) -> Result<String> {
+        self.backend
+            .translate_checked(id, args)
+            .map(|translation| pseudo_localize(&translation))
+    }
+    fn translate(&self, id: &str, args: Option<TranslationArgs>) -> String {
+        pseudo_localize(&self.backend.translate(id, args))
+    }
+    fn url(&self, url: &str) -> String {
+        self.backend.url(url)
+    }
+    fn filter_translations(translations: &str, prefixes: &[String]) -> String {
+        B::filter_translations(translations, prefixes)
+    }
+    fn join_resources(resources: Vec<String>) -> String {
+        B::join_resources(resources)
+    }
+}
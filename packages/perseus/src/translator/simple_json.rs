@@ -0,0 +1,166 @@
+use crate::translator::errors::*;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// The file extension used by the simple JSON translator, which expects JSON files.
+pub const SIMPLE_JSON_TRANSLATOR_FILE_EXT: &str = "json";
+
+/// Arguments to interpolate into a translation with the simple JSON translator. Each argument `x` will replace `{x}` in the translation.
+#[derive(Default, Debug, Clone)]
+pub struct SimpleJsonArgs {
+    args: HashMap<String, String>,
+}
+impl SimpleJsonArgs {
+    /// Creates a new, empty set of arguments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the given argument to the given value.
+    pub fn set<K: Into<String>, V: ToString>(&mut self, key: K, value: V) {
+        self.args.insert(key.into(), value.to_string());
+    }
+    /// Gets the value of the given argument, if it's been set. This is useful for custom translator backends.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.args.get(key).map(|value| value.as_str())
+    }
+    /// Gets an iterator over all the arguments and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.args
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// Manages translations on the client-side for a single locale using a simple JSON map of translation IDs to translations, with support
+/// for interpolating arguments like `{name}`. This avoids compiling any i18n parsing machinery into your app's WASM bundle beyond what
+/// Perseus already needs, making it a good choice for apps that don't need the full power of Fluent. This should generally be placed
+/// into an `Rc<T>` and referred to by every template in an app.
+///
+/// There's no special support for variants, but you can use IDs like `[id].[variant]` as keys in your JSON files.
+pub struct SimpleJsonTranslator {
+    /// The translations for this locale, by their IDs.
+    translations: Rc<HashMap<String, String>>,
+    /// The locale for which translations are being managed by this instance.
+    locale: String,
+}
+impl SimpleJsonTranslator {
+    /// Creates a new translator for a given locale, passing in translations as a JSON object of IDs to translations.
+    pub fn new(locale: String, json_string: String) -> Result<Self> {
+        // An empty string means there aren't any translations (e.g. if the app doesn't use i18n)
+        let translations =
+            match json_string.trim().is_empty() {
+                true => HashMap::new(),
+                false => serde_json::from_str::<HashMap<String, String>>(&json_string).map_err(
+                    |err| ErrorKind::TranslationsStrSerFailed(locale.clone(), err.to_string()),
+                )?,
+            };
+
+        Ok(Self {
+            translations: Rc::new(translations),
+            locale,
+        })
+    }
+    /// Gets the path to the given URL in whatever locale the instance is configured for.
+    pub fn url<S: Into<String> + std::fmt::Display>(&self, url: S) -> String {
+        format!("/{}{}", self.locale, url)
+    }
+    /// Gets the locale for which this instancce is configured.
+    pub fn get_locale(&self) -> String {
+        self.locale.clone()
+    }
+    /// Translates the given ID. This additionally takes any arguments that should be interpolated.
+    /// # Panics
+    /// This will `panic!` if the given ID doesn't exist. Therefore, this method should only be used for hardcoded IDs that can be
+    /// confirmed as valid. If you need to parse arbitrary IDs, use `.translate_checked()` instead.
+    pub fn translate<I: Into<String> + std::fmt::Display>(
+        &self,
+        id: I,
+        args: Option<SimpleJsonArgs>,
+    ) -> String {
+        let translation_res = self.translate_checked(&id.to_string(), args);
+        match translation_res {
+            Ok(translation) => translation,
+            Err(_) => panic!("translation id '{}' not found for locale '{}' (if you're not hardcoding the id, use `.translate_checked()` instead)", id, self.locale)
+        }
+    }
+    /// Translates the given ID, returning graceful errors. This additionally takes any arguments that should be interpolated.
+    pub fn translate_checked<I: Into<String> + std::fmt::Display>(
+        &self,
+        id: I,
+        args: Option<SimpleJsonArgs>,
+    ) -> Result<String> {
+        let id_str = id.to_string();
+        let mut translation = match self.translations.get(&id_str) {
+            Some(translation) => translation.to_string(),
+            None => bail!(ErrorKind::TranslationIdNotFound(
+                id_str,
+                self.locale.clone()
+            )),
+        };
+        if let Some(args) = args {
+            for (key, value) in args.args.iter() {
+                translation = translation.replace(&format!("{{{}}}", key), value);
+            }
+        }
+
+        Ok(translation)
+    }
+    /// Filters the given JSON translations down to those with IDs starting with any of the given prefixes. If the translations can't be
+    /// parsed, they'll be returned as they are, so that the error is reported when a translator is created from them.
+    pub fn filter_translations(json_string: &str, prefixes: &[String]) -> String {
+        let translations = match serde_json::from_str::<HashMap<String, String>>(json_string) {
+            Ok(translations) => translations,
+            Err(_) => return json_string.to_string(),
+        };
+        // These are sent to the browser (and cached), so they should be the same every time
+        let filtered: BTreeMap<String, String> = translations
+            .into_iter()
+            .filter(|(id, _)| {
+                prefixes
+                    .iter()
+                    .any(|prefix| id.starts_with(prefix.as_str()))
+            })
+            .collect();
+        // Serializing a map of strings can't fail
+        serde_json::to_string(&filtered).unwrap()
+    }
+    /// Joins several resources of JSON translations into one object with all their translations, with later resources overriding
+    /// earlier ones. If any of them can't be parsed, that one will be returned as it is, so that the error is reported when a translator
+    /// is created from it.
+    pub fn join_resources(resources: Vec<String>) -> String {
+        let mut joined = BTreeMap::new();
+        for resource in resources {
+            if resource.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<BTreeMap<String, String>>(&resource) {
+                Ok(translations) => joined.extend(translations),
+                Err(_) => return resource,
+            }
+        }
+        // Serializing a map of strings can't fail
+        serde_json::to_string(&joined).unwrap()
+    }
+}
+// Backends are given the default translator's arguments, so this can only be a backend if it's the default
+#[cfg(feature = "translator-dflt-simple-json")]
+impl crate::translator::TranslatorBackend for SimpleJsonTranslator {
+    fn new(locale: String, translations: String) -> Result<Self> {
+        SimpleJsonTranslator::new(locale, translations)
+    }
+    fn get_locale(&self) -> String {
+        self.get_locale()
+    }
+    fn translate_checked(&self, id: &str, args: Option<SimpleJsonArgs>) -> Result<String> {
+        self.translate_checked(id, args)
+    }
+    fn url(&self, url: &str) -> String {
+        self.url(url)
+    }
+    fn filter_translations(translations: &str, prefixes: &[String]) -> String {
+        SimpleJsonTranslator::filter_translations(translations, prefixes)
+    }
+    fn join_resources(resources: Vec<String>) -> String {
+        SimpleJsonTranslator::join_resources(resources)
+    }
+}
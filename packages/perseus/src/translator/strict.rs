@@ -0,0 +1,96 @@
+// This lets the build process find out which translations failed while a page was being rendered, so that strict i18n mode can fail the
+// build on them instead of leaving them to be found in the browser
+
+use std::cell::RefCell;
+
+thread_local! {
+    /// The IDs that have failed to translate on this thread since recording started, or `None` if nothing is being recorded. Pages are
+    /// rendered synchronously, so everything that fails while one is being rendered is from that page.
+    static FAILED_IDS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+}
+
+/// Checks if translations that fail on this thread are being recorded.
+pub(crate) fn is_recording_failed_translations() -> bool {
+    FAILED_IDS.with(|failed_ids| failed_ids.borrow().is_some())
+}
+
+/// Records that the given ID failed to translate, if failures are being recorded on this thread.
+pub(crate) fn record_failed_translation(id: &str) {
+    FAILED_IDS.with(|failed_ids| {
+        if let Some(failed_ids) = failed_ids.borrow_mut().as_mut() {
+            if !failed_ids.iter().any(|failed_id| failed_id == id) {
+                failed_ids.push(id.to_string());
+            }
+        }
+    })
+}
+
+/// Puts back whatever was being recorded before a recording started when it's dropped, so that a panic while recording doesn't leave
+/// this thread recording forever.
+struct RestoreRecording(Option<Vec<String>>);
+impl Drop for RestoreRecording {
+    fn drop(&mut self) {
+        let outer = self.0.take();
+        FAILED_IDS.with(|failed_ids| *failed_ids.borrow_mut() = outer);
+    }
+}
+
+/// Runs the given function (which should render a single page), returning what it returns along with every ID that failed to translate
+/// while it ran (in the order they first failed). Every call starts with nothing recorded and takes everything it recorded with it when
+/// it's done (even if the function panics), so failures never carry over from one page to the next one rendered on the same thread.
+pub(crate) fn record_failed_translations<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    // Anything already being recorded (if this is called while rendering another page) is put back afterwards
+    let outer = FAILED_IDS.with(|failed_ids| failed_ids.borrow_mut().replace(Vec::new()));
+    let _restore = RestoreRecording(outer);
+    let res = f();
+    let ids = FAILED_IDS
+        .with(|failed_ids| failed_ids.borrow_mut().replace(Vec::new()))
+        .unwrap_or_default();
+
+    (res, ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_are_reset_for_every_render() {
+        let (_, first) = record_failed_translations(|| {
+            record_failed_translation("a");
+            record_failed_translation("a");
+            record_failed_translation("b");
+        });
+        assert_eq!(first, vec!["a".to_string(), "b".to_string()]);
+        let (_, second) = record_failed_translations(|| record_failed_translation("c"));
+        assert_eq!(second, vec!["c".to_string()]);
+        // Nothing's recorded outside a render
+        record_failed_translation("d");
+        assert!(!is_recording_failed_translations());
+    }
+
+    #[test]
+    fn panics_dont_leave_failures_behind() {
+        let res = std::panic::catch_unwind(|| {
+            record_failed_translations(|| {
+                record_failed_translation("a");
+                panic!("render failed");
+            })
+        });
+        assert!(res.is_err());
+        assert!(!is_recording_failed_translations());
+        let (_, ids) = record_failed_translations(|| record_failed_translation("b"));
+        assert_eq!(ids, vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn nested_renders_dont_share_failures() {
+        let (inner, outer) = record_failed_translations(|| {
+            record_failed_translation("outer");
+            let (_, inner) = record_failed_translations(|| record_failed_translation("inner"));
+            inner
+        });
+        assert_eq!(inner, vec!["inner".to_string()]);
+        assert_eq!(outer, vec!["outer".to_string()]);
+    }
+}
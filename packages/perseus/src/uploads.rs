@@ -0,0 +1,580 @@
+// This parses `multipart/form-data` request bodies as they stream in, writing files straight to disk so that large uploads are never
+// held in memory, which lets apps accept uploads in their own routes with any server integration
+
+use crate::errors::*;
+use async_fs::File;
+use futures::io::AsyncWriteExt;
+use futures::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The most bytes the headers of a single part can take up, which stops a malicious body from making us buffer forever.
+const MAX_PART_HEADERS_SIZE: usize = 8 * 1024;
+/// A counter to make sure files uploaded at the same instant still get different names.
+static UPLOAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The limits on what an upload can contain, and where its files are written. Anything over these limits will be rejected with an
+/// error that gives a `413` or `415` status code from `err_to_status_code`.
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    /// The directory uploaded files are written to.
+    dir: PathBuf,
+    /// The most bytes a single file can be.
+    max_file_size: u64,
+    /// The most bytes the whole body can be (including text fields).
+    max_total_size: u64,
+    /// The most files a single upload can contain.
+    max_files: usize,
+    /// The content types files can have, which may end in `/*` to allow a whole type (like `image/*`). If this is empty, files can have
+    /// any content type.
+    allowed_types: Vec<String>,
+}
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self::new(std::env::temp_dir())
+    }
+}
+impl UploadLimits {
+    /// Creates new limits that write files to the given directory (which will be created if it doesn't exist), allowing files of up to
+    /// 10MB of any content type and bodies of up to 50MB.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_file_size: 10 * 1024 * 1024,
+            max_total_size: 50 * 1024 * 1024,
+            max_files: 10,
+            allowed_types: Vec::new(),
+        }
+    }
+    /// Sets the most bytes a single file can be.
+    pub fn max_file_size(mut self, val: u64) -> Self {
+        self.max_file_size = val;
+        self
+    }
+    /// Sets the most bytes the whole body can be (including text fields).
+    pub fn max_total_size(mut self, val: u64) -> Self {
+        self.max_total_size = val;
+        self
+    }
+    /// Sets the most files a single upload can contain.
+    pub fn max_files(mut self, val: usize) -> Self {
+        self.max_files = val;
+        self
+    }
+    /// Sets the content types files can have (e.g. `vec!["image/*".to_string(), "application/pdf".to_string()]`). Files without a
+    /// content type are treated as `application/octet-stream`.
+    pub fn allowed_types(mut self, val: Vec<String>) -> Self {
+        self.allowed_types = val;
+        self
+    }
+    /// Checks if files with the given content type can be uploaded.
+    fn is_type_allowed(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        self.allowed_types.is_empty()
+            || self.allowed_types.iter().any(|allowed| {
+                let allowed = allowed.to_lowercase();
+                match allowed.strip_suffix("/*") {
+                    Some(type_) => allowed == "*/*" || essence.split('/').next() == Some(type_),
+                    None => essence == allowed,
+                }
+            })
+    }
+}
+
+/// A file that was uploaded and written to disk. This isn't deleted automatically, so you should move it somewhere permanent or delete
+/// it once you're done with it.
+#[derive(Debug, Clone)]
+pub struct UploadedFile {
+    /// The name of the form field the file was uploaded with.
+    pub field: String,
+    /// The name the client gave the file, if it gave one. This is NOT safe to use as a path!
+    pub filename: Option<String>,
+    /// The content type the client gave the file.
+    pub content_type: String,
+    /// Where the file was written to (under a generated name).
+    pub path: PathBuf,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+/// The contents of a `multipart/form-data` body, with its text fields in memory and its files on disk.
+#[derive(Debug, Clone, Default)]
+pub struct UploadForm {
+    /// The text fields, by name. If a field was given more than once, the last value is used.
+    pub fields: HashMap<String, String>,
+    /// The files that were uploaded, in the order they were sent.
+    pub files: Vec<UploadedFile>,
+}
+impl UploadForm {
+    /// Gets the first file uploaded with the given field name.
+    pub fn get_file(&self, field: &str) -> Option<&UploadedFile> {
+        self.files.iter().find(|file| file.field == field)
+    }
+    /// Deletes every uploaded file, ignoring any errors (e.g. if you've already moved some of them).
+    pub fn delete_files(&self) {
+        for file in &self.files {
+            let _ = fs::remove_file(&file.path);
+        }
+    }
+}
+
+/// Gets the boundary between the parts of a body from its `Content-Type` header, which must be `multipart/form-data`.
+fn get_boundary(content_type: &str) -> Result<String> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        bail!(ErrorKind::MalformedUpload(
+            "content type isn't `multipart/form-data`".to_string()
+        ));
+    }
+    params
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+        .map(|(_, val)| val.trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty())
+        .ok_or_else(|| ErrorKind::MalformedUpload("no boundary given".to_string()).into())
+}
+
+/// Finds the first occurrence of the given needle in the given haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// A file that's been written to disk for an upload that hasn't been fully parsed yet, which is deleted if it's dropped before it's kept
+/// (whether parsing failed or the whole upload was abandoned).
+struct UnfinishedFile(Option<PathBuf>);
+impl UnfinishedFile {
+    /// Keeps the file, since the upload it's part of succeeded.
+    fn keep(mut self) {
+        self.0 = None;
+    }
+}
+impl Drop for UnfinishedFile {
+    fn drop(&mut self) {
+        // We can't wait for anything here, but removing a file is quick
+        if let Some(path) = &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The part of a body that's currently being read.
+enum Part {
+    /// A text field with the given name, and its value so far.
+    Field(String, Vec<u8>),
+    /// A file that's being written to disk.
+    File(UploadedFile, File, UnfinishedFile),
+}
+
+/// What's expected next in a body.
+enum State {
+    /// The delimiter before the first part (anything before it is ignored).
+    Preamble,
+    /// Either `--` (for the end of the body) or a line break (for the headers of the next part) after a delimiter.
+    AfterDelimiter,
+    /// The headers of a part.
+    Headers,
+    /// The body of the given part, up until the next delimiter.
+    Body(Part),
+    /// Nothing else (the body has ended).
+    End,
+}
+
+/// Parses the headers of a part into its field name, filename (if it's a file), and content type.
+fn parse_part_headers(headers: &[u8]) -> Result<(String, Option<String>, Option<String>)> {
+    let headers = String::from_utf8_lossy(headers);
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for line in headers.split("\r\n") {
+        let (key, val) = match line.split_once(':') {
+            Some(header) => header,
+            None => continue,
+        };
+        if key.trim().eq_ignore_ascii_case("content-disposition") {
+            for param in val.split(';').skip(1) {
+                match param.trim().split_once('=') {
+                    Some((key, val)) if key.eq_ignore_ascii_case("name") => {
+                        name = Some(val.trim_matches('"').to_string())
+                    }
+                    Some((key, val)) if key.eq_ignore_ascii_case("filename") => {
+                        filename = Some(val.trim_matches('"').to_string())
+                    }
+                    _ => (),
+                }
+            }
+        } else if key.trim().eq_ignore_ascii_case("content-type") {
+            content_type = Some(val.trim().to_string());
+        }
+    }
+    match name {
+        Some(name) => Ok((name, filename, content_type)),
+        None => bail!(ErrorKind::MalformedUpload(
+            "part has no field name".to_string()
+        )),
+    }
+}
+
+/// Starts a new part from its headers, creating a file on disk for it if it's a file.
+async fn start_part(headers: &[u8], limits: &UploadLimits, form: &UploadForm) -> Result<Part> {
+    let (name, filename, content_type) = parse_part_headers(headers)?;
+    // Parts are only files if they have a filename (even an empty one, which browsers send for empty file inputs)
+    let filename = match filename {
+        Some(filename) => filename,
+        None => return Ok(Part::Field(name, Vec::new())),
+    };
+    if form.files.len() >= limits.max_files {
+        bail!(ErrorKind::UploadTooLarge(format!(
+            "more than {} files",
+            limits.max_files
+        )));
+    }
+    let content_type = content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+    if !limits.is_type_allowed(&content_type) {
+        bail!(ErrorKind::UploadTypeNotAllowed(content_type));
+    }
+    // We never use the client's filename on disk, since it could be anything (like `../../etc/passwd`)
+    async_fs::create_dir_all(&limits.dir).await?;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    let count = UPLOAD_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let path = limits.dir.join(format!("upload-{}-{}", nanos, count));
+    let file = File::create(&path).await?;
+    let unfinished = UnfinishedFile(Some(path.clone()));
+
+    Ok(Part::File(
+        UploadedFile {
+            field: name,
+            filename: match filename.is_empty() {
+                true => None,
+                false => Some(filename),
+            },
+            content_type,
+            path,
+            size: 0,
+        },
+        file,
+        unfinished,
+    ))
+}
+
+/// Adds the given bytes to the given part, checking that it's within the limits.
+async fn write_to_part(part: &mut Part, bytes: &[u8], limits: &UploadLimits) -> Result<()> {
+    match part {
+        Part::Field(_, val) => val.extend_from_slice(bytes),
+        Part::File(uploaded, file, _) => {
+            uploaded.size += bytes.len() as u64;
+            if uploaded.size > limits.max_file_size {
+                bail!(ErrorKind::UploadTooLarge(format!(
+                    "file larger than {} bytes",
+                    limits.max_file_size
+                )));
+            }
+            file.write_all(bytes).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a `multipart/form-data` body with the given `Content-Type` header as it streams in, writing any files in it to disk within
+/// the given limits. If anything goes wrong (including the body being over the limits), or this is dropped before it finishes, any files
+/// that were written are deleted. Files are written without blocking the thread this is polled on.
+///
+/// The body can be a stream of any chunks of bytes, so server integrations can pass in their request payloads (the Actix Web
+/// integration has a `parse_upload` function that does this for you).
+pub async fn parse_multipart<S, B, E>(
+    content_type: &str,
+    body: S,
+    limits: &UploadLimits,
+) -> Result<UploadForm>
+where
+    S: Stream<Item = std::result::Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    let mut form = UploadForm::default();
+    // Every file we've finished writing is deleted if we return early, just like the one we're writing
+    let mut finished_files = Vec::new();
+    parse_multipart_into(content_type, body, limits, &mut form, &mut finished_files).await?;
+    for file in finished_files {
+        file.keep();
+    }
+
+    Ok(form)
+}
+
+/// Parses a `multipart/form-data` body into the given form, adding the guards of the files it finishes writing to the given list so that
+/// the caller can clean them up if this fails.
+async fn parse_multipart_into<S, B, E>(
+    content_type: &str,
+    mut body: S,
+    limits: &UploadLimits,
+    form: &mut UploadForm,
+    finished_files: &mut Vec<UnfinishedFile>,
+) -> Result<()>
+where
+    S: Stream<Item = std::result::Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    let boundary = get_boundary(content_type)?;
+    // Every delimiter follows a line break, so we pretend the body starts with one so that the first delimiter can be found the same way
+    let delimiter = format!("\r\n--{}", boundary).into_bytes();
+    let mut buf = b"\r\n".to_vec();
+    let mut state = State::Preamble;
+    let mut total_size = 0;
+    let mut body_ended = false;
+    loop {
+        // We process as much of the buffer as we can before we read any more of the body
+        let progressed = match &mut state {
+            State::Preamble => match find(&buf, &delimiter) {
+                Some(idx) => {
+                    buf.drain(..idx + delimiter.len());
+                    state = State::AfterDelimiter;
+                    true
+                }
+                None => {
+                    // We only need to keep enough to find a delimiter that's been split across chunks
+                    let keep_from = buf.len().saturating_sub(delimiter.len() - 1);
+                    buf.drain(..keep_from);
+                    false
+                }
+            },
+            State::AfterDelimiter if buf.len() >= 2 => {
+                if buf.starts_with(b"--") {
+                    state = State::End;
+                } else if buf.starts_with(b"\r\n") {
+                    buf.drain(..2);
+                    state = State::Headers;
+                } else {
+                    bail!(ErrorKind::MalformedUpload(
+                        "delimiter not followed by a line break".to_string()
+                    ));
+                }
+                true
+            }
+            State::AfterDelimiter => false,
+            State::Headers => match find(&buf, b"\r\n\r\n") {
+                Some(idx) => {
+                    let part = start_part(&buf[..idx], limits, form).await?;
+                    buf.drain(..idx + 4);
+                    state = State::Body(part);
+                    true
+                }
+                None if buf.len() > MAX_PART_HEADERS_SIZE => bail!(ErrorKind::MalformedUpload(
+                    "part headers too large".to_string()
+                )),
+                None => false,
+            },
+            State::Body(part) => match find(&buf, &delimiter) {
+                Some(idx) => {
+                    write_to_part(part, &buf[..idx], limits).await?;
+                    buf.drain(..idx + delimiter.len());
+                    match std::mem::replace(&mut state, State::AfterDelimiter) {
+                        State::Body(Part::Field(name, val)) => {
+                            let val = String::from_utf8(val).map_err(|_| {
+                                ErrorKind::MalformedUpload(format!(
+                                    "field '{}' isn't valid utf-8",
+                                    name
+                                ))
+                            })?;
+                            form.fields.insert(name, val);
+                        }
+                        State::Body(Part::File(uploaded, mut file, unfinished)) => {
+                            finished_files.push(unfinished);
+                            file.flush().await?;
+                            form.files.push(uploaded);
+                        }
+                        _ => unreachable!(),
+                    }
+                    true
+                }
+                None => {
+                    // Everything but what could be the start of a delimiter can be written out
+                    let keep_from = buf.len().saturating_sub(delimiter.len() - 1);
+                    write_to_part(part, &buf[..keep_from], limits).await?;
+                    buf.drain(..keep_from);
+                    false
+                }
+            },
+            State::End => return Ok(()),
+        };
+        if progressed {
+            continue;
+        }
+        if body_ended {
+            bail!(ErrorKind::MalformedUpload(
+                "body ended before the final delimiter".to_string()
+            ));
+        }
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                let chunk = chunk.as_ref();
+                total_size += chunk.len() as u64;
+                if total_size > limits.max_total_size {
+                    bail!(ErrorKind::UploadTooLarge(format!(
+                        "body larger than {} bytes",
+                        limits.max_total_size
+                    )));
+                }
+                buf.extend_from_slice(chunk);
+            }
+            Some(Err(err)) => bail!(ErrorKind::MalformedUpload(format!(
+                "couldn't read body: {}",
+                err
+            ))),
+            None => body_ended = true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::stream;
+
+    const CONTENT_TYPE: &str = "multipart/form-data; boundary=\"XyZ\"";
+
+    /// Gets an empty directory for a test to upload files to.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("perseus-uploads-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Parses the given body, split into chunks of the given size (so delimiters get split across chunks).
+    fn parse(body: &[u8], chunk_size: usize, limits: &UploadLimits) -> Result<UploadForm> {
+        let chunks: Vec<std::result::Result<Vec<u8>, String>> = body
+            .chunks(chunk_size)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect();
+        block_on(parse_multipart(CONTENT_TYPE, stream::iter(chunks), limits))
+    }
+
+    fn files_in(dir: &PathBuf) -> usize {
+        fs::read_dir(dir).map(|dir| dir.count()).unwrap_or(0)
+    }
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\n\
+Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+Hello --XyZ world\r\n--XyZ\r\n\
+Content-Disposition: form-data; name=\"doc\"; filename=\"a.txt\"\r\n\
+Content-Type: text/plain\r\n\r\n\
+line one\r\nline two\r\n--XyZ\r\n\
+Content-Disposition: form-data; name=\"empty\"; filename=\"\"\r\n\r\n\
+\r\n--XyZ--\r\n";
+
+    #[test]
+    fn parses_fields_and_files_across_chunks() {
+        for chunk_size in [1, 3, 7, BODY.len()] {
+            let dir = test_dir(&format!("parses-{}", chunk_size));
+            let form = parse(BODY, chunk_size, &UploadLimits::new(&dir)).unwrap();
+            assert_eq!(form.fields.get("title").unwrap(), "Hello --XyZ world");
+            assert_eq!(form.files.len(), 2);
+            let doc = form.get_file("doc").unwrap();
+            assert_eq!(doc.filename.as_deref(), Some("a.txt"));
+            assert_eq!(doc.content_type, "text/plain");
+            assert_eq!(fs::read(&doc.path).unwrap(), b"line one\r\nline two");
+            assert_eq!(doc.size, 18);
+            let empty = form.get_file("empty").unwrap();
+            assert_eq!(empty.filename, None);
+            assert_eq!(empty.content_type, "application/octet-stream");
+            assert_eq!(empty.size, 0);
+            form.delete_files();
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_boundaries() {
+        assert!(get_boundary("multipart/form-data").is_err());
+        assert!(get_boundary("multipart/form-data; boundary=").is_err());
+        assert!(get_boundary("application/json; boundary=XyZ").is_err());
+        assert_eq!(
+            get_boundary("Multipart/Form-Data; charset=utf-8; Boundary=XyZ").unwrap(),
+            "XyZ"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_bodies() {
+        let dir = test_dir("truncated");
+        let res = parse(&BODY[..BODY.len() - 10], 5, &UploadLimits::new(&dir));
+        assert!(matches!(
+            res.unwrap_err().kind(),
+            ErrorKind::MalformedUpload(_)
+        ));
+        assert_eq!(files_in(&dir), 0);
+    }
+
+    #[test]
+    fn enforces_limits_and_cleans_up() {
+        let cases = [
+            UploadLimits::new(test_dir("max-file-size")).max_file_size(10),
+            UploadLimits::new(test_dir("max-total-size")).max_total_size(200),
+            UploadLimits::new(test_dir("max-files")).max_files(1),
+        ];
+        for limits in &cases {
+            let res = parse(BODY, 4, limits);
+            assert!(matches!(
+                res.unwrap_err().kind(),
+                ErrorKind::UploadTooLarge(_)
+            ));
+            // Neither the finished file nor the one being written should be left behind
+            assert_eq!(files_in(&limits.dir), 0);
+        }
+
+        let limits =
+            UploadLimits::new(test_dir("allowed-types")).allowed_types(vec!["image/*".to_string()]);
+        let res = parse(BODY, 4, &limits);
+        assert!(matches!(
+            res.unwrap_err().kind(),
+            ErrorKind::UploadTypeNotAllowed(_)
+        ));
+        assert_eq!(files_in(&limits.dir), 0);
+    }
+
+    #[test]
+    fn cleans_up_when_the_body_fails() {
+        let dir = test_dir("body-fails");
+        let chunks: Vec<std::result::Result<Vec<u8>, String>> = vec![
+            Ok(BODY[..190].to_vec()),
+            Err("connection reset".to_string()),
+        ];
+        let res = block_on(parse_multipart(
+            CONTENT_TYPE,
+            stream::iter(chunks),
+            &UploadLimits::new(&dir),
+        ));
+        assert!(res.is_err());
+        assert_eq!(files_in(&dir), 0);
+    }
+
+    #[test]
+    fn matches_allowed_types() {
+        let limits = UploadLimits::default()
+            .allowed_types(vec!["image/*".to_string(), "application/pdf".to_string()]);
+        assert!(limits.is_type_allowed("image/png"));
+        assert!(limits.is_type_allowed("Application/PDF; charset=binary"));
+        assert!(!limits.is_type_allowed("text/plain"));
+        assert!(!limits.is_type_allowed("imagex/png"));
+    }
+}
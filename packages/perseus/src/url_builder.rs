@@ -0,0 +1,340 @@
+// This puts together the URLs of pages from their parts (the path, the locale, query parameters, etc.), so that the translator, the
+// locale switcher, and the server all build them the same way, with the app's URL strategy
+
+use crate::translator::get_translator_locales;
+use crate::Locales;
+
+/// A builder for the URL of a page in the app, which puts it in the given locale with the app's URL strategy, adds any query parameters
+/// and fragment, and puts the app's base path and origin before it if they're given. This is what `Translator::url()` and the `link!`
+/// macro use, and a builder preset with a translator's locale can be created with `Translator::url_builder()`.
+///
+/// Note that the path is the same in every locale, so the path of a page with a translated slug in the locale should be given (e.g. from
+/// the `alternates` of the `RenderCtx`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UrlBuilder {
+    path: String,
+    locale: Option<String>,
+    switching_locale: bool,
+    query: Vec<(String, String)>,
+    fragment: Option<String>,
+    base_path: Option<String>,
+    origin: Option<String>,
+}
+impl UrlBuilder {
+    /// Creates a new builder for the URL of the page at the given path, which may have a query string or fragment already (they'll be
+    /// kept). A leading `/` will be added if it's missing.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+    /// Sets the locale the URL should go to the page in. If this isn't set, the URL won't be localized.
+    pub fn locale(mut self, val: impl Into<String>) -> Self {
+        self.locale = Some(val.into());
+        self
+    }
+    /// Sets whether or not the URL will be used from a page in another locale (like in a locale switcher), in which case it will go to
+    /// the locale's domain with `UrlStrategy::Domains`.
+    pub fn switching_locale(mut self, val: bool) -> Self {
+        self.switching_locale = val;
+        self
+    }
+    /// Adds a query parameter with the given key and value, both of which will be percent-encoded.
+    pub fn query(mut self, key: impl Into<String>, val: impl Into<String>) -> Self {
+        self.query.push((key.into(), val.into()));
+        self
+    }
+    /// Sets the fragment of the URL (without the leading `#`), replacing any that was in the path.
+    pub fn fragment(mut self, val: impl Into<String>) -> Self {
+        self.fragment = Some(val.into());
+        self
+    }
+    /// Sets the path the app is served under (e.g. `/docs` if it's behind a reverse proxy at `example.com/docs`), which will be put
+    /// before the path (and any locale in it). Perseus' server and router don't strip this, so it should be removed before requests
+    /// reach the app.
+    pub fn base_path(mut self, val: impl Into<String>) -> Self {
+        self.base_path = Some(val.into());
+        self
+    }
+    /// Sets the origin the app is served from (e.g. `https://example.com`), which makes the URL absolute (like for a canonical URL in
+    /// the `<head>` of a page, or a sitemap). With `UrlStrategy::Domains`, only the scheme of this is used for URLs going to another
+    /// locale's domain.
+    pub fn origin(mut self, val: impl Into<String>) -> Self {
+        self.origin = Some(val.into());
+        self
+    }
+
+    /// Builds the URL with the app's URL strategy, if the app's locales have been given to translators (which `define_app!` does
+    /// automatically), or by putting the locale before the path otherwise.
+    pub fn build(&self) -> String {
+        self.build_url(get_translator_locales())
+    }
+    /// Builds the URL with the URL strategy of the given locales.
+    pub fn build_with(&self, locales: &Locales) -> String {
+        self.build_url(Some(locales))
+    }
+
+    /// Builds the URL, localizing it with the given locales, or by putting the locale before the path if there aren't any.
+    fn build_url(&self, locales: Option<&Locales>) -> String {
+        let (rest, fragment) = match self.path.find('#') {
+            Some(idx) => (&self.path[..idx], Some(&self.path[(idx + 1)..])),
+            None => (self.path.as_str(), None),
+        };
+        let (path, query) = match rest.find('?') {
+            Some(idx) => (&rest[..idx], Some(&rest[(idx + 1)..])),
+            None => (rest, None),
+        };
+
+        let mut url = format!("/{}", path.trim_start_matches('/'));
+        let query = query
+            .filter(|query| !query.is_empty())
+            .map(|query| query.to_string())
+            .into_iter()
+            .chain(self.query.iter().map(|(key, val)| {
+                format!("{}={}", urlencoding::encode(key), urlencoding::encode(val))
+            }))
+            .collect::<Vec<String>>();
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query.join("&"));
+        }
+        if let Some(fragment) = self.fragment.as_deref().or(fragment) {
+            url.push('#');
+            url.push_str(fragment);
+        }
+
+        let url = match (&self.locale, locales) {
+            (Some(locale), Some(locales)) if self.switching_locale => {
+                locales.url_strategy.get_switch_url(locale, &url, locales)
+            }
+            (Some(locale), Some(locales)) => {
+                locales.url_strategy.localize_url(locale, &url, locales)
+            }
+            (Some(locale), None) => format!("/{}{}", locale, url),
+            (None, _) => url,
+        };
+
+        // The base path goes after the host if we're going to another domain
+        let base_path = self
+            .base_path
+            .as_deref()
+            .map(|base_path| base_path.trim_matches('/'))
+            .filter(|base_path| !base_path.is_empty());
+        let url = match (base_path, url.strip_prefix("//")) {
+            (Some(base_path), Some(rest)) => {
+                let (host, path) = rest.split_at(rest.find('/').unwrap_or_else(|| rest.len()));
+                format!("//{}/{}{}", host, base_path, path)
+            }
+            (Some(base_path), None) => format!("/{}{}", base_path, url),
+            (None, _) => url,
+        };
+
+        match &self.origin {
+            Some(origin) if url.starts_with("//") => match origin.split_once("://") {
+                Some((scheme, _)) => format!("{}:{}", scheme, url),
+                None => url,
+            },
+            Some(origin) => format!("{}{}", origin.trim_end_matches('/'), url),
+            None => url,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UrlStrategy;
+
+    fn locales(url_strategy: UrlStrategy) -> Locales {
+        Locales {
+            default: "en-US".to_string(),
+            other: vec!["fr-FR".to_string(), "de-DE".to_string()],
+            using_i18n: true,
+            url_strategy,
+        }
+    }
+
+    #[test]
+    fn builds_unlocalized_urls() {
+        let locales = locales(UrlStrategy::PrefixAll);
+        assert_eq!(UrlBuilder::new("about").build_with(&locales), "/about");
+        assert_eq!(UrlBuilder::new("/about").build_with(&locales), "/about");
+        assert_eq!(UrlBuilder::new("//about").build_with(&locales), "/about");
+        assert_eq!(UrlBuilder::new("").build_with(&locales), "/");
+    }
+
+    #[test]
+    fn keeps_query_and_fragment_in_path() {
+        let locales = locales(UrlStrategy::PrefixAll);
+        assert_eq!(
+            UrlBuilder::new("search?q=x#results").build_with(&locales),
+            "/search?q=x#results"
+        );
+        // Empty query strings are dropped
+        assert_eq!(
+            UrlBuilder::new("about?#team").build_with(&locales),
+            "/about#team"
+        );
+        // Query parameters are added after the ones in the path, and the given fragment replaces the one in it
+        assert_eq!(
+            UrlBuilder::new("search?q=x#results")
+                .query("page", "2")
+                .fragment("top")
+                .build_with(&locales),
+            "/search?q=x&page=2#top"
+        );
+    }
+
+    #[test]
+    fn encodes_query_parameters() {
+        let locales = locales(UrlStrategy::PrefixAll);
+        assert_eq!(
+            UrlBuilder::new("search")
+                .query("q", "fish & chips")
+                .query("a=b", "50%/100%")
+                .build_with(&locales),
+            "/search?q=fish%20%26%20chips&a%3Db=50%25%2F100%25"
+        );
+        assert_eq!(
+            UrlBuilder::new("search")
+                .query("q", "")
+                .build_with(&locales),
+            "/search?q="
+        );
+    }
+
+    #[test]
+    fn builds_with_prefix_all() {
+        let locales = locales(UrlStrategy::PrefixAll);
+        assert_eq!(
+            UrlBuilder::new("about")
+                .locale("en-US")
+                .build_with(&locales),
+            "/en-US/about"
+        );
+        assert_eq!(
+            UrlBuilder::new("search?q=x")
+                .locale("fr-FR")
+                .query("page", "2")
+                .fragment("top")
+                .switching_locale(true)
+                .build_with(&locales),
+            "/fr-FR/search?q=x&page=2#top"
+        );
+    }
+
+    #[test]
+    fn builds_with_prefix_except_default() {
+        let locales = locales(UrlStrategy::PrefixExceptDefault);
+        assert_eq!(
+            UrlBuilder::new("about")
+                .locale("en-US")
+                .build_with(&locales),
+            "/about"
+        );
+        assert_eq!(
+            UrlBuilder::new("about")
+                .locale("de-DE")
+                .build_with(&locales),
+            "/de-DE/about"
+        );
+    }
+
+    #[test]
+    fn builds_with_domains() {
+        let locales = locales(UrlStrategy::Domains(vec![
+            ("en-US".to_string(), "example.com".to_string()),
+            ("fr-FR".to_string(), "example.fr:8080".to_string()),
+        ]));
+        let url = UrlBuilder::new("about?q=x").locale("fr-FR");
+        assert_eq!(url.build_with(&locales), "/about?q=x");
+        assert_eq!(
+            url.clone().switching_locale(true).build_with(&locales),
+            "//example.fr:8080/about?q=x"
+        );
+        // The base path goes after the host, and only the scheme of the origin is used
+        assert_eq!(
+            url.clone()
+                .switching_locale(true)
+                .base_path("/docs/")
+                .origin("https://example.com")
+                .build_with(&locales),
+            "https://example.fr:8080/docs/about?q=x"
+        );
+        // An origin without a scheme can't be used on another domain
+        assert_eq!(
+            url.clone()
+                .switching_locale(true)
+                .origin("example.com")
+                .build_with(&locales),
+            "//example.fr:8080/about?q=x"
+        );
+        // Locales without a domain stay on the current one
+        assert_eq!(
+            UrlBuilder::new("about")
+                .locale("de-DE")
+                .switching_locale(true)
+                .origin("https://example.com")
+                .build_with(&locales),
+            "https://example.com/about"
+        );
+    }
+
+    #[test]
+    fn builds_with_query_param() {
+        let locales = locales(UrlStrategy::QueryParam("lang".to_string()));
+        assert_eq!(
+            UrlBuilder::new("about")
+                .locale("en-US")
+                .build_with(&locales),
+            "/about?lang=en-US"
+        );
+        assert_eq!(
+            UrlBuilder::new("search")
+                .locale("fr-FR")
+                .query("q", "a b")
+                .fragment("top")
+                .build_with(&locales),
+            "/search?q=a%20b&lang=fr-FR#top"
+        );
+    }
+
+    #[test]
+    fn adds_base_path_and_origin() {
+        let locales = locales(UrlStrategy::PrefixAll);
+        let url = UrlBuilder::new("about").locale("fr-FR");
+        assert_eq!(
+            url.clone().base_path("/docs/").build_with(&locales),
+            "/docs/fr-FR/about"
+        );
+        assert_eq!(
+            url.clone().base_path("docs").build_with(&locales),
+            "/docs/fr-FR/about"
+        );
+        // An empty base path is ignored
+        assert_eq!(
+            url.clone().base_path("/").build_with(&locales),
+            "/fr-FR/about"
+        );
+        assert_eq!(
+            url.clone()
+                .base_path("docs")
+                .origin("https://example.com/")
+                .build_with(&locales),
+            "https://example.com/docs/fr-FR/about"
+        );
+    }
+
+    #[test]
+    fn prefixes_locale_without_locales() {
+        assert_eq!(
+            UrlBuilder::new("about")
+                .locale("fr-FR")
+                .query("q", "x")
+                .build_url(None),
+            "/fr-FR/about?q=x"
+        );
+        assert_eq!(UrlBuilder::new("about").build_url(None), "/about");
+    }
+}
@@ -0,0 +1,314 @@
+use crate::Locales;
+
+/// How the locale of a page is put into its URL, which is set for a whole app with `url_strategy` in the `locales` of `define_app!`.
+/// The translator, the router, the locale switcher, and the server's locale redirection all use this.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlStrategy {
+    /// Every page's path starts with its locale (e.g. `/en-US/about` and `/fr-FR/about`), and paths without one redirect the user to
+    /// their locale. This is the default.
+    PrefixAll,
+    /// Pages in the default locale have no prefix (e.g. `/about`), and pages in every other locale start with their locale (e.g.
+    /// `/fr-FR/about`). Paths without a locale are never redirected.
+    PrefixExceptDefault,
+    /// Each locale is served on its own domain (e.g. `example.com` and `example.fr`), given as pairs of locales and hosts (with a port if
+    /// it isn't the default one). Paths don't have locales in them, and hosts that aren't listed are served the default locale.
+    Domains(Vec<(String, String)>),
+    /// The locale is given in the query parameter with the given name (e.g. `/about?lang=fr-FR`), and pages without it are served in the
+    /// default locale.
+    QueryParam(String),
+}
+impl Default for UrlStrategy {
+    fn default() -> Self {
+        Self::PrefixAll
+    }
+}
+impl UrlStrategy {
+    /// Gets the URL of the given path (with a leading `/`, and possibly with a query string or fragment) in the given locale. With
+    /// `Domains`, this is just the path, since it's already on the right domain (use `.get_switch_url()` to go to another locale).
+    pub fn localize_url(&self, locale: &str, url: &str, locales: &Locales) -> String {
+        match self {
+            Self::PrefixAll => format!("/{}{}", locale, url),
+            Self::PrefixExceptDefault if locale == locales.default => url.to_string(),
+            Self::PrefixExceptDefault => format!("/{}{}", locale, url),
+            Self::Domains(_) => url.to_string(),
+            Self::QueryParam(param) => {
+                // The parameter has to go before any fragment
+                let (url, fragment) = match url.find('#') {
+                    Some(idx) => url.split_at(idx),
+                    None => (url, ""),
+                };
+                let sep = match url.contains('?') {
+                    true => '&',
+                    false => '?',
+                };
+                format!("{}{}{}={}{}", url, sep, param, locale, fragment)
+            }
+        }
+    }
+    /// Gets the URL of the given path (with a leading `/`) in the given locale from a page in another locale, which is the same as
+    /// `.localize_url()` except that it goes to the locale's domain with `Domains`.
+    pub fn get_switch_url(&self, locale: &str, url: &str, locales: &Locales) -> String {
+        match self {
+            Self::Domains(domains) => match domains.iter().find(|(l, _)| l == locale) {
+                Some((_, host)) => format!("//{}{}", host, url),
+                None => url.to_string(),
+            },
+            _ => self.localize_url(locale, url, locales),
+        }
+    }
+    /// Gets the locale of the page at the given path (without a leading `/`), along with the path without the locale in it, from the path
+    /// itself, the host it was requested on, and its query string. If the locale can't be worked out (only with `PrefixAll`, when the path
+    /// doesn't start with a supported locale), this will return `None`, and the user should be redirected to their locale.
+    pub fn get_locale<'a>(
+        &self,
+        path: &'a str,
+        host: Option<&str>,
+        query: Option<&str>,
+        locales: &Locales,
+    ) -> Option<(String, &'a str)> {
+        let path = path.trim_matches('/');
+        let (first, rest) = path.split_once('/').unwrap_or((path, ""));
+        match self {
+            Self::PrefixAll => match locales.is_supported(first) {
+                true => Some((first.to_string(), rest)),
+                false => None,
+            },
+            Self::PrefixExceptDefault => match locales.is_supported(first) {
+                true => Some((first.to_string(), rest)),
+                false => Some((locales.default.to_string(), path)),
+            },
+            Self::Domains(domains) => {
+                let locale = host
+                    .and_then(|host| domains.iter().find(|(_, h)| h.eq_ignore_ascii_case(host)))
+                    .map(|(locale, _)| locale.to_string())
+                    .filter(|locale| locales.is_supported(locale))
+                    .unwrap_or_else(|| locales.default.to_string());
+                Some((locale, path))
+            }
+            Self::QueryParam(param) => {
+                let locale = query
+                    .unwrap_or_default()
+                    .split('&')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key == param)
+                    .map(|(_, locale)| locale.to_string())
+                    .filter(|locale| locales.is_supported(locale))
+                    .unwrap_or_else(|| locales.default.to_string());
+                Some((locale, path))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locales(url_strategy: UrlStrategy) -> Locales {
+        Locales {
+            default: "en-US".to_string(),
+            other: vec!["fr-FR".to_string(), "de-DE".to_string()],
+            using_i18n: true,
+            url_strategy,
+        }
+    }
+
+    fn domains() -> UrlStrategy {
+        UrlStrategy::Domains(vec![
+            ("en-US".to_string(), "example.com".to_string()),
+            ("fr-FR".to_string(), "example.fr:8080".to_string()),
+            ("es-ES".to_string(), "example.es".to_string()),
+        ])
+    }
+
+    #[test]
+    fn localizes_with_prefix_all() {
+        let locales = locales(UrlStrategy::PrefixAll);
+        let strategy = &locales.url_strategy;
+        assert_eq!(
+            strategy.localize_url("en-US", "/about", &locales),
+            "/en-US/about"
+        );
+        assert_eq!(strategy.localize_url("fr-FR", "/", &locales), "/fr-FR/");
+        assert_eq!(
+            strategy.get_switch_url("fr-FR", "/about?x=1#top", &locales),
+            "/fr-FR/about?x=1#top"
+        );
+    }
+
+    #[test]
+    fn localizes_with_prefix_except_default() {
+        let locales = locales(UrlStrategy::PrefixExceptDefault);
+        let strategy = &locales.url_strategy;
+        assert_eq!(strategy.localize_url("en-US", "/about", &locales), "/about");
+        assert_eq!(
+            strategy.localize_url("fr-FR", "/about", &locales),
+            "/fr-FR/about"
+        );
+        assert_eq!(
+            strategy.get_switch_url("en-US", "/about", &locales),
+            "/about"
+        );
+    }
+
+    #[test]
+    fn localizes_with_domains() {
+        let locales = locales(domains());
+        let strategy = &locales.url_strategy;
+        assert_eq!(strategy.localize_url("fr-FR", "/about", &locales), "/about");
+        assert_eq!(
+            strategy.get_switch_url("fr-FR", "/about", &locales),
+            "//example.fr:8080/about"
+        );
+        assert_eq!(
+            strategy.get_switch_url("en-US", "/", &locales),
+            "//example.com/"
+        );
+        // Locales without a domain stay on the current one
+        assert_eq!(
+            strategy.get_switch_url("de-DE", "/about", &locales),
+            "/about"
+        );
+    }
+
+    #[test]
+    fn localizes_with_query_param() {
+        let locales = locales(UrlStrategy::QueryParam("lang".to_string()));
+        let strategy = &locales.url_strategy;
+        assert_eq!(
+            strategy.localize_url("fr-FR", "/about", &locales),
+            "/about?lang=fr-FR"
+        );
+        assert_eq!(
+            strategy.localize_url("fr-FR", "/search?q=x", &locales),
+            "/search?q=x&lang=fr-FR"
+        );
+        // The parameter goes before the fragment
+        assert_eq!(
+            strategy.localize_url("de-DE", "/about#team", &locales),
+            "/about?lang=de-DE#team"
+        );
+        assert_eq!(
+            strategy.get_switch_url("de-DE", "/search?q=x#top", &locales),
+            "/search?q=x&lang=de-DE#top"
+        );
+    }
+
+    #[test]
+    fn gets_locale_with_prefix_all() {
+        let locales = locales(UrlStrategy::PrefixAll);
+        let strategy = &locales.url_strategy;
+        assert_eq!(
+            strategy.get_locale("fr-FR/about/me", None, None, &locales),
+            Some(("fr-FR".to_string(), "about/me"))
+        );
+        assert_eq!(
+            strategy.get_locale("/de-DE/", None, None, &locales),
+            Some(("de-DE".to_string(), ""))
+        );
+        // Paths without a supported locale should be redirected
+        assert_eq!(strategy.get_locale("about", None, None, &locales), None);
+        assert_eq!(
+            strategy.get_locale("es-ES/about", None, None, &locales),
+            None
+        );
+        assert_eq!(strategy.get_locale("", None, None, &locales), None);
+    }
+
+    #[test]
+    fn gets_locale_with_prefix_except_default() {
+        let locales = locales(UrlStrategy::PrefixExceptDefault);
+        let strategy = &locales.url_strategy;
+        assert_eq!(
+            strategy.get_locale("fr-FR/about", None, None, &locales),
+            Some(("fr-FR".to_string(), "about"))
+        );
+        assert_eq!(
+            strategy.get_locale("about/me", None, None, &locales),
+            Some(("en-US".to_string(), "about/me"))
+        );
+        assert_eq!(
+            strategy.get_locale("", None, None, &locales),
+            Some(("en-US".to_string(), ""))
+        );
+    }
+
+    #[test]
+    fn gets_locale_with_domains() {
+        let locales = locales(domains());
+        let strategy = &locales.url_strategy;
+        assert_eq!(
+            strategy.get_locale("about", Some("example.fr:8080"), None, &locales),
+            Some(("fr-FR".to_string(), "about"))
+        );
+        // Hosts are case-insensitive
+        assert_eq!(
+            strategy.get_locale("about", Some("EXAMPLE.fr:8080"), None, &locales),
+            Some(("fr-FR".to_string(), "about"))
+        );
+        // Unknown hosts, missing hosts, and hosts for unsupported locales get the default locale
+        assert_eq!(
+            strategy.get_locale("about", Some("example.fr"), None, &locales),
+            Some(("en-US".to_string(), "about"))
+        );
+        assert_eq!(
+            strategy.get_locale("about", None, None, &locales),
+            Some(("en-US".to_string(), "about"))
+        );
+        assert_eq!(
+            strategy.get_locale("about", Some("example.es"), None, &locales),
+            Some(("en-US".to_string(), "about"))
+        );
+    }
+
+    #[test]
+    fn gets_locale_with_query_param() {
+        let locales = locales(UrlStrategy::QueryParam("lang".to_string()));
+        let strategy = &locales.url_strategy;
+        assert_eq!(
+            strategy.get_locale("about", None, Some("q=x&lang=fr-FR"), &locales),
+            Some(("fr-FR".to_string(), "about"))
+        );
+        // Missing, unsupported, and malformed parameters get the default locale
+        assert_eq!(
+            strategy.get_locale("about", None, None, &locales),
+            Some(("en-US".to_string(), "about"))
+        );
+        assert_eq!(
+            strategy.get_locale("about", None, Some("lang=es-ES"), &locales),
+            Some(("en-US".to_string(), "about"))
+        );
+        assert_eq!(
+            strategy.get_locale("about", None, Some("lang&language=fr-FR"), &locales),
+            Some(("en-US".to_string(), "about"))
+        );
+    }
+
+    #[test]
+    fn localized_urls_round_trip() {
+        let strategies = vec![
+            UrlStrategy::PrefixAll,
+            UrlStrategy::PrefixExceptDefault,
+            UrlStrategy::QueryParam("lang".to_string()),
+        ];
+        for strategy in strategies {
+            let locales = locales(strategy);
+            for locale in locales.get_all() {
+                let url = locales
+                    .url_strategy
+                    .localize_url(locale, "/about", &locales);
+                let (path, query) = match url.split_once('?') {
+                    Some((path, query)) => (path, Some(query)),
+                    None => (url.as_str(), None),
+                };
+                assert_eq!(
+                    locales.url_strategy.get_locale(path, None, query, &locales),
+                    Some((locale.to_string(), "about")),
+                    "{:?} didn't round-trip {}",
+                    locales.url_strategy,
+                    locale
+                );
+            }
+        }
+    }
+}
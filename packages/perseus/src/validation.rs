@@ -0,0 +1,210 @@
+// Forms are validated twice, once in the browser so users get feedback straight away, and again on the server because the browser can't
+// be trusted, so this lets the rules be written once on a typed state and run by both (they're plain Rust, so they compile for both)
+
+use crate::errors::*;
+use crate::template::{StringResult, StringResultWithCause};
+use futures::Future;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use sycamore::rx::Signal;
+
+/// The errors from validating a state, as lists of messages by the name of the field they're for. These serialize to a JSON object of
+/// those lists, which is what servers should respond to invalid submissions with (with a `422 Unprocessable Entity`), so that
+/// [`Form::submit`] can show them next to their fields just like the errors it finds itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ValidationErrors(BTreeMap<String, Vec<String>>);
+impl ValidationErrors {
+    /// Creates a new, empty set of errors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds the given message to the errors for the given field.
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.entry(field.into()).or_default().push(message.into());
+    }
+    /// Checks if there are no errors at all (meaning the state is valid).
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Gets the messages for the given field, which will be empty if it's valid.
+    pub fn get(&self, field: &str) -> &[String] {
+        self.0
+            .get(field)
+            .map(|messages| messages.as_slice())
+            .unwrap_or(&[])
+    }
+    /// Gets the first message for the given field, if it has any, which is usually all there's room to show beside it.
+    pub fn first(&self, field: &str) -> Option<&str> {
+        self.get(field).first().map(|message| message.as_str())
+    }
+    /// Gets the names of the fields that have errors.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(|field| field.as_str())
+    }
+    /// Serializes the errors to JSON, to be sent as the body of a response to an invalid submission.
+    pub fn to_json(&self) -> String {
+        // This is just a map of strings, so it can't fail
+        serde_json::to_string(&self.0).unwrap()
+    }
+    /// Deserializes errors sent by the server, if the given response body was made by [`ValidationErrors::to_json`].
+    pub fn from_json(body: &str) -> Option<Self> {
+        serde_json::from_str(body).ok()
+    }
+    /// Turns these into a result, which is only an error if there are any.
+    pub fn into_result(self) -> std::result::Result<(), Self> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(self),
+        }
+    }
+}
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self
+            .0
+            .iter()
+            .flat_map(|(field, messages)| {
+                messages
+                    .iter()
+                    .map(move |message| format!("{} {}", field, message))
+            })
+            .collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+/// A state that can be validated, like the data sent by a form. Implementing this once on a type shared by your templates and your server
+/// means the same rules are checked by [`Form`] in the browser and by your request state functions and API routes on the server. The
+/// [`validate!`](crate::validate) macro is the easiest way to write the rules.
+pub trait Validate {
+    /// Checks the state, returning the errors with each field (which will be empty if it's valid).
+    fn validate(&self) -> ValidationErrors;
+
+    /// Checks the state, failing with its errors if it's invalid.
+    fn check(&self) -> std::result::Result<(), ValidationErrors> {
+        self.validate().into_result()
+    }
+    /// Checks the state in a request state function, failing with a `422 Unprocessable Entity` whose message is the errors as JSON if it's
+    /// invalid.
+    fn check_request(&self) -> StringResultWithCause<()> {
+        self.check()
+            .map_err(|errors| (errors.to_json(), ErrorCause::Client(Some(422))))
+    }
+}
+
+/// Checks the given state on the server (e.g. in an API route), failing with a `ValidationFailed` error if it's invalid. That error's
+/// status code will be `422`, and its errors can be sent to the client as JSON with [`ValidationErrors::to_json`].
+pub fn ensure_valid<S: Validate>(state: &S) -> Result<()> {
+    if let Err(errors) = state.check() {
+        bail!(ErrorKind::ValidationFailed(errors))
+    }
+    Ok(())
+}
+
+/// Builds the [`ValidationErrors`] for a set of rules, each of which is a field name, a condition the field has to meet, and the message
+/// to give if it doesn't. This is intended to be the body of [`Validate::validate`].
+///
+/// ```rust,ignore
+/// impl Validate for Signup {
+///     fn validate(&self) -> ValidationErrors {
+///         validate! {
+///             "email": self.email.contains('@') => "must be an email address";
+///             "password": self.password.len() >= 12 => "must be at least 12 characters long";
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! validate {
+    {$($field:literal: $check:expr => $message:expr);* $(;)?} => {{
+        #[allow(unused_mut)]
+        let mut errors = $crate::validation::ValidationErrors::new();
+        $(
+            if !($check) {
+                errors.add($field, $message);
+            }
+        )*
+        errors
+    }};
+}
+
+/// A form in the browser for a state that can be validated, which keeps the state and its errors in signals for the form's inputs and
+/// messages to be bound to. Submitting the form checks it first (so nothing invalid is sent to the server), and then shows any errors the
+/// server sends back in the same way. Once a submission has failed validation, the state is checked again whenever it's updated, so
+/// errors disappear as they're fixed.
+#[derive(Clone)]
+pub struct Form<S: Validate + Clone + 'static> {
+    state: Signal<S>,
+    errors: Signal<ValidationErrors>,
+    submitting: Signal<bool>,
+}
+impl<S: Validate + Clone + 'static> Form<S> {
+    /// Creates a new form with the given initial state. This won't be checked until the form is submitted.
+    pub fn new(state: S) -> Self {
+        Self {
+            state: Signal::new(state),
+            errors: Signal::new(ValidationErrors::new()),
+            submitting: Signal::new(false),
+        }
+    }
+    /// Gets the state of the form.
+    pub fn state(&self) -> &Signal<S> {
+        &self.state
+    }
+    /// Gets the errors with the form from the last time it was checked (which will be empty if it hasn't been).
+    pub fn errors(&self) -> &Signal<ValidationErrors> {
+        &self.errors
+    }
+    /// Gets whether or not the form is being sent to the server.
+    pub fn submitting(&self) -> &Signal<bool> {
+        &self.submitting
+    }
+    /// Applies the given update to the state of the form (e.g. from an input's `input` event).
+    pub fn update(&self, update: impl FnOnce(&mut S)) {
+        let mut state = self.state.get().as_ref().clone();
+        update(&mut state);
+        if !self.errors.get().is_empty() {
+            self.errors.set(state.validate());
+        }
+        self.state.set(state);
+    }
+    /// Checks the state of the form, showing any errors, and returns whether or not it's valid.
+    pub fn validate(&self) -> bool {
+        let errors = self.state.get().validate();
+        let valid = errors.is_empty();
+        self.errors.set(errors);
+        valid
+    }
+    /// Checks the state of the form, and then, if it's valid, sends it to the server with the given function (e.g. a request to an API
+    /// route). If the server rejects it with errors made by [`ValidationErrors::to_json`] (which should be the body of its response), those
+    /// will be shown as if they had been found in the browser. This will fail with `ValidationFailed` if the state is invalid, or with
+    /// `SubmissionFailed` if the request failed for any other reason.
+    pub async fn submit<F, R>(&self, send: F) -> Result<()>
+    where
+        F: FnOnce(S) -> R,
+        R: Future<Output = StringResult<()>>,
+    {
+        let state = self.state.get().as_ref().clone();
+        if let Err(errors) = state.check() {
+            self.errors.set(errors.clone());
+            bail!(ErrorKind::ValidationFailed(errors));
+        }
+        self.errors.set(ValidationErrors::new());
+
+        self.submitting.set(true);
+        let res = send(state).await;
+        self.submitting.set(false);
+        if let Err(err) = res {
+            match ValidationErrors::from_json(&err) {
+                Some(errors) if !errors.is_empty() => {
+                    self.errors.set(errors.clone());
+                    bail!(ErrorKind::ValidationFailed(errors));
+                }
+                _ => bail!(ErrorKind::SubmissionFailed(err)),
+            }
+        }
+        Ok(())
+    }
+}